@@ -0,0 +1,21 @@
+-- Create expenses table
+CREATE TABLE IF NOT EXISTS expenses (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    vendor TEXT NOT NULL,
+    description TEXT,
+    expense_date DATE NOT NULL,
+    category TEXT DEFAULT 'other' CHECK(category IN ('goods', 'subcontractors', 'personnel', 'rent', 'telecommunication', 'travel', 'training', 'legal_and_tax_advice', 'leasing', 'insurance_and_fees', 'advertising', 'vat_payment', 'other')),
+    net_amount REAL NOT NULL,
+    tax_rate REAL DEFAULT 19.0,
+    tax_amount REAL NOT NULL,
+    gross_amount REAL NOT NULL,
+    paid_at TIMESTAMP,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+-- Create indexes
+CREATE INDEX IF NOT EXISTS idx_expenses_user_id ON expenses(user_id);
+CREATE INDEX IF NOT EXISTS idx_expenses_expense_date ON expenses(expense_date);
@@ -0,0 +1,8 @@
+-- Add taxation method (Soll- or Ist-Versteuerung) to user settings
+ALTER TABLE user_settings ADD COLUMN taxation_method TEXT DEFAULT 'soll' CHECK(taxation_method IN ('soll', 'ist'));
+
+-- Add VAT treatment to invoices
+ALTER TABLE invoices ADD COLUMN tax_treatment TEXT DEFAULT 'standard' CHECK(tax_treatment IN ('standard', 'reverse_charge', 'intra_community_supply', 'export', 'non_taxable', 'exempt'));
+
+-- Add VAT treatment to expenses
+ALTER TABLE expenses ADD COLUMN tax_treatment TEXT DEFAULT 'standard' CHECK(tax_treatment IN ('standard', 'reverse_charge'));
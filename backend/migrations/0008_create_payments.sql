@@ -0,0 +1,18 @@
+-- Create payments table
+CREATE TABLE IF NOT EXISTS payments (
+    id TEXT PRIMARY KEY,
+    invoice_id TEXT NOT NULL,
+    amount REAL NOT NULL,
+    payment_date DATE NOT NULL,
+    method TEXT,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (invoice_id) REFERENCES invoices(id) ON DELETE CASCADE
+);
+
+-- Create indexes
+CREATE INDEX IF NOT EXISTS idx_payments_invoice_id ON payments(invoice_id);
+CREATE INDEX IF NOT EXISTS idx_payments_payment_date ON payments(payment_date);
+
+-- Credit notes are stored as invoices referencing the invoice they correct
+ALTER TABLE invoices ADD COLUMN invoice_type TEXT DEFAULT 'invoice' CHECK(invoice_type IN ('invoice', 'credit_note'));
+ALTER TABLE invoices ADD COLUMN corrected_invoice_id TEXT REFERENCES invoices(id);
@@ -0,0 +1,10 @@
+-- Track deductible input VAT, payment status and receipt files on expenses
+ALTER TABLE expenses ADD COLUMN deductible_tax_amount REAL NOT NULL DEFAULT 0;
+ALTER TABLE expenses ADD COLUMN payment_status TEXT DEFAULT 'unpaid' CHECK(payment_status IN ('unpaid', 'paid'));
+ALTER TABLE expenses ADD COLUMN receipt_key TEXT;
+ALTER TABLE expenses ADD COLUMN receipt_filename TEXT;
+ALTER TABLE expenses ADD COLUMN receipt_content_type TEXT;
+
+-- Existing expenses keep their full input VAT and are paid if a payment date was recorded
+UPDATE expenses SET deductible_tax_amount = tax_amount;
+UPDATE expenses SET payment_status = 'paid' WHERE paid_at IS NOT NULL;
@@ -0,0 +1,88 @@
+-- GoBD: issued invoices can only move forward in their life cycle (sent, paid, overdue, cancelled).
+-- Their content and a rendered PDF are write-protected and they cannot be deleted.
+CREATE TRIGGER IF NOT EXISTS invoices_protect_issued_update
+BEFORE UPDATE ON invoices
+WHEN OLD.status <> 'draft' AND (
+    NEW.status = 'draft'
+    OR (OLD.status = 'cancelled' AND NEW.status <> 'cancelled')
+    OR NEW.user_id IS NOT OLD.user_id
+    OR NEW.client_id IS NOT OLD.client_id
+    OR NEW.invoice_number IS NOT OLD.invoice_number
+    OR NEW.issue_date IS NOT OLD.issue_date
+    OR NEW.due_date IS NOT OLD.due_date
+    OR NEW.currency IS NOT OLD.currency
+    OR NEW.subtotal IS NOT OLD.subtotal
+    OR NEW.tax_rate IS NOT OLD.tax_rate
+    OR NEW.tax_amount IS NOT OLD.tax_amount
+    OR NEW.total_amount IS NOT OLD.total_amount
+    OR NEW.tax_treatment IS NOT OLD.tax_treatment
+    OR NEW.invoice_type IS NOT OLD.invoice_type
+    OR NEW.corrected_invoice_id IS NOT OLD.corrected_invoice_id
+    OR NEW.notes IS NOT OLD.notes
+    OR (OLD.pdf_url IS NOT NULL AND NEW.pdf_url IS NOT OLD.pdf_url)
+)
+BEGIN
+    SELECT RAISE(ABORT, 'GoBD: issued invoices are immutable');
+END;
+
+CREATE TRIGGER IF NOT EXISTS invoices_protect_issued_delete
+BEFORE DELETE ON invoices
+WHEN OLD.status <> 'draft'
+BEGIN
+    SELECT RAISE(ABORT, 'GoBD: issued invoices are immutable');
+END;
+
+CREATE TRIGGER IF NOT EXISTS invoice_items_protect_issued_insert
+BEFORE INSERT ON invoice_items
+WHEN (SELECT status FROM invoices WHERE id = NEW.invoice_id) <> 'draft'
+BEGIN
+    SELECT RAISE(ABORT, 'GoBD: items of issued invoices are immutable');
+END;
+
+CREATE TRIGGER IF NOT EXISTS invoice_items_protect_issued_update
+BEFORE UPDATE ON invoice_items
+WHEN (SELECT status FROM invoices WHERE id = OLD.invoice_id) <> 'draft'
+    OR (SELECT status FROM invoices WHERE id = NEW.invoice_id) <> 'draft'
+BEGIN
+    SELECT RAISE(ABORT, 'GoBD: items of issued invoices are immutable');
+END;
+
+CREATE TRIGGER IF NOT EXISTS invoice_items_protect_issued_delete
+BEFORE DELETE ON invoice_items
+WHEN (SELECT status FROM invoices WHERE id = OLD.invoice_id) <> 'draft'
+BEGIN
+    SELECT RAISE(ABORT, 'GoBD: items of issued invoices are immutable');
+END;
+
+-- Append-only audit log. Every entry carries the hash of its predecessor in the
+-- user's chain, so changing or removing an entry breaks verification.
+CREATE TABLE IF NOT EXISTS audit_log (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    sequence INTEGER NOT NULL,
+    actor_id TEXT NOT NULL,
+    entity_type TEXT NOT NULL,
+    entity_id TEXT NOT NULL,
+    action TEXT NOT NULL CHECK(action IN ('create', 'update', 'delete')),
+    before_data TEXT,
+    after_data TEXT,
+    created_at TIMESTAMP NOT NULL,
+    prev_hash TEXT NOT NULL,
+    hash TEXT NOT NULL,
+    UNIQUE(user_id, sequence)
+);
+
+-- Create indexes
+CREATE INDEX IF NOT EXISTS idx_audit_log_entity ON audit_log(entity_type, entity_id);
+
+CREATE TRIGGER IF NOT EXISTS audit_log_append_only_update
+BEFORE UPDATE ON audit_log
+BEGIN
+    SELECT RAISE(ABORT, 'Audit log is append-only');
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_log_append_only_delete
+BEFORE DELETE ON audit_log
+BEGIN
+    SELECT RAISE(ABORT, 'Audit log is append-only');
+END;
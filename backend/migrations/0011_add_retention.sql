@@ -0,0 +1,68 @@
+-- Soft delete and anonymisation for users and clients, legal hold for users
+ALTER TABLE users ADD COLUMN deleted_at TIMESTAMP;
+ALTER TABLE users ADD COLUMN anonymized_at TIMESTAMP;
+ALTER TABLE users ADD COLUMN legal_hold_until DATE;
+ALTER TABLE clients ADD COLUMN deleted_at TIMESTAMP;
+ALTER TABLE clients ADD COLUMN anonymized_at TIMESTAMP;
+
+-- Issued invoices may be deleted once their retention period of 8 years after
+-- the end of the year of issue has elapsed (§ 147 Abs. 3 AO, § 14b UStG).
+DROP TRIGGER IF EXISTS invoices_protect_issued_delete;
+CREATE TRIGGER invoices_protect_issued_delete
+BEFORE DELETE ON invoices
+WHEN OLD.status <> 'draft'
+    AND CAST(strftime('%Y', OLD.issue_date) AS INTEGER) + 8 >= CAST(strftime('%Y', 'now') AS INTEGER)
+BEGIN
+    SELECT RAISE(ABORT, 'GoBD: issued invoices are immutable until their retention period has elapsed');
+END;
+
+DROP TRIGGER IF EXISTS invoice_items_protect_issued_delete;
+CREATE TRIGGER invoice_items_protect_issued_delete
+BEFORE DELETE ON invoice_items
+WHEN EXISTS (
+    SELECT 1 FROM invoices
+    WHERE id = OLD.invoice_id
+        AND status <> 'draft'
+        AND CAST(strftime('%Y', issue_date) AS INTEGER) + 8 >= CAST(strftime('%Y', 'now') AS INTEGER)
+)
+BEGIN
+    SELECT RAISE(ABORT, 'GoBD: items of issued invoices are immutable');
+END;
+
+-- Audit log entries are records in the sense of § 147 Abs. 1 Nr. 1 AO and kept for 10 years.
+DROP TRIGGER IF EXISTS audit_log_append_only_delete;
+CREATE TRIGGER audit_log_append_only_delete
+BEFORE DELETE ON audit_log
+WHEN CAST(strftime('%Y', OLD.created_at) AS INTEGER) + 10 >= CAST(strftime('%Y', 'now') AS INTEGER)
+BEGIN
+    SELECT RAISE(ABORT, 'Audit log is append-only');
+END;
+
+-- Hard deletes would cascade to documents under retention; users and clients are soft-deleted instead.
+CREATE TRIGGER IF NOT EXISTS users_protect_retained_delete
+BEFORE DELETE ON users
+WHEN EXISTS (
+    SELECT 1 FROM invoices
+    WHERE user_id = OLD.id
+        AND status <> 'draft'
+        AND CAST(strftime('%Y', issue_date) AS INTEGER) + 8 >= CAST(strftime('%Y', 'now') AS INTEGER)
+) OR EXISTS (
+    SELECT 1 FROM expenses
+    WHERE user_id = OLD.id
+        AND CAST(strftime('%Y', expense_date) AS INTEGER) + 8 >= CAST(strftime('%Y', 'now') AS INTEGER)
+)
+BEGIN
+    SELECT RAISE(ABORT, 'Retention: user has documents under retention, use soft delete');
+END;
+
+CREATE TRIGGER IF NOT EXISTS clients_protect_retained_delete
+BEFORE DELETE ON clients
+WHEN EXISTS (
+    SELECT 1 FROM invoices
+    WHERE client_id = OLD.id
+        AND status <> 'draft'
+        AND CAST(strftime('%Y', issue_date) AS INTEGER) + 8 >= CAST(strftime('%Y', 'now') AS INTEGER)
+)
+BEGIN
+    SELECT RAISE(ABORT, 'Retention: client has invoices under retention, use soft delete');
+END;
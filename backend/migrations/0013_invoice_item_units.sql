@@ -0,0 +1,50 @@
+-- Decimal quantities, units of measure (UN/ECE Recommendation 20) and line
+-- discounts. SQLite cannot change a column type, so the table is rebuilt; this
+-- also drops its triggers, which are recreated below.
+CREATE TABLE invoice_items_new (
+    id TEXT PRIMARY KEY,
+    invoice_id TEXT NOT NULL,
+    description TEXT NOT NULL,
+    quantity REAL NOT NULL,
+    unit_code TEXT NOT NULL DEFAULT 'C62' CHECK(unit_code IN ('HUR', 'DAY', 'H87', 'KMT', 'MTK', 'LS', 'C62')),
+    unit_price REAL NOT NULL,
+    discount_percent REAL NOT NULL DEFAULT 0 CHECK(discount_percent >= 0 AND discount_percent <= 100),
+    total_price REAL NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (invoice_id) REFERENCES invoices(id) ON DELETE CASCADE
+);
+
+INSERT INTO invoice_items_new (id, invoice_id, description, quantity, unit_price, total_price, created_at)
+SELECT id, invoice_id, description, quantity, unit_price, total_price, created_at FROM invoice_items;
+
+DROP TABLE invoice_items;
+ALTER TABLE invoice_items_new RENAME TO invoice_items;
+
+CREATE INDEX IF NOT EXISTS idx_invoice_items_invoice_id ON invoice_items(invoice_id);
+
+CREATE TRIGGER IF NOT EXISTS invoice_items_protect_issued_insert
+BEFORE INSERT ON invoice_items
+WHEN (SELECT status FROM invoices WHERE id = NEW.invoice_id) <> 'draft'
+BEGIN
+    SELECT RAISE(ABORT, 'GoBD: items of issued invoices are immutable');
+END;
+
+CREATE TRIGGER IF NOT EXISTS invoice_items_protect_issued_update
+BEFORE UPDATE ON invoice_items
+WHEN (SELECT status FROM invoices WHERE id = OLD.invoice_id) <> 'draft'
+    OR (SELECT status FROM invoices WHERE id = NEW.invoice_id) <> 'draft'
+BEGIN
+    SELECT RAISE(ABORT, 'GoBD: items of issued invoices are immutable');
+END;
+
+CREATE TRIGGER IF NOT EXISTS invoice_items_protect_issued_delete
+BEFORE DELETE ON invoice_items
+WHEN EXISTS (
+    SELECT 1 FROM invoices
+    WHERE id = OLD.invoice_id
+        AND status <> 'draft'
+        AND CAST(strftime('%Y', issue_date) AS INTEGER) + 8 >= CAST(strftime('%Y', 'now') AS INTEGER)
+)
+BEGIN
+    SELECT RAISE(ABORT, 'GoBD: items of issued invoices are immutable');
+END;
@@ -0,0 +1,25 @@
+-- Catalogue of articles and services used to prefill invoice items
+CREATE TABLE IF NOT EXISTS catalog_items (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    item_number TEXT NOT NULL,
+    description TEXT NOT NULL,
+    unit_code TEXT NOT NULL DEFAULT 'C62' CHECK(unit_code IN ('HUR', 'DAY', 'H87', 'KMT', 'MTK', 'LS', 'C62')),
+    net_price REAL NOT NULL,
+    tax_category TEXT NOT NULL DEFAULT 'standard' CHECK(tax_category IN ('standard', 'reduced', 'zero', 'exempt')),
+    revenue_account TEXT,
+    archived_at TIMESTAMP,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(user_id, item_number),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_catalog_items_user_id ON catalog_items(user_id);
+
+-- Invoice items keep a snapshot of the catalogue item they were created from.
+-- The reference has no foreign key: catalogue items may be deleted while
+-- issued items cannot change.
+ALTER TABLE invoice_items ADD COLUMN catalog_item_id TEXT;
+ALTER TABLE invoice_items ADD COLUMN tax_category TEXT;
+ALTER TABLE invoice_items ADD COLUMN revenue_account TEXT;
@@ -0,0 +1,66 @@
+-- Document-level allowances and charges (EN 16931 BG-20 / BG-21). A
+-- percentage is applied to the sum of the invoice lines; `amount` holds the
+-- resulting net amount.
+CREATE TABLE IF NOT EXISTS invoice_allowance_charges (
+    id TEXT PRIMARY KEY,
+    invoice_id TEXT NOT NULL,
+    kind TEXT NOT NULL CHECK(kind IN ('allowance', 'charge')),
+    reason TEXT NOT NULL,
+    percent REAL CHECK(percent IS NULL OR (percent > 0 AND percent <= 100)),
+    amount REAL NOT NULL CHECK(amount >= 0),
+    tax_category TEXT NOT NULL DEFAULT 'standard' CHECK(tax_category IN ('standard', 'reduced', 'zero', 'exempt')),
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (invoice_id) REFERENCES invoices(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_invoice_allowance_charges_invoice_id ON invoice_allowance_charges(invoice_id);
+
+-- `subtotal` stays the taxable amount: sum of lines less allowances plus charges
+ALTER TABLE invoices ADD COLUMN allowance_total REAL NOT NULL DEFAULT 0;
+ALTER TABLE invoices ADD COLUMN charge_total REAL NOT NULL DEFAULT 0;
+
+-- Early-payment discount (Skonto) on the gross amount
+ALTER TABLE invoices ADD COLUMN skonto_percent REAL CHECK(skonto_percent IS NULL OR (skonto_percent > 0 AND skonto_percent < 100));
+ALTER TABLE invoices ADD COLUMN skonto_days INTEGER CHECK(skonto_days IS NULL OR skonto_days >= 0);
+
+-- Discount granted when a payment within the Skonto period settles the invoice
+ALTER TABLE payments ADD COLUMN skonto_amount REAL NOT NULL DEFAULT 0;
+
+CREATE TRIGGER IF NOT EXISTS invoices_protect_issued_terms
+BEFORE UPDATE ON invoices
+WHEN OLD.status <> 'draft' AND (
+    NEW.allowance_total IS NOT OLD.allowance_total
+    OR NEW.charge_total IS NOT OLD.charge_total
+    OR NEW.skonto_percent IS NOT OLD.skonto_percent
+    OR NEW.skonto_days IS NOT OLD.skonto_days
+)
+BEGIN
+    SELECT RAISE(ABORT, 'GoBD: issued invoices are immutable');
+END;
+
+CREATE TRIGGER IF NOT EXISTS invoice_allowance_charges_protect_issued_insert
+BEFORE INSERT ON invoice_allowance_charges
+WHEN (SELECT status FROM invoices WHERE id = NEW.invoice_id) <> 'draft'
+BEGIN
+    SELECT RAISE(ABORT, 'GoBD: allowances and charges of issued invoices are immutable');
+END;
+
+CREATE TRIGGER IF NOT EXISTS invoice_allowance_charges_protect_issued_update
+BEFORE UPDATE ON invoice_allowance_charges
+WHEN (SELECT status FROM invoices WHERE id = OLD.invoice_id) <> 'draft'
+    OR (SELECT status FROM invoices WHERE id = NEW.invoice_id) <> 'draft'
+BEGIN
+    SELECT RAISE(ABORT, 'GoBD: allowances and charges of issued invoices are immutable');
+END;
+
+CREATE TRIGGER IF NOT EXISTS invoice_allowance_charges_protect_issued_delete
+BEFORE DELETE ON invoice_allowance_charges
+WHEN EXISTS (
+    SELECT 1 FROM invoices
+    WHERE id = OLD.invoice_id
+        AND status <> 'draft'
+        AND CAST(strftime('%Y', issue_date) AS INTEGER) + 8 >= CAST(strftime('%Y', 'now') AS INTEGER)
+)
+BEGIN
+    SELECT RAISE(ABORT, 'GoBD: allowances and charges of issued invoices are immutable');
+END;
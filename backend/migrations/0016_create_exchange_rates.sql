@@ -0,0 +1,24 @@
+-- ECB euro foreign exchange reference rates: units of `currency` per 1 EUR
+CREATE TABLE IF NOT EXISTS exchange_rates (
+    user_id TEXT NOT NULL,
+    currency TEXT NOT NULL,
+    rate_date DATE NOT NULL,
+    rate REAL NOT NULL CHECK(rate > 0),
+    imported_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (user_id, currency, rate_date),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+-- Snapshot of the rate on the issue date for invoices not in EUR
+ALTER TABLE invoices ADD COLUMN exchange_rate REAL CHECK(exchange_rate IS NULL OR exchange_rate > 0);
+ALTER TABLE invoices ADD COLUMN exchange_rate_date DATE;
+
+CREATE TRIGGER IF NOT EXISTS invoices_protect_issued_exchange_rate
+BEFORE UPDATE ON invoices
+WHEN OLD.status <> 'draft' AND (
+    NEW.exchange_rate IS NOT OLD.exchange_rate
+    OR NEW.exchange_rate_date IS NOT OLD.exchange_rate_date
+)
+BEGIN
+    SELECT RAISE(ABORT, 'GoBD: issued invoices are immutable');
+END;
@@ -0,0 +1,66 @@
+-- Advance invoices (Abschlagsrechnungen) for a project and the final invoice
+-- (Schlussrechnung) deducting the advance payments received (§ 14 Abs. 5 UStG)
+ALTER TABLE invoices ADD COLUMN billing_type TEXT NOT NULL DEFAULT 'standard' CHECK(billing_type IN ('standard', 'advance', 'final'));
+ALTER TABLE invoices ADD COLUMN project_id TEXT;
+ALTER TABLE invoices ADD COLUMN advance_deduction_net REAL NOT NULL DEFAULT 0;
+ALTER TABLE invoices ADD COLUMN advance_deduction_tax REAL NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS idx_invoices_project_id ON invoices(project_id);
+
+-- Advance payments deducted on a final invoice, copied from the advance
+-- invoice. The advance invoice is referenced without a foreign key as its
+-- retention period may end before that of the final invoice.
+CREATE TABLE IF NOT EXISTS invoice_advance_deductions (
+    id TEXT PRIMARY KEY,
+    final_invoice_id TEXT NOT NULL,
+    advance_invoice_id TEXT NOT NULL UNIQUE,
+    advance_invoice_number TEXT NOT NULL,
+    advance_issue_date DATE NOT NULL,
+    tax_rate REAL NOT NULL,
+    net_amount REAL NOT NULL,
+    tax_amount REAL NOT NULL,
+    gross_amount REAL NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (final_invoice_id) REFERENCES invoices(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_invoice_advance_deductions_final ON invoice_advance_deductions(final_invoice_id);
+
+CREATE TRIGGER IF NOT EXISTS invoices_protect_issued_billing
+BEFORE UPDATE ON invoices
+WHEN OLD.status <> 'draft' AND (
+    NEW.billing_type IS NOT OLD.billing_type
+    OR NEW.project_id IS NOT OLD.project_id
+    OR NEW.advance_deduction_net IS NOT OLD.advance_deduction_net
+    OR NEW.advance_deduction_tax IS NOT OLD.advance_deduction_tax
+)
+BEGIN
+    SELECT RAISE(ABORT, 'GoBD: issued invoices are immutable');
+END;
+
+CREATE TRIGGER IF NOT EXISTS invoice_advance_deductions_protect_issued_insert
+BEFORE INSERT ON invoice_advance_deductions
+WHEN (SELECT status FROM invoices WHERE id = NEW.final_invoice_id) <> 'draft'
+BEGIN
+    SELECT RAISE(ABORT, 'GoBD: deductions of issued invoices are immutable');
+END;
+
+CREATE TRIGGER IF NOT EXISTS invoice_advance_deductions_protect_issued_update
+BEFORE UPDATE ON invoice_advance_deductions
+WHEN (SELECT status FROM invoices WHERE id = OLD.final_invoice_id) <> 'draft'
+    OR (SELECT status FROM invoices WHERE id = NEW.final_invoice_id) <> 'draft'
+BEGIN
+    SELECT RAISE(ABORT, 'GoBD: deductions of issued invoices are immutable');
+END;
+
+CREATE TRIGGER IF NOT EXISTS invoice_advance_deductions_protect_issued_delete
+BEFORE DELETE ON invoice_advance_deductions
+WHEN EXISTS (
+    SELECT 1 FROM invoices
+    WHERE id = OLD.final_invoice_id
+        AND status <> 'draft'
+        AND CAST(strftime('%Y', issue_date) AS INTEGER) + 8 >= CAST(strftime('%Y', 'now') AS INTEGER)
+)
+BEGIN
+    SELECT RAISE(ABORT, 'GoBD: deductions of issued invoices are immutable');
+END;
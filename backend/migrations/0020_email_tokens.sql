@@ -0,0 +1,19 @@
+-- Set when the account holder has confirmed the email address with the
+-- verification link.
+ALTER TABLE users ADD COLUMN email_verified_at TIMESTAMP;
+
+-- Tokens sent by email for verification and password reset. The token itself
+-- is a signed JWT naming this row; the row makes it single-use and is bound to
+-- the address it was sent to.
+CREATE TABLE IF NOT EXISTS email_tokens (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    purpose TEXT NOT NULL CHECK(purpose IN ('verify_email', 'reset_password')),
+    email TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP NOT NULL,
+    used_at TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_email_tokens_user_id ON email_tokens(user_id);
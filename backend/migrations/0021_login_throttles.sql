@@ -0,0 +1,13 @@
+-- Failed login attempts per account (normalised email address, also for
+-- addresses without an account) and per client IP address. After a few
+-- failures every further attempt is delayed with exponential backoff, and
+-- too many failures lock the key temporarily. Counting restarts once the
+-- last failure is an hour old.
+CREATE TABLE IF NOT EXISTS login_throttles (
+    scope TEXT NOT NULL CHECK(scope IN ('account', 'ip')),
+    key TEXT NOT NULL,
+    failures INTEGER NOT NULL DEFAULT 0,
+    last_failure_at TIMESTAMP NOT NULL,
+    locked_until TIMESTAMP,
+    PRIMARY KEY (scope, key)
+);
@@ -0,0 +1,33 @@
+-- Team access. Every account is an organisation: the clients, invoices,
+-- expenses and settings it owns carry its ID in their user_id column. Other
+-- accounts work in it through a membership; the account holder is its owner
+-- without a row here.
+CREATE TABLE IF NOT EXISTS memberships (
+    organisation_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    role TEXT NOT NULL CHECK(role IN ('owner', 'accountant', 'member', 'read_only')),
+    invited_by TEXT,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (organisation_id, user_id),
+    FOREIGN KEY (organisation_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_memberships_user_id ON memberships(user_id);
+
+-- Invitations sent by email. Only the SHA-256 hash of the link token is
+-- stored; the invitation is accepted by the account with the invited address.
+CREATE TABLE IF NOT EXISTS invitations (
+    id TEXT PRIMARY KEY,
+    organisation_id TEXT NOT NULL,
+    email TEXT NOT NULL,
+    role TEXT NOT NULL CHECK(role IN ('owner', 'accountant', 'member', 'read_only')),
+    token_hash TEXT NOT NULL UNIQUE,
+    invited_by TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP NOT NULL,
+    accepted_at TIMESTAMP,
+    FOREIGN KEY (organisation_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_invitations_organisation_id ON invitations(organisation_id);
@@ -0,0 +1,33 @@
+-- Read-only access for a tax advisor (Steuerberater) to the invoices,
+-- payments, expenses and reports of selected fiscal years. The advisor needs
+-- no account: they log in with links mailed to the granted address.
+CREATE TABLE IF NOT EXISTS advisor_grants (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    advisor_email TEXT NOT NULL,
+    advisor_name TEXT,
+    -- Space-separated years, e.g. "2023 2024"
+    fiscal_years TEXT NOT NULL,
+    created_by TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP NOT NULL,
+    revoked_at TIMESTAMP,
+    last_accessed_at TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_advisor_grants_user_id ON advisor_grants(user_id);
+CREATE INDEX IF NOT EXISTS idx_advisor_grants_advisor_email ON advisor_grants(advisor_email);
+
+-- Single-use login links. Only the SHA-256 hash of the link token is stored.
+CREATE TABLE IF NOT EXISTS advisor_links (
+    id TEXT PRIMARY KEY,
+    grant_id TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP NOT NULL,
+    used_at TIMESTAMP,
+    FOREIGN KEY (grant_id) REFERENCES advisor_grants(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_advisor_links_grant_id ON advisor_links(grant_id);
@@ -0,0 +1,15 @@
+-- Link requests (password reset, verification resend) are throttled with the
+-- login counters, in scopes of their own so that requesting mails does not
+-- lock the login. Every request counts, not only failed ones.
+CREATE TABLE login_throttles_new (
+    scope TEXT NOT NULL CHECK(scope IN ('account', 'ip', 'mail_account', 'mail_ip')),
+    key TEXT NOT NULL,
+    failures INTEGER NOT NULL DEFAULT 0,
+    last_failure_at TIMESTAMP NOT NULL,
+    locked_until TIMESTAMP,
+    PRIMARY KEY (scope, key)
+);
+
+INSERT INTO login_throttles_new SELECT scope, key, failures, last_failure_at, locked_until FROM login_throttles;
+DROP TABLE login_throttles;
+ALTER TABLE login_throttles_new RENAME TO login_throttles;
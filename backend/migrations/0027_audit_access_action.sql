@@ -0,0 +1,41 @@
+-- Reads by a tax advisor are logged with their own action instead of
+-- passing as the creation of a record. SQLite cannot change a CHECK
+-- constraint in place, so the table is rebuilt with its index and triggers.
+CREATE TABLE audit_log_new (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    sequence INTEGER NOT NULL,
+    actor_id TEXT NOT NULL,
+    entity_type TEXT NOT NULL,
+    entity_id TEXT NOT NULL,
+    action TEXT NOT NULL CHECK(action IN ('create', 'update', 'delete', 'access')),
+    before_data TEXT,
+    after_data TEXT,
+    created_at TIMESTAMP NOT NULL,
+    prev_hash TEXT NOT NULL,
+    hash TEXT NOT NULL,
+    UNIQUE(user_id, sequence)
+);
+
+INSERT INTO audit_log_new
+SELECT id, user_id, sequence, actor_id, entity_type, entity_id, action, before_data, after_data, created_at, prev_hash, hash
+FROM audit_log;
+
+DROP TABLE audit_log;
+ALTER TABLE audit_log_new RENAME TO audit_log;
+
+CREATE INDEX IF NOT EXISTS idx_audit_log_entity ON audit_log(entity_type, entity_id);
+
+CREATE TRIGGER audit_log_append_only_update
+BEFORE UPDATE ON audit_log
+BEGIN
+    SELECT RAISE(ABORT, 'Audit log is append-only');
+END;
+
+-- Audit log entries are records in the sense of § 147 Abs. 1 Nr. 1 AO and kept for 10 years.
+CREATE TRIGGER audit_log_append_only_delete
+BEFORE DELETE ON audit_log
+WHEN CAST(strftime('%Y', OLD.created_at) AS INTEGER) + 10 >= CAST(strftime('%Y', 'now') AS INTEGER)
+BEGIN
+    SELECT RAISE(ABORT, 'Audit log is append-only');
+END;
@@ -0,0 +1,16 @@
+-- Last entry removed from each user's audit chain by the retention purge.
+-- Verification checks the first remaining entry against it, so entries
+-- cannot be dropped from the start of a chain unnoticed.
+CREATE TABLE IF NOT EXISTS audit_log_checkpoints (
+    user_id TEXT PRIMARY KEY,
+    sequence INTEGER NOT NULL,
+    hash TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL
+);
+
+CREATE TRIGGER IF NOT EXISTS audit_log_checkpoints_forward_only
+BEFORE UPDATE ON audit_log_checkpoints
+WHEN NEW.user_id IS NOT OLD.user_id OR NEW.sequence <= OLD.sequence
+BEGIN
+    SELECT RAISE(ABORT, 'Audit log checkpoints only move forward');
+END;
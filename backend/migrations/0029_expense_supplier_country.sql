@@ -0,0 +1,4 @@
+-- Country of the supplier (ISO 3166-1 alpha-2). For reverse-charge expenses it
+-- decides between services from other EU member states (Kz 46/47) and other
+-- § 13b cases such as domestic construction work (Kz 84/85).
+ALTER TABLE expenses ADD COLUMN supplier_country TEXT;
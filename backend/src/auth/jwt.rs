@@ -1,43 +1,259 @@
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
-use serde::{Deserialize, Serialize};
+use jsonwebtoken::{decode, decode_header, encode, Header, Validation};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::time::{SystemTime, UNIX_EPOCH};
+use chrono::{DateTime, Duration, Utc};
+use crate::auth::keys::JwtKeys;
+use crate::models::advisor_grant::AdvisorGrant;
+use crate::models::email_token::{EmailToken, EmailTokenPurpose};
 use crate::models::user::User;
 
-const JWT_SECRET: &str = "your-secret-key-change-in-production";
-const JWT_EXPIRATION_HOURS: u64 = 24;
+/// Access tokens are short-lived; clients renew them with their refresh token.
+pub const ACCESS_TOKEN_MINUTES: u64 = 15;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String, // user id
     pub email: String,
     pub exp: usize,
+    /// Session the token was issued for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
 }
 
-pub fn generate_token(user: &User) -> Result<String, jsonwebtoken::errors::Error> {
+pub fn generate_token(keys: &JwtKeys, user: &User, session_id: &str) -> Result<String, jsonwebtoken::errors::Error> {
     let expiration = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .expect("Time went backwards")
         .as_secs() as usize
-        + (JWT_EXPIRATION_HOURS * 3600) as usize;
+        + (ACCESS_TOKEN_MINUTES * 60) as usize;
 
     let claims = Claims {
         sub: user.id.clone(),
         email: user.email.clone(),
         exp: expiration,
+        sid: Some(session_id.to_string()),
     };
 
-    encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(JWT_SECRET.as_ref()),
-    )
-}
-
-pub fn validate_token(token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
-    decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(JWT_SECRET.as_ref()),
-        &Validation::default(),
-    )
-    .map(|data| data.claims)
-}
\ No newline at end of file
+    sign(keys, &claims)
+}
+
+/// Time to enter the second factor after the password was accepted.
+pub const CHALLENGE_MINUTES: u64 = 5;
+const CHALLENGE_AUDIENCE: &str = "two_factor";
+
+/// Claims of a login challenge: the password was correct and the second factor
+/// is pending. The audience keeps it from being accepted as an access token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChallengeClaims {
+    pub sub: String,
+    pub aud: String,
+    pub exp: usize,
+}
+
+pub fn generate_challenge(keys: &JwtKeys, user: &User) -> Result<String, jsonwebtoken::errors::Error> {
+    let expiration = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs() as usize
+        + (CHALLENGE_MINUTES * 60) as usize;
+
+    let claims = ChallengeClaims {
+        sub: user.id.clone(),
+        aud: CHALLENGE_AUDIENCE.to_string(),
+        exp: expiration,
+    };
+
+    sign(keys, &claims)
+}
+
+pub fn validate_challenge(keys: &JwtKeys, token: &str) -> Result<ChallengeClaims, jsonwebtoken::errors::Error> {
+    verify(keys, token, Some(CHALLENGE_AUDIENCE))
+}
+
+/// Claims of a token sent by email. The audience is the purpose, so a
+/// verification link cannot reset a password; `jti` names the `email_tokens`
+/// row that makes the token single-use.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailTokenClaims {
+    pub sub: String,
+    pub aud: String,
+    pub exp: usize,
+    pub jti: String,
+}
+
+pub fn generate_email_token(keys: &JwtKeys, token: &EmailToken) -> Result<String, jsonwebtoken::errors::Error> {
+    let claims = EmailTokenClaims {
+        sub: token.user_id.clone(),
+        aud: token.purpose.clone(),
+        exp: token.expires_at.timestamp().max(0) as usize,
+        jti: token.id.clone(),
+    };
+
+    sign(keys, &claims)
+}
+
+pub fn validate_email_token(
+    keys: &JwtKeys,
+    token: &str,
+    purpose: EmailTokenPurpose,
+) -> Result<EmailTokenClaims, jsonwebtoken::errors::Error> {
+    verify(keys, token, Some(purpose.as_str()))
+}
+
+/// Advisor portal sessions last an hour; the advisor logs in again with a
+/// new link afterwards.
+pub const ADVISOR_SESSION_MINUTES: i64 = 60;
+const ADVISOR_AUDIENCE: &str = "advisor";
+
+/// Claims of an advisor portal session. `sub` is the grant, not a user; the
+/// audience keeps the token from being accepted as an access token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdvisorClaims {
+    pub sub: String,
+    pub aud: String,
+    pub exp: usize,
+}
+
+/// Signs a session token for the grant. It never outlives the grant.
+pub fn generate_advisor_token(
+    keys: &JwtKeys,
+    grant: &AdvisorGrant,
+    now: DateTime<Utc>,
+) -> Result<(String, DateTime<Utc>), jsonwebtoken::errors::Error> {
+    let expires_at = (now + Duration::minutes(ADVISOR_SESSION_MINUTES)).min(grant.expires_at);
+    let claims = AdvisorClaims {
+        sub: grant.id.clone(),
+        aud: ADVISOR_AUDIENCE.to_string(),
+        exp: expires_at.timestamp().max(0) as usize,
+    };
+
+    sign(keys, &claims).map(|token| (token, expires_at))
+}
+
+pub fn validate_advisor_token(keys: &JwtKeys, token: &str) -> Result<AdvisorClaims, jsonwebtoken::errors::Error> {
+    verify(keys, token, Some(ADVISOR_AUDIENCE))
+}
+
+/// Verifies an access token; tokens with an audience are meant for something else.
+pub fn validate_token(keys: &JwtKeys, token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
+    verify(keys, token, None)
+}
+
+/// Signs the claims with the current key and names it in the `kid` header.
+fn sign<T: Serialize>(keys: &JwtKeys, claims: &T) -> Result<String, jsonwebtoken::errors::Error> {
+    let (kid, algorithm, key) = keys.signing_key();
+    let mut header = Header::new(algorithm);
+    header.kid = Some(kid.to_string());
+
+    encode(&header, claims, key)
+}
+
+/// Verifies a token with the key named in its `kid` header. Tokens without a
+/// `kid` or signed with a key that is no longer configured are rejected, and
+/// so are tokens whose audience is not `audience`; without one, tokens that
+/// carry any audience fail.
+fn verify<T: DeserializeOwned>(
+    keys: &JwtKeys,
+    token: &str,
+    audience: Option<&str>,
+) -> Result<T, jsonwebtoken::errors::Error> {
+    let (algorithm, key) = decode_header(token)?
+        .kid
+        .and_then(|kid| keys.verifying_key(&kid))
+        .ok_or(jsonwebtoken::errors::ErrorKind::InvalidKeyFormat)?;
+
+    let mut validation = Validation::new(algorithm);
+    if let Some(audience) = audience {
+        validation.set_audience(&[audience]);
+    }
+    decode::<T>(token, key, &validation).map(|data| data.claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(kid: &str, secret: &str, retired: Option<&str>) -> JwtKeys {
+        JwtKeys::from_vars(|name| match name {
+            "JWT_KEY_ID" => Some(kid.to_string()),
+            "JWT_SECRET" => Some(secret.to_string()),
+            "JWT_RETIRED_KEYS" => retired.map(str::to_string),
+            _ => None,
+        })
+        .unwrap()
+    }
+
+    fn user() -> User {
+        User::new("max@example.com".to_string(), "hash".to_string(), None, None, None, None)
+    }
+
+    #[test]
+    fn tokens_stay_valid_after_key_rotation() {
+        let old_secret = "a".repeat(32);
+        let before = keys("2024-01", &old_secret, None);
+        let token = generate_token(&before, &user(), "s1").unwrap();
+
+        let retired = format!(r#"[{{"kid": "2024-01", "secret": "{}"}}]"#, old_secret);
+        let after = keys("2024-06", &"b".repeat(32), Some(&retired));
+        let claims = validate_token(&after, &token).unwrap();
+        assert_eq!(claims.email, "max@example.com");
+        assert_eq!(claims.sid.as_deref(), Some("s1"));
+
+        let new_token = generate_token(&after, &user(), "s1").unwrap();
+        assert_eq!(decode_header(&new_token).unwrap().kid.as_deref(), Some("2024-06"));
+    }
+
+    #[test]
+    fn challenges_are_no_access_tokens() {
+        let keys = keys("2024-01", &"a".repeat(32), None);
+        let challenge = generate_challenge(&keys, &user()).unwrap();
+        let token = generate_token(&keys, &user(), "s1").unwrap();
+
+        assert!(validate_challenge(&keys, &challenge).is_ok());
+        assert!(validate_token(&keys, &challenge).is_err());
+        assert!(validate_challenge(&keys, &token).is_err());
+    }
+
+    #[test]
+    fn email_tokens_only_work_for_their_purpose() {
+        let keys = keys("2024-01", &"a".repeat(32), None);
+        let row = EmailToken::new(&user(), EmailTokenPurpose::VerifyEmail);
+        let token = generate_email_token(&keys, &row).unwrap();
+
+        let claims = validate_email_token(&keys, &token, EmailTokenPurpose::VerifyEmail).unwrap();
+        assert_eq!(claims.jti, row.id);
+        assert!(validate_email_token(&keys, &token, EmailTokenPurpose::ResetPassword).is_err());
+        assert!(validate_token(&keys, &token).is_err());
+        assert!(validate_challenge(&keys, &token).is_err());
+    }
+
+    #[test]
+    fn advisor_sessions_are_no_access_tokens() {
+        let keys = keys("2024-01", &"a".repeat(32), None);
+        let now = Utc::now();
+        let grant = crate::models::advisor_grant::NewAdvisorGrant {
+            advisor_email: "kanzlei@steuerberater.de".to_string(),
+            advisor_name: None,
+            fiscal_years: vec![2024],
+            expires_at: now + Duration::minutes(10),
+        }
+        .into_grant("org".to_string(), "u1".to_string(), now)
+        .unwrap();
+        let (token, expires_at) = generate_advisor_token(&keys, &grant, now).unwrap();
+
+        assert_eq!(expires_at, grant.expires_at);
+        assert_eq!(validate_advisor_token(&keys, &token).unwrap().sub, grant.id);
+        assert!(validate_token(&keys, &token).is_err());
+        let access = generate_token(&keys, &user(), "s1").unwrap();
+        assert!(validate_advisor_token(&keys, &access).is_err());
+    }
+
+    #[test]
+    fn tokens_of_removed_keys_are_rejected() {
+        let token = generate_token(&keys("2024-01", &"a".repeat(32), None), &user(), "s1").unwrap();
+
+        assert!(validate_token(&keys("2024-06", &"b".repeat(32), None), &token).is_err());
+        // Same secret under another kid: the key was removed, not renamed
+        assert!(validate_token(&keys("2024-06", &"a".repeat(32), None), &token).is_err());
+    }
+}
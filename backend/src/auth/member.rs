@@ -0,0 +1,26 @@
+use crate::error::ApiError;
+use crate::models::membership::{Permission, Role};
+
+/// Header that selects the organisation a request works in. Without it the
+/// request works in the caller's own account.
+pub const ORGANISATION_HEADER: &str = "X-Organisation-Id";
+
+/// The organisation a request works in and the caller's role there, set by
+/// the auth middleware next to the token claims.
+#[derive(Debug, Clone)]
+pub struct Member {
+    /// The caller; recorded as actor in the audit log
+    pub user_id: String,
+    /// Owner of the records the request reads and writes
+    pub organisation_id: String,
+    pub role: Role,
+}
+
+impl Member {
+    pub fn require(&self, permission: Permission) -> Result<(), ApiError> {
+        if !self.role.allows(permission) {
+            return Err(ApiError::forbidden(format!("The {} role does not allow this", self.role.as_str())));
+        }
+        Ok(())
+    }
+}
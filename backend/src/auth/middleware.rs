@@ -1,30 +1,200 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
 use axum::{
-    extract::Request,
-    http::StatusCode,
+    extract::{ConnectInfo, Request, State},
     middleware::Next,
     response::Response,
 };
+use chrono::Utc;
 use tracing::info;
 
-use super::jwt::validate_token;
+use super::jwt::{validate_advisor_token, validate_token, Claims};
+use super::keys::JwtKeys;
+use super::member::{Member, ORGANISATION_HEADER};
+use crate::db::{self, Db};
+use crate::error::ApiError;
+use crate::models::api_key::{hash_api_key, is_api_key, ApiScope};
+use crate::models::membership::Role;
+
+fn invalid_token() -> ApiError {
+    ApiError::unauthorized("Invalid or missing authentication token")
+}
 
 pub async fn auth_middleware(
-    request: Request,
+    State(keys): State<Arc<JwtKeys>>,
+    State(db): State<Db>,
+    mut request: Request,
     next: Next,
-) -> Result<Response, StatusCode> {
+) -> Result<Response, ApiError> {
     let auth_header = request
         .headers()
         .get("Authorization")
         .and_then(|header| header.to_str().ok())
         .and_then(|value| value.strip_prefix("Bearer "))
-        .ok_or(StatusCode::UNAUTHORIZED)?;
+        .ok_or_else(invalid_token)?;
+
+    let claims = if is_api_key(auth_header) {
+        let scope = ApiScope::required_for(request.method().as_str(), request.uri().path());
+        let ip_address = request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(address)| address.ip().to_string());
+        authenticate_api_key(&db, auth_header, scope, ip_address.as_deref()).await?
+    } else {
+        let claims = validate_token(&keys, auth_header).map_err(|_| invalid_token())?;
+        check_session(&db, &claims).await?;
+        info!("Authenticated user: {}", claims.email);
+        claims
+    };
+
+    let organisation_id = request
+        .headers()
+        .get(ORGANISATION_HEADER)
+        .and_then(|header| header.to_str().ok())
+        .map(str::to_string);
+    let member = resolve_member(&db, &claims.sub, organisation_id).await?;
+
+    request.extensions_mut().insert(claims);
+    request.extensions_mut().insert(member);
+    Ok(next.run(request).await)
+}
+
+/// Access tokens live until they expire, so each request checks that their
+/// session was not revoked in the meantime, e.g. by a logout or a reused
+/// refresh token.
+async fn check_session(db: &Db, claims: &Claims) -> Result<(), ApiError> {
+    let sid = claims.sid.as_deref().ok_or_else(invalid_token)?;
+    let session = db::session::find_by_id(db, &claims.sub, sid)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load session"))?;
+
+    match session {
+        Some(session) if session.is_active(Utc::now()) => Ok(()),
+        _ => Err(invalid_token()),
+    }
+}
+
+/// The organisation the request works in: the caller's own, or the one named
+/// in the header if the caller is a member of it.
+async fn resolve_member(db: &Db, user_id: &str, organisation_id: Option<String>) -> Result<Member, ApiError> {
+    let organisation_id = organisation_id.unwrap_or_else(|| user_id.to_string());
+    let role = if organisation_id == user_id {
+        Role::Owner
+    } else {
+        db::membership::find_role(db, &organisation_id, user_id)
+            .await
+            .map_err(|_| ApiError::internal("Failed to load membership"))?
+            .and_then(|role| Role::parse(&role))
+            .ok_or_else(|| ApiError::forbidden("Not a member of this organisation"))?
+    };
+
+    Ok(Member {
+        user_id: user_id.to_string(),
+        organisation_id,
+        role,
+    })
+}
+
+/// Accepts an active API key that has the scope the endpoint needs and
+/// records its use.
+async fn authenticate_api_key(
+    db: &Db,
+    key: &str,
+    scope: Option<ApiScope>,
+    ip_address: Option<&str>,
+) -> Result<Claims, ApiError> {
+    let now = Utc::now();
+    let api_key = db::api_key::find_by_hash(db, &hash_api_key(key))
+        .await
+        .map_err(|_| ApiError::internal("Failed to load API key"))?
+        .filter(|api_key| api_key.is_active(now))
+        .ok_or_else(invalid_token)?;
+
+    if !scope.is_some_and(|scope| api_key.has_scope(scope)) {
+        return Err(ApiError::forbidden("The API key lacks the scope for this endpoint"));
+    }
+
+    let user = db::user::find_by_id(db, &api_key.user_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load user"))?
+        .filter(|user| user.deleted_at.is_none())
+        .ok_or_else(invalid_token)?;
+
+    db::api_key::record_use(db, &api_key.id, ip_address)
+        .await
+        .map_err(|_| ApiError::internal("Failed to record API key use"))?;
+
+    info!("Authenticated user {} with API key {}", user.email, api_key.prefix);
+    Ok(Claims {
+        sub: user.id,
+        email: user.email,
+        exp: api_key.expires_at.map_or(usize::MAX, |expires_at| expires_at.timestamp().max(0) as usize),
+        sid: None,
+    })
+}
+
+/// Authenticates the session token of a tax advisor and makes the grant
+/// available to the portal handlers. Revoked and expired grants end the
+/// session at once.
+pub async fn advisor_middleware(
+    State(keys): State<Arc<JwtKeys>>,
+    State(db): State<Db>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let claims = request
+        .headers()
+        .get("Authorization")
+        .and_then(|header| header.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .and_then(|token| validate_advisor_token(&keys, token).ok())
+        .ok_or_else(invalid_token)?;
 
-    match validate_token(auth_header) {
-        Ok(claims) => {
-            info!("Authenticated user: {}", claims.email);
-            // In a real implementation, you'd attach the claims to the request
-            Ok(next.run(request).await)
+    let grant = db::advisor_grant::find_by_id(&db, &claims.sub)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load advisor grant"))?
+        .filter(|grant| grant.is_active(Utc::now()))
+        .ok_or_else(invalid_token)?;
+
+    info!("Authenticated advisor {} for grant {}", grant.advisor_email, grant.id);
+    request.extensions_mut().insert(grant);
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{test_db, test_user};
+    use crate::models::session::Session;
+
+    fn claims(user_id: &str, sid: Option<&str>) -> Claims {
+        Claims {
+            sub: user_id.to_string(),
+            email: "max@example.com".to_string(),
+            exp: usize::MAX,
+            sid: sid.map(str::to_string),
         }
-        Err(_) => Err(StatusCode::UNAUTHORIZED),
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn tokens_of_revoked_sessions_are_rejected() {
+        let db = test_db().await;
+        let user = test_user(&db).await;
+        let first = Session::new(user.id.clone(), None, None);
+        let second = Session::new(user.id.clone(), None, None);
+        db::session::create(&db, &first, "first").await.unwrap();
+        db::session::create(&db, &second, "second").await.unwrap();
+
+        assert!(check_session(&db, &claims(&user.id, Some(&first.id))).await.is_ok());
+        assert!(check_session(&db, &claims(&user.id, None)).await.is_err());
+        assert!(check_session(&db, &claims("someone-else", Some(&first.id))).await.is_err());
+
+        db::session::revoke_by_token(&db, "first").await.unwrap();
+        assert!(check_session(&db, &claims(&user.id, Some(&first.id))).await.is_err());
+        assert!(check_session(&db, &claims(&user.id, Some(&second.id))).await.is_ok());
+
+        db::session::revoke_all(&db, &user.id).await.unwrap();
+        assert!(check_session(&db, &claims(&user.id, Some(&second.id))).await.is_err());
+    }
+}
@@ -1,2 +1,6 @@
 pub mod jwt;
-pub mod middleware;
\ No newline at end of file
+pub mod keys;
+pub mod member;
+pub mod middleware;
+pub mod oidc;
+pub mod totp;
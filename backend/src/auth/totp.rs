@@ -0,0 +1,135 @@
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha1::Sha1;
+
+/// RFC 6238 defaults, which all common authenticator apps expect.
+pub const DIGITS: u32 = 6;
+pub const STEP_SECONDS: u64 = 30;
+/// Codes of the previous and the next step are accepted for clock drift.
+const ALLOWED_DRIFT_STEPS: u64 = 1;
+const SECRET_BYTES: usize = 20;
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// RFC 4648 base32 without padding, as used in otpauth URIs.
+pub fn base32_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(5) {
+        let mut buffer = [0u8; 5];
+        buffer[..chunk.len()].copy_from_slice(chunk);
+        let bits = buffer.iter().fold(0u64, |bits, byte| (bits << 8) | *byte as u64);
+        let characters = (chunk.len() * 8).div_ceil(5);
+        for index in 0..characters {
+            encoded.push(BASE32_ALPHABET[((bits >> (35 - index * 5)) & 31) as usize] as char);
+        }
+    }
+    encoded
+}
+
+pub fn base32_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let (mut bits, mut length) = (0u64, 0);
+    for character in encoded.trim_end_matches('=').chars().filter(|c| !c.is_whitespace()) {
+        let value = BASE32_ALPHABET.iter().position(|c| *c as char == character.to_ascii_uppercase())?;
+        bits = (bits << 5) | value as u64;
+        length += 5;
+        if length >= 8 {
+            length -= 8;
+            bytes.push((bits >> length) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+/// A new random secret, base32-encoded.
+pub fn generate_secret() -> String {
+    let mut secret = [0u8; SECRET_BYTES];
+    rand::rngs::OsRng.fill_bytes(&mut secret);
+    base32_encode(&secret)
+}
+
+/// The `otpauth://` URI authenticator apps read from a QR code.
+pub fn provisioning_uri(secret: &str, account: &str) -> String {
+    let issuer = "MiniDebet";
+    let encode = |value: &str| {
+        value
+            .bytes()
+            .map(|byte| match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+                _ => format!("%{:02X}", byte),
+            })
+            .collect::<String>()
+    };
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        issuer, encode(account), secret, issuer, DIGITS, STEP_SECONDS
+    )
+}
+
+fn hotp(secret: &[u8], counter: u64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let code = u32::from_be_bytes([hash[offset] & 0x7f, hash[offset + 1], hash[offset + 2], hash[offset + 3]]);
+    code % 10u32.pow(DIGITS)
+}
+
+/// The code for a Unix time, formatted with leading zeros.
+pub fn code_at(secret: &[u8], unix_time: u64) -> String {
+    format!("{:0width$}", hotp(secret, unix_time / STEP_SECONDS), width = DIGITS as usize)
+}
+
+/// Verifies a code against the steps around `unix_time`. Returns the matched
+/// step, which has to be stored: codes of that or an earlier step are
+/// rejected afterwards so a code cannot be used twice.
+pub fn verify(secret: &str, code: &str, unix_time: u64, last_used_step: Option<i64>) -> Option<i64> {
+    let secret = base32_decode(secret)?;
+    let code = code.trim().replace(' ', "");
+    if code.len() != DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let current = unix_time / STEP_SECONDS;
+    (current.saturating_sub(ALLOWED_DRIFT_STEPS)..=current + ALLOWED_DRIFT_STEPS)
+        .filter(|step| last_used_step.is_none_or(|last| *step as i64 > last))
+        .find(|step| code_at(&secret, step * STEP_SECONDS) == code)
+        .map(|step| step as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 6238 Appendix B, SHA-1 secret, truncated to six digits
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn codes_match_the_rfc_test_vectors() {
+        assert_eq!(code_at(RFC_SECRET, 59), "287082");
+        assert_eq!(code_at(RFC_SECRET, 1111111109), "081804");
+        assert_eq!(code_at(RFC_SECRET, 1234567890), "005924");
+        assert_eq!(code_at(RFC_SECRET, 2000000000), "279037");
+    }
+
+    #[test]
+    fn base32_round_trips() {
+        assert_eq!(base32_encode(RFC_SECRET), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(base32_decode("gezdgnbvgy3tqojqgezdgnbvgy3tqojq").unwrap(), RFC_SECRET);
+        assert_eq!(base32_encode(b"f"), "MY");
+        assert_eq!(base32_decode("MY======").unwrap(), b"f");
+        assert!(base32_decode("not base32!").is_none());
+    }
+
+    #[test]
+    fn codes_are_accepted_once_within_the_drift_window() {
+        let secret = base32_encode(RFC_SECRET);
+        let step = (1111111109 / STEP_SECONDS) as i64;
+
+        assert_eq!(verify(&secret, "081804", 1111111109, None), Some(step));
+        assert_eq!(verify(&secret, "081 804", 1111111109 + STEP_SECONDS, None), Some(step));
+        assert_eq!(verify(&secret, "081804", 1111111109 + 3 * STEP_SECONDS, None), None);
+        assert_eq!(verify(&secret, "081804", 1111111109, Some(step)), None);
+        assert_eq!(verify(&secret, "81804", 1111111109, None), None);
+    }
+}
@@ -0,0 +1,208 @@
+use chrono::Utc;
+use crate::db::{audit, Db};
+use crate::models::advisor_grant::{AdvisorAccess, AdvisorGrant, AdvisorLink, ADVISOR_ACCESS_ENTITY};
+use crate::models::audit::AuditEntry;
+
+/// All grants of an organisation including revoked and expired ones, newest
+/// first.
+pub async fn find_by_user(db: &Db, user_id: &str) -> Result<Vec<AdvisorGrant>, sqlx::Error> {
+    sqlx::query_as::<_, AdvisorGrant>("SELECT * FROM advisor_grants WHERE user_id = ? ORDER BY created_at DESC")
+        .bind(user_id)
+        .fetch_all(db.as_ref())
+        .await
+}
+
+/// A grant of an organisation whose account has not been deleted.
+pub async fn find_by_id(db: &Db, id: &str) -> Result<Option<AdvisorGrant>, sqlx::Error> {
+    sqlx::query_as::<_, AdvisorGrant>(
+        "SELECT g.* FROM advisor_grants g JOIN users u ON u.id = g.user_id
+         WHERE g.id = ? AND u.deleted_at IS NULL",
+    )
+    .bind(id)
+    .fetch_optional(db.as_ref())
+    .await
+}
+
+/// Active grants for an advisor's address, for mailing login links.
+pub async fn find_active_by_email(db: &Db, email: &str) -> Result<Vec<AdvisorGrant>, sqlx::Error> {
+    sqlx::query_as::<_, AdvisorGrant>(
+        "SELECT g.* FROM advisor_grants g JOIN users u ON u.id = g.user_id
+         WHERE g.advisor_email = ? AND g.revoked_at IS NULL AND g.expires_at > ? AND u.deleted_at IS NULL",
+    )
+    .bind(email.trim().to_lowercase())
+    .bind(Utc::now())
+    .fetch_all(db.as_ref())
+    .await
+}
+
+pub async fn create(db: &Db, actor_id: &str, grant: &AdvisorGrant) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    sqlx::query(
+        "INSERT INTO advisor_grants (id, user_id, advisor_email, advisor_name, fiscal_years, created_by, created_at, expires_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&grant.id)
+    .bind(&grant.user_id)
+    .bind(&grant.advisor_email)
+    .bind(&grant.advisor_name)
+    .bind(&grant.fiscal_years)
+    .bind(&grant.created_by)
+    .bind(grant.created_at)
+    .bind(grant.expires_at)
+    .execute(&mut *tx)
+    .await?;
+
+    audit::append(
+        &mut tx,
+        AuditEntry::new(&grant.user_id, actor_id, "advisor_grant", &grant.id, None, Some(grant)),
+    )
+    .await?;
+
+    tx.commit().await
+}
+
+/// Ends the grant and invalidates its open links. Returns false for unknown
+/// grants and grants that were already revoked.
+pub async fn revoke(db: &Db, actor_id: &str, user_id: &str, id: &str) -> Result<bool, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let grant = sqlx::query_as::<_, AdvisorGrant>(
+        "SELECT * FROM advisor_grants WHERE id = ? AND user_id = ? AND revoked_at IS NULL",
+    )
+    .bind(id)
+    .bind(user_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some(grant) = grant else {
+        return Ok(false);
+    };
+
+    let revoked = AdvisorGrant { revoked_at: Some(Utc::now()), ..grant.clone() };
+    sqlx::query("UPDATE advisor_grants SET revoked_at = ? WHERE id = ?")
+        .bind(revoked.revoked_at)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM advisor_links WHERE grant_id = ?")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+    audit::append(
+        &mut tx,
+        AuditEntry::new(user_id, actor_id, "advisor_grant", id, Some(&grant), Some(&revoked)),
+    )
+    .await?;
+
+    tx.commit().await?;
+    Ok(true)
+}
+
+pub async fn create_link(db: &Db, link: &AdvisorLink) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO advisor_links (id, grant_id, token_hash, created_at, expires_at) VALUES (?, ?, ?, ?, ?)",
+    )
+    .bind(&link.id)
+    .bind(&link.grant_id)
+    .bind(&link.token_hash)
+    .bind(link.created_at)
+    .bind(link.expires_at)
+    .execute(db.as_ref())
+    .await?;
+
+    Ok(())
+}
+
+pub async fn find_link_by_hash(db: &Db, token_hash: &str) -> Result<Option<AdvisorLink>, sqlx::Error> {
+    sqlx::query_as::<_, AdvisorLink>("SELECT * FROM advisor_links WHERE token_hash = ?")
+        .bind(token_hash)
+        .fetch_optional(db.as_ref())
+        .await
+}
+
+/// Marks the link as used. Returns false if it was used in the meantime.
+pub async fn use_link(db: &Db, id: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("UPDATE advisor_links SET used_at = ? WHERE id = ? AND used_at IS NULL")
+        .bind(Utc::now())
+        .bind(id)
+        .execute(db.as_ref())
+        .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+/// Records an access of the advisor in the owner's audit log.
+pub async fn record_access(
+    db: &Db,
+    grant: &AdvisorGrant,
+    resource: &str,
+    fiscal_year: Option<i32>,
+) -> Result<(), sqlx::Error> {
+    let access = AdvisorAccess {
+        grant_id: grant.id.clone(),
+        advisor_email: grant.advisor_email.clone(),
+        resource: resource.to_string(),
+        fiscal_year,
+    };
+    let mut tx = db.begin().await?;
+
+    sqlx::query("UPDATE advisor_grants SET last_accessed_at = ? WHERE id = ?")
+        .bind(Utc::now())
+        .bind(&grant.id)
+        .execute(&mut *tx)
+        .await?;
+
+    audit::append(
+        &mut tx,
+        AuditEntry::access(&grant.user_id, &grant.actor_id(), ADVISOR_ACCESS_ENTITY, &grant.id, &access),
+    )
+    .await?;
+
+    tx.commit().await
+}
+
+/// Deletes links that were used or expired.
+pub async fn purge_inactive_links(db: &Db) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM advisor_links WHERE used_at IS NOT NULL OR expires_at <= ?")
+        .bind(Utc::now())
+        .execute(db.as_ref())
+        .await?;
+
+    Ok(result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{test_db, test_user};
+    use crate::models::advisor_grant::NewAdvisorGrant;
+    use crate::models::audit::verify_chain;
+    use chrono::{Datelike, Duration};
+
+    #[tokio::test]
+    async fn advisor_reads_are_audited() {
+        let db = test_db().await;
+        let user = test_user(&db).await;
+        let now = Utc::now();
+        let grant = NewAdvisorGrant {
+            advisor_email: "steuer@example.com".to_string(),
+            advisor_name: None,
+            fiscal_years: vec![now.year()],
+            expires_at: now + Duration::days(30),
+        }
+        .into_grant(user.id.clone(), user.id.clone(), now)
+        .unwrap();
+        create(&db, &user.id, &grant).await.unwrap();
+
+        record_access(&db, &grant, "invoices", Some(now.year())).await.unwrap();
+
+        let entries = audit::find_by_user(&db, &user.id).await.unwrap();
+        let entry = entries.iter().find(|entry| entry.entity_type == ADVISOR_ACCESS_ENTITY).unwrap();
+        assert_eq!(entry.action, "access");
+        assert_eq!(entry.actor_id, grant.actor_id());
+        assert_eq!(entry.after_data.as_ref().unwrap()["resource"], "invoices");
+        assert!(verify_chain(&entries, None).valid);
+    }
+}
@@ -0,0 +1,73 @@
+use crate::db::{audit, invoice, Db};
+use crate::models::allowance_charge::AllowanceCharge;
+use crate::models::audit::AuditEntry;
+use crate::models::invoice::Invoice;
+
+pub async fn find_by_invoice(db: &Db, invoice_id: &str) -> Result<Vec<AllowanceCharge>, sqlx::Error> {
+    sqlx::query_as::<_, AllowanceCharge>(
+        "SELECT * FROM invoice_allowance_charges WHERE invoice_id = ? ORDER BY created_at",
+    )
+    .bind(invoice_id)
+    .fetch_all(db.as_ref())
+    .await
+}
+
+/// Adds an allowance or charge to a draft invoice and recalculates its totals.
+pub async fn create(
+    db: &Db,
+    actor_id: &str,
+    invoice: &Invoice,
+    allowance_charge: &AllowanceCharge,
+) -> Result<(Invoice, AllowanceCharge), sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    sqlx::query(
+        "INSERT INTO invoice_allowance_charges (id, invoice_id, kind, reason, percent, amount, tax_category, created_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&allowance_charge.id)
+    .bind(&allowance_charge.invoice_id)
+    .bind(&allowance_charge.kind)
+    .bind(&allowance_charge.reason)
+    .bind(allowance_charge.percent)
+    .bind(allowance_charge.amount)
+    .bind(&allowance_charge.tax_category)
+    .bind(allowance_charge.created_at)
+    .execute(&mut *tx)
+    .await?;
+
+    let entry = AuditEntry::new(&invoice.user_id, actor_id, "invoice_allowance_charge", &allowance_charge.id, None, Some(allowance_charge));
+    audit::append(&mut tx, entry).await?;
+
+    let updated = invoice::recalculate(&mut tx, actor_id, invoice).await?;
+    let created = sqlx::query_as::<_, AllowanceCharge>("SELECT * FROM invoice_allowance_charges WHERE id = ?")
+        .bind(&allowance_charge.id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok((updated, created))
+}
+
+pub async fn delete(
+    db: &Db,
+    actor_id: &str,
+    invoice: &Invoice,
+    allowance_charge: &AllowanceCharge,
+) -> Result<Invoice, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    sqlx::query("DELETE FROM invoice_allowance_charges WHERE id = ? AND invoice_id = ?")
+        .bind(&allowance_charge.id)
+        .bind(&invoice.id)
+        .execute(&mut *tx)
+        .await?;
+
+    let entry = AuditEntry::new(&invoice.user_id, actor_id, "invoice_allowance_charge", &allowance_charge.id, Some(allowance_charge), None);
+    audit::append(&mut tx, entry).await?;
+
+    let updated = invoice::recalculate(&mut tx, actor_id, invoice).await?;
+
+    tx.commit().await?;
+    Ok(updated)
+}
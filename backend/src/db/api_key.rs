@@ -0,0 +1,61 @@
+use chrono::Utc;
+use crate::db::Db;
+use crate::models::api_key::ApiKey;
+
+/// All keys of a user including revoked ones, newest first.
+pub async fn find_by_user(db: &Db, user_id: &str) -> Result<Vec<ApiKey>, sqlx::Error> {
+    sqlx::query_as::<_, ApiKey>("SELECT * FROM api_keys WHERE user_id = ? ORDER BY created_at DESC")
+        .bind(user_id)
+        .fetch_all(db.as_ref())
+        .await
+}
+
+pub async fn find_by_hash(db: &Db, key_hash: &str) -> Result<Option<ApiKey>, sqlx::Error> {
+    sqlx::query_as::<_, ApiKey>("SELECT * FROM api_keys WHERE key_hash = ?")
+        .bind(key_hash)
+        .fetch_optional(db.as_ref())
+        .await
+}
+
+pub async fn create(db: &Db, api_key: &ApiKey) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO api_keys (id, user_id, name, prefix, key_hash, scopes, created_at, expires_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&api_key.id)
+    .bind(&api_key.user_id)
+    .bind(&api_key.name)
+    .bind(&api_key.prefix)
+    .bind(&api_key.key_hash)
+    .bind(&api_key.scopes)
+    .bind(api_key.created_at)
+    .bind(api_key.expires_at)
+    .execute(db.as_ref())
+    .await?;
+
+    Ok(())
+}
+
+/// Records a request made with the key.
+pub async fn record_use(db: &Db, id: &str, ip_address: Option<&str>) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE api_keys SET last_used_at = ?, last_used_ip = COALESCE(?, last_used_ip) WHERE id = ?")
+        .bind(Utc::now())
+        .bind(ip_address)
+        .bind(id)
+        .execute(db.as_ref())
+        .await?;
+
+    Ok(())
+}
+
+/// Returns false for unknown keys and keys that were already revoked.
+pub async fn revoke(db: &Db, user_id: &str, id: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("UPDATE api_keys SET revoked_at = ? WHERE id = ? AND user_id = ? AND revoked_at IS NULL")
+        .bind(Utc::now())
+        .bind(id)
+        .bind(user_id)
+        .execute(db.as_ref())
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
@@ -0,0 +1,120 @@
+use sqlx::SqliteConnection;
+use crate::db::{audit, Db};
+use crate::models::audit::AuditEntry;
+use crate::models::catalog::CatalogItem;
+
+/// Catalogue items of a user ordered by item number. `query` matches item
+/// number or description, case-insensitively; archived items are only
+/// included on request.
+pub async fn search(
+    db: &Db,
+    user_id: &str,
+    query: Option<&str>,
+    include_archived: bool,
+) -> Result<Vec<CatalogItem>, sqlx::Error> {
+    let pattern = format!("%{}%", query.unwrap_or_default().trim());
+
+    sqlx::query_as::<_, CatalogItem>(
+        "SELECT * FROM catalog_items
+         WHERE user_id = ?
+           AND (item_number LIKE ? OR description LIKE ?)
+           AND (? OR archived_at IS NULL)
+         ORDER BY item_number",
+    )
+    .bind(user_id)
+    .bind(&pattern)
+    .bind(&pattern)
+    .bind(include_archived)
+    .fetch_all(db.as_ref())
+    .await
+}
+
+pub async fn find_by_id(db: &Db, user_id: &str, id: &str) -> Result<Option<CatalogItem>, sqlx::Error> {
+    sqlx::query_as::<_, CatalogItem>("SELECT * FROM catalog_items WHERE id = ? AND user_id = ?")
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(db.as_ref())
+        .await
+}
+
+async fn load(conn: &mut SqliteConnection, id: &str) -> Result<Option<CatalogItem>, sqlx::Error> {
+    sqlx::query_as::<_, CatalogItem>("SELECT * FROM catalog_items WHERE id = ?")
+        .bind(id)
+        .fetch_optional(conn)
+        .await
+}
+
+pub async fn create(db: &Db, actor_id: &str, item: &CatalogItem) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    sqlx::query(
+        "INSERT INTO catalog_items (id, user_id, item_number, description, unit_code, net_price, tax_category,
+            revenue_account, archived_at, created_at, updated_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&item.id)
+    .bind(&item.user_id)
+    .bind(&item.item_number)
+    .bind(&item.description)
+    .bind(&item.unit_code)
+    .bind(item.net_price)
+    .bind(&item.tax_category)
+    .bind(&item.revenue_account)
+    .bind(item.archived_at)
+    .bind(item.created_at)
+    .bind(item.updated_at)
+    .execute(&mut *tx)
+    .await?;
+
+    let entry = AuditEntry::new(&item.user_id, actor_id, "catalog_item", &item.id, None, Some(item));
+    audit::append(&mut tx, entry).await?;
+
+    tx.commit().await
+}
+
+pub async fn update(db: &Db, actor_id: &str, item: &CatalogItem) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let before = load(&mut tx, &item.id).await?;
+
+    sqlx::query(
+        "UPDATE catalog_items SET item_number = ?, description = ?, unit_code = ?, net_price = ?, tax_category = ?,
+            revenue_account = ?, archived_at = ?, updated_at = ?
+         WHERE id = ? AND user_id = ?",
+    )
+    .bind(&item.item_number)
+    .bind(&item.description)
+    .bind(&item.unit_code)
+    .bind(item.net_price)
+    .bind(&item.tax_category)
+    .bind(&item.revenue_account)
+    .bind(item.archived_at)
+    .bind(item.updated_at)
+    .bind(&item.id)
+    .bind(&item.user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    let entry = AuditEntry::new(&item.user_id, actor_id, "catalog_item", &item.id, before.as_ref(), Some(item));
+    audit::append(&mut tx, entry).await?;
+
+    tx.commit().await
+}
+
+/// Invoice items keep their copy of a deleted catalogue item.
+pub async fn delete(db: &Db, actor_id: &str, user_id: &str, id: &str) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let before = load(&mut tx, id).await?;
+
+    sqlx::query("DELETE FROM catalog_items WHERE id = ? AND user_id = ?")
+        .bind(id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    if let Some(before) = before.filter(|item| item.user_id == user_id) {
+        let entry = AuditEntry::new(user_id, actor_id, "catalog_item", id, Some(&before), None);
+        audit::append(&mut tx, entry).await?;
+    }
+
+    tx.commit().await
+}
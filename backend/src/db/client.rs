@@ -0,0 +1,41 @@
+use chrono::Utc;
+use crate::db::{audit, Db};
+use crate::models::audit::AuditEntry;
+use crate::models::client::Client;
+
+pub async fn find_by_user(db: &Db, user_id: &str) -> Result<Vec<Client>, sqlx::Error> {
+    sqlx::query_as::<_, Client>("SELECT * FROM clients WHERE user_id = ? ORDER BY name")
+        .bind(user_id)
+        .fetch_all(db.as_ref())
+        .await
+}
+
+pub async fn find_by_id(db: &Db, user_id: &str, id: &str) -> Result<Option<Client>, sqlx::Error> {
+    sqlx::query_as::<_, Client>("SELECT * FROM clients WHERE id = ? AND user_id = ?")
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(db.as_ref())
+        .await
+}
+
+/// Marks the client as deleted. The record stays until the invoices issued to
+/// it have passed their retention period.
+pub async fn soft_delete(db: &Db, actor_id: &str, client: &Client) -> Result<(), sqlx::Error> {
+    let mut deleted = client.clone();
+    deleted.deleted_at = Some(Utc::now());
+    deleted.updated_at = Utc::now();
+
+    let mut tx = db.begin().await?;
+
+    sqlx::query("UPDATE clients SET deleted_at = ?, updated_at = ? WHERE id = ?")
+        .bind(deleted.deleted_at)
+        .bind(deleted.updated_at)
+        .bind(&client.id)
+        .execute(&mut *tx)
+        .await?;
+
+    let entry = AuditEntry::new(&client.user_id, actor_id, "client", &client.id, Some(client), Some(&deleted));
+    audit::append(&mut tx, entry).await?;
+
+    tx.commit().await
+}
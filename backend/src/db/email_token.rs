@@ -0,0 +1,132 @@
+use chrono::Utc;
+use sqlx::SqliteConnection;
+use crate::db::{audit, Db};
+use crate::models::audit::AuditEntry;
+use crate::models::email_token::{EmailToken, EmailTokenPurpose};
+use crate::models::user::User;
+
+pub async fn create(db: &Db, token: &EmailToken) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO email_tokens (id, user_id, purpose, email, created_at, expires_at) VALUES (?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&token.id)
+    .bind(&token.user_id)
+    .bind(&token.purpose)
+    .bind(&token.email)
+    .bind(token.created_at)
+    .bind(token.expires_at)
+    .execute(db.as_ref())
+    .await?;
+
+    Ok(())
+}
+
+/// Marks the token as used and returns its user. All other open tokens of the
+/// same purpose are used up as well, so only the latest link works once one
+/// has been followed. Returns `None` for used and expired tokens and for
+/// tokens sent to an address the account no longer has.
+async fn consume(conn: &mut SqliteConnection, id: &str, purpose: EmailTokenPurpose) -> Result<Option<User>, sqlx::Error> {
+    let now = Utc::now();
+    let token = sqlx::query_as::<_, EmailToken>("SELECT * FROM email_tokens WHERE id = ?")
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .await?;
+    let Some(token) = token.filter(|token| token.is_usable(purpose, now)) else {
+        return Ok(None);
+    };
+
+    let used = sqlx::query("UPDATE email_tokens SET used_at = ? WHERE id = ? AND used_at IS NULL")
+        .bind(now)
+        .bind(&token.id)
+        .execute(&mut *conn)
+        .await?;
+    if used.rows_affected() != 1 {
+        return Ok(None);
+    }
+
+    sqlx::query("UPDATE email_tokens SET used_at = ? WHERE user_id = ? AND purpose = ? AND used_at IS NULL")
+        .bind(now)
+        .bind(&token.user_id)
+        .bind(&token.purpose)
+        .execute(&mut *conn)
+        .await?;
+
+    let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ? AND deleted_at IS NULL")
+        .bind(&token.user_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+    Ok(user.filter(|user| user.email == token.email))
+}
+
+/// Confirms the email address with a verification token.
+pub async fn verify_email(db: &Db, id: &str) -> Result<Option<User>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let Some(user) = consume(&mut tx, id, EmailTokenPurpose::VerifyEmail).await? else {
+        tx.commit().await?;
+        return Ok(None);
+    };
+
+    let mut verified = user.clone();
+    verified.email_verified_at = user.email_verified_at.or(Some(Utc::now()));
+    verified.updated_at = Utc::now();
+
+    sqlx::query("UPDATE users SET email_verified_at = ?, updated_at = ? WHERE id = ?")
+        .bind(verified.email_verified_at)
+        .bind(verified.updated_at)
+        .bind(&user.id)
+        .execute(&mut *tx)
+        .await?;
+
+    let entry = AuditEntry::new(&user.id, &user.id, "user", &user.id, Some(&user), Some(&verified));
+    audit::append(&mut tx, entry).await?;
+
+    tx.commit().await?;
+    Ok(Some(verified))
+}
+
+/// Sets a new password with a reset token and ends all sessions of the
+/// account. Following the link also proves the address.
+pub async fn reset_password(db: &Db, id: &str, password_hash: &str) -> Result<Option<User>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let Some(user) = consume(&mut tx, id, EmailTokenPurpose::ResetPassword).await? else {
+        tx.commit().await?;
+        return Ok(None);
+    };
+
+    let mut updated = user.clone();
+    updated.password_hash = password_hash.to_string();
+    updated.email_verified_at = user.email_verified_at.or(Some(Utc::now()));
+    updated.updated_at = Utc::now();
+
+    sqlx::query("UPDATE users SET password_hash = ?, email_verified_at = ?, updated_at = ? WHERE id = ?")
+        .bind(&updated.password_hash)
+        .bind(updated.email_verified_at)
+        .bind(updated.updated_at)
+        .bind(&user.id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("UPDATE sessions SET revoked_at = ?, revoked_reason = 'logout_all' WHERE user_id = ? AND revoked_at IS NULL")
+        .bind(updated.updated_at)
+        .bind(&user.id)
+        .execute(&mut *tx)
+        .await?;
+
+    let entry = AuditEntry::new(&user.id, &user.id, "user", &user.id, Some(&user), Some(&updated));
+    audit::append(&mut tx, entry).await?;
+
+    tx.commit().await?;
+    Ok(Some(updated))
+}
+
+pub async fn purge_inactive(db: &Db) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM email_tokens WHERE used_at IS NOT NULL OR expires_at <= ?")
+        .bind(Utc::now())
+        .execute(db.as_ref())
+        .await?;
+
+    Ok(result.rows_affected())
+}
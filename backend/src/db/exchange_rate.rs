@@ -0,0 +1,73 @@
+use chrono::{NaiveDate, Utc};
+use crate::db::Db;
+use crate::models::exchange_rate::{EcbRate, ExchangeRate};
+
+pub async fn find_by_user(
+    db: &Db,
+    user_id: &str,
+    currency: Option<&str>,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> Result<Vec<ExchangeRate>, sqlx::Error> {
+    sqlx::query_as::<_, ExchangeRate>(
+        "SELECT * FROM exchange_rates
+         WHERE user_id = ?
+           AND (? IS NULL OR currency = ?)
+           AND (? IS NULL OR rate_date >= ?)
+           AND (? IS NULL OR rate_date <= ?)
+         ORDER BY rate_date DESC, currency",
+    )
+    .bind(user_id)
+    .bind(currency)
+    .bind(currency)
+    .bind(from)
+    .bind(from)
+    .bind(to)
+    .bind(to)
+    .fetch_all(db.as_ref())
+    .await
+}
+
+/// The latest rate published on or before `date`.
+pub async fn find_for_date(
+    db: &Db,
+    user_id: &str,
+    currency: &str,
+    date: NaiveDate,
+) -> Result<Option<ExchangeRate>, sqlx::Error> {
+    sqlx::query_as::<_, ExchangeRate>(
+        "SELECT * FROM exchange_rates
+         WHERE user_id = ? AND currency = ? AND rate_date <= ?
+         ORDER BY rate_date DESC
+         LIMIT 1",
+    )
+    .bind(user_id)
+    .bind(currency)
+    .bind(date)
+    .fetch_optional(db.as_ref())
+    .await
+}
+
+/// Stores imported rates, replacing those already stored for the same day.
+/// Invoices keep their own copy of the rate, so replacing one does not change
+/// them.
+pub async fn import(db: &Db, user_id: &str, rates: &[EcbRate]) -> Result<(), sqlx::Error> {
+    let now = Utc::now();
+    let mut tx = db.begin().await?;
+
+    for rate in rates {
+        sqlx::query(
+            "INSERT OR REPLACE INTO exchange_rates (user_id, currency, rate_date, rate, imported_at)
+             VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(user_id)
+        .bind(&rate.currency)
+        .bind(rate.rate_date)
+        .bind(rate.rate)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await
+}
@@ -0,0 +1,143 @@
+use chrono::Utc;
+use sqlx::SqliteConnection;
+use crate::db::{audit, Db};
+use crate::models::audit::AuditEntry;
+use crate::models::expense::Expense;
+
+pub async fn find_by_user(db: &Db, user_id: &str) -> Result<Vec<Expense>, sqlx::Error> {
+    sqlx::query_as::<_, Expense>(
+        "SELECT * FROM expenses WHERE user_id = ? ORDER BY expense_date",
+    )
+    .bind(user_id)
+    .fetch_all(db.as_ref())
+    .await
+}
+
+pub async fn find_by_id(db: &Db, user_id: &str, id: &str) -> Result<Option<Expense>, sqlx::Error> {
+    sqlx::query_as::<_, Expense>("SELECT * FROM expenses WHERE id = ? AND user_id = ?")
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(db.as_ref())
+        .await
+}
+
+async fn load(conn: &mut SqliteConnection, id: &str) -> Result<Option<Expense>, sqlx::Error> {
+    sqlx::query_as::<_, Expense>("SELECT * FROM expenses WHERE id = ?")
+        .bind(id)
+        .fetch_optional(conn)
+        .await
+}
+
+pub async fn create(db: &Db, actor_id: &str, expense: &Expense) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    sqlx::query(
+        "INSERT INTO expenses (id, user_id, vendor, description, expense_date, category, net_amount, tax_rate, tax_amount, gross_amount, tax_treatment, supplier_country, deductible_tax_amount, payment_status, paid_at, created_at, updated_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&expense.id)
+    .bind(&expense.user_id)
+    .bind(&expense.vendor)
+    .bind(&expense.description)
+    .bind(expense.expense_date)
+    .bind(&expense.category)
+    .bind(expense.net_amount)
+    .bind(expense.tax_rate)
+    .bind(expense.tax_amount)
+    .bind(expense.gross_amount)
+    .bind(&expense.tax_treatment)
+    .bind(&expense.supplier_country)
+    .bind(expense.deductible_tax_amount)
+    .bind(&expense.payment_status)
+    .bind(expense.paid_at)
+    .bind(expense.created_at)
+    .bind(expense.updated_at)
+    .execute(&mut *tx)
+    .await?;
+
+    let entry = AuditEntry::new(&expense.user_id, actor_id, "expense", &expense.id, None, Some(expense));
+    audit::append(&mut tx, entry).await?;
+
+    tx.commit().await
+}
+
+pub async fn update(db: &Db, actor_id: &str, expense: &Expense) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let before = load(&mut tx, &expense.id).await?;
+
+    sqlx::query(
+        "UPDATE expenses SET vendor = ?, description = ?, expense_date = ?, category = ?, net_amount = ?, tax_rate = ?, tax_amount = ?, gross_amount = ?, tax_treatment = ?, supplier_country = ?, deductible_tax_amount = ?, payment_status = ?, paid_at = ?, updated_at = ?
+         WHERE id = ? AND user_id = ?",
+    )
+    .bind(&expense.vendor)
+    .bind(&expense.description)
+    .bind(expense.expense_date)
+    .bind(&expense.category)
+    .bind(expense.net_amount)
+    .bind(expense.tax_rate)
+    .bind(expense.tax_amount)
+    .bind(expense.gross_amount)
+    .bind(&expense.tax_treatment)
+    .bind(&expense.supplier_country)
+    .bind(expense.deductible_tax_amount)
+    .bind(&expense.payment_status)
+    .bind(expense.paid_at)
+    .bind(expense.updated_at)
+    .bind(&expense.id)
+    .bind(&expense.user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    let entry = AuditEntry::new(&expense.user_id, actor_id, "expense", &expense.id, before.as_ref(), Some(expense));
+    audit::append(&mut tx, entry).await?;
+
+    tx.commit().await
+}
+
+pub async fn set_receipt(
+    db: &Db,
+    actor_id: &str,
+    id: &str,
+    key: Option<&str>,
+    filename: Option<&str>,
+    content_type: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let before = load(&mut tx, id).await?;
+
+    sqlx::query(
+        "UPDATE expenses SET receipt_key = ?, receipt_filename = ?, receipt_content_type = ?, updated_at = ? WHERE id = ?",
+    )
+    .bind(key)
+    .bind(filename)
+    .bind(content_type)
+    .bind(Utc::now())
+    .bind(id)
+    .execute(&mut *tx)
+    .await?;
+
+    if let (Some(before), Some(after)) = (before, load(&mut tx, id).await?) {
+        let entry = AuditEntry::new(&after.user_id, actor_id, "expense", id, Some(&before), Some(&after));
+        audit::append(&mut tx, entry).await?;
+    }
+
+    tx.commit().await
+}
+
+pub async fn delete(db: &Db, actor_id: &str, user_id: &str, id: &str) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let before = load(&mut tx, id).await?;
+
+    sqlx::query("DELETE FROM expenses WHERE id = ? AND user_id = ?")
+        .bind(id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    if let Some(before) = before.filter(|expense| expense.user_id == user_id) {
+        let entry = AuditEntry::new(user_id, actor_id, "expense", id, Some(&before), None);
+        audit::append(&mut tx, entry).await?;
+    }
+
+    tx.commit().await
+}
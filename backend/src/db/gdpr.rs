@@ -0,0 +1,179 @@
+use chrono::Utc;
+use serde_json::json;
+use crate::db::{audit, Db};
+use crate::gdpr::{client_pseudonym, user_pseudonym_email, ErasureReport};
+use crate::models::audit::AuditEntry;
+use crate::models::client::Client;
+use crate::models::user::User;
+
+/// Deletes the drafts in the transaction, auditing only their ids.
+async fn delete_drafts(
+    tx: &mut sqlx::SqliteConnection,
+    actor_id: &str,
+    user_id: &str,
+    draft_ids: &[String],
+) -> Result<(), sqlx::Error> {
+    for id in draft_ids {
+        sqlx::query("DELETE FROM invoices WHERE id = ? AND status = 'draft'")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        let before = json!({ "id": id });
+        let entry = AuditEntry::new(user_id, actor_id, "invoice", id, Some(&before), None);
+        audit::append(&mut *tx, entry).await?;
+    }
+
+    Ok(())
+}
+
+/// Erases a client as planned in `report`. The audit entry only records the
+/// pseudonymised state, so the erased data is not copied into the log.
+pub async fn erase_client(
+    db: &Db,
+    actor_id: &str,
+    client: &Client,
+    draft_ids: &[String],
+    report: &ErasureReport,
+) -> Result<(), sqlx::Error> {
+    let now = Utc::now();
+    let mut erased = client.clone();
+    erased.email = None;
+    if report.fully_erased {
+        erased.name = client_pseudonym(&client.id);
+        erased.company = None;
+        erased.street = None;
+        erased.city = None;
+        erased.postal_code = None;
+        erased.vat_number = None;
+        erased.anonymized_at = Some(now);
+    }
+    erased.deleted_at = client.deleted_at.or(Some(now));
+    erased.updated_at = now;
+
+    let mut tx = db.begin().await?;
+
+    delete_drafts(&mut tx, actor_id, &client.user_id, draft_ids).await?;
+
+    sqlx::query(
+        "UPDATE clients SET name = ?, email = ?, company = ?, street = ?, city = ?, postal_code = ?, vat_number = ?, deleted_at = ?, anonymized_at = ?, updated_at = ?
+         WHERE id = ?",
+    )
+    .bind(&erased.name)
+    .bind(&erased.email)
+    .bind(&erased.company)
+    .bind(&erased.street)
+    .bind(&erased.city)
+    .bind(&erased.postal_code)
+    .bind(&erased.vat_number)
+    .bind(erased.deleted_at)
+    .bind(erased.anonymized_at)
+    .bind(erased.updated_at)
+    .bind(&client.id)
+    .execute(&mut *tx)
+    .await?;
+
+    let before = json!({ "id": client.id });
+    let after = serde_json::to_value(&erased).unwrap_or(before.clone());
+    let entry = AuditEntry::new(&client.user_id, actor_id, "client", &client.id, Some(&before), Some(&after));
+    audit::append(&mut tx, entry).await?;
+
+    tx.commit().await
+}
+
+/// Erases the account holder as planned in `report` and closes the account.
+pub async fn erase_user(
+    db: &Db,
+    actor_id: &str,
+    user: &User,
+    draft_ids: &[String],
+    report: &ErasureReport,
+) -> Result<(), sqlx::Error> {
+    let now = Utc::now();
+    let mut erased = user.clone();
+    erased.email = user_pseudonym_email(&user.id);
+    erased.password_hash = String::new();
+    erased.totp_secret = None;
+    erased.totp_enabled_at = None;
+    erased.totp_last_step = None;
+    erased.email_verified_at = None;
+    if report.fully_erased {
+        erased.first_name = None;
+        erased.last_name = None;
+        erased.company_name = None;
+        erased.tax_id = None;
+        erased.anonymized_at = Some(now);
+    }
+    erased.deleted_at = user.deleted_at.or(Some(now));
+    erased.updated_at = now;
+
+    let mut tx = db.begin().await?;
+
+    delete_drafts(&mut tx, actor_id, &user.id, draft_ids).await?;
+
+    // Logs the account out everywhere and removes the recorded IP addresses
+    sqlx::query("DELETE FROM sessions WHERE user_id = ?")
+        .bind(&user.id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM recovery_codes WHERE user_id = ?")
+        .bind(&user.id)
+        .execute(&mut *tx)
+        .await?;
+
+    // Ends the access of members and to other organisations
+    sqlx::query("DELETE FROM memberships WHERE organisation_id = ? OR user_id = ?")
+        .bind(&user.id)
+        .bind(&user.id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM invitations WHERE organisation_id = ?")
+        .bind(&user.id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM user_identities WHERE user_id = ?")
+        .bind(&user.id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM advisor_grants WHERE user_id = ?")
+        .bind(&user.id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM api_keys WHERE user_id = ?")
+        .bind(&user.id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM email_tokens WHERE user_id = ?")
+        .bind(&user.id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query(
+        "UPDATE users SET email = ?, password_hash = '', totp_secret = NULL, totp_enabled_at = NULL, totp_last_step = NULL, email_verified_at = NULL, first_name = ?, last_name = ?, company_name = ?, tax_id = ?, deleted_at = ?, anonymized_at = ?, updated_at = ?
+         WHERE id = ?",
+    )
+    .bind(&erased.email)
+    .bind(&erased.first_name)
+    .bind(&erased.last_name)
+    .bind(&erased.company_name)
+    .bind(&erased.tax_id)
+    .bind(erased.deleted_at)
+    .bind(erased.anonymized_at)
+    .bind(erased.updated_at)
+    .bind(&user.id)
+    .execute(&mut *tx)
+    .await?;
+
+    let before = json!({ "id": user.id });
+    let after = serde_json::to_value(&erased).unwrap_or(before.clone());
+    let entry = AuditEntry::new(&user.id, actor_id, "user", &user.id, Some(&before), Some(&after));
+    audit::append(&mut tx, entry).await?;
+
+    tx.commit().await
+}
@@ -0,0 +1,91 @@
+use chrono::Utc;
+use crate::db::Db;
+use crate::models::identity::{SsoLogin, UserIdentity};
+
+pub async fn find(db: &Db, provider: &str, subject: &str) -> Result<Option<UserIdentity>, sqlx::Error> {
+    sqlx::query_as::<_, UserIdentity>("SELECT * FROM user_identities WHERE provider = ? AND subject = ?")
+        .bind(provider)
+        .bind(subject)
+        .fetch_optional(db.as_ref())
+        .await
+}
+
+pub async fn find_by_user(db: &Db, user_id: &str) -> Result<Vec<UserIdentity>, sqlx::Error> {
+    sqlx::query_as::<_, UserIdentity>("SELECT * FROM user_identities WHERE user_id = ? ORDER BY created_at")
+        .bind(user_id)
+        .fetch_all(db.as_ref())
+        .await
+}
+
+pub async fn link(db: &Db, identity: &UserIdentity) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO user_identities (provider, subject, user_id, email, created_at) VALUES (?, ?, ?, ?, ?)",
+    )
+    .bind(&identity.provider)
+    .bind(&identity.subject)
+    .bind(&identity.user_id)
+    .bind(&identity.email)
+    .bind(identity.created_at)
+    .execute(db.as_ref())
+    .await?;
+
+    Ok(())
+}
+
+pub async fn record_login(db: &Db, provider: &str, subject: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE user_identities SET last_login_at = ? WHERE provider = ? AND subject = ?")
+        .bind(Utc::now())
+        .bind(provider)
+        .bind(subject)
+        .execute(db.as_ref())
+        .await?;
+
+    Ok(())
+}
+
+/// Unlinks the account's identities at a provider. Returns false if there
+/// were none.
+pub async fn unlink(db: &Db, user_id: &str, provider: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM user_identities WHERE user_id = ? AND provider = ?")
+        .bind(user_id)
+        .bind(provider)
+        .execute(db.as_ref())
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn create_login(db: &Db, login: &SsoLogin) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO sso_logins (state, provider, nonce, code_verifier, created_at, expires_at) VALUES (?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&login.state)
+    .bind(&login.provider)
+    .bind(&login.nonce)
+    .bind(&login.code_verifier)
+    .bind(login.created_at)
+    .bind(login.expires_at)
+    .execute(db.as_ref())
+    .await?;
+
+    Ok(())
+}
+
+/// Removes and returns the login for the state, so a callback can only be
+/// completed once.
+pub async fn take_login(db: &Db, state: &str) -> Result<Option<SsoLogin>, sqlx::Error> {
+    sqlx::query_as::<_, SsoLogin>("DELETE FROM sso_logins WHERE state = ? RETURNING *")
+        .bind(state)
+        .fetch_optional(db.as_ref())
+        .await
+}
+
+/// Deletes logins that were never completed.
+pub async fn purge_expired_logins(db: &Db) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM sso_logins WHERE expires_at <= ?")
+        .bind(Utc::now())
+        .execute(db.as_ref())
+        .await?;
+
+    Ok(result.rows_affected())
+}
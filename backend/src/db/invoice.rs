@@ -0,0 +1,377 @@
+use chrono::{DateTime, Utc};
+use sqlx::SqliteConnection;
+use crate::db::{audit, Db};
+use crate::models::audit::AuditEntry;
+use crate::models::advance_deduction::AdvanceDeduction;
+use crate::models::allowance_charge::AllowanceCharge;
+use crate::models::invoice::{Invoice, InvoiceItem, NewInvoiceItem};
+
+pub async fn find_by_user(db: &Db, user_id: &str) -> Result<Vec<Invoice>, sqlx::Error> {
+    sqlx::query_as::<_, Invoice>(
+        "SELECT * FROM invoices WHERE user_id = ? ORDER BY issue_date, invoice_number",
+    )
+    .bind(user_id)
+    .fetch_all(db.as_ref())
+    .await
+}
+
+pub async fn find_by_id(db: &Db, user_id: &str, id: &str) -> Result<Option<Invoice>, sqlx::Error> {
+    sqlx::query_as::<_, Invoice>("SELECT * FROM invoices WHERE id = ? AND user_id = ?")
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(db.as_ref())
+        .await
+}
+
+pub async fn find_items_by_user(db: &Db, user_id: &str) -> Result<Vec<InvoiceItem>, sqlx::Error> {
+    sqlx::query_as::<_, InvoiceItem>(
+        "SELECT invoice_items.* FROM invoice_items
+         JOIN invoices ON invoices.id = invoice_items.invoice_id
+         WHERE invoices.user_id = ?
+         ORDER BY invoices.invoice_number, invoice_items.created_at",
+    )
+    .bind(user_id)
+    .fetch_all(db.as_ref())
+    .await
+}
+
+async fn load(conn: &mut SqliteConnection, id: &str) -> Result<Option<Invoice>, sqlx::Error> {
+    sqlx::query_as::<_, Invoice>("SELECT * FROM invoices WHERE id = ?")
+        .bind(id)
+        .fetch_optional(conn)
+        .await
+}
+
+pub async fn mark_paid(db: &Db, actor_id: &str, id: &str, paid_at: DateTime<Utc>) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let before = load(&mut tx, id).await?;
+
+    sqlx::query("UPDATE invoices SET status = 'paid', paid_at = ?, updated_at = ? WHERE id = ?")
+        .bind(paid_at)
+        .bind(Utc::now())
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+    if let (Some(before), Some(after)) = (before, load(&mut tx, id).await?) {
+        let entry = AuditEntry::new(&after.user_id, actor_id, "invoice", id, Some(&before), Some(&after));
+        audit::append(&mut tx, entry).await?;
+    }
+
+    tx.commit().await
+}
+
+/// Inserts items into a draft invoice within the caller's transaction and
+/// recomputes the invoice totals. Returns the updated invoice and the items.
+pub async fn insert_items(
+    conn: &mut SqliteConnection,
+    actor_id: &str,
+    invoice: &Invoice,
+    new_items: &[&NewInvoiceItem],
+) -> Result<(Invoice, Vec<InvoiceItem>), sqlx::Error> {
+    let mut items = Vec::new();
+
+    for new_item in new_items {
+        let item = InvoiceItem::from_new(invoice.id.clone(), new_item);
+
+        sqlx::query(
+            "INSERT INTO invoice_items (id, invoice_id, description, quantity, unit_code, unit_price, discount_percent, total_price,
+                catalog_item_id, tax_category, revenue_account, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&item.id)
+        .bind(&item.invoice_id)
+        .bind(&item.description)
+        .bind(item.quantity)
+        .bind(&item.unit_code)
+        .bind(item.unit_price)
+        .bind(item.discount_percent)
+        .bind(item.total_price)
+        .bind(&item.catalog_item_id)
+        .bind(&item.tax_category)
+        .bind(&item.revenue_account)
+        .bind(item.created_at)
+        .execute(&mut *conn)
+        .await?;
+
+        let audit_entry = AuditEntry::new(&invoice.user_id, actor_id, "invoice_item", &item.id, None, Some(&item));
+        audit::append(conn, audit_entry).await?;
+
+        items.push(item);
+    }
+
+    let updated = recalculate(conn, actor_id, invoice).await?;
+    Ok((updated, items))
+}
+
+/// Recomputes the totals of a draft invoice from its items and its
+/// allowances and charges within the caller's transaction.
+pub async fn recalculate(
+    conn: &mut SqliteConnection,
+    actor_id: &str,
+    invoice: &Invoice,
+) -> Result<Invoice, sqlx::Error> {
+    let items = sqlx::query_as::<_, InvoiceItem>("SELECT * FROM invoice_items WHERE invoice_id = ?")
+        .bind(&invoice.id)
+        .fetch_all(&mut *conn)
+        .await?;
+    let before = sqlx::query_as::<_, AllowanceCharge>(
+        "SELECT * FROM invoice_allowance_charges WHERE invoice_id = ? ORDER BY created_at",
+    )
+    .bind(&invoice.id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let mut allowance_charges = before.clone();
+    let mut updated = invoice.clone();
+    updated.recalculate(&items, &mut allowance_charges);
+
+    for (before, after) in before.iter().zip(&allowance_charges) {
+        if before.amount == after.amount {
+            continue;
+        }
+        sqlx::query("UPDATE invoice_allowance_charges SET amount = ? WHERE id = ?")
+            .bind(after.amount)
+            .bind(&after.id)
+            .execute(&mut *conn)
+            .await?;
+
+        let audit_entry = AuditEntry::new(&invoice.user_id, actor_id, "invoice_allowance_charge", &after.id, Some(before), Some(after));
+        audit::append(conn, audit_entry).await?;
+    }
+
+    sqlx::query(
+        "UPDATE invoices SET subtotal = ?, allowance_total = ?, charge_total = ?, tax_amount = ?, total_amount = ?, updated_at = ?
+         WHERE id = ?",
+    )
+    .bind(updated.subtotal)
+    .bind(updated.allowance_total)
+    .bind(updated.charge_total)
+    .bind(updated.tax_amount)
+    .bind(updated.total_amount)
+    .bind(updated.updated_at)
+    .bind(&invoice.id)
+    .execute(&mut *conn)
+    .await?;
+
+    let audit_entry = AuditEntry::new(&invoice.user_id, actor_id, "invoice", &invoice.id, Some(invoice), Some(&updated));
+    audit::append(conn, audit_entry).await?;
+
+    Ok(updated)
+}
+
+pub async fn add_items(
+    db: &Db,
+    actor_id: &str,
+    invoice: &Invoice,
+    new_items: &[NewInvoiceItem],
+) -> Result<(Invoice, Vec<InvoiceItem>), sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let new_items: Vec<&NewInvoiceItem> = new_items.iter().collect();
+    let result = insert_items(&mut tx, actor_id, invoice, &new_items).await?;
+    tx.commit().await?;
+    Ok(result)
+}
+
+pub async fn set_payment_terms(db: &Db, actor_id: &str, invoice: &Invoice) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let before = load(&mut tx, &invoice.id).await?;
+
+    sqlx::query("UPDATE invoices SET skonto_percent = ?, skonto_days = ?, updated_at = ? WHERE id = ? AND user_id = ?")
+        .bind(invoice.skonto_percent)
+        .bind(invoice.skonto_days)
+        .bind(invoice.updated_at)
+        .bind(&invoice.id)
+        .bind(&invoice.user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    let entry = AuditEntry::new(&invoice.user_id, actor_id, "invoice", &invoice.id, before.as_ref(), Some(invoice));
+    audit::append(&mut tx, entry).await?;
+
+    tx.commit().await
+}
+
+pub async fn set_currency(db: &Db, actor_id: &str, invoice: &Invoice) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let before = load(&mut tx, &invoice.id).await?;
+
+    sqlx::query(
+        "UPDATE invoices SET currency = ?, exchange_rate = ?, exchange_rate_date = ?, updated_at = ?
+         WHERE id = ? AND user_id = ?",
+    )
+    .bind(&invoice.currency)
+    .bind(invoice.exchange_rate)
+    .bind(invoice.exchange_rate_date)
+    .bind(invoice.updated_at)
+    .bind(&invoice.id)
+    .bind(&invoice.user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    let entry = AuditEntry::new(&invoice.user_id, actor_id, "invoice", &invoice.id, before.as_ref(), Some(invoice));
+    audit::append(&mut tx, entry).await?;
+
+    tx.commit().await
+}
+
+/// Issued advance invoices of a project that no other final invoice has
+/// deducted yet.
+pub async fn find_open_advances(
+    db: &Db,
+    user_id: &str,
+    project_id: &str,
+    final_invoice_id: &str,
+) -> Result<Vec<Invoice>, sqlx::Error> {
+    sqlx::query_as::<_, Invoice>(
+        "SELECT * FROM invoices
+         WHERE user_id = ? AND project_id = ? AND billing_type = 'advance'
+           AND id NOT IN (
+               SELECT advance_invoice_id FROM invoice_advance_deductions WHERE final_invoice_id <> ?
+           )
+         ORDER BY issue_date, invoice_number",
+    )
+    .bind(user_id)
+    .bind(project_id)
+    .bind(final_invoice_id)
+    .fetch_all(db.as_ref())
+    .await
+}
+
+pub async fn find_deductions(db: &Db, final_invoice_id: &str) -> Result<Vec<AdvanceDeduction>, sqlx::Error> {
+    sqlx::query_as::<_, AdvanceDeduction>(
+        "SELECT * FROM invoice_advance_deductions WHERE final_invoice_id = ? ORDER BY advance_issue_date, advance_invoice_number",
+    )
+    .bind(final_invoice_id)
+    .fetch_all(db.as_ref())
+    .await
+}
+
+/// Stores the billing type and project of a draft invoice and replaces its
+/// advance deductions.
+pub async fn set_billing(
+    db: &Db,
+    actor_id: &str,
+    invoice: &Invoice,
+    deductions: &[AdvanceDeduction],
+) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let before = load(&mut tx, &invoice.id).await?;
+
+    let previous = sqlx::query_as::<_, AdvanceDeduction>("SELECT * FROM invoice_advance_deductions WHERE final_invoice_id = ?")
+        .bind(&invoice.id)
+        .fetch_all(&mut *tx)
+        .await?;
+    for deduction in &previous {
+        sqlx::query("DELETE FROM invoice_advance_deductions WHERE id = ?")
+            .bind(&deduction.id)
+            .execute(&mut *tx)
+            .await?;
+
+        let entry = AuditEntry::new(&invoice.user_id, actor_id, "invoice_advance_deduction", &deduction.id, Some(deduction), None);
+        audit::append(&mut tx, entry).await?;
+    }
+
+    for deduction in deductions {
+        sqlx::query(
+            "INSERT INTO invoice_advance_deductions (id, final_invoice_id, advance_invoice_id, advance_invoice_number,
+                advance_issue_date, tax_rate, net_amount, tax_amount, gross_amount, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&deduction.id)
+        .bind(&deduction.final_invoice_id)
+        .bind(&deduction.advance_invoice_id)
+        .bind(&deduction.advance_invoice_number)
+        .bind(deduction.advance_issue_date)
+        .bind(deduction.tax_rate)
+        .bind(deduction.net_amount)
+        .bind(deduction.tax_amount)
+        .bind(deduction.gross_amount)
+        .bind(deduction.created_at)
+        .execute(&mut *tx)
+        .await?;
+
+        let entry = AuditEntry::new(&invoice.user_id, actor_id, "invoice_advance_deduction", &deduction.id, None, Some(deduction));
+        audit::append(&mut tx, entry).await?;
+    }
+
+    sqlx::query(
+        "UPDATE invoices SET billing_type = ?, project_id = ?, advance_deduction_net = ?, advance_deduction_tax = ?, updated_at = ?
+         WHERE id = ? AND user_id = ?",
+    )
+    .bind(&invoice.billing_type)
+    .bind(&invoice.project_id)
+    .bind(invoice.advance_deduction_net)
+    .bind(invoice.advance_deduction_tax)
+    .bind(invoice.updated_at)
+    .bind(&invoice.id)
+    .bind(&invoice.user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    let entry = AuditEntry::new(&invoice.user_id, actor_id, "invoice", &invoice.id, before.as_ref(), Some(invoice));
+    audit::append(&mut tx, entry).await?;
+
+    tx.commit().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{test_db, test_invoice};
+    use crate::models::audit::verify_chain;
+
+    async fn rejected(db: &Db, sql: &str, id: &str) -> String {
+        sqlx::query(sql)
+            .bind(id)
+            .execute(db.as_ref())
+            .await
+            .expect_err("write to an issued invoice must fail")
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn issued_invoices_are_write_protected() {
+        let db = test_db().await;
+        let (user, invoice) = test_invoice(&db, "2024-03-01").await;
+        let item = NewInvoiceItem {
+            description: "Beratung".to_string(),
+            quantity: 1.5,
+            unit_code: Some("HUR".to_string()),
+            unit_price: 120.0,
+            ..Default::default()
+        };
+        let mut tx = db.begin().await.unwrap();
+        let (invoice, items) = insert_items(&mut tx, &user.id, &invoice, &[&item]).await.unwrap();
+        tx.commit().await.unwrap();
+        assert_eq!(invoice.subtotal, 180.0);
+
+        sqlx::query("UPDATE invoices SET status = 'sent' WHERE id = ?")
+            .bind(&invoice.id)
+            .execute(db.as_ref())
+            .await
+            .unwrap();
+
+        for sql in [
+            "UPDATE invoices SET total_amount = 1 WHERE id = ?",
+            "UPDATE invoices SET notes = 'changed' WHERE id = ?",
+            "UPDATE invoices SET status = 'draft' WHERE id = ?",
+            "DELETE FROM invoices WHERE id = ?",
+            // The triggers on invoice_items survive its rebuild in 0013
+            "INSERT INTO invoice_items (id, invoice_id, description, quantity, unit_price, total_price) VALUES ('x', ?, 'x', 1, 1, 1)",
+            "DELETE FROM invoice_items WHERE invoice_id = ?",
+        ] {
+            assert!(rejected(&db, sql, &invoice.id).await.contains("GoBD"), "{}", sql);
+        }
+        assert!(rejected(&db, "UPDATE invoice_items SET unit_price = 1 WHERE id = ?", &items[0].id)
+            .await
+            .contains("GoBD"));
+
+        // Moving forward in the life cycle is allowed and logged
+        mark_paid(&db, &user.id, &invoice.id, Utc::now()).await.unwrap();
+        let entries = audit::find_by_user(&db, &user.id).await.unwrap();
+        assert_eq!(entries.last().unwrap().entity_type, "invoice");
+        let result = verify_chain(&entries, None);
+        assert!(result.valid, "{:?}", result.error);
+    }
+}
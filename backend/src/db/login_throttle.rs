@@ -0,0 +1,68 @@
+use chrono::{Duration, Utc};
+use crate::db::Db;
+use crate::models::login_throttle::{LoginThrottle, ThrottleScope, FAILURE_WINDOW_MINUTES};
+
+pub async fn find(db: &Db, scope: ThrottleScope, key: &str) -> Result<Option<LoginThrottle>, sqlx::Error> {
+    sqlx::query_as::<_, LoginThrottle>("SELECT * FROM login_throttles WHERE scope = ? AND key = ?")
+        .bind(scope.as_str())
+        .bind(key)
+        .fetch_optional(db.as_ref())
+        .await
+}
+
+/// Counts a failed attempt and delays the next one by the scope's policy.
+/// The count restarts if the previous failure is outside the window.
+pub async fn record_failure(db: &Db, scope: ThrottleScope, key: &str) -> Result<LoginThrottle, sqlx::Error> {
+    let now = Utc::now();
+    let mut tx = db.begin().await?;
+
+    let mut throttle = sqlx::query_as::<_, LoginThrottle>(
+        "INSERT INTO login_throttles (scope, key, failures, last_failure_at) VALUES (?, ?, 1, ?)
+         ON CONFLICT (scope, key) DO UPDATE SET
+             failures = CASE WHEN last_failure_at < ? THEN 1 ELSE failures + 1 END,
+             last_failure_at = excluded.last_failure_at
+         RETURNING *",
+    )
+    .bind(scope.as_str())
+    .bind(key)
+    .bind(now)
+    .bind(now - Duration::minutes(FAILURE_WINDOW_MINUTES))
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if let Some(delay) = scope.policy().delay(throttle.failures) {
+        throttle.locked_until = Some(now + delay);
+        sqlx::query("UPDATE login_throttles SET locked_until = ? WHERE scope = ? AND key = ?")
+            .bind(throttle.locked_until)
+            .bind(scope.as_str())
+            .bind(key)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+    Ok(throttle)
+}
+
+pub async fn clear(db: &Db, scope: ThrottleScope, key: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM login_throttles WHERE scope = ? AND key = ?")
+        .bind(scope.as_str())
+        .bind(key)
+        .execute(db.as_ref())
+        .await?;
+
+    Ok(())
+}
+
+pub async fn purge_inactive(db: &Db) -> Result<u64, sqlx::Error> {
+    let now = Utc::now();
+    let result = sqlx::query(
+        "DELETE FROM login_throttles WHERE last_failure_at < ? AND (locked_until IS NULL OR locked_until <= ?)",
+    )
+    .bind(now - Duration::minutes(FAILURE_WINDOW_MINUTES))
+    .bind(now)
+    .execute(db.as_ref())
+    .await?;
+
+    Ok(result.rows_affected())
+}
@@ -0,0 +1,183 @@
+use chrono::Utc;
+use crate::db::Db;
+use crate::models::membership::{Invitation, Membership, OrganisationAccess, TeamMember};
+
+/// Role of a user in another account's organisation. Organisations of
+/// deleted accounts grant no access.
+pub async fn find_role(db: &Db, organisation_id: &str, user_id: &str) -> Result<Option<String>, sqlx::Error> {
+    let role: Option<(String,)> = sqlx::query_as(
+        "SELECT m.role FROM memberships m JOIN users u ON u.id = m.organisation_id
+         WHERE m.organisation_id = ? AND m.user_id = ? AND u.deleted_at IS NULL",
+    )
+    .bind(organisation_id)
+    .bind(user_id)
+    .fetch_optional(db.as_ref())
+    .await?;
+
+    Ok(role.map(|(role,)| role))
+}
+
+/// Memberships of a user in other organisations, for the data export.
+pub async fn find_by_user(db: &Db, user_id: &str) -> Result<Vec<Membership>, sqlx::Error> {
+    sqlx::query_as::<_, Membership>("SELECT * FROM memberships WHERE user_id = ? ORDER BY created_at")
+        .bind(user_id)
+        .fetch_all(db.as_ref())
+        .await
+}
+
+/// The user's own organisation and those it is a member of.
+pub async fn find_organisations(db: &Db, user_id: &str) -> Result<Vec<OrganisationAccess>, sqlx::Error> {
+    sqlx::query_as::<_, OrganisationAccess>(
+        "SELECT id AS organisation_id, COALESCE(company_name, email) AS name, 'owner' AS role
+         FROM users WHERE id = ?
+         UNION ALL
+         SELECT m.organisation_id, COALESCE(u.company_name, u.email) AS name, m.role
+         FROM memberships m JOIN users u ON u.id = m.organisation_id
+         WHERE m.user_id = ? AND u.deleted_at IS NULL",
+    )
+    .bind(user_id)
+    .bind(user_id)
+    .fetch_all(db.as_ref())
+    .await
+}
+
+/// The account holder followed by the members of the organisation.
+pub async fn find_members(db: &Db, organisation_id: &str) -> Result<Vec<TeamMember>, sqlx::Error> {
+    sqlx::query_as::<_, TeamMember>(
+        "SELECT id AS user_id, email, first_name, last_name, 'owner' AS role, created_at
+         FROM users WHERE id = ?
+         UNION ALL
+         SELECT u.id AS user_id, u.email, u.first_name, u.last_name, m.role, m.created_at
+         FROM memberships m JOIN users u ON u.id = m.user_id
+         WHERE m.organisation_id = ?",
+    )
+    .bind(organisation_id)
+    .bind(organisation_id)
+    .fetch_all(db.as_ref())
+    .await
+}
+
+/// Returns false if the user is no member.
+pub async fn set_role(db: &Db, organisation_id: &str, user_id: &str, role: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("UPDATE memberships SET role = ? WHERE organisation_id = ? AND user_id = ?")
+        .bind(role)
+        .bind(organisation_id)
+        .bind(user_id)
+        .execute(db.as_ref())
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Returns false if the user is no member.
+pub async fn remove(db: &Db, organisation_id: &str, user_id: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM memberships WHERE organisation_id = ? AND user_id = ?")
+        .bind(organisation_id)
+        .bind(user_id)
+        .execute(db.as_ref())
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Open invitations of an organisation, newest first.
+pub async fn find_open_invitations(db: &Db, organisation_id: &str) -> Result<Vec<Invitation>, sqlx::Error> {
+    sqlx::query_as::<_, Invitation>(
+        "SELECT * FROM invitations WHERE organisation_id = ? AND accepted_at IS NULL AND expires_at > ?
+         ORDER BY created_at DESC",
+    )
+    .bind(organisation_id)
+    .bind(Utc::now())
+    .fetch_all(db.as_ref())
+    .await
+}
+
+pub async fn find_invitation_by_hash(db: &Db, token_hash: &str) -> Result<Option<Invitation>, sqlx::Error> {
+    sqlx::query_as::<_, Invitation>("SELECT * FROM invitations WHERE token_hash = ?")
+        .bind(token_hash)
+        .fetch_optional(db.as_ref())
+        .await
+}
+
+/// Stores an invitation. An open invitation to the same address is replaced,
+/// so only the newest link works.
+pub async fn create_invitation(db: &Db, invitation: &Invitation) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    sqlx::query("DELETE FROM invitations WHERE organisation_id = ? AND email = ? AND accepted_at IS NULL")
+        .bind(&invitation.organisation_id)
+        .bind(&invitation.email)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query(
+        "INSERT INTO invitations (id, organisation_id, email, role, token_hash, invited_by, created_at, expires_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&invitation.id)
+    .bind(&invitation.organisation_id)
+    .bind(&invitation.email)
+    .bind(&invitation.role)
+    .bind(&invitation.token_hash)
+    .bind(&invitation.invited_by)
+    .bind(invitation.created_at)
+    .bind(invitation.expires_at)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await
+}
+
+/// Returns false for unknown and accepted invitations.
+pub async fn delete_invitation(db: &Db, organisation_id: &str, id: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM invitations WHERE id = ? AND organisation_id = ? AND accepted_at IS NULL")
+        .bind(id)
+        .bind(organisation_id)
+        .execute(db.as_ref())
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Makes the user a member with the invited role; a member keeps the
+/// membership with the new role. Returns false if the invitation was
+/// accepted in the meantime.
+pub async fn accept_invitation(db: &Db, invitation: &Invitation, user_id: &str) -> Result<bool, sqlx::Error> {
+    let now = Utc::now();
+    let mut tx = db.begin().await?;
+
+    let accepted = sqlx::query("UPDATE invitations SET accepted_at = ? WHERE id = ? AND accepted_at IS NULL")
+        .bind(now)
+        .bind(&invitation.id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected() == 1;
+    if !accepted {
+        return Ok(false);
+    }
+
+    sqlx::query(
+        "INSERT INTO memberships (organisation_id, user_id, role, invited_by, created_at) VALUES (?, ?, ?, ?, ?)
+         ON CONFLICT (organisation_id, user_id) DO UPDATE SET role = excluded.role",
+    )
+    .bind(&invitation.organisation_id)
+    .bind(user_id)
+    .bind(&invitation.role)
+    .bind(&invitation.invited_by)
+    .bind(now)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(true)
+}
+
+/// Deletes invitations that expired or were accepted.
+pub async fn purge_inactive_invitations(db: &Db) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM invitations WHERE accepted_at IS NOT NULL OR expires_at <= ?")
+        .bind(Utc::now())
+        .execute(db.as_ref())
+        .await?;
+
+    Ok(result.rows_affected())
+}
@@ -1,3 +1,26 @@
+pub mod invoice;
+pub mod expense;
+pub mod client;
+pub mod payment;
+pub mod user;
+pub mod settings;
+pub mod audit;
+pub mod retention;
+pub mod gdpr;
+pub mod project;
+pub mod time_entry;
+pub mod catalog;
+pub mod allowance_charge;
+pub mod exchange_rate;
+pub mod session;
+pub mod two_factor;
+pub mod email_token;
+pub mod login_throttle;
+pub mod api_key;
+pub mod membership;
+pub mod advisor_grant;
+pub mod identity;
+
 use sqlx::{sqlite::SqlitePoolOptions, Pool, Sqlite};
 use std::sync::Arc;
 
@@ -16,4 +39,61 @@ pub async fn init_db() -> Result<Db, sqlx::Error> {
     sqlx::migrate!("./migrations").run(&pool).await?;
 
     Ok(Arc::new(pool))
-}
\ No newline at end of file
+}
+
+/// Fresh in-memory database with all migrations applied. Uses a single
+/// connection, since every SQLite memory connection has its own database.
+#[cfg(test)]
+pub async fn test_db() -> Db {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect("sqlite::memory:")
+        .await
+        .expect("in-memory database");
+    sqlx::migrate!("./migrations").run(&pool).await.expect("migrations");
+
+    Arc::new(pool)
+}
+
+/// A stored account with a random email address.
+#[cfg(test)]
+pub async fn test_user(db: &Db) -> crate::models::user::User {
+    use crate::models::user::User;
+
+    let email = format!("{}@example.com", uuid::Uuid::new_v4());
+    let user = User::new(email, "hash".to_string(), None, None, None, None);
+    user::create(db, &user).await.expect("user");
+    user
+}
+
+/// An account with one client and an empty draft invoice dated `issue_date`.
+#[cfg(test)]
+pub async fn test_invoice(db: &Db, issue_date: &str) -> (crate::models::user::User, crate::models::invoice::Invoice) {
+    let user = test_user(db).await;
+    let client_id = uuid::Uuid::new_v4().to_string();
+    sqlx::query("INSERT INTO clients (id, user_id, name) VALUES (?, ?, 'Kunde GmbH')")
+        .bind(&client_id)
+        .bind(&user.id)
+        .execute(db.as_ref())
+        .await
+        .expect("client");
+    let invoice_id = uuid::Uuid::new_v4().to_string();
+    sqlx::query(
+        "INSERT INTO invoices (id, user_id, client_id, invoice_number, issue_date, due_date, subtotal, tax_amount, total_amount)
+         VALUES (?, ?, ?, ?, ?, ?, 0, 0, 0)",
+    )
+    .bind(&invoice_id)
+    .bind(&user.id)
+    .bind(&client_id)
+    .bind(format!("RE-{}", &invoice_id[..8]))
+    .bind(issue_date)
+    .bind(issue_date)
+    .execute(db.as_ref())
+    .await
+    .expect("invoice");
+
+    let invoice = invoice::find_by_id(db, &user.id, &invoice_id).await.expect("load").expect("invoice");
+    (user, invoice)
+}
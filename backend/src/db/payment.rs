@@ -0,0 +1,47 @@
+use crate::db::{audit, Db};
+use crate::models::audit::AuditEntry;
+use crate::models::payment::Payment;
+
+pub async fn find_by_user(db: &Db, user_id: &str) -> Result<Vec<Payment>, sqlx::Error> {
+    sqlx::query_as::<_, Payment>(
+        "SELECT payments.* FROM payments
+         JOIN invoices ON invoices.id = payments.invoice_id
+         WHERE invoices.user_id = ?
+         ORDER BY payments.payment_date",
+    )
+    .bind(user_id)
+    .fetch_all(db.as_ref())
+    .await
+}
+
+pub async fn find_by_invoice(db: &Db, invoice_id: &str) -> Result<Vec<Payment>, sqlx::Error> {
+    sqlx::query_as::<_, Payment>(
+        "SELECT * FROM payments WHERE invoice_id = ? ORDER BY payment_date",
+    )
+    .bind(invoice_id)
+    .fetch_all(db.as_ref())
+    .await
+}
+
+pub async fn create(db: &Db, actor_id: &str, user_id: &str, payment: &Payment) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    sqlx::query(
+        "INSERT INTO payments (id, invoice_id, amount, payment_date, method, skonto_amount, created_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&payment.id)
+    .bind(&payment.invoice_id)
+    .bind(payment.amount)
+    .bind(payment.payment_date)
+    .bind(&payment.method)
+    .bind(payment.skonto_amount)
+    .bind(payment.created_at)
+    .execute(&mut *tx)
+    .await?;
+
+    let entry = AuditEntry::new(user_id, actor_id, "payment", &payment.id, None, Some(payment));
+    audit::append(&mut tx, entry).await?;
+
+    tx.commit().await
+}
@@ -0,0 +1,81 @@
+use sqlx::SqliteConnection;
+use crate::db::{audit, Db};
+use crate::models::audit::AuditEntry;
+use crate::models::project::Project;
+
+pub async fn find_by_user(db: &Db, user_id: &str) -> Result<Vec<Project>, sqlx::Error> {
+    sqlx::query_as::<_, Project>("SELECT * FROM projects WHERE user_id = ? ORDER BY name")
+        .bind(user_id)
+        .fetch_all(db.as_ref())
+        .await
+}
+
+pub async fn find_by_client(db: &Db, user_id: &str, client_id: &str) -> Result<Vec<Project>, sqlx::Error> {
+    sqlx::query_as::<_, Project>("SELECT * FROM projects WHERE user_id = ? AND client_id = ? ORDER BY name")
+        .bind(user_id)
+        .bind(client_id)
+        .fetch_all(db.as_ref())
+        .await
+}
+
+pub async fn find_by_id(db: &Db, user_id: &str, id: &str) -> Result<Option<Project>, sqlx::Error> {
+    sqlx::query_as::<_, Project>("SELECT * FROM projects WHERE id = ? AND user_id = ?")
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(db.as_ref())
+        .await
+}
+
+async fn load(conn: &mut SqliteConnection, id: &str) -> Result<Option<Project>, sqlx::Error> {
+    sqlx::query_as::<_, Project>("SELECT * FROM projects WHERE id = ?")
+        .bind(id)
+        .fetch_optional(conn)
+        .await
+}
+
+pub async fn create(db: &Db, actor_id: &str, project: &Project) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    sqlx::query(
+        "INSERT INTO projects (id, user_id, client_id, name, hourly_rate, archived_at, created_at, updated_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&project.id)
+    .bind(&project.user_id)
+    .bind(&project.client_id)
+    .bind(&project.name)
+    .bind(project.hourly_rate)
+    .bind(project.archived_at)
+    .bind(project.created_at)
+    .bind(project.updated_at)
+    .execute(&mut *tx)
+    .await?;
+
+    let entry = AuditEntry::new(&project.user_id, actor_id, "project", &project.id, None, Some(project));
+    audit::append(&mut tx, entry).await?;
+
+    tx.commit().await
+}
+
+pub async fn update(db: &Db, actor_id: &str, project: &Project) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let before = load(&mut tx, &project.id).await?;
+
+    sqlx::query(
+        "UPDATE projects SET name = ?, hourly_rate = ?, archived_at = ?, updated_at = ?
+         WHERE id = ? AND user_id = ?",
+    )
+    .bind(&project.name)
+    .bind(project.hourly_rate)
+    .bind(project.archived_at)
+    .bind(project.updated_at)
+    .bind(&project.id)
+    .bind(&project.user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    let entry = AuditEntry::new(&project.user_id, actor_id, "project", &project.id, before.as_ref(), Some(project));
+    audit::append(&mut tx, entry).await?;
+
+    tx.commit().await
+}
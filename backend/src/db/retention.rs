@@ -0,0 +1,320 @@
+use chrono::{NaiveDate, Utc};
+use serde_json::json;
+use crate::db::{audit, Db};
+use crate::gdpr::user_pseudonym_email;
+use crate::models::audit::{AuditEntry, SYSTEM_ACTOR};
+use crate::models::client::Client;
+use crate::models::expense::Expense;
+use crate::models::invoice::Invoice;
+use crate::models::user::User;
+
+/// Owners whose data the purge job may touch: soft-deleted and not under legal hold.
+const PURGEABLE_OWNER: &str =
+    "users.deleted_at IS NOT NULL AND (users.legal_hold_until IS NULL OR users.legal_hold_until < ?)";
+
+/// Invoices of deleted users or deleted clients: drafts at once, issued
+/// invoices once the year of issue is at or before `cutoff_year`.
+pub async fn find_purgeable_invoices(
+    db: &Db,
+    cutoff_year: i32,
+    today: NaiveDate,
+) -> Result<Vec<Invoice>, sqlx::Error> {
+    sqlx::query_as::<_, Invoice>(
+        "SELECT invoices.* FROM invoices
+         JOIN users ON users.id = invoices.user_id
+         JOIN clients ON clients.id = invoices.client_id
+         WHERE (users.deleted_at IS NOT NULL OR clients.deleted_at IS NOT NULL)
+           AND (users.legal_hold_until IS NULL OR users.legal_hold_until < ?)
+           AND (invoices.status = 'draft' OR CAST(strftime('%Y', invoices.issue_date) AS INTEGER) <= ?)",
+    )
+    .bind(today)
+    .bind(cutoff_year)
+    .fetch_all(db.as_ref())
+    .await
+}
+
+pub async fn find_purgeable_expenses(
+    db: &Db,
+    cutoff_year: i32,
+    today: NaiveDate,
+) -> Result<Vec<Expense>, sqlx::Error> {
+    sqlx::query_as::<_, Expense>(&format!(
+        "SELECT expenses.* FROM expenses
+         JOIN users ON users.id = expenses.user_id
+         WHERE {} AND CAST(strftime('%Y', expenses.expense_date) AS INTEGER) <= ?",
+        PURGEABLE_OWNER
+    ))
+    .bind(today)
+    .bind(cutoff_year)
+    .fetch_all(db.as_ref())
+    .await
+}
+
+/// Deleted clients, or clients of deleted users, without any remaining invoice.
+pub async fn find_anonymizable_clients(db: &Db, today: NaiveDate) -> Result<Vec<Client>, sqlx::Error> {
+    sqlx::query_as::<_, Client>(
+        "SELECT clients.* FROM clients
+         JOIN users ON users.id = clients.user_id
+         WHERE clients.anonymized_at IS NULL
+           AND (clients.deleted_at IS NOT NULL OR users.deleted_at IS NOT NULL)
+           AND (users.legal_hold_until IS NULL OR users.legal_hold_until < ?)
+           AND NOT EXISTS (SELECT 1 FROM invoices WHERE invoices.client_id = clients.id)",
+    )
+    .bind(today)
+    .fetch_all(db.as_ref())
+    .await
+}
+
+/// Deleted users without remaining documents or non-anonymised clients.
+pub async fn find_anonymizable_users(db: &Db, today: NaiveDate) -> Result<Vec<User>, sqlx::Error> {
+    sqlx::query_as::<_, User>(&format!(
+        "SELECT users.* FROM users
+         WHERE users.anonymized_at IS NULL AND {}
+           AND NOT EXISTS (SELECT 1 FROM invoices WHERE invoices.user_id = users.id)
+           AND NOT EXISTS (SELECT 1 FROM expenses WHERE expenses.user_id = users.id)
+           AND NOT EXISTS (SELECT 1 FROM clients WHERE clients.user_id = users.id AND clients.anonymized_at IS NULL)",
+        PURGEABLE_OWNER
+    ))
+    .bind(today)
+    .fetch_all(db.as_ref())
+    .await
+}
+
+/// Deletes a record whose retention has elapsed. The audit entry only keeps
+/// the record's id, so no personal data outlives the retention period.
+async fn purge_record(
+    db: &Db,
+    table: &str,
+    entity_type: &str,
+    user_id: &str,
+    id: &str,
+) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    sqlx::query(&format!("DELETE FROM {} WHERE id = ?", table))
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+    let before = json!({ "id": id });
+    let entry = AuditEntry::new(user_id, SYSTEM_ACTOR, entity_type, id, Some(&before), None);
+    audit::append(&mut tx, entry).await?;
+
+    tx.commit().await
+}
+
+pub async fn purge_invoice(db: &Db, invoice: &Invoice) -> Result<(), sqlx::Error> {
+    purge_record(db, "invoices", "invoice", &invoice.user_id, &invoice.id).await
+}
+
+pub async fn purge_expense(db: &Db, expense: &Expense) -> Result<(), sqlx::Error> {
+    purge_record(db, "expenses", "expense", &expense.user_id, &expense.id).await
+}
+
+pub async fn anonymize_client(db: &Db, client: &Client) -> Result<(), sqlx::Error> {
+    let mut anonymized = client.clone();
+    anonymized.name = "Anonymisiert".to_string();
+    anonymized.email = None;
+    anonymized.company = None;
+    anonymized.street = None;
+    anonymized.city = None;
+    anonymized.postal_code = None;
+    anonymized.vat_number = None;
+    anonymized.anonymized_at = Some(Utc::now());
+    anonymized.updated_at = Utc::now();
+
+    let mut tx = db.begin().await?;
+
+    sqlx::query(
+        "UPDATE clients SET name = ?, email = NULL, company = NULL, street = NULL, city = NULL, postal_code = NULL, vat_number = NULL, anonymized_at = ?, updated_at = ?
+         WHERE id = ?",
+    )
+    .bind(&anonymized.name)
+    .bind(anonymized.anonymized_at)
+    .bind(anonymized.updated_at)
+    .bind(&client.id)
+    .execute(&mut *tx)
+    .await?;
+
+    let before = json!({ "id": client.id });
+    let after = serde_json::to_value(&anonymized).unwrap_or(before.clone());
+    let entry = AuditEntry::new(&client.user_id, SYSTEM_ACTOR, "client", &client.id, Some(&before), Some(&after));
+    audit::append(&mut tx, entry).await?;
+
+    tx.commit().await
+}
+
+pub async fn anonymize_user(db: &Db, user: &User) -> Result<(), sqlx::Error> {
+    let mut anonymized = user.clone();
+    anonymized.email = user_pseudonym_email(&user.id);
+    anonymized.password_hash = String::new();
+    anonymized.first_name = None;
+    anonymized.last_name = None;
+    anonymized.company_name = None;
+    anonymized.tax_id = None;
+    anonymized.anonymized_at = Some(Utc::now());
+    anonymized.updated_at = Utc::now();
+
+    let mut tx = db.begin().await?;
+
+    sqlx::query(
+        "UPDATE users SET email = ?, password_hash = '', first_name = NULL, last_name = NULL, company_name = NULL, tax_id = NULL, anonymized_at = ?, updated_at = ?
+         WHERE id = ?",
+    )
+    .bind(&anonymized.email)
+    .bind(anonymized.anonymized_at)
+    .bind(anonymized.updated_at)
+    .bind(&user.id)
+    .execute(&mut *tx)
+    .await?;
+
+    let before = json!({ "id": user.id });
+    let after = serde_json::to_value(&anonymized).unwrap_or(before.clone());
+    let entry = AuditEntry::new(&user.id, SYSTEM_ACTOR, "user", &user.id, Some(&before), Some(&after));
+    audit::append(&mut tx, entry).await?;
+
+    tx.commit().await
+}
+
+/// Removes audit log entries of purgeable owners written in or before
+/// `cutoff_year`. The last removed entry of each chain is kept as checkpoint
+/// that verification continues from.
+pub async fn purge_audit_log(db: &Db, cutoff_year: i32, today: NaiveDate) -> Result<u64, sqlx::Error> {
+    // SQLite takes the bare `hash` column from the row with the highest sequence
+    let heads: Vec<(String, i64, String)> = sqlx::query_as(&format!(
+        "SELECT user_id, MAX(sequence), hash FROM audit_log
+         WHERE CAST(strftime('%Y', created_at) AS INTEGER) <= ?
+           AND user_id IN (SELECT users.id FROM users WHERE {})
+         GROUP BY user_id",
+        PURGEABLE_OWNER
+    ))
+    .bind(cutoff_year)
+    .bind(today)
+    .fetch_all(db.as_ref())
+    .await?;
+
+    let mut purged = 0;
+    for (user_id, sequence, hash) in heads {
+        let mut tx = db.begin().await?;
+
+        sqlx::query(
+            "INSERT INTO audit_log_checkpoints (user_id, sequence, hash, created_at) VALUES (?, ?, ?, ?)
+             ON CONFLICT(user_id) DO UPDATE SET sequence = excluded.sequence, hash = excluded.hash, created_at = excluded.created_at",
+        )
+        .bind(&user_id)
+        .bind(sequence)
+        .bind(&hash)
+        .bind(Utc::now())
+        .execute(&mut *tx)
+        .await?;
+
+        let result = sqlx::query("DELETE FROM audit_log WHERE user_id = ? AND sequence <= ?")
+            .bind(&user_id)
+            .bind(sequence)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        purged += result.rows_affected();
+    }
+
+    Ok(purged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{self, test_db, test_invoice};
+    use crate::models::audit::verify_chain;
+
+    async fn issue(db: &Db, invoice: &Invoice) {
+        sqlx::query("UPDATE invoices SET status = 'sent' WHERE id = ?")
+            .bind(&invoice.id)
+            .execute(db.as_ref())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn issued_invoices_can_only_be_purged_after_retention() {
+        let db = test_db().await;
+        let (user, old) = test_invoice(&db, "2010-01-15").await;
+        issue(&db, &old).await;
+        let (_, recent) = test_invoice(&db, &Utc::now().date_naive().to_string()).await;
+        issue(&db, &recent).await;
+
+        purge_invoice(&db, &old).await.unwrap();
+        assert!(db::invoice::find_by_id(&db, &user.id, &old.id).await.unwrap().is_none());
+        let error = purge_invoice(&db, &recent).await.unwrap_err();
+        assert!(error.to_string().contains("retention period"));
+
+        let entries = db::audit::find_by_user(&db, &user.id).await.unwrap();
+        assert_eq!(entries.last().unwrap().action, "delete");
+        assert!(verify_chain(&entries, None).valid);
+    }
+
+    #[tokio::test]
+    async fn audit_log_and_owners_of_retained_documents_are_protected() {
+        let db = test_db().await;
+        let (user, invoice) = test_invoice(&db, "2024-03-01").await;
+        issue(&db, &invoice).await;
+
+        for sql in ["UPDATE audit_log SET actor_id = 'x' WHERE user_id = ?", "DELETE FROM audit_log WHERE user_id = ?"] {
+            let error = sqlx::query(sql).bind(&user.id).execute(db.as_ref()).await.unwrap_err();
+            assert!(error.to_string().contains("append-only"), "{}", sql);
+        }
+        // Users with documents under retention can only be soft-deleted
+        let error = sqlx::query("DELETE FROM users WHERE id = ?")
+            .bind(&user.id)
+            .execute(db.as_ref())
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("use soft delete"));
+    }
+
+    #[tokio::test]
+    async fn purged_audit_entries_leave_a_checkpoint() {
+        let db = test_db().await;
+        let user_id = uuid::Uuid::new_v4().to_string();
+        sqlx::query(
+            "INSERT INTO users (id, email, password_hash, created_at, updated_at, deleted_at) VALUES (?, ?, '', ?, ?, ?)",
+        )
+        .bind(&user_id)
+        .bind(format!("{}@example.com", user_id))
+        .bind(Utc::now())
+        .bind(Utc::now())
+        .bind(Utc::now())
+        .execute(db.as_ref())
+        .await
+        .unwrap();
+
+        let old = NaiveDate::from_ymd_opt(2010, 6, 1).unwrap().and_hms_opt(12, 0, 0).unwrap().and_utc();
+        for created_at in [old, old, old, Utc::now()] {
+            let mut tx = db.begin().await.unwrap();
+            let mut entry = AuditEntry::new(&user_id, SYSTEM_ACTOR, "user", &user_id, None, Some(&json!({})));
+            entry.created_at = created_at;
+            audit::append(&mut tx, entry).await.unwrap();
+            tx.commit().await.unwrap();
+        }
+        let purged_head = db::audit::find_by_user(&db, &user_id).await.unwrap()[2].hash.clone();
+
+        let today = Utc::now().date_naive();
+        assert_eq!(purge_audit_log(&db, 2015, today).await.unwrap(), 3);
+
+        let entries = db::audit::find_by_user(&db, &user_id).await.unwrap();
+        let checkpoint = db::audit::find_checkpoint(&db, &user_id).await.unwrap().unwrap();
+        assert_eq!((checkpoint.sequence, checkpoint.hash.as_str()), (3, purged_head.as_str()));
+        let result = verify_chain(&entries, Some(&checkpoint));
+        assert!(result.valid, "{:?}", result.error);
+        assert_eq!(result.purged_entries, 3);
+        assert!(!verify_chain(&entries, None).valid);
+
+        // Checkpoints cannot be moved back to hide purged entries
+        let error = sqlx::query("UPDATE audit_log_checkpoints SET sequence = 1 WHERE user_id = ?")
+            .bind(&user_id)
+            .execute(db.as_ref())
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("only move forward"));
+    }
+}
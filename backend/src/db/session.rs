@@ -0,0 +1,181 @@
+use chrono::{Duration, Utc};
+use crate::db::Db;
+use crate::models::session::{RefreshError, Session, SESSION_IDLE_DAYS};
+
+/// Active sessions of a user, most recently used first.
+pub async fn find_active(db: &Db, user_id: &str) -> Result<Vec<Session>, sqlx::Error> {
+    sqlx::query_as::<_, Session>(
+        "SELECT * FROM sessions WHERE user_id = ? AND revoked_at IS NULL AND expires_at > ? ORDER BY last_used_at DESC",
+    )
+    .bind(user_id)
+    .bind(Utc::now())
+    .fetch_all(db.as_ref())
+    .await
+}
+
+pub async fn find_by_id(db: &Db, user_id: &str, id: &str) -> Result<Option<Session>, sqlx::Error> {
+    sqlx::query_as::<_, Session>("SELECT * FROM sessions WHERE id = ? AND user_id = ?")
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(db.as_ref())
+        .await
+}
+
+/// All sessions of a user including revoked ones, for the data export.
+pub async fn find_by_user(db: &Db, user_id: &str) -> Result<Vec<Session>, sqlx::Error> {
+    sqlx::query_as::<_, Session>("SELECT * FROM sessions WHERE user_id = ? ORDER BY created_at")
+        .bind(user_id)
+        .fetch_all(db.as_ref())
+        .await
+}
+
+/// Stores a new session with its first refresh token.
+pub async fn create(db: &Db, session: &Session, token_hash: &str) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    sqlx::query(
+        "INSERT INTO sessions (id, user_id, device_label, ip_address, created_at, last_used_at, expires_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&session.id)
+    .bind(&session.user_id)
+    .bind(&session.device_label)
+    .bind(&session.ip_address)
+    .bind(session.created_at)
+    .bind(session.last_used_at)
+    .bind(session.expires_at)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query("INSERT INTO refresh_tokens (token_hash, session_id, created_at) VALUES (?, ?, ?)")
+        .bind(token_hash)
+        .bind(&session.id)
+        .bind(session.created_at)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await
+}
+
+/// Replaces a refresh token with a new one and extends its session. A token
+/// that was already replaced revokes the whole session.
+pub async fn rotate(
+    db: &Db,
+    token_hash: &str,
+    new_token_hash: &str,
+    ip_address: Option<&str>,
+) -> Result<Result<Session, RefreshError>, sqlx::Error> {
+    let now = Utc::now();
+    let mut tx = db.begin().await?;
+
+    let token: Option<(String, Option<chrono::DateTime<Utc>>)> =
+        sqlx::query_as("SELECT session_id, used_at FROM refresh_tokens WHERE token_hash = ?")
+            .bind(token_hash)
+            .fetch_optional(&mut *tx)
+            .await?;
+    let Some((session_id, used_at)) = token else {
+        return Ok(Err(RefreshError::Invalid));
+    };
+    let mut session = sqlx::query_as::<_, Session>("SELECT * FROM sessions WHERE id = ?")
+        .bind(&session_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+    // A concurrent refresh may have used the token since it was read
+    let used = match session.check_refresh(used_at, now) {
+        Ok(()) => sqlx::query("UPDATE refresh_tokens SET used_at = ? WHERE token_hash = ? AND used_at IS NULL")
+            .bind(now)
+            .bind(token_hash)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected() == 1,
+        Err(RefreshError::Reused) => false,
+        Err(error) => return Ok(Err(error)),
+    };
+    if !used {
+        sqlx::query("UPDATE sessions SET revoked_at = ?, revoked_reason = 'reuse_detected' WHERE id = ?")
+            .bind(now)
+            .bind(&session.id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        tracing::warn!("Refresh token reuse detected, revoked session {}", session.id);
+        return Ok(Err(RefreshError::Reused));
+    }
+
+    session.last_used_at = now;
+    session.expires_at = now + Duration::days(SESSION_IDLE_DAYS);
+    if let Some(ip_address) = ip_address {
+        session.ip_address = Some(ip_address.to_string());
+    }
+
+    sqlx::query("INSERT INTO refresh_tokens (token_hash, session_id, created_at) VALUES (?, ?, ?)")
+        .bind(new_token_hash)
+        .bind(&session.id)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("UPDATE sessions SET last_used_at = ?, expires_at = ?, ip_address = ? WHERE id = ?")
+        .bind(session.last_used_at)
+        .bind(session.expires_at)
+        .bind(&session.ip_address)
+        .bind(&session.id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(Ok(session))
+}
+
+/// Revokes the session a refresh token belongs to. Returns false for unknown
+/// tokens and sessions that were already revoked.
+pub async fn revoke_by_token(db: &Db, token_hash: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE sessions SET revoked_at = ?, revoked_reason = 'logout'
+         WHERE id = (SELECT session_id FROM refresh_tokens WHERE token_hash = ?) AND revoked_at IS NULL",
+    )
+    .bind(Utc::now())
+    .bind(token_hash)
+    .execute(db.as_ref())
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn revoke(db: &Db, user_id: &str, id: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE sessions SET revoked_at = ?, revoked_reason = 'logout' WHERE id = ? AND user_id = ? AND revoked_at IS NULL",
+    )
+    .bind(Utc::now())
+    .bind(id)
+    .bind(user_id)
+    .execute(db.as_ref())
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Logs the user out on all devices. Returns the number of sessions revoked.
+pub async fn revoke_all(db: &Db, user_id: &str) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE sessions SET revoked_at = ?, revoked_reason = 'logout_all' WHERE user_id = ? AND revoked_at IS NULL",
+    )
+    .bind(Utc::now())
+    .bind(user_id)
+    .execute(db.as_ref())
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Deletes expired and revoked sessions with their tokens. A token of a
+/// deleted session is unknown and rejected like one of a revoked session.
+pub async fn purge_inactive(db: &Db) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM sessions WHERE revoked_at IS NOT NULL OR expires_at <= ?")
+        .bind(Utc::now())
+        .execute(db.as_ref())
+        .await?;
+
+    Ok(result.rows_affected())
+}
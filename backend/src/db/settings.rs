@@ -0,0 +1,12 @@
+use crate::db::Db;
+use crate::models::settings::UserSettings;
+
+/// Loads the user's settings, falling back to the defaults when none were stored yet.
+pub async fn find_by_user(db: &Db, user_id: &str) -> Result<UserSettings, sqlx::Error> {
+    let settings = sqlx::query_as::<_, UserSettings>("SELECT * FROM user_settings WHERE user_id = ?")
+        .bind(user_id)
+        .fetch_optional(db.as_ref())
+        .await?;
+
+    Ok(settings.unwrap_or_else(|| UserSettings::new(user_id.to_string())))
+}
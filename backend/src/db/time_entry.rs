@@ -0,0 +1,161 @@
+use chrono::Utc;
+use sqlx::SqliteConnection;
+use crate::db::{audit, invoice, Db};
+use crate::models::audit::AuditEntry;
+use crate::models::invoice::{Invoice, InvoiceItem, NewInvoiceItem};
+use crate::models::time_entry::{TimeBillingGroup, TimeEntry};
+
+pub async fn find_by_user(db: &Db, user_id: &str) -> Result<Vec<TimeEntry>, sqlx::Error> {
+    sqlx::query_as::<_, TimeEntry>("SELECT * FROM time_entries WHERE user_id = ? ORDER BY started_at")
+        .bind(user_id)
+        .fetch_all(db.as_ref())
+        .await
+}
+
+pub async fn find_by_id(db: &Db, user_id: &str, id: &str) -> Result<Option<TimeEntry>, sqlx::Error> {
+    sqlx::query_as::<_, TimeEntry>("SELECT * FROM time_entries WHERE id = ? AND user_id = ?")
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(db.as_ref())
+        .await
+}
+
+pub async fn find_running(db: &Db, user_id: &str) -> Result<Option<TimeEntry>, sqlx::Error> {
+    sqlx::query_as::<_, TimeEntry>("SELECT * FROM time_entries WHERE user_id = ? AND ended_at IS NULL")
+        .bind(user_id)
+        .fetch_optional(db.as_ref())
+        .await
+}
+
+/// Billable entries not yet billed on any of the client's projects.
+pub async fn find_unbilled_by_client(db: &Db, user_id: &str, client_id: &str) -> Result<Vec<TimeEntry>, sqlx::Error> {
+    sqlx::query_as::<_, TimeEntry>(
+        "SELECT time_entries.* FROM time_entries
+         JOIN projects ON projects.id = time_entries.project_id
+         WHERE time_entries.user_id = ? AND projects.client_id = ?
+           AND time_entries.billable = 1 AND time_entries.billed_at IS NULL
+         ORDER BY time_entries.started_at",
+    )
+    .bind(user_id)
+    .bind(client_id)
+    .fetch_all(db.as_ref())
+    .await
+}
+
+async fn load(conn: &mut SqliteConnection, id: &str) -> Result<Option<TimeEntry>, sqlx::Error> {
+    sqlx::query_as::<_, TimeEntry>("SELECT * FROM time_entries WHERE id = ?")
+        .bind(id)
+        .fetch_optional(conn)
+        .await
+}
+
+pub async fn create(db: &Db, actor_id: &str, entry: &TimeEntry) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    sqlx::query(
+        "INSERT INTO time_entries (id, user_id, project_id, task, description, started_at, ended_at, billable, created_at, updated_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&entry.id)
+    .bind(&entry.user_id)
+    .bind(&entry.project_id)
+    .bind(&entry.task)
+    .bind(&entry.description)
+    .bind(entry.started_at)
+    .bind(entry.ended_at)
+    .bind(entry.billable)
+    .bind(entry.created_at)
+    .bind(entry.updated_at)
+    .execute(&mut *tx)
+    .await?;
+
+    let audit_entry = AuditEntry::new(&entry.user_id, actor_id, "time_entry", &entry.id, None, Some(entry));
+    audit::append(&mut tx, audit_entry).await?;
+
+    tx.commit().await
+}
+
+/// Saves changes to an unbilled entry; billed entries are left untouched.
+pub async fn update(db: &Db, actor_id: &str, entry: &TimeEntry) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let before = load(&mut tx, &entry.id).await?;
+
+    sqlx::query(
+        "UPDATE time_entries SET project_id = ?, task = ?, description = ?, started_at = ?, ended_at = ?, billable = ?, updated_at = ?
+         WHERE id = ? AND user_id = ? AND billed_at IS NULL",
+    )
+    .bind(&entry.project_id)
+    .bind(&entry.task)
+    .bind(&entry.description)
+    .bind(entry.started_at)
+    .bind(entry.ended_at)
+    .bind(entry.billable)
+    .bind(entry.updated_at)
+    .bind(&entry.id)
+    .bind(&entry.user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    let audit_entry = AuditEntry::new(&entry.user_id, actor_id, "time_entry", &entry.id, before.as_ref(), Some(entry));
+    audit::append(&mut tx, audit_entry).await?;
+
+    tx.commit().await
+}
+
+pub async fn delete(db: &Db, actor_id: &str, user_id: &str, id: &str) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let before = load(&mut tx, id).await?;
+
+    sqlx::query("DELETE FROM time_entries WHERE id = ? AND user_id = ? AND billed_at IS NULL")
+        .bind(id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    if let Some(before) = before.filter(|entry| entry.user_id == user_id) {
+        let audit_entry = AuditEntry::new(user_id, actor_id, "time_entry", id, Some(&before), None);
+        audit::append(&mut tx, audit_entry).await?;
+    }
+
+    tx.commit().await
+}
+
+/// Adds one item per group to the draft invoice, updates its totals and marks
+/// the grouped entries as billed on it.
+pub async fn bill(
+    db: &Db,
+    actor_id: &str,
+    invoice: &Invoice,
+    groups: &[TimeBillingGroup],
+) -> Result<(Invoice, Vec<InvoiceItem>), sqlx::Error> {
+    let now = Utc::now();
+    let mut tx = db.begin().await?;
+
+    for id in groups.iter().flat_map(|group| &group.entry_ids) {
+        let before = load(&mut tx, id).await?;
+
+        let result = sqlx::query(
+            "UPDATE time_entries SET invoice_id = ?, billed_at = ?, updated_at = ? WHERE id = ? AND billed_at IS NULL",
+        )
+        .bind(&invoice.id)
+        .bind(now)
+        .bind(now)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+        // Billed in the meantime: roll back rather than bill it twice.
+        if result.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        let after = load(&mut tx, id).await?;
+        let audit_entry = AuditEntry::new(&invoice.user_id, actor_id, "time_entry", id, before.as_ref(), after.as_ref());
+        audit::append(&mut tx, audit_entry).await?;
+    }
+
+    let new_items: Vec<&NewInvoiceItem> = groups.iter().map(|group| &group.item).collect();
+    let (updated, items) = invoice::insert_items(&mut tx, actor_id, invoice, &new_items).await?;
+
+    tx.commit().await?;
+    Ok((updated, items))
+}
@@ -0,0 +1,161 @@
+use chrono::Utc;
+use sqlx::SqliteConnection;
+use crate::db::{audit, Db};
+use crate::models::audit::AuditEntry;
+use crate::models::two_factor::{hash_recovery_code, RecoveryCode};
+use crate::models::user::User;
+
+async fn insert_recovery_codes(conn: &mut SqliteConnection, user_id: &str, codes: &[String]) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM recovery_codes WHERE user_id = ?")
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+
+    for code in codes {
+        let recovery_code = RecoveryCode::new(user_id.to_string(), code);
+        sqlx::query("INSERT INTO recovery_codes (id, user_id, code_hash, created_at) VALUES (?, ?, ?, ?)")
+            .bind(&recovery_code.id)
+            .bind(&recovery_code.user_id)
+            .bind(&recovery_code.code_hash)
+            .bind(recovery_code.created_at)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Stores a new secret that becomes active once a code generated from it
+/// has been confirmed.
+pub async fn start_enrolment(db: &Db, user: &User, secret: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE users SET totp_secret = ?, totp_last_step = NULL, updated_at = ? WHERE id = ? AND totp_enabled_at IS NULL",
+    )
+    .bind(secret)
+    .bind(Utc::now())
+    .bind(&user.id)
+    .execute(db.as_ref())
+    .await?;
+
+    Ok(())
+}
+
+/// Activates the enrolled secret and replaces the recovery codes.
+pub async fn enable(
+    db: &Db,
+    actor_id: &str,
+    user: &User,
+    step: i64,
+    recovery_codes: &[String],
+) -> Result<User, sqlx::Error> {
+    let mut enabled = user.clone();
+    enabled.totp_enabled_at = Some(Utc::now());
+    enabled.totp_last_step = Some(step);
+    enabled.updated_at = Utc::now();
+
+    let mut tx = db.begin().await?;
+
+    sqlx::query("UPDATE users SET totp_enabled_at = ?, totp_last_step = ?, updated_at = ? WHERE id = ?")
+        .bind(enabled.totp_enabled_at)
+        .bind(enabled.totp_last_step)
+        .bind(enabled.updated_at)
+        .bind(&user.id)
+        .execute(&mut *tx)
+        .await?;
+
+    insert_recovery_codes(&mut tx, &user.id, recovery_codes).await?;
+
+    let entry = AuditEntry::new(&user.id, actor_id, "user", &user.id, Some(user), Some(&enabled));
+    audit::append(&mut tx, entry).await?;
+
+    tx.commit().await?;
+    Ok(enabled)
+}
+
+pub async fn disable(db: &Db, actor_id: &str, user: &User) -> Result<(), sqlx::Error> {
+    let mut disabled = user.clone();
+    disabled.totp_secret = None;
+    disabled.totp_enabled_at = None;
+    disabled.totp_last_step = None;
+    disabled.updated_at = Utc::now();
+
+    let mut tx = db.begin().await?;
+
+    sqlx::query("UPDATE users SET totp_secret = NULL, totp_enabled_at = NULL, totp_last_step = NULL, updated_at = ? WHERE id = ?")
+        .bind(disabled.updated_at)
+        .bind(&user.id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM recovery_codes WHERE user_id = ?")
+        .bind(&user.id)
+        .execute(&mut *tx)
+        .await?;
+
+    let entry = AuditEntry::new(&user.id, actor_id, "user", &user.id, Some(user), Some(&disabled));
+    audit::append(&mut tx, entry).await?;
+
+    tx.commit().await
+}
+
+pub async fn set_required(db: &Db, actor_id: &str, user: &User, required: bool) -> Result<User, sqlx::Error> {
+    let mut updated = user.clone();
+    updated.two_factor_required = required;
+    updated.updated_at = Utc::now();
+
+    let mut tx = db.begin().await?;
+
+    sqlx::query("UPDATE users SET two_factor_required = ?, updated_at = ? WHERE id = ?")
+        .bind(updated.two_factor_required)
+        .bind(updated.updated_at)
+        .bind(&user.id)
+        .execute(&mut *tx)
+        .await?;
+
+    let entry = AuditEntry::new(&user.id, actor_id, "user", &user.id, Some(user), Some(&updated));
+    audit::append(&mut tx, entry).await?;
+
+    tx.commit().await?;
+    Ok(updated)
+}
+
+/// Records the time step of an accepted code. Returns false if a code of
+/// this or a later step was accepted in the meantime.
+pub async fn record_step(db: &Db, user_id: &str, step: i64) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE users SET totp_last_step = ? WHERE id = ? AND (totp_last_step IS NULL OR totp_last_step < ?)",
+    )
+    .bind(step)
+    .bind(user_id)
+    .bind(step)
+    .execute(db.as_ref())
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+pub async fn replace_recovery_codes(db: &Db, user_id: &str, recovery_codes: &[String]) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+    insert_recovery_codes(&mut tx, user_id, recovery_codes).await?;
+    tx.commit().await
+}
+
+/// Marks a recovery code as used. Returns false for unknown and used codes.
+pub async fn use_recovery_code(db: &Db, user_id: &str, code: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE recovery_codes SET used_at = ? WHERE user_id = ? AND code_hash = ? AND used_at IS NULL",
+    )
+    .bind(Utc::now())
+    .bind(user_id)
+    .bind(hash_recovery_code(code))
+    .execute(db.as_ref())
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+pub async fn count_recovery_codes_left(db: &Db, user_id: &str) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar("SELECT COUNT(*) FROM recovery_codes WHERE user_id = ? AND used_at IS NULL")
+        .bind(user_id)
+        .fetch_one(db.as_ref())
+        .await
+}
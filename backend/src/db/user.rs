@@ -0,0 +1,99 @@
+use chrono::{NaiveDate, Utc};
+use crate::db::{audit, Db};
+use crate::models::audit::AuditEntry;
+use crate::models::user::User;
+
+pub async fn find_by_id(db: &Db, id: &str) -> Result<Option<User>, sqlx::Error> {
+    sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ?")
+        .bind(id)
+        .fetch_optional(db.as_ref())
+        .await
+}
+
+/// Active account with this email address, for login.
+pub async fn find_by_email(db: &Db, email: &str) -> Result<Option<User>, sqlx::Error> {
+    sqlx::query_as::<_, User>("SELECT * FROM users WHERE email = ? AND deleted_at IS NULL")
+        .bind(email)
+        .fetch_optional(db.as_ref())
+        .await
+}
+
+/// Registers an account. Fails with a unique violation if the address is
+/// taken, also by a deleted account that has not been anonymised yet.
+pub async fn create(db: &Db, user: &User) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    sqlx::query(
+        "INSERT INTO users (id, email, password_hash, first_name, last_name, company_name, tax_id, created_at, updated_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&user.id)
+    .bind(&user.email)
+    .bind(&user.password_hash)
+    .bind(&user.first_name)
+    .bind(&user.last_name)
+    .bind(&user.company_name)
+    .bind(&user.tax_id)
+    .bind(user.created_at)
+    .bind(user.updated_at)
+    .execute(&mut *tx)
+    .await?;
+
+    let entry = AuditEntry::new(&user.id, &user.id, "user", &user.id, None, Some(user));
+    audit::append(&mut tx, entry).await?;
+
+    tx.commit().await
+}
+
+/// Marks the account as deleted. Documents under retention are kept and the
+/// purge job anonymises the account once they have expired.
+pub async fn soft_delete(db: &Db, actor_id: &str, user: &User) -> Result<(), sqlx::Error> {
+    let mut deleted = user.clone();
+    deleted.deleted_at = Some(Utc::now());
+    deleted.updated_at = Utc::now();
+
+    let mut tx = db.begin().await?;
+
+    sqlx::query("UPDATE users SET deleted_at = ?, updated_at = ? WHERE id = ?")
+        .bind(deleted.deleted_at)
+        .bind(deleted.updated_at)
+        .bind(&user.id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("UPDATE sessions SET revoked_at = ?, revoked_reason = 'logout_all' WHERE user_id = ? AND revoked_at IS NULL")
+        .bind(deleted.updated_at)
+        .bind(&user.id)
+        .execute(&mut *tx)
+        .await?;
+
+    let entry = AuditEntry::new(&user.id, actor_id, "user", &user.id, Some(user), Some(&deleted));
+    audit::append(&mut tx, entry).await?;
+
+    tx.commit().await
+}
+
+pub async fn set_legal_hold(
+    db: &Db,
+    actor_id: &str,
+    user: &User,
+    until: Option<NaiveDate>,
+) -> Result<(), sqlx::Error> {
+    let mut updated = user.clone();
+    updated.legal_hold_until = until;
+    updated.updated_at = Utc::now();
+
+    let mut tx = db.begin().await?;
+
+    sqlx::query("UPDATE users SET legal_hold_until = ?, updated_at = ? WHERE id = ?")
+        .bind(updated.legal_hold_until)
+        .bind(updated.updated_at)
+        .bind(&user.id)
+        .execute(&mut *tx)
+        .await?;
+
+    let entry = AuditEntry::new(&user.id, actor_id, "user", &user.id, Some(user), Some(&updated));
+    audit::append(&mut tx, entry).await?;
+
+    tx.commit().await
+}
@@ -0,0 +1,269 @@
+use std::borrow::Cow;
+
+use axum::{
+    async_trait,
+    extract::{rejection::JsonRejection, FromRequest, Request},
+    http::{header::RETRY_AFTER, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use serde::Serialize;
+use validator::ValidationErrors;
+
+/// A problem with one field of the request body.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+/// Errors returned by the handlers. Every variant is sent as the same JSON
+/// body, see `docs/api/api-reference.md` (Error Handling).
+#[derive(Debug)]
+pub enum ApiError {
+    /// The request data is invalid; `field_errors` names the fields if known.
+    Validation { message: String, field_errors: Vec<FieldError> },
+    /// Missing or invalid credentials
+    Unauthorized(String),
+    /// Authenticated, but not allowed to do this
+    Forbidden(String),
+    NotFound(String),
+    /// The request clashes with existing data, e.g. a taken email address
+    Conflict(String),
+    /// The data is valid but breaks a business rule, e.g. a tax category
+    /// whose rate differs from the invoice's
+    Unprocessable(String),
+    PayloadTooLarge(String),
+    UnsupportedMediaType(String),
+    RateLimited { message: String, retry_after: i64 },
+    /// A service the request depends on failed, e.g. the mail server or an
+    /// identity provider
+    Upstream(String),
+    Internal(String),
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    error: &'a str,
+    code: &'a str,
+    message: &'a str,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    field_errors: &'a [FieldError],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retry_after: Option<i64>,
+}
+
+impl ApiError {
+    pub fn validation(message: impl Into<String>) -> Self {
+        ApiError::Validation { message: message.into(), field_errors: Vec::new() }
+    }
+
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        ApiError::Unauthorized(message.into())
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        ApiError::Forbidden(message.into())
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        ApiError::NotFound(message.into())
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        ApiError::Conflict(message.into())
+    }
+
+    pub fn unprocessable(message: impl Into<String>) -> Self {
+        ApiError::Unprocessable(message.into())
+    }
+
+    pub fn upstream(message: impl Into<String>) -> Self {
+        ApiError::Upstream(message.into())
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        ApiError::Internal(message.into())
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::Validation { .. } => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::Unprocessable(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ApiError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Upstream(_) => StatusCode::BAD_GATEWAY,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Stable, machine-readable name of the error; clients should check this
+    /// rather than the message.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::Validation { .. } => "validation_failed",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::Unprocessable(_) => "unprocessable",
+            ApiError::PayloadTooLarge(_) => "payload_too_large",
+            ApiError::UnsupportedMediaType(_) => "unsupported_media_type",
+            ApiError::RateLimited { .. } => "rate_limited",
+            ApiError::Upstream(_) => "upstream_failed",
+            ApiError::Internal(_) => "internal",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            ApiError::Validation { message, .. } | ApiError::RateLimited { message, .. } => message,
+            ApiError::Unauthorized(message)
+            | ApiError::Forbidden(message)
+            | ApiError::NotFound(message)
+            | ApiError::Conflict(message)
+            | ApiError::Unprocessable(message)
+            | ApiError::PayloadTooLarge(message)
+            | ApiError::UnsupportedMediaType(message)
+            | ApiError::Upstream(message)
+            | ApiError::Internal(message) => message,
+        }
+    }
+}
+
+impl From<ValidationErrors> for ApiError {
+    fn from(errors: ValidationErrors) -> Self {
+        let mut field_errors: Vec<FieldError> = errors
+            .field_errors()
+            .into_iter()
+            .flat_map(|(field, errors)| {
+                errors.iter().map(move |error| FieldError {
+                    field: field.to_string(),
+                    message: error
+                        .message
+                        .clone()
+                        .map(Cow::into_owned)
+                        .unwrap_or_else(|| error.code.to_string()),
+                })
+            })
+            .collect();
+        field_errors.sort_by(|a, b| a.field.cmp(&b.field));
+
+        ApiError::Validation { message: "Invalid request data".to_string(), field_errors }
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        let message = rejection.body_text();
+        match rejection.status() {
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => ApiError::UnsupportedMediaType(message),
+            StatusCode::PAYLOAD_TOO_LARGE => ApiError::PayloadTooLarge(message),
+            status if status.is_server_error() => ApiError::Internal(message),
+            _ => ApiError::validation(message),
+        }
+    }
+}
+
+/// JSON request body. Unlike `axum::Json` it rejects malformed bodies with
+/// an `ApiError`, so they get the same response format as other errors.
+pub struct JsonBody<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for JsonBody<T>
+where
+    Json<T>: FromRequest<S, Rejection = JsonRejection>,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(request, state).await?;
+        Ok(JsonBody(value))
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        if status.is_server_error() {
+            tracing::error!("{} ({})", self.message(), status);
+        }
+
+        let (field_errors, retry_after) = match &self {
+            ApiError::Validation { field_errors, .. } => (field_errors.as_slice(), None),
+            ApiError::RateLimited { retry_after, .. } => (&[][..], Some(*retry_after)),
+            _ => (&[][..], None),
+        };
+        let body = Json(ErrorBody {
+            error: status.canonical_reason().unwrap_or_default(),
+            code: self.code(),
+            message: self.message(),
+            field_errors,
+            retry_after,
+        });
+
+        match retry_after {
+            Some(seconds) => (status, [(RETRY_AFTER, seconds.to_string())], body).into_response(),
+            None => (status, body).into_response(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+    use validator::Validate;
+
+    async fn body(response: Response) -> Value {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[derive(Validate)]
+    struct Payload {
+        #[validate(length(min = 1))]
+        name: String,
+        #[validate(range(min = 0.0, max = 100.0, message = "must be a percentage"))]
+        rate: f64,
+    }
+
+    #[tokio::test]
+    async fn errors_share_one_json_shape() {
+        let response = ApiError::not_found("Invoice not found").into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            body(response).await,
+            json!({ "error": "Not Found", "code": "not_found", "message": "Invoice not found" })
+        );
+
+        let response = ApiError::RateLimited { message: "Too many attempts".to_string(), retry_after: 30 }.into_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[RETRY_AFTER], "30");
+        let value = body(response).await;
+        assert_eq!(value["code"], "rate_limited");
+        assert_eq!(value["retry_after"], 30);
+    }
+
+    #[tokio::test]
+    async fn validation_errors_name_the_fields() {
+        let errors = Payload { name: String::new(), rate: 120.0 }.validate().unwrap_err();
+        let response = ApiError::from(errors).into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let value = body(response).await;
+        assert_eq!(value["code"], "validation_failed");
+        assert_eq!(
+            value["field_errors"],
+            json!([
+                { "field": "name", "message": "length" },
+                { "field": "rate", "message": "must be a percentage" },
+            ])
+        );
+    }
+}
@@ -0,0 +1,343 @@
+use std::collections::HashSet;
+
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use serde::Serialize;
+
+use crate::models::advisor_grant::AdvisorGrant;
+use crate::models::api_key::ApiKey;
+use crate::models::audit::AuditEntry;
+use crate::models::client::Client;
+use crate::models::expense::Expense;
+use crate::models::identity::UserIdentity;
+use crate::models::invoice::{Invoice, InvoiceItem};
+use crate::models::membership::Membership;
+use crate::models::payment::Payment;
+use crate::models::project::Project;
+use crate::models::session::Session;
+use crate::models::settings::UserSettings;
+use crate::models::time_entry::TimeEntry;
+use crate::models::user::User;
+use crate::retention::{RetentionRule, AUDIT_LOG_RETENTION, EXPENSE_RETENTION, INVOICE_RETENTION};
+
+/// Client fields printed on an invoice (§ 14 Abs. 4 Nr. 1 UStG). They are kept
+/// as long as an issued invoice to the client is retained.
+pub const CLIENT_INVOICE_FIELDS: &[&str] = &["name", "company", "street", "postal_code", "city", "country", "vat_number"];
+
+/// Issuer fields printed on an invoice (§ 14 Abs. 4 Nr. 1 und 2 UStG).
+pub const USER_INVOICE_FIELDS: &[&str] = &["first_name", "last_name", "company_name", "tax_id"];
+
+/// Everything held about the account holder (Art. 15 Abs. 3, Art. 20 DSGVO).
+#[derive(Debug, Serialize)]
+pub struct UserDataExport {
+    pub generated_at: DateTime<Utc>,
+    pub user: User,
+    pub settings: Option<UserSettings>,
+    pub clients: Vec<Client>,
+    pub invoices: Vec<Invoice>,
+    pub invoice_items: Vec<InvoiceItem>,
+    pub payments: Vec<Payment>,
+    pub expenses: Vec<Expense>,
+    pub projects: Vec<Project>,
+    pub time_entries: Vec<TimeEntry>,
+    pub sessions: Vec<Session>,
+    pub api_keys: Vec<ApiKey>,
+    pub memberships: Vec<Membership>,
+    pub advisor_grants: Vec<AdvisorGrant>,
+    pub identities: Vec<UserIdentity>,
+    pub audit_log: Vec<AuditEntry>,
+}
+
+/// Everything held about one client of the account holder.
+#[derive(Debug, Serialize)]
+pub struct ClientDataExport {
+    pub generated_at: DateTime<Utc>,
+    pub client: Client,
+    pub invoices: Vec<Invoice>,
+    pub invoice_items: Vec<InvoiceItem>,
+    pub payments: Vec<Payment>,
+    pub audit_log: Vec<AuditEntry>,
+}
+
+impl ClientDataExport {
+    /// Picks the client's invoices, their items and payments and every audit
+    /// entry about one of these records from the owner's data.
+    pub fn build(
+        client: Client,
+        invoices: &[Invoice],
+        items: &[InvoiceItem],
+        payments: &[Payment],
+        audit_log: &[AuditEntry],
+    ) -> Self {
+        let invoices: Vec<Invoice> = invoices
+            .iter()
+            .filter(|invoice| invoice.client_id == client.id)
+            .cloned()
+            .collect();
+        let invoice_ids: HashSet<&str> = invoices.iter().map(|invoice| invoice.id.as_str()).collect();
+        let invoice_items: Vec<InvoiceItem> = items
+            .iter()
+            .filter(|item| invoice_ids.contains(item.invoice_id.as_str()))
+            .cloned()
+            .collect();
+        let payments: Vec<Payment> = payments
+            .iter()
+            .filter(|payment| invoice_ids.contains(payment.invoice_id.as_str()))
+            .cloned()
+            .collect();
+
+        let audit_log = audit_log
+            .iter()
+            .filter(|entry| match entry.entity_type.as_str() {
+                "client" => entry.entity_id == client.id,
+                "invoice" => invoice_ids.contains(entry.entity_id.as_str()),
+                "invoice_item" => invoice_items.iter().any(|item| item.id == entry.entity_id),
+                "payment" => payments.iter().any(|payment| payment.id == entry.entity_id),
+                _ => false,
+            })
+            .cloned()
+            .collect();
+
+        Self {
+            generated_at: Utc::now(),
+            client,
+            invoices,
+            invoice_items,
+            payments,
+            audit_log,
+        }
+    }
+}
+
+/// Data that could not be erased because a retention duty applies
+/// (Art. 17 Abs. 3 lit. b DSGVO).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RetainedData {
+    pub record_type: &'static str,
+    pub records: usize,
+    /// Personal fields of the data subject kept for these records.
+    pub fields: Vec<&'static str>,
+    pub legal_basis: String,
+    pub retained_until: NaiveDate,
+}
+
+impl RetainedData {
+    fn new(rule: RetentionRule, years: impl Iterator<Item = i32>, fields: &[&'static str]) -> Option<Self> {
+        let years: Vec<i32> = years.collect();
+        let last_year = years.iter().copied().max()?;
+
+        Some(Self {
+            record_type: rule.record_type,
+            records: years.len(),
+            fields: fields.to_vec(),
+            legal_basis: format!("Art. 17 Abs. 3 lit. b DSGVO i. V. m. {}", rule.legal_basis),
+            retained_until: rule.retained_until(last_year),
+        })
+    }
+}
+
+/// Outcome of an erasure request.
+#[derive(Debug, Clone, Serialize)]
+pub struct ErasureReport {
+    pub subject_type: &'static str,
+    pub subject_id: String,
+    /// Fields pseudonymised or cleared now.
+    pub erased_fields: Vec<&'static str>,
+    /// Draft invoices, which are not records yet and are deleted.
+    pub deleted_drafts: usize,
+    pub retained: Vec<RetainedData>,
+    /// The remaining fields are erased by the purge job once nothing is retained.
+    pub fully_erased: bool,
+}
+
+impl ErasureReport {
+    /// Plans the erasure of a client. The address stays as long as an issued
+    /// invoice to the client is retained; the e-mail address is not part of an
+    /// invoice and goes at once.
+    pub fn for_client(client: &Client, invoices: &[Invoice], audit_log: &[AuditEntry]) -> Self {
+        let invoices: Vec<&Invoice> = invoices.iter().filter(|invoice| invoice.client_id == client.id).collect();
+        let issued = invoices.iter().filter(|invoice| invoice.status != "draft");
+        let retained_invoices =
+            RetainedData::new(INVOICE_RETENTION, issued.map(|invoice| invoice.issue_date.year()), CLIENT_INVOICE_FIELDS);
+        let audit_entries = RetainedData::new(
+            AUDIT_LOG_RETENTION,
+            audit_log
+                .iter()
+                .filter(|entry| entry.entity_type == "client" && entry.entity_id == client.id)
+                .map(|entry| entry.created_at.year()),
+            &["name", "email", "company", "street", "postal_code", "city", "vat_number"],
+        );
+
+        let fully_erased = retained_invoices.is_none();
+        let mut erased_fields = vec!["email"];
+        if fully_erased {
+            erased_fields.extend(CLIENT_INVOICE_FIELDS.iter().filter(|field| **field != "country"));
+        }
+
+        Self {
+            subject_type: "client",
+            subject_id: client.id.clone(),
+            erased_fields,
+            deleted_drafts: invoices.iter().filter(|invoice| invoice.status == "draft").count(),
+            retained: retained_invoices.into_iter().chain(audit_entries).collect(),
+            fully_erased,
+        }
+    }
+
+    /// Plans the erasure of the account holder from their own records. Login
+    /// data goes at once, the issuer details stay while issued invoices or
+    /// expenses are retained.
+    pub fn for_user(
+        user: &User,
+        invoices: &[Invoice],
+        expenses: &[Expense],
+        audit_log: &[AuditEntry],
+    ) -> Self {
+        let issued = invoices.iter().filter(|invoice| invoice.status != "draft");
+        let retained_invoices =
+            RetainedData::new(INVOICE_RETENTION, issued.map(|invoice| invoice.issue_date.year()), USER_INVOICE_FIELDS);
+        let retained_expenses = RetainedData::new(
+            EXPENSE_RETENTION,
+            expenses.iter().map(|expense| expense.expense_date.year()),
+            USER_INVOICE_FIELDS,
+        );
+        let audit_entries = RetainedData::new(
+            AUDIT_LOG_RETENTION,
+            audit_log.iter().map(|entry| entry.created_at.year()),
+            &["email", "first_name", "last_name", "company_name", "tax_id"],
+        );
+
+        let fully_erased = retained_invoices.is_none() && retained_expenses.is_none();
+        let mut erased_fields = vec!["email", "password"];
+        if fully_erased {
+            erased_fields.extend(USER_INVOICE_FIELDS);
+        }
+
+        Self {
+            subject_type: "user",
+            subject_id: user.id.clone(),
+            erased_fields,
+            deleted_drafts: invoices.iter().filter(|invoice| invoice.status == "draft").count(),
+            retained: [retained_invoices, retained_expenses, audit_entries].into_iter().flatten().collect(),
+            fully_erased,
+        }
+    }
+}
+
+/// Replacement for a client name that is no longer needed for a retained invoice.
+pub fn client_pseudonym(client_id: &str) -> String {
+    format!("Pseudonym {}", &client_id[..client_id.len().min(8)])
+}
+
+/// Replacement for the login e-mail address; `.invalid` never resolves (RFC 2606).
+pub fn user_pseudonym_email(user_id: &str) -> String {
+    format!("deleted-{}@anonymized.invalid", user_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn client() -> Client {
+        Client {
+            id: "c1".to_string(),
+            user_id: "u1".to_string(),
+            name: "Erika Mustermann".to_string(),
+            email: Some("erika@example.com".to_string()),
+            company: None,
+            street: Some("Heidestraße 17".to_string()),
+            city: Some("Köln".to_string()),
+            postal_code: Some("51147".to_string()),
+            country: "DE".to_string(),
+            vat_number: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            deleted_at: None,
+            anonymized_at: None,
+        }
+    }
+
+    fn invoice(id: &str, client_id: &str, status: &str, year: i32) -> Invoice {
+        let date = NaiveDate::from_ymd_opt(year, 6, 1).unwrap();
+        Invoice {
+            id: id.to_string(),
+            user_id: "u1".to_string(),
+            client_id: client_id.to_string(),
+            invoice_number: format!("RE-{}", id),
+            issue_date: date,
+            due_date: date,
+            currency: "EUR".to_string(),
+            exchange_rate: None,
+            exchange_rate_date: None,
+            subtotal: 100.0,
+            allowance_total: 0.0,
+            charge_total: 0.0,
+            tax_rate: 19.0,
+            tax_amount: 19.0,
+            total_amount: 119.0,
+            skonto_percent: None,
+            skonto_days: None,
+            billing_type: "standard".to_string(),
+            project_id: None,
+            advance_deduction_net: 0.0,
+            advance_deduction_tax: 0.0,
+            status: status.to_string(),
+            tax_treatment: "standard".to_string(),
+            invoice_type: "invoice".to_string(),
+            corrected_invoice_id: None,
+            notes: None,
+            pdf_url: None,
+            sent_at: None,
+            paid_at: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn client_export_only_contains_the_clients_records() {
+        let invoices = vec![invoice("i1", "c1", "sent", 2024), invoice("i2", "c2", "sent", 2024)];
+        let after = json!({ "id": "x" });
+        let audit_log = vec![
+            AuditEntry::new("u1", "u1", "client", "c1", None, Some(&after)),
+            AuditEntry::new("u1", "u1", "invoice", "i1", None, Some(&after)),
+            AuditEntry::new("u1", "u1", "invoice", "i2", None, Some(&after)),
+            AuditEntry::new("u1", "u1", "client", "c2", None, Some(&after)),
+        ];
+
+        let export = ClientDataExport::build(client(), &invoices, &[], &[], &audit_log);
+
+        assert_eq!(export.invoices.len(), 1);
+        assert_eq!(export.invoices[0].id, "i1");
+        assert_eq!(export.audit_log.len(), 2);
+    }
+
+    #[test]
+    fn client_with_issued_invoices_keeps_the_invoice_address() {
+        let invoices = vec![
+            invoice("i1", "c1", "sent", 2023),
+            invoice("i2", "c1", "paid", 2024),
+            invoice("i3", "c1", "draft", 2025),
+        ];
+
+        let report = ErasureReport::for_client(&client(), &invoices, &[]);
+
+        assert!(!report.fully_erased);
+        assert_eq!(report.erased_fields, vec!["email"]);
+        assert_eq!(report.deleted_drafts, 1);
+        assert_eq!(report.retained.len(), 1);
+        assert_eq!(report.retained[0].records, 2);
+        assert_eq!(report.retained[0].retained_until, NaiveDate::from_ymd_opt(2032, 12, 31).unwrap());
+        assert!(report.retained[0].legal_basis.contains("§ 14b"));
+    }
+
+    #[test]
+    fn client_without_issued_invoices_is_erased_completely() {
+        let report = ErasureReport::for_client(&client(), &[invoice("i1", "c1", "draft", 2025)], &[]);
+
+        assert!(report.fully_erased);
+        assert!(report.erased_fields.contains(&"street"));
+        assert!(!report.erased_fields.contains(&"country"));
+        assert!(report.retained.is_empty());
+    }
+}
@@ -0,0 +1,279 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::{
+    extract::{ConnectInfo, Path, Query, State},
+    http::StatusCode,
+    response::{Json, Response},
+    Extension,
+};
+use chrono::{Datelike, Utc};
+use crate::auth::jwt::generate_advisor_token;
+use crate::auth::keys::JwtKeys;
+use crate::auth::member::Member;
+use crate::db::{self, Db};
+use crate::error::{ApiError, JsonBody};
+use crate::handlers::email_token::throttle_mail_request;
+use crate::handlers::report::{euer_response, ustva_response, ReportQuery};
+use crate::mail::Mailer;
+use crate::models::advisor_grant::{
+    hash_advisor_token, AdvisorGrant, AdvisorLink, AdvisorLinkRequest, AdvisorLogin, AdvisorSession,
+    NewAdvisorGrant, ADVISOR_LINK_HOURS,
+};
+use crate::models::expense::Expense;
+use crate::models::invoice::Invoice;
+use crate::models::login_throttle::account_key;
+use crate::models::membership::Permission;
+use crate::models::payment::Payment;
+
+/// Name of the organisation shown to the advisor.
+async fn organisation_name(db: &Db, organisation_id: &str) -> Result<String, ApiError> {
+    let organisation = db::user::find_by_id(db, organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load organisation"))?
+        .ok_or_else(|| ApiError::not_found("Organisation not found"))?;
+
+    Ok(organisation.company_name.unwrap_or(organisation.email))
+}
+
+/// Stores a new login link for the grant and mails it to the advisor.
+async fn send_advisor_link(db: &Db, mailer: &Mailer, grant: &AdvisorGrant) -> Result<(), ApiError> {
+    let organisation = organisation_name(db, &grant.user_id).await?;
+    let (link, token) = AdvisorLink::new(grant, Utc::now());
+    db::advisor_grant::create_link(db, &link)
+        .await
+        .map_err(|_| ApiError::internal("Failed to create link"))?;
+
+    let message = mailer.advisor_link(&grant.advisor_email, &organisation, &grant.fiscal_years, &token, ADVISOR_LINK_HOURS);
+    mailer.send(&message).await.map_err(|error| {
+        tracing::error!("Failed to send advisor link: {}", error);
+        ApiError::upstream("Failed to send mail")
+    })
+}
+
+pub async fn get_advisor_grants(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+) -> Result<Json<Vec<AdvisorGrant>>, ApiError> {
+    member.require(Permission::ManageTeam)?;
+    let grants = db::advisor_grant::find_by_user(&db, &member.organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load advisor grants"))?;
+
+    Ok(Json(grants))
+}
+
+/// Grants a tax advisor read-only access to the selected fiscal years and
+/// mails the first login link.
+pub async fn create_advisor_grant(
+    State(db): State<Db>,
+    State(mailer): State<Arc<Mailer>>,
+    Extension(member): Extension<Member>,
+    JsonBody(payload): JsonBody<NewAdvisorGrant>,
+) -> Result<(StatusCode, Json<AdvisorGrant>), ApiError> {
+    member.require(Permission::ManageTeam)?;
+    let grant = payload
+        .into_grant(member.organisation_id.clone(), member.user_id.clone(), Utc::now())
+        .map_err(ApiError::validation)?;
+
+    db::advisor_grant::create(&db, &member.user_id, &grant)
+        .await
+        .map_err(|_| ApiError::internal("Failed to save advisor grant"))?;
+    send_advisor_link(&db, &mailer, &grant).await?;
+
+    Ok((StatusCode::CREATED, Json(grant)))
+}
+
+/// Revokes a grant. Sessions of the advisor end with their next request.
+pub async fn delete_advisor_grant(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    member.require(Permission::ManageTeam)?;
+
+    let revoked = db::advisor_grant::revoke(&db, &member.user_id, &member.organisation_id, &id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to revoke advisor grant"))?;
+    if !revoked {
+        return Err(ApiError::not_found("Advisor grant not found"));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Mails a login link for every active grant of the address. Like a password
+/// reset, the response is the same either way and reveals nothing about
+/// granted addresses, and requests are throttled per address and client.
+pub async fn request_advisor_link(
+    State(db): State<Db>,
+    State(mailer): State<Arc<Mailer>>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    JsonBody(payload): JsonBody<AdvisorLinkRequest>,
+) -> Result<StatusCode, ApiError> {
+    throttle_mail_request(&db, &account_key(&payload.email), &address.ip().to_string()).await?;
+
+    tokio::spawn(async move {
+        match db::advisor_grant::find_active_by_email(&db, &payload.email).await {
+            Ok(grants) => {
+                for grant in grants {
+                    // Errors are logged by send_advisor_link
+                    let _ = send_advisor_link(&db, &mailer, &grant).await;
+                }
+            }
+            Err(error) => tracing::error!("Advisor link lookup failed: {}", error),
+        }
+    });
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Exchanges a mailed link for a portal session.
+pub async fn advisor_login(
+    State(db): State<Db>,
+    State(keys): State<Arc<JwtKeys>>,
+    JsonBody(payload): JsonBody<AdvisorLogin>,
+) -> Result<Json<AdvisorSession>, ApiError> {
+    let invalid = || ApiError::validation("Invalid or expired link");
+    let now = Utc::now();
+    let link = db::advisor_grant::find_link_by_hash(&db, &hash_advisor_token(&payload.token))
+        .await
+        .map_err(|_| ApiError::internal("Failed to load link"))?
+        .filter(|link| link.is_open(now))
+        .ok_or_else(invalid)?;
+    let grant = db::advisor_grant::find_by_id(&db, &link.grant_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load advisor grant"))?
+        .filter(|grant| grant.is_active(now))
+        .ok_or_else(invalid)?;
+
+    let used = db::advisor_grant::use_link(&db, &link.id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to use link"))?;
+    if !used {
+        return Err(invalid());
+    }
+
+    record_access(&db, &grant, "login", None).await?;
+    let (token, expires_at) = generate_advisor_token(&keys, &grant, now)
+        .map_err(|_| ApiError::internal("Failed to generate token"))?;
+
+    Ok(Json(AdvisorSession {
+        token,
+        expires_at,
+        organisation: organisation_name(&db, &grant.user_id).await?,
+        fiscal_years: grant.fiscal_years.split(' ').filter_map(|year| year.parse().ok()).collect(),
+    }))
+}
+
+/// Checks the year against the grant and records the access before anything
+/// is read, so no access goes unlogged.
+async fn authorize(db: &Db, grant: &AdvisorGrant, resource: &str, year: i32) -> Result<(), ApiError> {
+    if !grant.covers(year) {
+        return Err(ApiError::forbidden(format!("Fiscal year {} is not part of this grant", year)));
+    }
+    record_access(db, grant, resource, Some(year)).await
+}
+
+async fn record_access(
+    db: &Db,
+    grant: &AdvisorGrant,
+    resource: &str,
+    fiscal_year: Option<i32>,
+) -> Result<(), ApiError> {
+    db::advisor_grant::record_access(db, grant, resource, fiscal_year)
+        .await
+        .map_err(|_| ApiError::internal("Failed to record access"))
+}
+
+/// Issued invoices of the year; drafts stay private.
+pub async fn get_invoices(
+    State(db): State<Db>,
+    Extension(grant): Extension<AdvisorGrant>,
+    Path(year): Path<i32>,
+) -> Result<Json<Vec<Invoice>>, ApiError> {
+    authorize(&db, &grant, "invoices", year).await?;
+    let invoices = db::invoice::find_by_user(&db, &grant.user_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load invoices"))?;
+
+    Ok(Json(
+        invoices
+            .into_iter()
+            .filter(|invoice| invoice.status != "draft" && invoice.issue_date.year() == year)
+            .collect(),
+    ))
+}
+
+pub async fn get_payments(
+    State(db): State<Db>,
+    Extension(grant): Extension<AdvisorGrant>,
+    Path(year): Path<i32>,
+) -> Result<Json<Vec<Payment>>, ApiError> {
+    authorize(&db, &grant, "payments", year).await?;
+    let payments = db::payment::find_by_user(&db, &grant.user_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load payments"))?;
+
+    Ok(Json(payments.into_iter().filter(|payment| payment.payment_date.year() == year).collect()))
+}
+
+pub async fn get_expenses(
+    State(db): State<Db>,
+    Extension(grant): Extension<AdvisorGrant>,
+    Path(year): Path<i32>,
+) -> Result<Json<Vec<Expense>>, ApiError> {
+    authorize(&db, &grant, "expenses", year).await?;
+    let expenses = db::expense::find_by_user(&db, &grant.user_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load expenses"))?;
+
+    Ok(Json(expenses.into_iter().filter(|expense| expense.expense_date.year() == year).collect()))
+}
+
+pub async fn get_euer(
+    State(db): State<Db>,
+    Extension(grant): Extension<AdvisorGrant>,
+    Path(year): Path<i32>,
+    Query(query): Query<ReportQuery>,
+) -> Result<Response, ApiError> {
+    authorize(&db, &grant, "euer", year).await?;
+    euer_response(&db, &grant.user_id, year, query.format.as_deref()).await
+}
+
+pub async fn get_ustva(
+    State(db): State<Db>,
+    Extension(grant): Extension<AdvisorGrant>,
+    Path((year, period)): Path<(i32, String)>,
+    Query(query): Query<ReportQuery>,
+) -> Result<Response, ApiError> {
+    authorize(&db, &grant, "ustva", year).await?;
+    ustva_response(&db, &grant.user_id, year, &period, query.format.as_deref()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_db;
+    use crate::mail::LogMailTransport;
+
+    #[tokio::test]
+    async fn link_requests_are_throttled() {
+        let db = test_db().await;
+        let mailer = Arc::new(Mailer::new(Box::new(LogMailTransport), "noreply@example.com", "http://localhost"));
+        let request = |email: &str| {
+            request_advisor_link(
+                State(db.clone()),
+                State(mailer.clone()),
+                ConnectInfo(SocketAddr::from(([203, 0, 113, 7], 443))),
+                JsonBody(AdvisorLinkRequest { email: email.to_string() }),
+            )
+        };
+
+        for _ in 0..4 {
+            assert_eq!(request("steuer@example.com").await.unwrap(), StatusCode::ACCEPTED);
+        }
+        let error = request(" Steuer@Example.com").await.unwrap_err();
+        assert!(matches!(error, ApiError::RateLimited { .. }));
+        assert_eq!(request("other@example.com").await.unwrap(), StatusCode::ACCEPTED);
+    }
+}
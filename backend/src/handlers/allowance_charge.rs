@@ -0,0 +1,97 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    Extension,
+};
+use serde::Serialize;
+use validator::Validate;
+use crate::auth::member::Member;
+use crate::db::{self, Db};
+use crate::error::{ApiError, JsonBody};
+use crate::models::membership::Permission;
+use crate::models::allowance_charge::{AllowanceCharge, NewAllowanceCharge};
+use crate::models::catalog::check_tax_category;
+use crate::models::invoice::Invoice;
+
+#[derive(Debug, Serialize)]
+pub struct AllowanceChargeResponse {
+    pub invoice: Invoice,
+    pub allowance_charge: AllowanceCharge,
+}
+
+async fn load_invoice(db: &Db, user_id: &str, id: &str) -> Result<Invoice, ApiError> {
+    db::invoice::find_by_id(db, user_id, id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load invoice"))?
+        .ok_or_else(|| ApiError::not_found("Invoice not found"))
+}
+
+fn ensure_draft(invoice: &Invoice) -> Result<(), ApiError> {
+    if invoice.status != "draft" {
+        return Err(ApiError::conflict("Allowances and charges can only be changed on draft invoices"));
+    }
+    Ok(())
+}
+
+pub async fn get_allowance_charges(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<AllowanceCharge>>, ApiError> {
+    member.require(Permission::View)?;
+    let invoice = load_invoice(&db, &member.organisation_id, &id).await?;
+
+    let allowance_charges = db::allowance_charge::find_by_invoice(&db, &invoice.id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load allowances and charges"))?;
+
+    Ok(Json(allowance_charges))
+}
+
+pub async fn create_allowance_charge(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path(id): Path<String>,
+    JsonBody(payload): JsonBody<NewAllowanceCharge>,
+) -> Result<(StatusCode, Json<AllowanceChargeResponse>), ApiError> {
+    member.require(Permission::Edit)?;
+    payload.validate()?;
+    payload.check().map_err(ApiError::validation)?;
+
+    let invoice = load_invoice(&db, &member.organisation_id, &id).await?;
+    ensure_draft(&invoice)?;
+
+    let allowance_charge = AllowanceCharge::new(invoice.id.clone(), payload);
+    check_tax_category(&invoice, &allowance_charge.tax_category)
+        .map_err(ApiError::unprocessable)?;
+
+    let (invoice, allowance_charge) = db::allowance_charge::create(&db, &member.user_id, &invoice, &allowance_charge)
+        .await
+        .map_err(|_| ApiError::internal("Failed to save allowance or charge"))?;
+
+    Ok((StatusCode::CREATED, Json(AllowanceChargeResponse { invoice, allowance_charge })))
+}
+
+pub async fn delete_allowance_charge(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path((id, allowance_charge_id)): Path<(String, String)>,
+) -> Result<Json<Invoice>, ApiError> {
+    member.require(Permission::Edit)?;
+    let invoice = load_invoice(&db, &member.organisation_id, &id).await?;
+    ensure_draft(&invoice)?;
+
+    let allowance_charge = db::allowance_charge::find_by_invoice(&db, &invoice.id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load allowances and charges"))?
+        .into_iter()
+        .find(|allowance_charge| allowance_charge.id == allowance_charge_id)
+        .ok_or_else(|| ApiError::not_found("Allowance or charge not found"))?;
+
+    let invoice = db::allowance_charge::delete(&db, &member.user_id, &invoice, &allowance_charge)
+        .await
+        .map_err(|_| ApiError::internal("Failed to delete allowance or charge"))?;
+
+    Ok(Json(invoice))
+}
@@ -0,0 +1,62 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    Extension,
+};
+use chrono::Utc;
+use serde::Serialize;
+use crate::auth::jwt::Claims;
+use crate::db::{self, Db};
+use crate::error::{ApiError, JsonBody};
+use crate::models::api_key::{ApiKey, NewApiKey};
+
+#[derive(Debug, Serialize)]
+pub struct CreatedApiKey {
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+    /// Shown once; only its hash is stored.
+    pub key: String,
+}
+
+pub async fn get_api_keys(
+    State(db): State<Db>,
+    Extension(claims): Extension<Claims>,
+) -> Result<Json<Vec<ApiKey>>, ApiError> {
+    let api_keys = db::api_key::find_by_user(&db, &claims.sub)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load API keys"))?;
+
+    Ok(Json(api_keys))
+}
+
+pub async fn create_api_key(
+    State(db): State<Db>,
+    Extension(claims): Extension<Claims>,
+    JsonBody(payload): JsonBody<NewApiKey>,
+) -> Result<(StatusCode, Json<CreatedApiKey>), ApiError> {
+    let (api_key, key) = payload
+        .into_api_key(claims.sub, Utc::now())
+        .map_err(ApiError::validation)?;
+
+    db::api_key::create(&db, &api_key)
+        .await
+        .map_err(|_| ApiError::internal("Failed to save API key"))?;
+
+    Ok((StatusCode::CREATED, Json(CreatedApiKey { api_key, key })))
+}
+
+pub async fn delete_api_key(
+    State(db): State<Db>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let revoked = db::api_key::revoke(&db, &claims.sub, &id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to revoke API key"))?;
+
+    if !revoked {
+        return Err(ApiError::not_found("API key not found"));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
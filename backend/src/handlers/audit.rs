@@ -0,0 +1,52 @@
+use axum::{
+    extract::{Query, State},
+    response::Json,
+    Extension,
+};
+use serde::Deserialize;
+use crate::auth::member::Member;
+use crate::db::{self, Db};
+use crate::error::ApiError;
+use crate::models::membership::Permission;
+use crate::models::audit::{verify_chain, AuditEntry, ChainVerification};
+
+#[derive(Debug, Deserialize)]
+pub struct AuditLogQuery {
+    pub entity_type: Option<String>,
+    pub entity_id: Option<String>,
+}
+
+pub async fn get_audit_log(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Query(query): Query<AuditLogQuery>,
+) -> Result<Json<Vec<AuditEntry>>, ApiError> {
+    member.require(Permission::AuditLog)?;
+    let entries = match (query.entity_type.as_deref(), query.entity_id.as_deref()) {
+        (Some(entity_type), Some(entity_id)) => {
+            db::audit::find_by_entity(&db, &member.organisation_id, entity_type, entity_id).await
+        }
+        (None, None) => db::audit::find_by_user(&db, &member.organisation_id).await,
+        _ => {
+            return Err(ApiError::validation("entity_type and entity_id must be given together"))
+        }
+    }
+    .map_err(|_| ApiError::internal("Failed to load audit log"))?;
+
+    Ok(Json(entries))
+}
+
+pub async fn verify_audit_log(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+) -> Result<Json<ChainVerification>, ApiError> {
+    member.require(Permission::AuditLog)?;
+    let entries = db::audit::find_by_user(&db, &member.organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load audit log"))?;
+    let checkpoint = db::audit::find_checkpoint(&db, &member.organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load audit log"))?;
+
+    Ok(Json(verify_chain(&entries, checkpoint.as_ref())))
+}
@@ -1,58 +1,383 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, OnceLock};
+
 use axum::{
-    extract::State,
-    http::StatusCode,
+    extract::{ConnectInfo, Path, State},
+    http::{header::USER_AGENT, HeaderMap, StatusCode},
     response::Json,
-    Json as AxumJson,
+    Extension,
 };
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
-use crate::db::Db;
+use crate::auth::jwt::{generate_challenge, generate_token, validate_challenge, Claims, ACCESS_TOKEN_MINUTES, CHALLENGE_MINUTES};
+use crate::auth::keys::JwtKeys;
+use crate::db::{self, Db};
+use crate::error::{ApiError, JsonBody};
+use crate::handlers::two_factor::{complete_enrolment, start_enrolment, verify_second_factor};
+use crate::models::login_throttle::{account_key, ThrottleScope};
+use crate::models::session::{hash_refresh_token, new_refresh_token, RefreshError, Session};
+use crate::models::two_factor::TwoFactorEnrolment;
 use crate::models::user::User;
-use crate::auth::jwt::generate_token;
-use bcrypt::{verify, hash, DEFAULT_COST};
+use bcrypt::{hash, verify, DEFAULT_COST};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LoginRequest {
     pub email: String,
     pub password: String,
+    /// Shown in the session list; defaults to the User-Agent header.
+    pub device_label: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct LoginResponse {
     pub token: String,
+    pub refresh_token: String,
+    /// Seconds until `token` expires
+    pub expires_in: u64,
+    pub session_id: String,
+    /// Issued when two-factor authentication was set up during login
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recovery_codes: Option<Vec<String>>,
+}
+
+/// Returned instead of tokens when the password was correct and the second
+/// factor is pending.
+#[derive(Debug, Serialize)]
+pub struct TwoFactorChallenge {
+    pub two_factor_required: bool,
+    /// The account requires two-factor authentication but has none set up
+    pub setup_required: bool,
+    pub challenge_token: String,
+    pub expires_in: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum LoginResult {
+    Tokens(LoginResponse),
+    Challenge(TwoFactorChallenge),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorLoginRequest {
+    pub challenge_token: String,
+    pub code: Option<String>,
+    pub recovery_code: Option<String>,
+    pub device_label: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChallengeRequest {
+    pub challenge_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionResponse {
+    #[serde(flatten)]
+    pub session: Session,
+    /// The session the request was made with
+    pub current: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LogoutAllResponse {
+    pub revoked_sessions: u64,
+}
+
+fn issue_tokens(keys: &JwtKeys, user: &User, session: &Session, refresh_token: String) -> Result<LoginResponse, ApiError> {
+    let token = generate_token(keys, user, &session.id)
+        .map_err(|_| ApiError::internal("Failed to generate token"))?;
+
+    Ok(LoginResponse {
+        token,
+        refresh_token,
+        expires_in: ACCESS_TOKEN_MINUTES * 60,
+        session_id: session.id.clone(),
+        recovery_codes: None,
+    })
+}
+
+async fn start_session(
+    db: &Db,
+    keys: &JwtKeys,
+    user: &User,
+    device_label: Option<String>,
+    address: SocketAddr,
+    headers: &HeaderMap,
+) -> Result<LoginResponse, ApiError> {
+    let device_label = device_label.or_else(|| {
+        headers.get(USER_AGENT).and_then(|value| value.to_str().ok()).map(str::to_string)
+    });
+    let session = Session::new(user.id.clone(), device_label, Some(address.ip().to_string()));
+    let refresh_token = new_refresh_token();
+
+    db::session::create(db, &session, &hash_refresh_token(&refresh_token))
+        .await
+        .map_err(|_| ApiError::internal("Failed to create session"))?;
+
+    issue_tokens(keys, user, &session, refresh_token)
+}
+
+fn invalid_credentials() -> ApiError {
+    ApiError::unauthorized("Invalid credentials")
+}
+
+/// Checked instead of the password when no account has the address, so the
+/// response time does not tell which addresses are registered.
+fn dummy_password_hash() -> &'static str {
+    static HASH: OnceLock<String> = OnceLock::new();
+    HASH.get_or_init(|| hash("not a password", DEFAULT_COST).expect("bcrypt hashing failed"))
+}
+
+/// Rejects the attempt while the account or the client address is locked
+/// after failed attempts.
+async fn check_throttle(db: &Db, account: &str, ip_address: &str) -> Result<(), ApiError> {
+    let now = Utc::now();
+    for (scope, key) in [(ThrottleScope::Account, account), (ThrottleScope::Ip, ip_address)] {
+        let throttle = db::login_throttle::find(db, scope, key)
+            .await
+            .map_err(|_| ApiError::internal("Failed to check login attempts"))?;
+        if let Some(seconds) = throttle.and_then(|throttle| throttle.retry_after(now)) {
+            return Err(ApiError::RateLimited {
+                message: format!("Too many failed login attempts; try again in {} seconds", seconds),
+                retry_after: seconds,
+            });
+        }
+    }
+    Ok(())
+}
+
+async fn record_failure(db: &Db, account: &str, ip_address: &str) -> Result<(), ApiError> {
+    for (scope, key) in [(ThrottleScope::Account, account), (ThrottleScope::Ip, ip_address)] {
+        db::login_throttle::record_failure(db, scope, key)
+            .await
+            .map_err(|_| ApiError::internal("Failed to record login attempt"))?;
+    }
+    Ok(())
+}
+
+/// A completed login resets the account's count; the address keeps its
+/// count, or one valid account would unlock guessing at others.
+async fn clear_failures(db: &Db, account: &str) -> Result<(), ApiError> {
+    db::login_throttle::clear(db, ThrottleScope::Account, account)
+        .await
+        .map_err(|_| ApiError::internal("Failed to record login attempt"))
+}
+
+async fn load_challenge_user(db: &Db, keys: &JwtKeys, challenge_token: &str) -> Result<User, ApiError> {
+    let claims = validate_challenge(keys, challenge_token)
+        .map_err(|_| ApiError::unauthorized("Invalid or expired login challenge"))?;
+
+    db::user::find_by_id(db, &claims.sub)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load user"))?
+        .filter(|user| user.deleted_at.is_none())
+        .ok_or_else(|| ApiError::unauthorized("Invalid or expired login challenge"))
 }
 
 pub async fn login(
-    State(_db): State<Db>,
-    AxumJson(payload): AxumJson<LoginRequest>,
-) -> Result<Json<LoginResponse>, (StatusCode, String)> {
-    // In a real implementation, you'd query the database
-    // This is a simplified example
-    
-    // For demo purposes, create a mock user
-    let user = User::new(
-        payload.email.clone(),
-        hash(&payload.password, DEFAULT_COST).unwrap(),
-        Some("Max".to_string()),
-        Some("Mustermann".to_string()),
-        Some("My Company GmbH".to_string()),
-        Some("DE123456789".to_string()),
-    );
-
-    // Verify password (in real implementation, query from DB first)
-    let is_valid = verify(&payload.password, &user.password_hash)
-        .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid credentials".to_string()))?;
-
-    if !is_valid {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid credentials".to_string()));
+    State(db): State<Db>,
+    State(keys): State<Arc<JwtKeys>>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    JsonBody(payload): JsonBody<LoginRequest>,
+) -> Result<Json<LoginResult>, ApiError> {
+    let account = account_key(&payload.email);
+    let ip_address = address.ip().to_string();
+    check_throttle(&db, &account, &ip_address).await?;
+
+    let user = db::user::find_by_email(&db, payload.email.trim())
+        .await
+        .map_err(|_| ApiError::internal("Failed to load user"))?;
+
+    // Unknown addresses and wrong passwords get the same answer
+    let password_hash = user.as_ref().map_or(dummy_password_hash(), |user| user.password_hash.as_str());
+    let is_valid = verify(&payload.password, password_hash).unwrap_or(false);
+    let Some(user) = user.filter(|_| is_valid) else {
+        record_failure(&db, &account, &ip_address).await?;
+        return Err(invalid_credentials());
+    };
+
+    if !user.two_factor_enabled() && !user.two_factor_required {
+        clear_failures(&db, &account).await?;
     }
+    finish_login(&db, &keys, &user, payload.device_label, address, &headers).await.map(Json)
+}
 
-    // Generate JWT token
-    let token = generate_token(&user)
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Failed to generate token".to_string()))?;
+/// Continues a login whose first factor was accepted, by password or single
+/// sign-on: asks for the second factor where the account has or requires
+/// one, otherwise starts the session.
+pub(crate) async fn finish_login(
+    db: &Db,
+    keys: &JwtKeys,
+    user: &User,
+    device_label: Option<String>,
+    address: SocketAddr,
+    headers: &HeaderMap,
+) -> Result<LoginResult, ApiError> {
+    if user.two_factor_enabled() || user.two_factor_required {
+        let challenge_token = generate_challenge(keys, user)
+            .map_err(|_| ApiError::internal("Failed to generate token"))?;
 
-    let response = LoginResponse {
-        token,
+        return Ok(LoginResult::Challenge(TwoFactorChallenge {
+            two_factor_required: true,
+            setup_required: !user.two_factor_enabled(),
+            challenge_token,
+            expires_in: CHALLENGE_MINUTES * 60,
+        }));
+    }
+
+    let response = start_session(db, keys, user, device_label, address, headers).await?;
+    Ok(LoginResult::Tokens(response))
+}
+
+/// Second login step: a TOTP code or a recovery code for the challenge
+/// returned by the password check. Accounts that have to set up two-factor
+/// authentication confirm their new secret here and receive recovery codes.
+pub async fn login_two_factor(
+    State(db): State<Db>,
+    State(keys): State<Arc<JwtKeys>>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    JsonBody(payload): JsonBody<TwoFactorLoginRequest>,
+) -> Result<Json<LoginResponse>, ApiError> {
+    let user = load_challenge_user(&db, &keys, &payload.challenge_token).await?;
+    let account = account_key(&user.email);
+    let ip_address = address.ip().to_string();
+    check_throttle(&db, &account, &ip_address).await?;
+
+    let verified = if user.two_factor_enabled() {
+        verify_second_factor(&db, &user, payload.code.as_deref(), payload.recovery_code.as_deref())
+            .await
+            .map(|_| None)
+    } else {
+        match payload.code.as_deref() {
+            Some(code) => complete_enrolment(&db, &user.id, &user, code).await.map(Some),
+            None => Err(ApiError::validation("A two-factor code is required")),
+        }
+    };
+    // Wrong codes count like wrong passwords
+    let recovery_codes = match verified {
+        Err(error @ ApiError::Unauthorized(_)) => {
+            record_failure(&db, &account, &ip_address).await?;
+            return Err(error);
+        }
+        result => result?,
     };
 
+    clear_failures(&db, &account).await?;
+    let mut response = start_session(&db, &keys, &user, payload.device_label, address, &headers).await?;
+    response.recovery_codes = recovery_codes;
     Ok(Json(response))
-}
\ No newline at end of file
+}
+
+/// Enrolment during login for accounts that require two-factor
+/// authentication but have none set up.
+pub async fn login_two_factor_setup(
+    State(db): State<Db>,
+    State(keys): State<Arc<JwtKeys>>,
+    JsonBody(payload): JsonBody<ChallengeRequest>,
+) -> Result<Json<TwoFactorEnrolment>, ApiError> {
+    let user = load_challenge_user(&db, &keys, &payload.challenge_token).await?;
+    if !user.two_factor_required {
+        return Err(ApiError::conflict("Set up two-factor authentication in the account settings"));
+    }
+
+    Ok(Json(start_enrolment(&db, &user).await?))
+}
+
+/// Exchanges a refresh token for a new access token and a new refresh token.
+/// Each refresh token can be used once.
+pub async fn refresh(
+    State(db): State<Db>,
+    State(keys): State<Arc<JwtKeys>>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    JsonBody(payload): JsonBody<RefreshRequest>,
+) -> Result<Json<LoginResponse>, ApiError> {
+    let refresh_token = new_refresh_token();
+    let ip_address = address.ip().to_string();
+
+    let session = db::session::rotate(
+        &db,
+        &hash_refresh_token(&payload.refresh_token),
+        &hash_refresh_token(&refresh_token),
+        Some(&ip_address),
+    )
+    .await
+    .map_err(|_| ApiError::internal("Failed to refresh session"))?
+    .map_err(|error| match error {
+        RefreshError::Invalid => ApiError::unauthorized("Invalid or expired refresh token"),
+        RefreshError::Reused => ApiError::unauthorized("Refresh token was already used; the session has been revoked"),
+    })?;
+
+    let user = db::user::find_by_id(&db, &session.user_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load user"))?
+        .filter(|user| user.deleted_at.is_none())
+        .ok_or_else(|| ApiError::unauthorized("Invalid or expired refresh token"))?;
+
+    Ok(Json(issue_tokens(&keys, &user, &session, refresh_token)?))
+}
+
+/// Ends the session of a refresh token immediately: access tokens issued for
+/// it are rejected from then on.
+pub async fn logout(
+    State(db): State<Db>,
+    JsonBody(payload): JsonBody<RefreshRequest>,
+) -> Result<StatusCode, ApiError> {
+    db::session::revoke_by_token(&db, &hash_refresh_token(&payload.refresh_token))
+        .await
+        .map_err(|_| ApiError::internal("Failed to end session"))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn logout_all(
+    State(db): State<Db>,
+    Extension(claims): Extension<Claims>,
+) -> Result<Json<LogoutAllResponse>, ApiError> {
+    let revoked_sessions = db::session::revoke_all(&db, &claims.sub)
+        .await
+        .map_err(|_| ApiError::internal("Failed to end sessions"))?;
+
+    Ok(Json(LogoutAllResponse { revoked_sessions }))
+}
+
+pub async fn get_sessions(
+    State(db): State<Db>,
+    Extension(claims): Extension<Claims>,
+) -> Result<Json<Vec<SessionResponse>>, ApiError> {
+    let sessions = db::session::find_active(&db, &claims.sub)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load sessions"))?;
+
+    Ok(Json(
+        sessions
+            .into_iter()
+            .map(|session| SessionResponse {
+                current: claims.sid.as_deref() == Some(session.id.as_str()),
+                session,
+            })
+            .collect(),
+    ))
+}
+
+pub async fn delete_session(
+    State(db): State<Db>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let revoked = db::session::revoke(&db, &claims.sub, &id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to end session"))?;
+
+    if !revoked {
+        return Err(ApiError::not_found("Session not found"));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,109 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+    Extension,
+};
+use serde::Deserialize;
+use validator::Validate;
+use crate::auth::member::Member;
+use crate::db::{self, Db};
+use crate::error::{ApiError, JsonBody};
+use crate::models::membership::Permission;
+use crate::models::catalog::{CatalogItem, NewCatalogItem};
+
+#[derive(Debug, Deserialize)]
+pub struct CatalogQuery {
+    pub q: Option<String>,
+    #[serde(default)]
+    pub include_archived: bool,
+}
+
+async fn load_item(db: &Db, user_id: &str, id: &str) -> Result<CatalogItem, ApiError> {
+    db::catalog::find_by_id(db, user_id, id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load catalogue item"))?
+        .ok_or_else(|| ApiError::not_found("Catalogue item not found"))
+}
+
+fn validate(payload: &NewCatalogItem) -> Result<(), ApiError> {
+    payload.validate()?;
+    payload.check().map_err(ApiError::validation)
+}
+
+fn save_error(error: sqlx::Error) -> ApiError {
+    match error {
+        sqlx::Error::Database(error) if error.is_unique_violation() => {
+            ApiError::conflict("Item number is already in use")
+        }
+        _ => ApiError::internal("Failed to save catalogue item"),
+    }
+}
+
+pub async fn get_catalog_items(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Query(query): Query<CatalogQuery>,
+) -> Result<Json<Vec<CatalogItem>>, ApiError> {
+    member.require(Permission::View)?;
+    let items = db::catalog::search(&db, &member.organisation_id, query.q.as_deref(), query.include_archived)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load catalogue"))?;
+
+    Ok(Json(items))
+}
+
+pub async fn get_catalog_item(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path(id): Path<String>,
+) -> Result<Json<CatalogItem>, ApiError> {
+    member.require(Permission::View)?;
+    Ok(Json(load_item(&db, &member.organisation_id, &id).await?))
+}
+
+pub async fn create_catalog_item(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    JsonBody(payload): JsonBody<NewCatalogItem>,
+) -> Result<(StatusCode, Json<CatalogItem>), ApiError> {
+    member.require(Permission::Edit)?;
+    validate(&payload)?;
+
+    let mut item = CatalogItem::new(member.organisation_id.clone(), String::new(), String::new(), payload.net_price);
+    item.apply(payload);
+    db::catalog::create(&db, &member.user_id, &item).await.map_err(save_error)?;
+
+    Ok((StatusCode::CREATED, Json(item)))
+}
+
+pub async fn update_catalog_item(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path(id): Path<String>,
+    JsonBody(payload): JsonBody<NewCatalogItem>,
+) -> Result<Json<CatalogItem>, ApiError> {
+    member.require(Permission::Edit)?;
+    validate(&payload)?;
+    let mut item = load_item(&db, &member.organisation_id, &id).await?;
+
+    item.apply(payload);
+    db::catalog::update(&db, &member.user_id, &item).await.map_err(save_error)?;
+
+    Ok(Json(item))
+}
+
+pub async fn delete_catalog_item(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    member.require(Permission::Edit)?;
+    let item = load_item(&db, &member.organisation_id, &id).await?;
+
+    db::catalog::delete(&db, &member.user_id, &member.organisation_id, &item.id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to delete catalogue item"))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,9 +1,13 @@
 use axum::{
-    extract::State,
+    extract::{Path, State},
     http::StatusCode,
     response::Json,
+    Extension,
 };
-use crate::db::Db;
+use crate::auth::member::Member;
+use crate::db::{self, Db};
+use crate::error::ApiError;
+use crate::models::membership::Permission;
 
 pub async fn create_client(
     State(_db): State<Db>,
@@ -17,4 +21,25 @@ pub async fn get_clients(
 ) -> Result<Json<&'static str>, StatusCode> {
     // Placeholder implementation
     Ok(Json("List of clients"))
-}
\ No newline at end of file
+}
+
+/// Soft-deletes the client. Invoices issued to it are kept for their
+/// retention period; afterwards the purge job anonymises the client.
+pub async fn delete_client(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    member.require(Permission::DeleteClients)?;
+    let client = db::client::find_by_id(&db, &member.organisation_id, &id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load client"))?
+        .filter(|client| client.deleted_at.is_none())
+        .ok_or_else(|| ApiError::not_found("Client not found"))?;
+
+    db::client::soft_delete(&db, &member.user_id, &client)
+        .await
+        .map_err(|_| ApiError::internal("Failed to delete client"))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
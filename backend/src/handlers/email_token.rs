@@ -0,0 +1,187 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::{
+    extract::{ConnectInfo, State},
+    http::StatusCode,
+    Extension,
+};
+use bcrypt::{hash, DEFAULT_COST};
+use chrono::Utc;
+use crate::auth::jwt::{generate_email_token, validate_email_token, Claims};
+use crate::auth::keys::JwtKeys;
+use crate::db::{self, Db};
+use crate::error::{ApiError, JsonBody};
+use crate::mail::Mailer;
+use crate::models::email_token::{
+    EmailToken, EmailTokenPurpose, EmailTokenRequest, NewPasswordRequest, PasswordResetRequest,
+    EMAIL_VERIFICATION_HOURS, PASSWORD_RESET_MINUTES,
+};
+use crate::models::login_throttle::{account_key, ThrottleScope};
+use crate::models::user::{User, MIN_PASSWORD_LENGTH};
+
+/// Stores a new token for the user and mails the link for it.
+pub(crate) async fn send_email_token(
+    db: &Db,
+    keys: &JwtKeys,
+    mailer: &Mailer,
+    user: &User,
+    purpose: EmailTokenPurpose,
+) -> Result<(), ApiError> {
+    let token = EmailToken::new(user, purpose);
+    db::email_token::create(db, &token)
+        .await
+        .map_err(|_| ApiError::internal("Failed to create token"))?;
+    let signed = generate_email_token(keys, &token)
+        .map_err(|_| ApiError::internal("Failed to generate token"))?;
+
+    let message = match purpose {
+        EmailTokenPurpose::VerifyEmail => mailer.email_verification(&user.email, &signed, EMAIL_VERIFICATION_HOURS),
+        EmailTokenPurpose::ResetPassword => mailer.password_reset(&user.email, &signed, PASSWORD_RESET_MINUTES),
+    };
+    mailer.send(&message).await.map_err(|error| {
+        tracing::error!("Failed to send {} mail: {}", purpose.as_str(), error);
+        ApiError::upstream("Failed to send mail")
+    })
+}
+
+/// Counts a request for a mailed link against the address and the client,
+/// like failed logins but in scopes of their own, and rejects it while
+/// either is locked.
+pub(crate) async fn throttle_mail_request(db: &Db, account: &str, ip_address: &str) -> Result<(), ApiError> {
+    let now = Utc::now();
+    let scopes = [(ThrottleScope::MailAccount, account), (ThrottleScope::MailIp, ip_address)];
+    for (scope, key) in scopes {
+        let throttle = db::login_throttle::find(db, scope, key)
+            .await
+            .map_err(|_| ApiError::internal("Failed to check mail requests"))?;
+        if let Some(seconds) = throttle.and_then(|throttle| throttle.retry_after(now)) {
+            return Err(ApiError::RateLimited {
+                message: format!("Too many requests for this address; try again in {} seconds", seconds),
+                retry_after: seconds,
+            });
+        }
+    }
+    for (scope, key) in scopes {
+        db::login_throttle::record_failure(db, scope, key)
+            .await
+            .map_err(|_| ApiError::internal("Failed to record mail request"))?;
+    }
+    Ok(())
+}
+
+fn invalid_link() -> ApiError {
+    ApiError::validation("Invalid or expired link")
+}
+
+/// Sends the current user a new verification link.
+pub async fn request_email_verification(
+    State(db): State<Db>,
+    State(keys): State<Arc<JwtKeys>>,
+    State(mailer): State<Arc<Mailer>>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    Extension(claims): Extension<Claims>,
+) -> Result<StatusCode, ApiError> {
+    let user = db::user::find_by_id(&db, &claims.sub)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load user"))?
+        .ok_or_else(|| ApiError::not_found("User not found"))?;
+    if user.email_verified_at.is_some() {
+        return Err(ApiError::conflict("Email address is already verified"));
+    }
+    throttle_mail_request(&db, &account_key(&user.email), &address.ip().to_string()).await?;
+
+    send_email_token(&db, &keys, &mailer, &user, EmailTokenPurpose::VerifyEmail).await?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+pub async fn verify_email(
+    State(db): State<Db>,
+    State(keys): State<Arc<JwtKeys>>,
+    JsonBody(payload): JsonBody<EmailTokenRequest>,
+) -> Result<StatusCode, ApiError> {
+    let claims = validate_email_token(&keys, &payload.token, EmailTokenPurpose::VerifyEmail)
+        .map_err(|_| invalid_link())?;
+
+    db::email_token::verify_email(&db, &claims.jti)
+        .await
+        .map_err(|_| ApiError::internal("Failed to verify email address"))?
+        .ok_or_else(invalid_link)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Mails a reset link if an account with the address exists. The response is
+/// the same either way and is sent before the lookup, so it reveals nothing
+/// about registered addresses; the throttle counts unknown addresses too.
+pub async fn request_password_reset(
+    State(db): State<Db>,
+    State(keys): State<Arc<JwtKeys>>,
+    State(mailer): State<Arc<Mailer>>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    JsonBody(payload): JsonBody<PasswordResetRequest>,
+) -> Result<StatusCode, ApiError> {
+    throttle_mail_request(&db, &account_key(&payload.email), &address.ip().to_string()).await?;
+
+    tokio::spawn(async move {
+        match db::user::find_by_email(&db, payload.email.trim()).await {
+            Ok(Some(user)) => {
+                // Errors are logged by send_email_token
+                let _ = send_email_token(&db, &keys, &mailer, &user, EmailTokenPurpose::ResetPassword).await;
+            }
+            Ok(None) => {}
+            Err(error) => tracing::error!("Password reset lookup failed: {}", error),
+        }
+    });
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Sets a new password and ends all sessions of the account.
+pub async fn reset_password(
+    State(db): State<Db>,
+    State(keys): State<Arc<JwtKeys>>,
+    JsonBody(payload): JsonBody<NewPasswordRequest>,
+) -> Result<StatusCode, ApiError> {
+    if payload.password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(ApiError::validation(format!("Password must be at least {} characters long", MIN_PASSWORD_LENGTH)));
+    }
+    let claims = validate_email_token(&keys, &payload.token, EmailTokenPurpose::ResetPassword)
+        .map_err(|_| invalid_link())?;
+
+    let password_hash = hash(&payload.password, DEFAULT_COST)
+        .map_err(|_| ApiError::internal("Failed to hash password"))?;
+
+    db::email_token::reset_password(&db, &claims.jti, &password_hash)
+        .await
+        .map_err(|_| ApiError::internal("Failed to reset password"))?
+        .ok_or_else(invalid_link)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::test_db;
+
+    #[tokio::test]
+    async fn mail_requests_are_throttled_per_address_and_client() {
+        let db = test_db().await;
+        for _ in 0..4 {
+            throttle_mail_request(&db, "max@example.com", "203.0.113.7").await.unwrap();
+        }
+        let error = throttle_mail_request(&db, "max@example.com", "198.51.100.1").await.unwrap_err();
+        assert!(matches!(error, ApiError::RateLimited { .. }));
+
+        // The login counters are separate
+        let login = db::login_throttle::find(&db, ThrottleScope::Account, "max@example.com").await.unwrap();
+        assert!(login.is_none());
+
+        for n in 0..11 {
+            throttle_mail_request(&db, &format!("user{}@example.com", n), "192.0.2.1").await.unwrap();
+        }
+        let error = throttle_mail_request(&db, "other@example.com", "192.0.2.1").await.unwrap_err();
+        assert!(matches!(error, ApiError::RateLimited { .. }));
+    }
+}
@@ -0,0 +1,69 @@
+use axum::{
+    extract::{Query, State},
+    response::Json,
+    Extension,
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use crate::auth::member::Member;
+use crate::db::{self, Db};
+use crate::error::ApiError;
+use crate::models::membership::Permission;
+use crate::models::exchange_rate::{parse_ecb_xml, ExchangeRate};
+
+/// The full ECB history since 1999 is a few megabytes.
+pub const MAX_IMPORT_SIZE: usize = 32 * 1024 * 1024;
+
+#[derive(Debug, Deserialize)]
+pub struct ExchangeRateQuery {
+    pub currency: Option<String>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportResponse {
+    pub imported: usize,
+    pub currencies: Vec<String>,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+}
+
+pub async fn get_exchange_rates(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Query(query): Query<ExchangeRateQuery>,
+) -> Result<Json<Vec<ExchangeRate>>, ApiError> {
+    member.require(Permission::View)?;
+    let rates = db::exchange_rate::find_by_user(&db, &member.organisation_id, query.currency.as_deref(), query.from, query.to)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load exchange rates"))?;
+
+    Ok(Json(rates))
+}
+
+/// Imports an ECB euro reference rate file (`eurofxref-daily.xml`,
+/// `eurofxref-hist-90d.xml` or `eurofxref-hist.xml`) sent as the request body.
+pub async fn import_exchange_rates(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    body: String,
+) -> Result<Json<ImportResponse>, ApiError> {
+    member.require(Permission::Edit)?;
+    let rates = parse_ecb_xml(&body).map_err(ApiError::validation)?;
+
+    db::exchange_rate::import(&db, &member.organisation_id, &rates)
+        .await
+        .map_err(|_| ApiError::internal("Failed to save exchange rates"))?;
+
+    let mut currencies: Vec<String> = rates.iter().map(|rate| rate.currency.clone()).collect();
+    currencies.sort();
+    currencies.dedup();
+
+    Ok(Json(ImportResponse {
+        imported: rates.len(),
+        currencies,
+        from: rates.iter().map(|rate| rate.rate_date).min().unwrap_or_default(),
+        to: rates.iter().map(|rate| rate.rate_date).max().unwrap_or_default(),
+    }))
+}
@@ -0,0 +1,229 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use serde::Deserialize;
+use validator::Validate;
+use crate::auth::member::Member;
+use crate::db::{self, Db};
+use crate::error::{ApiError, JsonBody};
+use crate::models::membership::Permission;
+use crate::models::expense::{Expense, NewExpense};
+use crate::storage::{receipt_key, ReceiptStorage};
+
+pub const MAX_RECEIPT_SIZE: usize = 10 * 1024 * 1024;
+
+const RECEIPT_CONTENT_TYPES: &[&str] = &["application/pdf", "image/jpeg", "image/png", "image/webp"];
+
+#[derive(Debug, Deserialize)]
+pub struct ReceiptQuery {
+    pub filename: Option<String>,
+}
+
+async fn load_expense(db: &Db, user_id: &str, id: &str) -> Result<Expense, ApiError> {
+    db::expense::find_by_id(db, user_id, id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load expense"))?
+        .ok_or_else(|| ApiError::not_found("Expense not found"))
+}
+
+fn validate(payload: &NewExpense) -> Result<(), ApiError> {
+    payload.validate()?;
+    payload.check().map_err(ApiError::validation)
+}
+
+pub async fn create_expense(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    JsonBody(payload): JsonBody<NewExpense>,
+) -> Result<(StatusCode, Json<Expense>), ApiError> {
+    member.require(Permission::Edit)?;
+    validate(&payload)?;
+
+    let mut expense = Expense::new(
+        member.organisation_id.clone(),
+        String::new(),
+        payload.expense_date,
+        String::new(),
+        0.0,
+        0.0,
+        0.0,
+    );
+    expense.apply(payload);
+
+    db::expense::create(&db, &member.user_id, &expense)
+        .await
+        .map_err(|_| ApiError::internal("Failed to save expense"))?;
+
+    Ok((StatusCode::CREATED, Json(expense)))
+}
+
+pub async fn get_expenses(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+) -> Result<Json<Vec<Expense>>, ApiError> {
+    member.require(Permission::View)?;
+    let expenses = db::expense::find_by_user(&db, &member.organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load expenses"))?;
+
+    Ok(Json(expenses))
+}
+
+pub async fn get_expense(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path(id): Path<String>,
+) -> Result<Json<Expense>, ApiError> {
+    member.require(Permission::View)?;
+    Ok(Json(load_expense(&db, &member.organisation_id, &id).await?))
+}
+
+pub async fn update_expense(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path(id): Path<String>,
+    JsonBody(payload): JsonBody<NewExpense>,
+) -> Result<Json<Expense>, ApiError> {
+    member.require(Permission::Edit)?;
+    validate(&payload)?;
+
+    let mut expense = load_expense(&db, &member.organisation_id, &id).await?;
+    expense.apply(payload);
+
+    db::expense::update(&db, &member.user_id, &expense)
+        .await
+        .map_err(|_| ApiError::internal("Failed to update expense"))?;
+
+    Ok(Json(expense))
+}
+
+pub async fn delete_expense(
+    State(db): State<Db>,
+    State(receipts): State<Arc<dyn ReceiptStorage>>,
+    Extension(member): Extension<Member>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    member.require(Permission::Edit)?;
+    let expense = load_expense(&db, &member.organisation_id, &id).await?;
+
+    if let Some(key) = expense.receipt_key.as_deref() {
+        receipts
+            .delete(key)
+            .await
+            .map_err(|_| ApiError::internal("Failed to delete receipt"))?;
+    }
+
+    db::expense::delete(&db, &member.user_id, &member.organisation_id, &expense.id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to delete expense"))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn upload_receipt(
+    State(db): State<Db>,
+    State(receipts): State<Arc<dyn ReceiptStorage>>,
+    Extension(member): Extension<Member>,
+    Path(id): Path<String>,
+    Query(query): Query<ReceiptQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<Expense>, ApiError> {
+    member.require(Permission::Edit)?;
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.split(';').next().unwrap_or_default().trim().to_lowercase())
+        .unwrap_or_default();
+
+    if !RECEIPT_CONTENT_TYPES.contains(&content_type.as_str()) {
+        return Err(ApiError::UnsupportedMediaType(
+            "Receipts must be PDF, JPEG, PNG or WebP files".to_string(),
+        ));
+    }
+    if body.is_empty() {
+        return Err(ApiError::validation("Receipt file is empty"));
+    }
+
+    let mut expense = load_expense(&db, &member.organisation_id, &id).await?;
+    let key = receipt_key(&member.organisation_id, &expense.id);
+    let filename = query.filename.unwrap_or_else(|| "receipt".to_string());
+
+    receipts
+        .put(&key, &body, &content_type)
+        .await
+        .map_err(|_| ApiError::internal("Failed to store receipt"))?;
+    db::expense::set_receipt(&db, &member.user_id, &expense.id, Some(&key), Some(&filename), Some(&content_type))
+        .await
+        .map_err(|_| ApiError::internal("Failed to update expense"))?;
+
+    expense.receipt_key = Some(key);
+    expense.receipt_filename = Some(filename);
+    expense.receipt_content_type = Some(content_type);
+
+    Ok(Json(expense))
+}
+
+pub async fn get_receipt(
+    State(db): State<Db>,
+    State(receipts): State<Arc<dyn ReceiptStorage>>,
+    Extension(member): Extension<Member>,
+    Path(id): Path<String>,
+) -> Result<Response, ApiError> {
+    member.require(Permission::View)?;
+    let expense = load_expense(&db, &member.organisation_id, &id).await?;
+    let key = expense
+        .receipt_key
+        .ok_or_else(|| ApiError::not_found("Expense has no receipt"))?;
+
+    let data = receipts
+        .get(&key)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load receipt"))?
+        .ok_or_else(|| ApiError::not_found("Receipt file is missing"))?;
+
+    let content_type = expense
+        .receipt_content_type
+        .unwrap_or_else(|| "application/octet-stream".to_string());
+    let filename = expense.receipt_filename.unwrap_or_else(|| "receipt".to_string());
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("inline; filename=\"{}\"", filename.replace('"', "")),
+            ),
+        ],
+        data,
+    )
+        .into_response())
+}
+
+pub async fn delete_receipt(
+    State(db): State<Db>,
+    State(receipts): State<Arc<dyn ReceiptStorage>>,
+    Extension(member): Extension<Member>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    member.require(Permission::Edit)?;
+    let expense = load_expense(&db, &member.organisation_id, &id).await?;
+
+    if let Some(key) = expense.receipt_key.as_deref() {
+        receipts
+            .delete(key)
+            .await
+            .map_err(|_| ApiError::internal("Failed to delete receipt"))?;
+        db::expense::set_receipt(&db, &member.user_id, &expense.id, None, None, None)
+            .await
+            .map_err(|_| ApiError::internal("Failed to update expense"))?;
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
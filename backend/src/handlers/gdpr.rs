@@ -0,0 +1,181 @@
+use axum::{
+    extract::{Path, State},
+    response::Json,
+    Extension,
+};
+use chrono::Utc;
+use crate::auth::jwt::Claims;
+use crate::auth::member::Member;
+use crate::db::{self, Db};
+use crate::error::ApiError;
+use crate::models::membership::Permission;
+use crate::gdpr::{ClientDataExport, ErasureReport, UserDataExport};
+use crate::models::client::Client;
+use crate::models::invoice::Invoice;
+use crate::models::user::User;
+
+async fn load_user(db: &Db, id: &str) -> Result<User, ApiError> {
+    db::user::find_by_id(db, id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load user"))?
+        .filter(|user| user.anonymized_at.is_none())
+        .ok_or_else(|| ApiError::not_found("User not found"))
+}
+
+async fn load_client(db: &Db, user_id: &str, id: &str) -> Result<Client, ApiError> {
+    db::client::find_by_id(db, user_id, id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load client"))?
+        .filter(|client| client.anonymized_at.is_none())
+        .ok_or_else(|| ApiError::not_found("Client not found"))
+}
+
+async fn load_invoices(db: &Db, user_id: &str) -> Result<Vec<Invoice>, ApiError> {
+    db::invoice::find_by_user(db, user_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load invoices"))
+}
+
+fn draft_ids<'a>(invoices: impl Iterator<Item = &'a Invoice>) -> Vec<String> {
+    invoices
+        .filter(|invoice| invoice.status == "draft")
+        .map(|invoice| invoice.id.clone())
+        .collect()
+}
+
+/// Art. 15 / 20 DSGVO: everything stored about the account holder.
+pub async fn export_current_user(
+    State(db): State<Db>,
+    Extension(claims): Extension<Claims>,
+) -> Result<Json<UserDataExport>, ApiError> {
+    let user = load_user(&db, &claims.sub).await?;
+    let settings = match db::settings::find_by_user(&db, &claims.sub).await {
+        Ok(settings) => Some(settings),
+        Err(sqlx::Error::RowNotFound) => None,
+        Err(_) => return Err(ApiError::internal("Failed to load settings")),
+    };
+    let clients = db::client::find_by_user(&db, &claims.sub)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load clients"))?;
+    let invoices = load_invoices(&db, &claims.sub).await?;
+    let invoice_items = db::invoice::find_items_by_user(&db, &claims.sub)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load invoice items"))?;
+    let payments = db::payment::find_by_user(&db, &claims.sub)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load payments"))?;
+    let expenses = db::expense::find_by_user(&db, &claims.sub)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load expenses"))?;
+    let projects = db::project::find_by_user(&db, &claims.sub)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load projects"))?;
+    let time_entries = db::time_entry::find_by_user(&db, &claims.sub)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load time entries"))?;
+    let sessions = db::session::find_by_user(&db, &claims.sub)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load sessions"))?;
+    let api_keys = db::api_key::find_by_user(&db, &claims.sub)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load API keys"))?;
+    let memberships = db::membership::find_by_user(&db, &claims.sub)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load memberships"))?;
+    let advisor_grants = db::advisor_grant::find_by_user(&db, &claims.sub)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load advisor grants"))?;
+    let identities = db::identity::find_by_user(&db, &claims.sub)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load identities"))?;
+    let audit_log = db::audit::find_by_user(&db, &claims.sub)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load audit log"))?;
+
+    Ok(Json(UserDataExport {
+        generated_at: Utc::now(),
+        user,
+        settings,
+        clients,
+        invoices,
+        invoice_items,
+        payments,
+        expenses,
+        projects,
+        time_entries,
+        sessions,
+        api_keys,
+        memberships,
+        advisor_grants,
+        identities,
+        audit_log,
+    }))
+}
+
+/// Art. 15 / 20 DSGVO: everything stored about one client.
+pub async fn export_client(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path(id): Path<String>,
+) -> Result<Json<ClientDataExport>, ApiError> {
+    member.require(Permission::DeleteClients)?;
+    let client = load_client(&db, &member.organisation_id, &id).await?;
+    let invoices = load_invoices(&db, &member.organisation_id).await?;
+    let items = db::invoice::find_items_by_user(&db, &member.organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load invoice items"))?;
+    let payments = db::payment::find_by_user(&db, &member.organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load payments"))?;
+    let audit_log = db::audit::find_by_user(&db, &member.organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load audit log"))?;
+
+    Ok(Json(ClientDataExport::build(client, &invoices, &items, &payments, &audit_log)))
+}
+
+/// Art. 17 DSGVO for the account holder. Closes the account and reports what
+/// has to be kept.
+pub async fn erase_current_user(
+    State(db): State<Db>,
+    Extension(claims): Extension<Claims>,
+) -> Result<Json<ErasureReport>, ApiError> {
+    let user = load_user(&db, &claims.sub).await?;
+    let invoices = load_invoices(&db, &claims.sub).await?;
+    let expenses = db::expense::find_by_user(&db, &claims.sub)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load expenses"))?;
+    let audit_log = db::audit::find_by_user(&db, &claims.sub)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load audit log"))?;
+
+    let report = ErasureReport::for_user(&user, &invoices, &expenses, &audit_log);
+    db::gdpr::erase_user(&db, &claims.sub, &user, &draft_ids(invoices.iter()), &report)
+        .await
+        .map_err(|_| ApiError::internal("Failed to erase user"))?;
+
+    Ok(Json(report))
+}
+
+/// Art. 17 DSGVO for a client. Invoices issued to the client are kept with
+/// the address printed on them.
+pub async fn erase_client(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path(id): Path<String>,
+) -> Result<Json<ErasureReport>, ApiError> {
+    member.require(Permission::DeleteClients)?;
+    let client = load_client(&db, &member.organisation_id, &id).await?;
+    let invoices = load_invoices(&db, &member.organisation_id).await?;
+    let audit_log = db::audit::find_by_entity(&db, &member.organisation_id, "client", &client.id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load audit log"))?;
+
+    let report = ErasureReport::for_client(&client, &invoices, &audit_log);
+    let drafts = draft_ids(invoices.iter().filter(|invoice| invoice.client_id == client.id));
+    db::gdpr::erase_client(&db, &member.user_id, &client, &drafts, &report)
+        .await
+        .map_err(|_| ApiError::internal("Failed to erase client"))?;
+
+    Ok(Json(report))
+}
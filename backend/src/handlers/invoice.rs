@@ -1,9 +1,22 @@
 use axum::{
-    extract::State,
+    extract::{Path, State},
     http::StatusCode,
     response::Json,
+    Extension,
 };
-use crate::db::Db;
+use chrono::{NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use crate::auth::member::Member;
+use crate::db::{self, Db};
+use crate::error::{ApiError, JsonBody};
+use crate::models::membership::Permission;
+use crate::models::advance_deduction::{deductions_for, AdvanceDeduction, BillingSummary};
+use crate::models::catalog::{check_tax_category, InvoiceLineInput};
+use crate::models::exchange_rate::{is_currency_code, BASE_CURRENCY, MAX_RATE_AGE_DAYS};
+use crate::models::invoice::{CurrencyConversion, Invoice, InvoiceItem, PaymentTerms};
+use crate::models::payment::{NewPayment, Payment};
+use crate::reports::round_cents;
 
 pub async fn create_invoice(
     State(_db): State<Db>,
@@ -24,4 +37,342 @@ pub async fn get_invoice(
 ) -> Result<Json<&'static str>, StatusCode> {
     // Placeholder implementation
     Ok(Json("Single invoice"))
-}
\ No newline at end of file
+}
+#[derive(Debug, Deserialize)]
+pub struct PaymentTermsRequest {
+    pub skonto_percent: Option<f64>,
+    pub skonto_days: Option<i64>,
+}
+
+impl PaymentTermsRequest {
+    pub fn check(&self) -> Result<(), String> {
+        match (self.skonto_percent, self.skonto_days) {
+            (None, None) => Ok(()),
+            (Some(percent), Some(days)) if percent > 0.0 && percent < 100.0 && days >= 0 => Ok(()),
+            (Some(_), Some(_)) => Err("Skonto needs a percentage between 0 and 100 and a non-negative number of days".to_string()),
+            _ => Err("Skonto needs both skonto_percent and skonto_days".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BillingRequest {
+    pub billing_type: String,
+    pub project_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CurrencyRequest {
+    pub currency: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AddInvoiceItemResponse {
+    pub invoice: Invoice,
+    pub item: InvoiceItem,
+}
+
+/// Adds a line to a draft invoice, prefilled from a catalogue item when one is
+/// referenced.
+pub async fn add_invoice_item(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path(id): Path<String>,
+    JsonBody(payload): JsonBody<InvoiceLineInput>,
+) -> Result<(StatusCode, Json<AddInvoiceItemResponse>), ApiError> {
+    member.require(Permission::Edit)?;
+    payload.validate()?;
+
+    let invoice = db::invoice::find_by_id(&db, &member.organisation_id, &id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load invoice"))?
+        .ok_or_else(|| ApiError::not_found("Invoice not found"))?;
+    if invoice.status != "draft" {
+        return Err(ApiError::conflict("Items can only be added to draft invoices"));
+    }
+
+    let catalog_item = match payload.catalog_item_id.as_deref() {
+        Some(catalog_item_id) => Some(
+            db::catalog::find_by_id(&db, &member.organisation_id, catalog_item_id)
+                .await
+                .map_err(|_| ApiError::internal("Failed to load catalogue item"))?
+                .ok_or_else(|| ApiError::validation("Unknown catalogue item"))?,
+        ),
+        None => None,
+    };
+
+    let line = payload
+        .resolve(catalog_item.as_ref())
+        .map_err(ApiError::validation)?;
+    if let Some(category) = line.tax_category.as_deref() {
+        check_tax_category(&invoice, category).map_err(ApiError::unprocessable)?;
+    }
+
+    let (invoice, mut items) = db::invoice::add_items(&db, &member.user_id, &invoice, &[line])
+        .await
+        .map_err(|_| ApiError::internal("Failed to save invoice item"))?;
+
+    Ok((StatusCode::CREATED, Json(AddInvoiceItemResponse { invoice, item: items.remove(0) })))
+}
+
+pub async fn get_payment_terms(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path(id): Path<String>,
+) -> Result<Json<PaymentTerms>, ApiError> {
+    member.require(Permission::View)?;
+    let invoice = db::invoice::find_by_id(&db, &member.organisation_id, &id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load invoice"))?
+        .ok_or_else(|| ApiError::not_found("Invoice not found"))?;
+
+    Ok(Json(invoice.payment_terms()))
+}
+
+/// Sets or removes the Skonto terms of a draft invoice.
+pub async fn set_payment_terms(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path(id): Path<String>,
+    JsonBody(payload): JsonBody<PaymentTermsRequest>,
+) -> Result<Json<PaymentTerms>, ApiError> {
+    member.require(Permission::Edit)?;
+    payload.check().map_err(ApiError::validation)?;
+
+    let mut invoice = db::invoice::find_by_id(&db, &member.organisation_id, &id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load invoice"))?
+        .ok_or_else(|| ApiError::not_found("Invoice not found"))?;
+    if invoice.status != "draft" {
+        return Err(ApiError::conflict("Payment terms can only be changed on draft invoices"));
+    }
+    if invoice.is_credit_note() && payload.skonto_percent.is_some() {
+        return Err(ApiError::validation("Credit notes cannot grant Skonto"));
+    }
+
+    invoice.skonto_percent = payload.skonto_percent;
+    invoice.skonto_days = payload.skonto_days;
+    invoice.updated_at = Utc::now();
+    if invoice.skonto_due_date().is_some_and(|skonto_due| skonto_due > invoice.due_date) {
+        return Err(ApiError::validation("Skonto period ends after the due date"));
+    }
+
+    db::invoice::set_payment_terms(&db, &member.user_id, &invoice)
+        .await
+        .map_err(|_| ApiError::internal("Failed to update invoice"))?;
+
+    Ok(Json(invoice.payment_terms()))
+}
+
+pub async fn get_billing(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path(id): Path<String>,
+) -> Result<Json<BillingSummary>, ApiError> {
+    member.require(Permission::View)?;
+    let invoice = db::invoice::find_by_id(&db, &member.organisation_id, &id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load invoice"))?
+        .ok_or_else(|| ApiError::not_found("Invoice not found"))?;
+
+    let deductions = db::invoice::find_deductions(&db, &invoice.id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load advance deductions"))?;
+
+    Ok(Json(BillingSummary::new(&invoice, deductions)))
+}
+
+/// Makes a draft invoice a standard, advance or final invoice. A final
+/// invoice deducts the advance payments received on the project's advance
+/// invoices; they are recalculated each time this is called.
+pub async fn set_billing(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path(id): Path<String>,
+    JsonBody(payload): JsonBody<BillingRequest>,
+) -> Result<Json<BillingSummary>, ApiError> {
+    member.require(Permission::Edit)?;
+    if !matches!(payload.billing_type.as_str(), "standard" | "advance" | "final") {
+        return Err(ApiError::validation(format!("Unknown billing type: {}", payload.billing_type)));
+    }
+
+    let mut invoice = db::invoice::find_by_id(&db, &member.organisation_id, &id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load invoice"))?
+        .ok_or_else(|| ApiError::not_found("Invoice not found"))?;
+    if invoice.status != "draft" {
+        return Err(ApiError::conflict("The billing type can only be changed on draft invoices"));
+    }
+    if invoice.is_credit_note() && payload.billing_type != "standard" {
+        return Err(ApiError::validation("Credit notes cannot be advance or final invoices"));
+    }
+
+    let project = match payload.project_id.as_deref() {
+        Some(project_id) => Some(
+            db::project::find_by_id(&db, &member.organisation_id, project_id)
+                .await
+                .map_err(|_| ApiError::internal("Failed to load project"))?
+                .filter(|project| project.client_id == invoice.client_id)
+                .ok_or_else(|| ApiError::validation("Unknown project of the invoice's client"))?,
+        ),
+        None if payload.billing_type != "standard" => {
+            return Err(ApiError::validation("Advance and final invoices need a project"));
+        }
+        None => None,
+    };
+
+    let mut deductions: Vec<AdvanceDeduction> = Vec::new();
+    if let (Some(project), "final") = (&project, payload.billing_type.as_str()) {
+        let advances = db::invoice::find_open_advances(&db, &member.organisation_id, &project.id, &invoice.id)
+            .await
+            .map_err(|_| ApiError::internal("Failed to load advance invoices"))?;
+        let payments = db::payment::find_by_user(&db, &member.organisation_id)
+            .await
+            .map_err(|_| ApiError::internal("Failed to load payments"))?;
+        deductions = deductions_for(&invoice, &advances, &payments)
+            .map_err(ApiError::unprocessable)?;
+    }
+
+    invoice.billing_type = payload.billing_type;
+    invoice.project_id = project.map(|project| project.id);
+    invoice.advance_deduction_net = round_cents(deductions.iter().fold(0.0, |sum, deduction| sum + deduction.net_amount));
+    invoice.advance_deduction_tax = round_cents(deductions.iter().fold(0.0, |sum, deduction| sum + deduction.tax_amount));
+    invoice.updated_at = Utc::now();
+    if invoice.amount_due() < 0.0 {
+        return Err(ApiError::unprocessable("The advance payments exceed the final invoice; add its items first"));
+    }
+
+    db::invoice::set_billing(&db, &member.user_id, &invoice, &deductions)
+        .await
+        .map_err(|_| ApiError::internal("Failed to update invoice"))?;
+
+    Ok(Json(BillingSummary::new(&invoice, deductions)))
+}
+
+pub async fn get_currency(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path(id): Path<String>,
+) -> Result<Json<CurrencyConversion>, ApiError> {
+    member.require(Permission::View)?;
+    let invoice = db::invoice::find_by_id(&db, &member.organisation_id, &id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load invoice"))?
+        .ok_or_else(|| ApiError::not_found("Invoice not found"))?;
+
+    Ok(Json(invoice.currency_conversion()))
+}
+
+/// Sets the currency of a draft invoice and stores the ECB reference rate of
+/// its issue date with it.
+pub async fn set_currency(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path(id): Path<String>,
+    JsonBody(payload): JsonBody<CurrencyRequest>,
+) -> Result<Json<CurrencyConversion>, ApiError> {
+    member.require(Permission::Edit)?;
+    if !is_currency_code(&payload.currency) {
+        return Err(ApiError::validation("Currency must be an ISO 4217 code"));
+    }
+
+    let mut invoice = db::invoice::find_by_id(&db, &member.organisation_id, &id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load invoice"))?
+        .ok_or_else(|| ApiError::not_found("Invoice not found"))?;
+    if invoice.status != "draft" {
+        return Err(ApiError::conflict("The currency can only be changed on draft invoices"));
+    }
+
+    if payload.currency == BASE_CURRENCY {
+        invoice.exchange_rate = None;
+        invoice.exchange_rate_date = None;
+    } else {
+        let rate = db::exchange_rate::find_for_date(&db, &member.organisation_id, &payload.currency, invoice.issue_date)
+            .await
+            .map_err(|_| ApiError::internal("Failed to load exchange rates"))?
+            .filter(|rate| (invoice.issue_date - rate.rate_date).num_days() <= MAX_RATE_AGE_DAYS)
+            .ok_or_else(|| {
+                ApiError::unprocessable(format!(
+                    "No ECB reference rate for {} on {}; import the rates first",
+                    payload.currency, invoice.issue_date
+                ))
+            })?;
+        invoice.exchange_rate = Some(rate.rate);
+        invoice.exchange_rate_date = Some(rate.rate_date);
+    }
+    invoice.currency = payload.currency;
+    invoice.updated_at = Utc::now();
+
+    db::invoice::set_currency(&db, &member.user_id, &invoice)
+        .await
+        .map_err(|_| ApiError::internal("Failed to update invoice"))?;
+
+    Ok(Json(invoice.currency_conversion()))
+}
+
+pub async fn create_payment(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path(id): Path<String>,
+    JsonBody(payload): JsonBody<NewPayment>,
+) -> Result<(StatusCode, Json<Payment>), ApiError> {
+    member.require(Permission::Edit)?;
+    if payload.amount <= 0.0 {
+        return Err(ApiError::validation("Payment amount must be positive"));
+    }
+
+    let invoice = db::invoice::find_by_id(&db, &member.organisation_id, &id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load invoice"))?
+        .ok_or_else(|| ApiError::not_found("Invoice not found"))?;
+
+    if matches!(invoice.status.as_str(), "draft" | "cancelled") {
+        return Err(ApiError::conflict(format!("Cannot record a payment for a {} invoice", invoice.status)));
+    }
+
+    let already_paid: f64 = db::payment::find_by_invoice(&db, &invoice.id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load payments"))?
+        .iter()
+        .map(|payment| payment.amount)
+        .sum();
+
+    let mut payment = Payment::new(invoice.id.clone(), payload.amount, payload.payment_date, payload.method);
+    if invoice.status != "paid" {
+        payment.skonto_amount = invoice.skonto_for_payment(already_paid, payment.amount, payment.payment_date);
+    }
+    db::payment::create(&db, &member.user_id, &invoice.user_id, &payment)
+        .await
+        .map_err(|_| ApiError::internal("Failed to save payment"))?;
+
+    // Partial payments leave the invoice open until the amount due is
+    // settled; deducted Skonto counts as settled.
+    let settled = round_cents(already_paid + payment.amount + payment.skonto_amount);
+    if invoice.status != "paid" && settled >= invoice.amount_due() {
+        let paid_at = payment.payment_date.and_time(NaiveTime::MIN).and_utc();
+        db::invoice::mark_paid(&db, &member.user_id, &invoice.id, paid_at)
+            .await
+            .map_err(|_| ApiError::internal("Failed to update invoice"))?;
+    }
+
+    Ok((StatusCode::CREATED, Json(payment)))
+}
+
+pub async fn get_payments(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<Payment>>, ApiError> {
+    member.require(Permission::View)?;
+    let invoice = db::invoice::find_by_id(&db, &member.organisation_id, &id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load invoice"))?
+        .ok_or_else(|| ApiError::not_found("Invoice not found"))?;
+
+    let payments = db::payment::find_by_invoice(&db, &invoice.id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load payments"))?;
+
+    Ok(Json(payments))
+}
@@ -2,6 +2,22 @@ pub mod user;
 pub mod client;
 pub mod invoice;
 pub mod auth;
+pub mod expense;
+pub mod report;
+pub mod audit;
+pub mod retention;
+pub mod gdpr;
+pub mod project;
+pub mod time_entry;
+pub mod catalog;
+pub mod allowance_charge;
+pub mod exchange_rate;
+pub mod two_factor;
+pub mod email_token;
+pub mod api_key;
+pub mod organisation;
+pub mod advisor;
+pub mod sso;
 
 pub use user::*;
 pub use client::*;
@@ -0,0 +1,204 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    Extension,
+};
+use chrono::Utc;
+use crate::auth::jwt::Claims;
+use crate::auth::member::Member;
+use crate::db::{self, Db};
+use crate::error::{ApiError, JsonBody};
+use crate::mail::Mailer;
+use crate::models::membership::{
+    hash_invitation_token, AcceptInvitation, Invitation, NewInvitation, OrganisationAccess, Permission, Role,
+    RoleUpdate, TeamMember, INVITATION_DAYS,
+};
+
+/// Roles that can be given to members. The account holder is the only owner,
+/// so owner rights cannot be handed out through a membership.
+fn parse_role(role: &str) -> Result<Role, ApiError> {
+    match Role::parse(role) {
+        Some(Role::Owner) => Err(ApiError::validation("The owner role cannot be assigned")),
+        Some(role) => Ok(role),
+        None => Err(ApiError::validation(format!("Unknown role: {}", role))),
+    }
+}
+
+/// Organisations the current user can work in, starting with the own one.
+/// Their ID goes into the `X-Organisation-Id` header.
+pub async fn get_organisations(
+    State(db): State<Db>,
+    Extension(claims): Extension<Claims>,
+) -> Result<Json<Vec<OrganisationAccess>>, ApiError> {
+    let organisations = db::membership::find_organisations(&db, &claims.sub)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load organisations"))?;
+
+    Ok(Json(organisations))
+}
+
+pub async fn get_members(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+) -> Result<Json<Vec<TeamMember>>, ApiError> {
+    member.require(Permission::View)?;
+    let members = db::membership::find_members(&db, &member.organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load members"))?;
+
+    Ok(Json(members))
+}
+
+pub async fn update_member(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path(user_id): Path<String>,
+    JsonBody(payload): JsonBody<RoleUpdate>,
+) -> Result<StatusCode, ApiError> {
+    member.require(Permission::ManageTeam)?;
+    let role = parse_role(&payload.role)?;
+
+    let updated = db::membership::set_role(&db, &member.organisation_id, &user_id, role.as_str())
+        .await
+        .map_err(|_| ApiError::internal("Failed to update member"))?;
+    if !updated {
+        return Err(ApiError::not_found("Member not found"));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Removes a member. Every member may leave; the account holder cannot be
+/// removed.
+pub async fn delete_member(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path(user_id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    if user_id != member.user_id {
+        member.require(Permission::ManageTeam)?;
+    }
+
+    let removed = db::membership::remove(&db, &member.organisation_id, &user_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to remove member"))?;
+    if !removed {
+        return Err(ApiError::not_found("Member not found"));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_invitations(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+) -> Result<Json<Vec<Invitation>>, ApiError> {
+    member.require(Permission::ManageTeam)?;
+    let invitations = db::membership::find_open_invitations(&db, &member.organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load invitations"))?;
+
+    Ok(Json(invitations))
+}
+
+/// Invites an email address and mails the link. Inviting the address again
+/// replaces the earlier invitation.
+pub async fn create_invitation(
+    State(db): State<Db>,
+    State(mailer): State<Arc<Mailer>>,
+    Extension(member): Extension<Member>,
+    JsonBody(payload): JsonBody<NewInvitation>,
+) -> Result<(StatusCode, Json<Invitation>), ApiError> {
+    member.require(Permission::ManageTeam)?;
+    let role = parse_role(&payload.role)?;
+    let email = payload.email.trim();
+    if !email.contains('@') {
+        return Err(ApiError::validation("Invalid email address"));
+    }
+
+    let organisation = db::user::find_by_id(&db, &member.organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load organisation"))?
+        .ok_or_else(|| ApiError::not_found("Organisation not found"))?;
+    if organisation.email.eq_ignore_ascii_case(email) {
+        return Err(ApiError::conflict("The account holder is already owner"));
+    }
+
+    let (invitation, token) = Invitation::new(member.organisation_id.clone(), email, role, member.user_id.clone());
+    db::membership::create_invitation(&db, &invitation)
+        .await
+        .map_err(|_| ApiError::internal("Failed to save invitation"))?;
+
+    let name = organisation.company_name.as_deref().unwrap_or(&organisation.email);
+    let message = mailer.invitation(&invitation.email, name, role.as_str(), &token, INVITATION_DAYS);
+    mailer.send(&message).await.map_err(|error| {
+        tracing::error!("Failed to send invitation mail: {}", error);
+        ApiError::upstream("Failed to send mail")
+    })?;
+
+    Ok((StatusCode::CREATED, Json(invitation)))
+}
+
+pub async fn delete_invitation(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    member.require(Permission::ManageTeam)?;
+
+    let deleted = db::membership::delete_invitation(&db, &member.organisation_id, &id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to delete invitation"))?;
+    if !deleted {
+        return Err(ApiError::not_found("Invitation not found"));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Joins the organisation of an invitation sent to the current user's email
+/// address.
+pub async fn accept_invitation(
+    State(db): State<Db>,
+    Extension(claims): Extension<Claims>,
+    JsonBody(payload): JsonBody<AcceptInvitation>,
+) -> Result<Json<OrganisationAccess>, ApiError> {
+    let invalid = || ApiError::validation("Invalid or expired invitation");
+    let invitation = db::membership::find_invitation_by_hash(&db, &hash_invitation_token(&payload.token))
+        .await
+        .map_err(|_| ApiError::internal("Failed to load invitation"))?
+        .filter(|invitation| invitation.is_open(Utc::now()) && invitation.is_for(&claims.email))
+        .ok_or_else(invalid)?;
+    if invitation.organisation_id == claims.sub {
+        return Err(ApiError::conflict("You already own this organisation"));
+    }
+
+    let accepted = db::membership::accept_invitation(&db, &invitation, &claims.sub)
+        .await
+        .map_err(|_| ApiError::internal("Failed to accept invitation"))?;
+    if !accepted {
+        return Err(invalid());
+    }
+
+    let organisations = db::membership::find_organisations(&db, &claims.sub)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load organisations"))?;
+    organisations
+        .into_iter()
+        .find(|organisation| organisation.organisation_id == invitation.organisation_id)
+        .map(Json)
+        .ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn owner_role_cannot_be_assigned() {
+        assert!(matches!(parse_role("owner"), Err(ApiError::Validation { .. })));
+        assert!(matches!(parse_role("admin"), Err(ApiError::Validation { .. })));
+        assert_eq!(parse_role("accountant").unwrap(), Role::Accountant);
+        assert_eq!(parse_role("read_only").unwrap(), Role::ReadOnly);
+    }
+}
@@ -0,0 +1,68 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    Extension,
+};
+use validator::Validate;
+use crate::auth::member::Member;
+use crate::db::{self, Db};
+use crate::error::{ApiError, JsonBody};
+use crate::models::membership::Permission;
+use crate::models::project::{NewProject, Project, UpdateProject};
+
+pub async fn get_projects(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+) -> Result<Json<Vec<Project>>, ApiError> {
+    member.require(Permission::View)?;
+    let projects = db::project::find_by_user(&db, &member.organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load projects"))?;
+
+    Ok(Json(projects))
+}
+
+pub async fn create_project(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    JsonBody(payload): JsonBody<NewProject>,
+) -> Result<(StatusCode, Json<Project>), ApiError> {
+    member.require(Permission::Edit)?;
+    payload.validate()?;
+
+    db::client::find_by_id(&db, &member.organisation_id, &payload.client_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load client"))?
+        .filter(|client| client.deleted_at.is_none())
+        .ok_or_else(|| ApiError::validation("Unknown client"))?;
+
+    let project = Project::new(member.organisation_id.clone(), payload.client_id, payload.name, payload.hourly_rate);
+    db::project::create(&db, &member.user_id, &project)
+        .await
+        .map_err(|_| ApiError::internal("Failed to save project"))?;
+
+    Ok((StatusCode::CREATED, Json(project)))
+}
+
+pub async fn update_project(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path(id): Path<String>,
+    JsonBody(payload): JsonBody<UpdateProject>,
+) -> Result<Json<Project>, ApiError> {
+    member.require(Permission::Edit)?;
+    payload.validate()?;
+
+    let mut project = db::project::find_by_id(&db, &member.organisation_id, &id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load project"))?
+        .ok_or_else(|| ApiError::not_found("Project not found"))?;
+
+    project.apply(payload);
+    db::project::update(&db, &member.user_id, &project)
+        .await
+        .map_err(|_| ApiError::internal("Failed to save project"))?;
+
+    Ok(Json(project))
+}
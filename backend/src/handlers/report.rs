@@ -0,0 +1,223 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Json, Response},
+    Extension,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use crate::auth::member::Member;
+use crate::db::{self, Db};
+use crate::error::ApiError;
+use crate::models::membership::Permission;
+use crate::reports::dashboard::DashboardSummary;
+use crate::reports::euer::EuerReport;
+use crate::reports::gdpdu::GdpduExport;
+use crate::reports::period::{TaxationMethod, VatPeriod};
+use crate::reports::ustva::UstvaReport;
+use crate::reports::zm::ZmReport;
+
+#[derive(Debug, Deserialize)]
+pub struct ReportQuery {
+    pub format: Option<String>,
+}
+
+pub async fn get_euer(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path(year): Path<i32>,
+    Query(query): Query<ReportQuery>,
+) -> Result<Response, ApiError> {
+    member.require(Permission::Reports)?;
+    euer_response(&db, &member.organisation_id, year, query.format.as_deref()).await
+}
+
+/// EÜR of an organisation as JSON or CSV; also served to tax advisors.
+pub(crate) async fn euer_response(
+    db: &Db,
+    organisation_id: &str,
+    year: i32,
+    format: Option<&str>,
+) -> Result<Response, ApiError> {
+    let invoices = db::invoice::find_by_user(db, organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load invoices"))?;
+    let payments = db::payment::find_by_user(db, organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load payments"))?;
+    let expenses = db::expense::find_by_user(db, organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load expenses"))?;
+
+    let report = EuerReport::build(year, &invoices, &payments, &expenses);
+
+    match format {
+        None | Some("json") => Ok(Json(report).into_response()),
+        Some("csv") => Ok(download(
+            "text/csv; charset=utf-8",
+            &format!("euer-{}.csv", year),
+            report.to_csv(),
+        )),
+        Some(other) => Err(ApiError::validation(format!("Unsupported format: {}", other))),
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct UstvaResponse {
+    #[serde(flatten)]
+    pub report: UstvaReport,
+    pub validation_errors: Vec<String>,
+}
+
+pub async fn get_ustva(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path((year, period)): Path<(i32, String)>,
+    Query(query): Query<ReportQuery>,
+) -> Result<Response, ApiError> {
+    member.require(Permission::Reports)?;
+    ustva_response(&db, &member.organisation_id, year, &period, query.format.as_deref()).await
+}
+
+/// UStVA of an organisation as JSON or ELSTER XML; also served to tax
+/// advisors.
+pub(crate) async fn ustva_response(
+    db: &Db,
+    organisation_id: &str,
+    year: i32,
+    period: &str,
+    format: Option<&str>,
+) -> Result<Response, ApiError> {
+    let period = VatPeriod::parse(period)
+        .ok_or_else(|| ApiError::validation(format!("Invalid period: {}", period)))?;
+
+    let user = db::user::find_by_id(db, organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load user"))?
+        .ok_or_else(|| ApiError::not_found("User not found"))?;
+    let settings = db::settings::find_by_user(db, organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load settings"))?;
+    let invoices = db::invoice::find_by_user(db, organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load invoices"))?;
+    let payments = db::payment::find_by_user(db, organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load payments"))?;
+    let expenses = db::expense::find_by_user(db, organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load expenses"))?;
+
+    let taxation_method = TaxationMethod::from_setting(&settings.taxation_method);
+    let report = UstvaReport::build(year, period, taxation_method, &invoices, &payments, &expenses);
+    let validation_errors = report.validate(&user);
+
+    match format {
+        None | Some("json") => Ok(Json(UstvaResponse { report, validation_errors }).into_response()),
+        Some("xml") if !validation_errors.is_empty() => Err(ApiError::unprocessable(validation_errors.join("; "))),
+        Some("xml") => Ok(download(
+            "application/xml; charset=utf-8",
+            &format!("ustva-{}-{}.xml", year, report.period),
+            report.to_xml(&user, Utc::now().date_naive()),
+        )),
+        Some(other) => Err(ApiError::validation(format!("Unsupported format: {}", other))),
+    }
+}
+
+pub async fn get_zm(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path((year, period)): Path<(i32, String)>,
+    Query(query): Query<ReportQuery>,
+) -> Result<Response, ApiError> {
+    member.require(Permission::Reports)?;
+    let period = VatPeriod::parse(&period)
+        .ok_or_else(|| ApiError::validation(format!("Invalid period: {}", period)))?;
+
+    let invoices = db::invoice::find_by_user(&db, &member.organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load invoices"))?;
+    let clients = db::client::find_by_user(&db, &member.organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load clients"))?;
+
+    let report = ZmReport::build(year, period, &invoices, &clients);
+
+    match query.format.as_deref() {
+        None | Some("json") => Ok(Json(report).into_response()),
+        Some("csv") => Ok(download(
+            "text/csv; charset=utf-8",
+            &format!("zm-{}-{}.csv", year, report.period),
+            report.to_csv(),
+        )),
+        Some(other) => Err(ApiError::validation(format!("Unsupported format: {}", other))),
+    }
+}
+
+pub async fn get_dashboard(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path(year): Path<i32>,
+) -> Result<Json<DashboardSummary>, ApiError> {
+    member.require(Permission::Reports)?;
+    let settings = db::settings::find_by_user(&db, &member.organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load settings"))?;
+    let invoices = db::invoice::find_by_user(&db, &member.organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load invoices"))?;
+    let payments = db::payment::find_by_user(&db, &member.organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load payments"))?;
+
+    let taxation_method = TaxationMethod::from_setting(&settings.taxation_method);
+
+    Ok(Json(DashboardSummary::build(year, taxation_method, &invoices, &payments)))
+}
+
+/// Datenträgerüberlassung for a tax audit: CSV files and `index.xml` as ZIP.
+pub async fn get_gdpdu_export(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path(year): Path<i32>,
+) -> Result<Response, ApiError> {
+    member.require(Permission::Reports)?;
+    let user = db::user::find_by_id(&db, &member.organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load user"))?
+        .ok_or_else(|| ApiError::not_found("User not found"))?;
+    let invoices = db::invoice::find_by_user(&db, &member.organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load invoices"))?;
+    let items = db::invoice::find_items_by_user(&db, &member.organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load invoice items"))?;
+    let payments = db::payment::find_by_user(&db, &member.organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load payments"))?;
+    let clients = db::client::find_by_user(&db, &member.organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load clients"))?;
+    let audit_log = db::audit::find_by_user(&db, &member.organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load audit log"))?;
+
+    let export = GdpduExport::build(year, &user, &invoices, &items, &payments, &clients, &audit_log);
+
+    Ok(download(
+        "application/zip",
+        &format!("gdpdu-export-{}.zip", year),
+        export.to_zip(Utc::now()),
+    ))
+}
+
+fn download(content_type: &'static str, filename: &str, body: impl IntoResponse) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        body,
+    )
+        .into_response()
+}
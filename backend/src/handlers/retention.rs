@@ -0,0 +1,63 @@
+use axum::{
+    extract::State,
+    response::Json,
+    Extension,
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use crate::auth::member::Member;
+use crate::db::{self, Db};
+use crate::error::{ApiError, JsonBody};
+use crate::models::membership::Permission;
+use crate::models::user::User;
+use crate::retention::{RetentionRule, RETENTION_RULES};
+
+#[derive(Debug, Serialize)]
+pub struct RetentionStatus {
+    pub rules: &'static [RetentionRule],
+    pub legal_hold_until: Option<NaiveDate>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LegalHoldRequest {
+    /// Suspends the purge of the account's data until this day; `null` lifts the hold.
+    pub until: Option<NaiveDate>,
+}
+
+async fn load_user(db: &Db, id: &str) -> Result<User, ApiError> {
+    db::user::find_by_id(db, id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load user"))?
+        .ok_or_else(|| ApiError::not_found("User not found"))
+}
+
+pub async fn get_retention(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+) -> Result<Json<RetentionStatus>, ApiError> {
+    member.require(Permission::AuditLog)?;
+    let user = load_user(&db, &member.organisation_id).await?;
+
+    Ok(Json(RetentionStatus {
+        rules: RETENTION_RULES,
+        legal_hold_until: user.legal_hold_until,
+    }))
+}
+
+pub async fn set_legal_hold(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    JsonBody(payload): JsonBody<LegalHoldRequest>,
+) -> Result<Json<RetentionStatus>, ApiError> {
+    member.require(Permission::ManageRetention)?;
+    let user = load_user(&db, &member.organisation_id).await?;
+
+    db::user::set_legal_hold(&db, &member.user_id, &user, payload.until)
+        .await
+        .map_err(|_| ApiError::internal("Failed to update legal hold"))?;
+
+    Ok(Json(RetentionStatus {
+        rules: RETENTION_RULES,
+        legal_hold_until: payload.until,
+    }))
+}
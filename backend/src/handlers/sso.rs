@@ -0,0 +1,241 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::{
+    extract::{ConnectInfo, Path, State},
+    http::{HeaderMap, StatusCode},
+    response::Json,
+    Extension,
+};
+use chrono::Utc;
+use crate::auth::jwt::Claims;
+use crate::auth::keys::JwtKeys;
+use crate::auth::oidc::{Oidc, OidcProvider};
+use crate::db::{self, Db};
+use crate::error::{ApiError, JsonBody};
+use crate::handlers::auth::{finish_login, LoginResult};
+use crate::models::identity::{SsoCallback, SsoLogin, SsoProvider, SsoStart, UserIdentity};
+use crate::models::user::User;
+
+fn unreachable_provider(error: String) -> ApiError {
+    tracing::error!("OIDC provider error: {}", error);
+    ApiError::upstream("The identity provider could not be reached")
+}
+
+fn find_provider<'a>(oidc: &'a Oidc, id: &str) -> Result<&'a OidcProvider, ApiError> {
+    oidc.provider(id)
+        .ok_or_else(|| ApiError::not_found(format!("Unknown identity provider: {}", id)))
+}
+
+/// Providers offered on the login page.
+pub async fn get_sso_providers(State(oidc): State<Arc<Oidc>>) -> Json<Vec<SsoProvider>> {
+    Json(
+        oidc.providers()
+            .iter()
+            .map(|provider| SsoProvider { id: provider.id.clone(), name: provider.name.clone() })
+            .collect(),
+    )
+}
+
+/// Starts a login at the provider. The frontend sends the browser to the
+/// returned URL; the provider redirects back with `code` and `state`.
+pub async fn start_sso(
+    State(db): State<Db>,
+    State(oidc): State<Arc<Oidc>>,
+    Path(provider): Path<String>,
+) -> Result<Json<SsoStart>, ApiError> {
+    let provider = find_provider(&oidc, &provider)?;
+    let discovery = oidc.discover(provider).await.map_err(unreachable_provider)?;
+
+    let login = SsoLogin::new(&provider.id);
+    let authorization_url = oidc.authorization_url(provider, &discovery, &login).map_err(unreachable_provider)?;
+    db::identity::create_login(&db, &login)
+        .await
+        .map_err(|_| ApiError::internal("Failed to start login"))?;
+
+    Ok(Json(SsoStart { authorization_url }))
+}
+
+/// Completes a login with the code from the provider's redirect. The first
+/// login links the provider identity to the account with the verified email
+/// address; later logins find the account by the identity. Two-factor
+/// authentication applies as with a password.
+pub async fn complete_sso(
+    State(db): State<Db>,
+    State(keys): State<Arc<JwtKeys>>,
+    State(oidc): State<Arc<Oidc>>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    JsonBody(payload): JsonBody<SsoCallback>,
+) -> Result<Json<LoginResult>, ApiError> {
+    let invalid = || ApiError::validation("Invalid or expired login");
+    let login = db::identity::take_login(&db, &payload.state)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load login"))?
+        .filter(|login| login.is_open(Utc::now()))
+        .ok_or_else(invalid)?;
+    let provider = oidc.provider(&login.provider).ok_or_else(invalid)?;
+    let discovery = oidc.discover(provider).await.map_err(unreachable_provider)?;
+
+    let rejected = |error: String| {
+        tracing::warn!("SSO login with {} rejected: {}", provider.id, error);
+        ApiError::unauthorized("The identity provider did not confirm the login")
+    };
+    let id_token = oidc
+        .exchange_code(provider, &discovery, &payload.code, &login.code_verifier)
+        .await
+        .map_err(rejected)?;
+    let claims = oidc
+        .verify_id_token(provider, &discovery, &id_token, &login.nonce)
+        .await
+        .map_err(rejected)?;
+
+    let user = sso_user(&db, &provider.id, &claims.sub, claims.verified_email(provider)).await?;
+    finish_login(&db, &keys, &user, payload.device_label, address, &headers).await.map(Json)
+}
+
+/// The account a provider identity logs in to. An unknown identity is linked
+/// to the account with the address the provider verified, but only if the
+/// account has verified it too: otherwise whoever registered the address
+/// first, without owning it, would get the identity's logins. Linking ends
+/// the account's sessions.
+async fn sso_user(db: &Db, provider: &str, subject: &str, verified_email: Option<&str>) -> Result<User, ApiError> {
+    let load_failed = |_| ApiError::internal("Failed to load user");
+    let deleted = || ApiError::unauthorized("The account has been deleted");
+
+    if let Some(identity) = db::identity::find(db, provider, subject)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load identity"))?
+    {
+        let user = db::user::find_by_id(db, &identity.user_id)
+            .await
+            .map_err(load_failed)?
+            .filter(|user| user.deleted_at.is_none())
+            .ok_or_else(deleted)?;
+        db::identity::record_login(db, provider, subject)
+            .await
+            .map_err(|_| ApiError::internal("Failed to record login"))?;
+        return Ok(user);
+    }
+
+    let email = verified_email
+        .ok_or_else(|| ApiError::forbidden("The identity provider has not verified the email address"))?;
+    let user = db::user::find_by_email(db, email)
+        .await
+        .map_err(load_failed)?
+        .ok_or_else(|| ApiError::forbidden(format!("No account is registered for {}", email)))?;
+    if user.deleted_at.is_some() {
+        return Err(deleted());
+    }
+    if user.email_verified_at.is_none() {
+        return Err(ApiError::forbidden(
+            "The account's email address is not verified; log in with your password and verify it first",
+        ));
+    }
+
+    db::identity::link(db, &UserIdentity::new(provider, subject, user.id.clone(), email))
+        .await
+        .map_err(|_| ApiError::internal("Failed to link identity"))?;
+    db::session::revoke_all(db, &user.id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to end sessions"))?;
+    db::identity::record_login(db, provider, subject)
+        .await
+        .map_err(|_| ApiError::internal("Failed to record login"))?;
+    Ok(user)
+}
+
+/// Provider identities linked to the current account.
+pub async fn get_identities(
+    State(db): State<Db>,
+    Extension(claims): Extension<Claims>,
+) -> Result<Json<Vec<UserIdentity>>, ApiError> {
+    let identities = db::identity::find_by_user(&db, &claims.sub)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load identities"))?;
+
+    Ok(Json(identities))
+}
+
+/// Unlinks the account from a provider. The next SSO login links it again
+/// by email address.
+pub async fn delete_identity(
+    State(db): State<Db>,
+    Extension(claims): Extension<Claims>,
+    Path(provider): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let unlinked = db::identity::unlink(&db, &claims.sub, &provider)
+        .await
+        .map_err(|_| ApiError::internal("Failed to unlink identity"))?;
+    if !unlinked {
+        return Err(ApiError::not_found("Identity not found"));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{test_db, test_user};
+    use crate::models::session::Session;
+
+    async fn verify_email(db: &Db, user: &User) {
+        sqlx::query("UPDATE users SET email_verified_at = ? WHERE id = ?")
+            .bind(Utc::now())
+            .bind(&user.id)
+            .execute(db.as_ref())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn unverified_accounts_are_not_linked() {
+        let db = test_db().await;
+        let user = test_user(&db).await;
+
+        let error = sso_user(&db, "google", "sub-1", Some(&user.email)).await.unwrap_err();
+        assert!(matches!(error, ApiError::Forbidden(_)));
+        assert!(db::identity::find(&db, "google", "sub-1").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn linking_ends_the_sessions_of_the_account() {
+        let db = test_db().await;
+        let user = test_user(&db).await;
+        verify_email(&db, &user).await;
+        let session = Session::new(user.id.clone(), None, None);
+        db::session::create(&db, &session, "token").await.unwrap();
+
+        let linked = sso_user(&db, "google", "sub-1", Some(&user.email)).await.unwrap();
+        assert_eq!(linked.id, user.id);
+        assert!(db::session::find_active(&db, &user.id).await.unwrap().is_empty());
+
+        // Later logins use the link and keep the sessions
+        let session = Session::new(user.id.clone(), None, None);
+        db::session::create(&db, &session, "token-2").await.unwrap();
+        let again = sso_user(&db, "google", "sub-1", None).await.unwrap();
+        assert_eq!(again.id, user.id);
+        assert_eq!(db::session::find_active(&db, &user.id).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn deleted_accounts_cannot_log_in() {
+        let db = test_db().await;
+        let user = test_user(&db).await;
+        verify_email(&db, &user).await;
+        sso_user(&db, "google", "sub-1", Some(&user.email)).await.unwrap();
+        let linked = db::identity::find(&db, "google", "sub-1").await.unwrap().unwrap();
+        sqlx::query("UPDATE users SET deleted_at = ? WHERE id = ?")
+            .bind(Utc::now())
+            .bind(&user.id)
+            .execute(db.as_ref())
+            .await
+            .unwrap();
+
+        let error = sso_user(&db, "google", "sub-1", Some(&user.email)).await.unwrap_err();
+        assert!(matches!(error, ApiError::Unauthorized(_)));
+        // Nothing is written for the deleted account
+        let identity = db::identity::find(&db, "google", "sub-1").await.unwrap().unwrap();
+        assert_eq!(identity.last_login_at, linked.last_login_at);
+    }
+}
@@ -0,0 +1,234 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+    Extension,
+};
+use chrono::{NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use crate::auth::member::Member;
+use crate::db::{self, Db};
+use crate::error::{ApiError, JsonBody};
+use crate::models::membership::Permission;
+use crate::models::invoice::{Invoice, InvoiceItem};
+use crate::models::project::Project;
+use crate::models::time_entry::{group_for_billing, NewTimeEntry, StartTimer, TimeEntry};
+
+#[derive(Debug, Deserialize)]
+pub struct TimeEntryQuery {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub project_id: Option<String>,
+    #[serde(default)]
+    pub unbilled: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BillTimeRequest {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BillTimeResponse {
+    pub invoice: Invoice,
+    pub items: Vec<InvoiceItem>,
+    pub billed_entries: usize,
+}
+
+async fn load_entry(db: &Db, user_id: &str, id: &str) -> Result<TimeEntry, ApiError> {
+    db::time_entry::find_by_id(db, user_id, id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load time entry"))?
+        .ok_or_else(|| ApiError::not_found("Time entry not found"))
+}
+
+async fn load_project(db: &Db, user_id: &str, id: &str) -> Result<Project, ApiError> {
+    db::project::find_by_id(db, user_id, id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load project"))?
+        .ok_or_else(|| ApiError::validation("Unknown project"))
+}
+
+fn ensure_unbilled(entry: &TimeEntry) -> Result<(), ApiError> {
+    if entry.is_billed() {
+        return Err(ApiError::conflict("Time entry has already been billed"));
+    }
+    Ok(())
+}
+
+fn validate(payload: &NewTimeEntry) -> Result<(), ApiError> {
+    payload.validate()?;
+    payload.check().map_err(ApiError::validation)
+}
+
+pub async fn get_time_entries(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Query(query): Query<TimeEntryQuery>,
+) -> Result<Json<Vec<TimeEntry>>, ApiError> {
+    member.require(Permission::View)?;
+    let entries = db::time_entry::find_by_user(&db, &member.organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load time entries"))?
+        .into_iter()
+        .filter(|entry| query.from.is_none_or(|from| entry.started_at.date_naive() >= from))
+        .filter(|entry| query.to.is_none_or(|to| entry.started_at.date_naive() <= to))
+        .filter(|entry| query.project_id.as_ref().is_none_or(|id| &entry.project_id == id))
+        .filter(|entry| !query.unbilled || !entry.is_billed())
+        .collect();
+
+    Ok(Json(entries))
+}
+
+pub async fn create_time_entry(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    JsonBody(payload): JsonBody<NewTimeEntry>,
+) -> Result<(StatusCode, Json<TimeEntry>), ApiError> {
+    member.require(Permission::Edit)?;
+    validate(&payload)?;
+    load_project(&db, &member.organisation_id, &payload.project_id).await?;
+
+    let mut entry = TimeEntry::new(member.organisation_id.clone(), String::new(), payload.started_at);
+    entry.apply(payload);
+
+    db::time_entry::create(&db, &member.user_id, &entry)
+        .await
+        .map_err(|_| ApiError::internal("Failed to save time entry"))?;
+
+    Ok((StatusCode::CREATED, Json(entry)))
+}
+
+pub async fn update_time_entry(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path(id): Path<String>,
+    JsonBody(payload): JsonBody<NewTimeEntry>,
+) -> Result<Json<TimeEntry>, ApiError> {
+    member.require(Permission::Edit)?;
+    validate(&payload)?;
+    let mut entry = load_entry(&db, &member.organisation_id, &id).await?;
+    ensure_unbilled(&entry)?;
+    load_project(&db, &member.organisation_id, &payload.project_id).await?;
+
+    entry.apply(payload);
+    db::time_entry::update(&db, &member.user_id, &entry)
+        .await
+        .map_err(|_| ApiError::internal("Failed to save time entry"))?;
+
+    Ok(Json(entry))
+}
+
+pub async fn delete_time_entry(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    member.require(Permission::Edit)?;
+    let entry = load_entry(&db, &member.organisation_id, &id).await?;
+    ensure_unbilled(&entry)?;
+
+    db::time_entry::delete(&db, &member.user_id, &member.organisation_id, &entry.id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to delete time entry"))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Starts a timer. Only one timer can run at a time.
+pub async fn start_timer(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    JsonBody(payload): JsonBody<StartTimer>,
+) -> Result<(StatusCode, Json<TimeEntry>), ApiError> {
+    member.require(Permission::Edit)?;
+    payload.validate()?;
+    let project = load_project(&db, &member.organisation_id, &payload.project_id).await?;
+    if project.archived_at.is_some() {
+        return Err(ApiError::validation("Project is archived"));
+    }
+
+    let running = db::time_entry::find_running(&db, &member.organisation_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load time entries"))?;
+    if running.is_some() {
+        return Err(ApiError::conflict("A timer is already running"));
+    }
+
+    let mut entry = TimeEntry::new(member.organisation_id.clone(), project.id, Utc::now());
+    entry.task = payload.task;
+    entry.description = payload.description;
+    entry.billable = payload.billable.unwrap_or(true);
+
+    db::time_entry::create(&db, &member.user_id, &entry)
+        .await
+        .map_err(|_| ApiError::internal("Failed to save time entry"))?;
+
+    Ok((StatusCode::CREATED, Json(entry)))
+}
+
+pub async fn stop_timer(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path(id): Path<String>,
+) -> Result<Json<TimeEntry>, ApiError> {
+    member.require(Permission::Edit)?;
+    let mut entry = load_entry(&db, &member.organisation_id, &id).await?;
+    if entry.ended_at.is_some() {
+        return Err(ApiError::conflict("Timer is not running"));
+    }
+
+    entry.ended_at = Some(Utc::now());
+    entry.updated_at = Utc::now();
+    db::time_entry::update(&db, &member.user_id, &entry)
+        .await
+        .map_err(|_| ApiError::internal("Failed to save time entry"))?;
+
+    Ok(Json(entry))
+}
+
+/// Adds the unbilled time on the invoice client's projects within the period
+/// to a draft invoice, one item per project and task.
+pub async fn bill_time_entries(
+    State(db): State<Db>,
+    Extension(member): Extension<Member>,
+    Path(id): Path<String>,
+    JsonBody(payload): JsonBody<BillTimeRequest>,
+) -> Result<Json<BillTimeResponse>, ApiError> {
+    member.require(Permission::Edit)?;
+    if payload.to < payload.from {
+        return Err(ApiError::validation("Period ends before it starts"));
+    }
+
+    let invoice = db::invoice::find_by_id(&db, &member.organisation_id, &id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load invoice"))?
+        .ok_or_else(|| ApiError::not_found("Invoice not found"))?;
+    if invoice.status != "draft" {
+        return Err(ApiError::conflict("Time can only be billed on draft invoices"));
+    }
+
+    let projects = db::project::find_by_client(&db, &member.organisation_id, &invoice.client_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load projects"))?;
+    let entries = db::time_entry::find_unbilled_by_client(&db, &member.organisation_id, &invoice.client_id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load time entries"))?;
+
+    let groups = group_for_billing(&projects, &entries, payload.from, payload.to);
+    if groups.is_empty() {
+        return Err(ApiError::unprocessable("No unbilled time in this period"));
+    }
+
+    let (invoice, items) = db::time_entry::bill(&db, &member.user_id, &invoice, &groups)
+        .await
+        .map_err(|_| ApiError::internal("Failed to bill time entries"))?;
+
+    Ok(Json(BillTimeResponse {
+        invoice,
+        items,
+        billed_entries: groups.iter().map(|group| group.entry_ids.len()).sum(),
+    }))
+}
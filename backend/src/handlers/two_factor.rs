@@ -0,0 +1,207 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::Json,
+    Extension,
+};
+use chrono::Utc;
+use serde::Serialize;
+use crate::auth::jwt::Claims;
+use crate::auth::totp;
+use crate::db::{self, Db};
+use crate::error::{ApiError, JsonBody};
+use crate::models::two_factor::{
+    generate_recovery_codes, TwoFactorCode, TwoFactorEnrolment, TwoFactorRequirement, TwoFactorStatus,
+};
+use crate::models::user::User;
+
+#[derive(Debug, Serialize)]
+pub struct RecoveryCodesResponse {
+    /// Shown once; only hashes are stored.
+    pub recovery_codes: Vec<String>,
+}
+
+async fn load_user(db: &Db, id: &str) -> Result<User, ApiError> {
+    db::user::find_by_id(db, id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load user"))?
+        .ok_or_else(|| ApiError::not_found("User not found"))
+}
+
+fn invalid_code() -> ApiError {
+    ApiError::unauthorized("Invalid two-factor code")
+}
+
+/// Checks a TOTP code against the user's secret, also one still being
+/// enrolled, and returns its time step. A code is only accepted once.
+pub(crate) async fn verify_totp(db: &Db, user: &User, code: &str) -> Result<i64, ApiError> {
+    let secret = user.totp_secret.as_deref().ok_or_else(invalid_code)?;
+    let now = Utc::now().timestamp().max(0) as u64;
+    let step = totp::verify(secret, code, now, user.totp_last_step).ok_or_else(invalid_code)?;
+
+    let recorded = db::two_factor::record_step(db, &user.id, step)
+        .await
+        .map_err(|_| ApiError::internal("Failed to verify code"))?;
+    if !recorded {
+        return Err(invalid_code());
+    }
+    Ok(step)
+}
+
+/// Accepts either a TOTP code or an unused recovery code.
+pub(crate) async fn verify_second_factor(
+    db: &Db,
+    user: &User,
+    code: Option<&str>,
+    recovery_code: Option<&str>,
+) -> Result<(), ApiError> {
+    match (code, recovery_code) {
+        (Some(code), _) => verify_totp(db, user, code).await.map(|_| ()),
+        (None, Some(recovery_code)) => {
+            let used = db::two_factor::use_recovery_code(db, &user.id, recovery_code)
+                .await
+                .map_err(|_| ApiError::internal("Failed to verify recovery code"))?;
+            if !used {
+                return Err(ApiError::unauthorized("Invalid recovery code"));
+            }
+            Ok(())
+        }
+        (None, None) => Err(ApiError::validation("A two-factor code or recovery code is required")),
+    }
+}
+
+/// Stores a new secret and returns it for the authenticator app.
+pub(crate) async fn start_enrolment(db: &Db, user: &User) -> Result<TwoFactorEnrolment, ApiError> {
+    if user.two_factor_enabled() {
+        return Err(ApiError::conflict("Two-factor authentication is already enabled"));
+    }
+
+    let secret = totp::generate_secret();
+    db::two_factor::start_enrolment(db, user, &secret)
+        .await
+        .map_err(|_| ApiError::internal("Failed to start enrolment"))?;
+
+    Ok(TwoFactorEnrolment {
+        otpauth_uri: totp::provisioning_uri(&secret, &user.email),
+        secret,
+    })
+}
+
+/// Confirms the enrolled secret with a first code and issues recovery codes.
+pub(crate) async fn complete_enrolment(db: &Db, actor_id: &str, user: &User, code: &str) -> Result<Vec<String>, ApiError> {
+    if user.two_factor_enabled() {
+        return Err(ApiError::conflict("Two-factor authentication is already enabled"));
+    }
+    if user.totp_secret.is_none() {
+        return Err(ApiError::conflict("Start the enrolment first"));
+    }
+
+    let step = verify_totp(db, user, code).await?;
+    let recovery_codes = generate_recovery_codes();
+    db::two_factor::enable(db, actor_id, user, step, &recovery_codes)
+        .await
+        .map_err(|_| ApiError::internal("Failed to enable two-factor authentication"))?;
+
+    Ok(recovery_codes)
+}
+
+pub async fn get_two_factor(
+    State(db): State<Db>,
+    Extension(claims): Extension<Claims>,
+) -> Result<Json<TwoFactorStatus>, ApiError> {
+    let user = load_user(&db, &claims.sub).await?;
+    let recovery_codes_left = db::two_factor::count_recovery_codes_left(&db, &user.id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load recovery codes"))?;
+
+    Ok(Json(TwoFactorStatus {
+        enabled: user.two_factor_enabled(),
+        enabled_at: user.totp_enabled_at,
+        required: user.two_factor_required,
+        recovery_codes_left,
+    }))
+}
+
+pub async fn setup_two_factor(
+    State(db): State<Db>,
+    Extension(claims): Extension<Claims>,
+) -> Result<Json<TwoFactorEnrolment>, ApiError> {
+    let user = load_user(&db, &claims.sub).await?;
+    Ok(Json(start_enrolment(&db, &user).await?))
+}
+
+pub async fn enable_two_factor(
+    State(db): State<Db>,
+    Extension(claims): Extension<Claims>,
+    JsonBody(payload): JsonBody<TwoFactorCode>,
+) -> Result<Json<RecoveryCodesResponse>, ApiError> {
+    let user = load_user(&db, &claims.sub).await?;
+    let recovery_codes = complete_enrolment(&db, &claims.sub, &user, &payload.code).await?;
+
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+/// Turns two-factor authentication off; needs a current code.
+pub async fn disable_two_factor(
+    State(db): State<Db>,
+    Extension(claims): Extension<Claims>,
+    JsonBody(payload): JsonBody<TwoFactorCode>,
+) -> Result<StatusCode, ApiError> {
+    let user = load_user(&db, &claims.sub).await?;
+    if !user.two_factor_enabled() {
+        return Err(ApiError::conflict("Two-factor authentication is not enabled"));
+    }
+    if user.two_factor_required {
+        return Err(ApiError::conflict("Two-factor authentication is required for this account"));
+    }
+    verify_totp(&db, &user, &payload.code).await?;
+
+    db::two_factor::disable(&db, &claims.sub, &user)
+        .await
+        .map_err(|_| ApiError::internal("Failed to disable two-factor authentication"))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Replaces all recovery codes with new ones; needs a current code.
+pub async fn regenerate_recovery_codes(
+    State(db): State<Db>,
+    Extension(claims): Extension<Claims>,
+    JsonBody(payload): JsonBody<TwoFactorCode>,
+) -> Result<Json<RecoveryCodesResponse>, ApiError> {
+    let user = load_user(&db, &claims.sub).await?;
+    if !user.two_factor_enabled() {
+        return Err(ApiError::conflict("Two-factor authentication is not enabled"));
+    }
+    verify_totp(&db, &user, &payload.code).await?;
+
+    let recovery_codes = generate_recovery_codes();
+    db::two_factor::replace_recovery_codes(&db, &user.id, &recovery_codes)
+        .await
+        .map_err(|_| ApiError::internal("Failed to save recovery codes"))?;
+
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+/// Requires the second factor at every login. Accounts without it have to
+/// enrol at their next login.
+pub async fn set_two_factor_required(
+    State(db): State<Db>,
+    Extension(claims): Extension<Claims>,
+    JsonBody(payload): JsonBody<TwoFactorRequirement>,
+) -> Result<Json<TwoFactorStatus>, ApiError> {
+    let user = load_user(&db, &claims.sub).await?;
+    let user = db::two_factor::set_required(&db, &claims.sub, &user, payload.required)
+        .await
+        .map_err(|_| ApiError::internal("Failed to update user"))?;
+    let recovery_codes_left = db::two_factor::count_recovery_codes_left(&db, &user.id)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load recovery codes"))?;
+
+    Ok(Json(TwoFactorStatus {
+        enabled: user.two_factor_enabled(),
+        enabled_at: user.totp_enabled_at,
+        required: user.two_factor_required,
+        recovery_codes_left,
+    }))
+}
@@ -1,12 +1,21 @@
+use std::sync::Arc;
+
 use axum::{
     extract::State,
     http::StatusCode,
     response::Json,
-    Json as AxumJson,
+    Extension,
 };
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use crate::db::Db;
-use crate::models::user::{User, NewUser};
+use crate::auth::jwt::Claims;
+use crate::auth::keys::JwtKeys;
+use crate::db::{self, Db};
+use crate::error::{ApiError, JsonBody};
+use crate::handlers::email_token::send_email_token;
+use crate::mail::Mailer;
+use crate::models::email_token::EmailTokenPurpose;
+use crate::models::user::{User, MIN_PASSWORD_LENGTH};
 use bcrypt::{hash, DEFAULT_COST};
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -26,35 +35,43 @@ pub struct CreateUserResponse {
     pub first_name: Option<String>,
     pub last_name: Option<String>,
     pub company_name: Option<String>,
+    pub email_verified_at: Option<DateTime<Utc>>,
 }
 
+/// Registers an account and mails a link to verify the email address.
 pub async fn create_user(
-    State(_db): State<Db>,
-    AxumJson(payload): AxumJson<CreateUserRequest>,
-) -> Result<(StatusCode, Json<CreateUserResponse>), (StatusCode, String)> {
+    State(db): State<Db>,
+    State(keys): State<Arc<JwtKeys>>,
+    State(mailer): State<Arc<Mailer>>,
+    JsonBody(payload): JsonBody<CreateUserRequest>,
+) -> Result<(StatusCode, Json<CreateUserResponse>), ApiError> {
+    if payload.password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(ApiError::validation(format!("Password must be at least {} characters long", MIN_PASSWORD_LENGTH)));
+    }
+
     // Hash the password
     let password_hash = hash(&payload.password, DEFAULT_COST)
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Failed to hash password".to_string()))?;
+        .map_err(|_| ApiError::internal("Failed to hash password"))?;
 
-    // Create new user
-    let new_user = NewUser::new(
-        payload.email.clone(),
+    let user = User::new(
+        payload.email.trim().to_string(),
         password_hash,
-        payload.first_name.clone(),
-        payload.last_name.clone(),
-        payload.company_name.clone(),
-        payload.tax_id.clone(),
+        payload.first_name,
+        payload.last_name,
+        payload.company_name,
+        payload.tax_id,
     );
 
-    // Save to database (placeholder)
-    let user = User::new(
-        new_user.email,
-        "hashed_password_placeholder".to_string(), // In real implementation, use the hashed password
-        new_user.first_name,
-        new_user.last_name,
-        new_user.company_name,
-        new_user.tax_id,
-    );
+    db::user::create(&db, &user).await.map_err(|error| {
+        if error.as_database_error().is_some_and(|error| error.is_unique_violation()) {
+            ApiError::conflict("Email address is already registered")
+        } else {
+            ApiError::internal("Failed to create user")
+        }
+    })?;
+
+    // The account exists either way; the link can be requested again later
+    let _ = send_email_token(&db, &keys, &mailer, &user, EmailTokenPurpose::VerifyEmail).await;
 
     let response = CreateUserResponse {
         id: user.id,
@@ -62,7 +79,27 @@ pub async fn create_user(
         first_name: user.first_name,
         last_name: user.last_name,
         company_name: user.company_name,
+        email_verified_at: user.email_verified_at,
     };
 
     Ok((StatusCode::CREATED, Json(response)))
-}
\ No newline at end of file
+}
+
+/// Soft-deletes the current user's account. Documents under retention are
+/// kept; the purge job removes them and anonymises the account afterwards.
+pub async fn delete_current_user(
+    State(db): State<Db>,
+    Extension(claims): Extension<Claims>,
+) -> Result<StatusCode, ApiError> {
+    let user = db::user::find_by_id(&db, &claims.sub)
+        .await
+        .map_err(|_| ApiError::internal("Failed to load user"))?
+        .filter(|user| user.deleted_at.is_none())
+        .ok_or_else(|| ApiError::not_found("User not found"))?;
+
+    db::user::soft_delete(&db, &claims.sub, &user)
+        .await
+        .map_err(|_| ApiError::internal("Failed to delete user"))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
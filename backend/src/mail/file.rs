@@ -0,0 +1,39 @@
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use tokio::fs;
+use uuid::Uuid;
+
+use super::{MailMessage, MailTransport};
+
+/// Writes every mail as `.eml` file into a directory, for tests and local
+/// setups without a mail server.
+pub struct FileMailTransport {
+    root: PathBuf,
+}
+
+impl FileMailTransport {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+#[async_trait]
+impl MailTransport for FileMailTransport {
+    async fn send(&self, message: &MailMessage) -> std::io::Result<()> {
+        let now = Utc::now();
+        fs::create_dir_all(&self.root).await?;
+
+        let content = format!(
+            "Date: {}\r\nFrom: {}\r\nTo: {}\r\nSubject: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{}",
+            now.to_rfc2822(),
+            message.from,
+            message.to,
+            message.subject,
+            message.body.replace('\n', "\r\n"),
+        );
+        let name = format!("{}-{}.eml", now.format("%Y%m%dT%H%M%S%.3f"), Uuid::new_v4().simple());
+        fs::write(self.root.join(name), content).await
+    }
+}
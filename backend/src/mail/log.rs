@@ -0,0 +1,14 @@
+use async_trait::async_trait;
+
+use super::{MailMessage, MailTransport};
+
+/// Writes mails to the server log instead of sending them.
+pub struct LogMailTransport;
+
+#[async_trait]
+impl MailTransport for LogMailTransport {
+    async fn send(&self, message: &MailMessage) -> std::io::Result<()> {
+        tracing::info!("Mail to {}: {}\n{}", message.to, message.subject, message.body);
+        Ok(())
+    }
+}
@@ -0,0 +1,156 @@
+pub mod file;
+pub mod log;
+pub mod smtp;
+
+use async_trait::async_trait;
+
+pub use file::FileMailTransport;
+pub use log::LogMailTransport;
+pub use smtp::SmtpMailTransport;
+
+const DEFAULT_FROM: &str = "MiniDebet <noreply@localhost>";
+const DEFAULT_FRONTEND_URL: &str = "http://localhost:5173";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MailMessage {
+    pub from: String,
+    pub to: String,
+    pub subject: String,
+    /// Plain text
+    pub body: String,
+}
+
+/// Delivers mail. The server sends through SMTP; the file and log transports
+/// keep messages local for development and tests.
+#[async_trait]
+pub trait MailTransport: Send + Sync {
+    async fn send(&self, message: &MailMessage) -> std::io::Result<()>;
+}
+
+/// Composes the account mails and hands them to the configured transport.
+pub struct Mailer {
+    transport: Box<dyn MailTransport>,
+    from: String,
+    /// Address the links in the mails point to
+    frontend_url: String,
+}
+
+impl Mailer {
+    pub fn new(transport: Box<dyn MailTransport>, from: impl Into<String>, frontend_url: impl Into<String>) -> Self {
+        Self {
+            transport,
+            from: from.into(),
+            frontend_url: frontend_url.into().trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Reads `MAIL_TRANSPORT` (`smtp`, `file` or `log`), `SMTP_URL`,
+    /// `MAIL_DIR`, `MAIL_FROM` and `FRONTEND_URL`. Outside development mode the
+    /// transport has to be chosen explicitly, since the log transport writes
+    /// the links into the server log.
+    pub fn from_env() -> Result<Self, String> {
+        Self::from_vars(|name| std::env::var(name).ok().filter(|value| !value.is_empty()))
+    }
+
+    pub(crate) fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, String> {
+        let development = var("MINIDEBET_ENV").as_deref() == Some("development");
+
+        let transport: Box<dyn MailTransport> = match var("MAIL_TRANSPORT").as_deref() {
+            Some("smtp") => {
+                let url = var("SMTP_URL").ok_or("SMTP_URL is not set")?;
+                Box::new(SmtpMailTransport::from_url(&url)?)
+            }
+            Some("file") => Box::new(FileMailTransport::new(var("MAIL_DIR").unwrap_or_else(|| "mail".to_string()))),
+            Some("log") => Box::new(LogMailTransport),
+            None if development => Box::new(LogMailTransport),
+            None => return Err("MAIL_TRANSPORT is not set; set it or MINIDEBET_ENV=development".to_string()),
+            Some(other) => return Err(format!("Unsupported MAIL_TRANSPORT: {}", other)),
+        };
+
+        Ok(Self::new(
+            transport,
+            var("MAIL_FROM").unwrap_or_else(|| DEFAULT_FROM.to_string()),
+            var("FRONTEND_URL").unwrap_or_else(|| DEFAULT_FRONTEND_URL.to_string()),
+        ))
+    }
+
+    pub fn email_verification(&self, to: &str, token: &str, hours: i64) -> MailMessage {
+        MailMessage {
+            from: self.from.clone(),
+            to: to.to_string(),
+            subject: "Confirm your email address for MiniDebet".to_string(),
+            body: format!(
+                "Please confirm your email address by opening this link:\n\n{}/verify-email?token={}\n\nThe link is valid for {} hours.\n",
+                self.frontend_url, token, hours
+            ),
+        }
+    }
+
+    pub fn password_reset(&self, to: &str, token: &str, minutes: i64) -> MailMessage {
+        MailMessage {
+            from: self.from.clone(),
+            to: to.to_string(),
+            subject: "Reset your MiniDebet password".to_string(),
+            body: format!(
+                "Somebody asked to reset the password of your MiniDebet account. To choose a new password, open this link:\n\n{}/reset-password?token={}\n\nThe link is valid for {} minutes and works once. If you did not ask for it, ignore this mail; your password stays unchanged.\n",
+                self.frontend_url, token, minutes
+            ),
+        }
+    }
+
+    pub fn invitation(&self, to: &str, organisation: &str, role: &str, token: &str, days: i64) -> MailMessage {
+        MailMessage {
+            from: self.from.clone(),
+            to: to.to_string(),
+            subject: format!("Invitation to {} on MiniDebet", organisation),
+            body: format!(
+                "You have been invited to work in {} on MiniDebet with the role {}. To accept, log in or register with this email address and open this link:\n\n{}/invitations/accept?token={}\n\nThe link is valid for {} days.\n",
+                organisation, role, self.frontend_url, token, days
+            ),
+        }
+    }
+
+    pub fn advisor_link(&self, to: &str, organisation: &str, fiscal_years: &str, token: &str, hours: i64) -> MailMessage {
+        MailMessage {
+            from: self.from.clone(),
+            to: to.to_string(),
+            subject: format!("Access to the books of {} on MiniDebet", organisation),
+            body: format!(
+                "{} has given you read-only access to invoices, payments, expenses and reports for the fiscal years {} on MiniDebet. Open this link to log in:\n\n{}/advisor/login?token={}\n\nThe link can be used once and is valid for {} hours. You can request a new one on the login page.\n",
+                organisation, fiscal_years.replace(' ', ", "), self.frontend_url, token, hours
+            ),
+        }
+    }
+
+    pub async fn send(&self, message: &MailMessage) -> std::io::Result<()> {
+        self.transport.send(message).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mailer(vars: &[(&str, &str)]) -> Result<Mailer, String> {
+        Mailer::from_vars(|name| vars.iter().find(|(key, _)| *key == name).map(|(_, value)| value.to_string()))
+    }
+
+    #[test]
+    fn a_transport_is_required_outside_development() {
+        assert!(mailer(&[]).is_err());
+        assert!(mailer(&[("MAIL_TRANSPORT", "smtp")]).is_err());
+        assert!(mailer(&[("MAIL_TRANSPORT", "pigeon")]).is_err());
+        assert!(mailer(&[("MINIDEBET_ENV", "development")]).is_ok());
+        assert!(mailer(&[("MAIL_TRANSPORT", "file"), ("MAIL_DIR", "/tmp/mail")]).is_ok());
+    }
+
+    #[test]
+    fn links_point_to_the_frontend() {
+        let mailer = mailer(&[("MAIL_TRANSPORT", "log"), ("FRONTEND_URL", "https://minidebet.pages.dev/")]).unwrap();
+        let message = mailer.password_reset("max@example.com", "abc.def.ghi", 60);
+
+        assert_eq!(message.to, "max@example.com");
+        assert_eq!(message.from, DEFAULT_FROM);
+        assert!(message.body.contains("https://minidebet.pages.dev/reset-password?token=abc.def.ghi\n"));
+    }
+}
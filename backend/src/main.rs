@@ -1,5 +1,9 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
 use axum::{
-    routing::{get, post},
+    extract::DefaultBodyLimit,
+    routing::{delete, get, post, put},
     Router,
 };
 
@@ -7,33 +11,203 @@ use tower_http::cors::CorsLayer;
 use tracing_subscriber;
 
 mod db;
+mod error;
 mod models;
 mod handlers;
 mod auth;
+mod reports;
+mod retention;
+mod gdpr;
+mod mail;
+mod state;
+mod storage;
 
 use db::init_db;
-use handlers::{create_user, create_client, get_clients, create_invoice, get_invoices, get_invoice};
-use auth::middleware::auth_middleware;
+use handlers::{create_user, delete_current_user, create_client, get_clients, delete_client, create_invoice, get_invoices, get_invoice, create_payment, get_payments};
+use auth::keys::JwtKeys;
+use auth::oidc::Oidc;
+use auth::middleware::{advisor_middleware, auth_middleware};
+use mail::Mailer;
+use state::AppState;
+use storage::LocalReceiptStorage;
 
 #[tokio::main]
 async fn main() {
     // Initialize tracing
     tracing_subscriber::fmt::init();
 
+    // Refuse to start without a JWT signing key or a mail transport outside
+    // development mode
+    let jwt_keys = JwtKeys::from_env().expect("Invalid JWT key configuration");
+    let mailer = Mailer::from_env().expect("Invalid mail configuration");
+    let oidc = Oidc::from_env().expect("Invalid OIDC configuration");
+
     // Initialize database
     let db = init_db().await.expect("Failed to initialize database");
-    
-    // Build our application with routes
-    let app = Router::new()
+    let state = AppState {
+        db,
+        receipts: Arc::new(LocalReceiptStorage::from_env()),
+        jwt_keys: Arc::new(jwt_keys),
+        mailer: Arc::new(mailer),
+        oidc: Arc::new(oidc),
+    };
+
+    // Delete documents whose retention period has elapsed
+    retention::spawn_purge_job(state.db.clone(), state.receipts.clone());
+
+    // Routes reachable without an access token
+    let public = Router::new()
         .route("/", get(root))
         .route("/health", get(health_check))
         .route("/api/users", post(create_user))
         .route("/api/auth/login", post(handlers::auth::login))
+        .route("/api/auth/login/2fa", post(handlers::auth::login_two_factor))
+        .route("/api/auth/login/2fa/setup", post(handlers::auth::login_two_factor_setup))
+        .route("/api/auth/refresh", post(handlers::auth::refresh))
+        .route("/api/auth/logout", post(handlers::auth::logout))
+        .route("/api/auth/verify-email", post(handlers::email_token::verify_email))
+        .route("/api/auth/password-reset/request", post(handlers::email_token::request_password_reset))
+        .route("/api/auth/password-reset", post(handlers::email_token::reset_password))
+        .route("/api/auth/sso/providers", get(handlers::sso::get_sso_providers))
+        .route("/api/auth/sso/:provider/start", post(handlers::sso::start_sso))
+        .route("/api/auth/sso/callback", post(handlers::sso::complete_sso))
+        .route("/api/advisor/login", post(handlers::advisor::advisor_login))
+        .route("/api/advisor/login/request", post(handlers::advisor::request_advisor_link));
+
+    // Read-only portal for tax advisors, authenticated with their own
+    // session token
+    let advisor = Router::new()
+        .route("/api/advisor/invoices/:year", get(handlers::advisor::get_invoices))
+        .route("/api/advisor/payments/:year", get(handlers::advisor::get_payments))
+        .route("/api/advisor/expenses/:year", get(handlers::advisor::get_expenses))
+        .route("/api/advisor/reports/euer/:year", get(handlers::advisor::get_euer))
+        .route("/api/advisor/reports/ustva/:year/:period", get(handlers::advisor::get_ustva))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), advisor_middleware));
+
+    // Build our application with routes
+    let app = Router::new()
+        .route("/api/users/me", delete(delete_current_user))
+        .route("/api/auth/logout-all", post(handlers::auth::logout_all))
+        .route("/api/auth/verify-email/request", post(handlers::email_token::request_email_verification))
+        .route("/api/auth/sessions", get(handlers::auth::get_sessions))
+        .route("/api/auth/sessions/:id", delete(handlers::auth::delete_session))
+        .route("/api/auth/sso/identities", get(handlers::sso::get_identities))
+        .route("/api/auth/sso/identities/:provider", delete(handlers::sso::delete_identity))
+        .route("/api/auth/2fa", get(handlers::two_factor::get_two_factor))
+        .route("/api/auth/2fa/setup", post(handlers::two_factor::setup_two_factor))
+        .route("/api/auth/2fa/enable", post(handlers::two_factor::enable_two_factor))
+        .route("/api/auth/2fa/disable", post(handlers::two_factor::disable_two_factor))
+        .route("/api/auth/2fa/recovery-codes", post(handlers::two_factor::regenerate_recovery_codes))
+        .route("/api/auth/2fa/required", put(handlers::two_factor::set_two_factor_required))
+        .route("/api/api-keys", get(handlers::api_key::get_api_keys).post(handlers::api_key::create_api_key))
+        .route("/api/api-keys/:id", delete(handlers::api_key::delete_api_key))
+        .route("/api/organisations", get(handlers::organisation::get_organisations))
+        .route("/api/invitations/accept", post(handlers::organisation::accept_invitation))
+        .route("/api/organisation/members", get(handlers::organisation::get_members))
+        .route(
+            "/api/organisation/members/:user_id",
+            put(handlers::organisation::update_member).delete(handlers::organisation::delete_member),
+        )
+        .route(
+            "/api/organisation/invitations",
+            get(handlers::organisation::get_invitations).post(handlers::organisation::create_invitation),
+        )
+        .route("/api/organisation/invitations/:id", delete(handlers::organisation::delete_invitation))
+        .route(
+            "/api/advisor-grants",
+            get(handlers::advisor::get_advisor_grants).post(handlers::advisor::create_advisor_grant),
+        )
+        .route("/api/advisor-grants/:id", delete(handlers::advisor::delete_advisor_grant))
         .route("/api/clients", post(create_client).get(get_clients))
+        .route("/api/clients/:id", delete(delete_client))
         .route("/api/invoices", post(create_invoice).get(get_invoices))
         .route("/api/invoices/:id", get(get_invoice))
-        .layer(axum::middleware::from_fn(auth_middleware))
-        .with_state(db)
+        .route("/api/invoices/:id/items", post(handlers::invoice::add_invoice_item))
+        .route(
+            "/api/invoices/:id/allowance-charges",
+            get(handlers::allowance_charge::get_allowance_charges)
+                .post(handlers::allowance_charge::create_allowance_charge),
+        )
+        .route(
+            "/api/invoices/:id/allowance-charges/:allowance_charge_id",
+            delete(handlers::allowance_charge::delete_allowance_charge),
+        )
+        .route(
+            "/api/invoices/:id/payment-terms",
+            get(handlers::invoice::get_payment_terms).put(handlers::invoice::set_payment_terms),
+        )
+        .route(
+            "/api/invoices/:id/billing",
+            get(handlers::invoice::get_billing).put(handlers::invoice::set_billing),
+        )
+        .route(
+            "/api/invoices/:id/currency",
+            get(handlers::invoice::get_currency).put(handlers::invoice::set_currency),
+        )
+        .route("/api/invoices/:id/payments", post(create_payment).get(get_payments))
+        .route("/api/expenses", post(handlers::expense::create_expense).get(handlers::expense::get_expenses))
+        .route(
+            "/api/expenses/:id",
+            get(handlers::expense::get_expense)
+                .put(handlers::expense::update_expense)
+                .delete(handlers::expense::delete_expense),
+        )
+        .route(
+            "/api/expenses/:id/receipt",
+            put(handlers::expense::upload_receipt)
+                .get(handlers::expense::get_receipt)
+                .delete(handlers::expense::delete_receipt)
+                .layer(DefaultBodyLimit::max(handlers::expense::MAX_RECEIPT_SIZE)),
+        )
+        .route(
+            "/api/projects",
+            get(handlers::project::get_projects).post(handlers::project::create_project),
+        )
+        .route("/api/projects/:id", put(handlers::project::update_project))
+        .route(
+            "/api/time-entries",
+            get(handlers::time_entry::get_time_entries).post(handlers::time_entry::create_time_entry),
+        )
+        .route(
+            "/api/time-entries/:id",
+            put(handlers::time_entry::update_time_entry).delete(handlers::time_entry::delete_time_entry),
+        )
+        .route("/api/time-entries/timer", post(handlers::time_entry::start_timer))
+        .route("/api/time-entries/:id/stop", post(handlers::time_entry::stop_timer))
+        .route("/api/invoices/:id/time-entries", post(handlers::time_entry::bill_time_entries))
+        .route(
+            "/api/catalog",
+            get(handlers::catalog::get_catalog_items).post(handlers::catalog::create_catalog_item),
+        )
+        .route(
+            "/api/catalog/:id",
+            get(handlers::catalog::get_catalog_item)
+                .put(handlers::catalog::update_catalog_item)
+                .delete(handlers::catalog::delete_catalog_item),
+        )
+        .route("/api/exchange-rates", get(handlers::exchange_rate::get_exchange_rates))
+        .route(
+            "/api/exchange-rates/import",
+            post(handlers::exchange_rate::import_exchange_rates)
+                .layer(DefaultBodyLimit::max(handlers::exchange_rate::MAX_IMPORT_SIZE)),
+        )
+        .route("/api/reports/euer/:year", get(handlers::report::get_euer))
+        .route("/api/reports/ustva/:year/:period", get(handlers::report::get_ustva))
+        .route("/api/reports/zm/:year/:period", get(handlers::report::get_zm))
+        .route("/api/reports/dashboard/:year", get(handlers::report::get_dashboard))
+        .route("/api/exports/gdpdu/:year", get(handlers::report::get_gdpdu_export))
+        .route("/api/audit-log", get(handlers::audit::get_audit_log))
+        .route("/api/audit-log/verify", get(handlers::audit::verify_audit_log))
+        .route("/api/retention", get(handlers::retention::get_retention))
+        .route("/api/retention/legal-hold", put(handlers::retention::set_legal_hold))
+        .route("/api/gdpr/export", get(handlers::gdpr::export_current_user))
+        .route("/api/gdpr/erase", post(handlers::gdpr::erase_current_user))
+        .route("/api/clients/:id/gdpr/export", get(handlers::gdpr::export_client))
+        .route("/api/clients/:id/gdpr/erase", post(handlers::gdpr::erase_client))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), auth_middleware))
+        .merge(public)
+        .merge(advisor)
+        .with_state(state)
         .layer(CorsLayer::permissive());
 
     // Run our application
@@ -41,7 +215,8 @@ async fn main() {
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
     println!("listening on {}", addr);
     
-    axum::serve(listener, app).await.unwrap();
+    // Sessions record the client address
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
 }
 
 async fn root() -> &'static str {
@@ -0,0 +1,159 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use chrono::{DateTime, NaiveDate, Utc};
+use crate::models::invoice::Invoice;
+use crate::models::payment::Payment;
+use crate::reports::round_cents;
+
+/// An advance payment deducted on a final invoice, with its VAT shown
+/// separately as § 14 Abs. 5 Satz 2 UStG requires.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AdvanceDeduction {
+    pub id: String,
+    pub final_invoice_id: String,
+    pub advance_invoice_id: String,
+    pub advance_invoice_number: String,
+    pub advance_issue_date: NaiveDate,
+    pub tax_rate: f64,
+    pub net_amount: f64,
+    pub tax_amount: f64,
+    pub gross_amount: f64,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AdvanceDeduction {
+    /// Splits the amount received on an advance invoice into net and VAT at
+    /// the advance invoice's rate.
+    pub fn new(final_invoice_id: String, advance: &Invoice, received: f64) -> Self {
+        let gross_amount = round_cents(received);
+        let tax_amount = round_cents(gross_amount * advance.tax_rate / (100.0 + advance.tax_rate));
+
+        Self {
+            id: Uuid::new_v4().to_string(),
+            final_invoice_id,
+            advance_invoice_id: advance.id.clone(),
+            advance_invoice_number: advance.invoice_number.clone(),
+            advance_issue_date: advance.issue_date,
+            tax_rate: advance.tax_rate,
+            net_amount: round_cents(gross_amount - tax_amount),
+            tax_amount,
+            gross_amount,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+/// Deductions for the paid advance invoices of a project. Advance invoices
+/// still open have to be paid or cancelled first, so the final invoice
+/// deducts exactly what was received.
+pub fn deductions_for(
+    final_invoice: &Invoice,
+    advances: &[Invoice],
+    payments: &[Payment],
+) -> Result<Vec<AdvanceDeduction>, String> {
+    let mut deductions = Vec::new();
+
+    for advance in advances {
+        if !advance.is_advance() || matches!(advance.status.as_str(), "draft" | "cancelled") {
+            continue;
+        }
+        if advance.status != "paid" {
+            return Err(format!(
+                "Advance invoice {} is still open; record its payment or cancel it first",
+                advance.invoice_number
+            ));
+        }
+        if advance.currency != final_invoice.currency {
+            return Err(format!(
+                "Advance invoice {} is in {}, the final invoice in {}",
+                advance.invoice_number, advance.currency, final_invoice.currency
+            ));
+        }
+
+        let received: f64 = payments
+            .iter()
+            .filter(|payment| payment.invoice_id == advance.id)
+            .fold(0.0, |sum, payment| sum + payment.amount);
+        // Marked as paid without recording the payment
+        let received = if received == 0.0 { advance.total_amount } else { received };
+
+        deductions.push(AdvanceDeduction::new(final_invoice.id.clone(), advance, received));
+    }
+
+    Ok(deductions)
+}
+
+/// The amounts of an advance or final invoice as they are printed: the total
+/// service, the deducted advance payments and the amount still due.
+#[derive(Debug, Clone, Serialize)]
+pub struct BillingSummary {
+    pub billing_type: String,
+    pub project_id: Option<String>,
+    pub subtotal: f64,
+    pub tax_amount: f64,
+    pub total_amount: f64,
+    pub deductions: Vec<AdvanceDeduction>,
+    pub deducted_net: f64,
+    pub deducted_tax: f64,
+    pub deducted_gross: f64,
+    pub amount_due: f64,
+}
+
+impl BillingSummary {
+    pub fn new(invoice: &Invoice, deductions: Vec<AdvanceDeduction>) -> Self {
+        Self {
+            billing_type: invoice.billing_type.clone(),
+            project_id: invoice.project_id.clone(),
+            subtotal: invoice.subtotal,
+            tax_amount: invoice.tax_amount,
+            total_amount: invoice.total_amount,
+            deductions,
+            deducted_net: invoice.advance_deduction_net,
+            deducted_tax: invoice.advance_deduction_tax,
+            deducted_gross: round_cents(invoice.advance_deduction_net + invoice.advance_deduction_tax),
+            amount_due: invoice.amount_due(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invoice(number: &str, billing_type: &str, status: &str, subtotal: f64) -> Invoice {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let mut invoice = Invoice::new(
+            "u1".to_string(), "c1".to_string(), number.to_string(), date, date,
+            "EUR".to_string(), subtotal, 19.0, round_cents(subtotal * 0.19), round_cents(subtotal * 1.19), None,
+        );
+        invoice.billing_type = billing_type.to_string();
+        invoice.status = status.to_string();
+        invoice
+    }
+
+    #[test]
+    fn received_advance_payments_are_split_into_net_and_vat() {
+        let final_invoice = invoice("RE-3", "final", "draft", 10000.0);
+        let first = invoice("RE-1", "advance", "paid", 3000.0);
+        let second = invoice("RE-2", "advance", "paid", 2000.0);
+        let cancelled = invoice("RE-0", "advance", "cancelled", 500.0);
+        let payments = vec![Payment::new(first.id.clone(), 3570.0, NaiveDate::from_ymd_opt(2024, 3, 5).unwrap(), None)];
+
+        let deductions = deductions_for(&final_invoice, &[first, second, cancelled], &payments).unwrap();
+
+        assert_eq!(deductions.len(), 2);
+        assert_eq!(deductions[0].advance_invoice_number, "RE-1");
+        assert_eq!(deductions[0].net_amount, 3000.0);
+        assert_eq!(deductions[0].tax_amount, 570.0);
+        assert_eq!(deductions[1].gross_amount, 2380.0);
+    }
+
+    #[test]
+    fn open_advance_invoices_block_the_final_invoice() {
+        let final_invoice = invoice("RE-3", "final", "draft", 10000.0);
+        let open = invoice("RE-1", "advance", "sent", 3000.0);
+
+        assert!(deductions_for(&final_invoice, &[open], &[]).is_err());
+    }
+}
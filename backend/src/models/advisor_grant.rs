@@ -0,0 +1,215 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use chrono::{DateTime, Datelike, Duration, Utc};
+use sha2::{Digest, Sha256};
+
+/// Login links for the advisor portal stay valid for three days.
+pub const ADVISOR_LINK_HOURS: i64 = 72;
+/// Grants are time-limited to at most a year.
+pub const MAX_GRANT_DAYS: i64 = 366;
+/// Earliest fiscal year that can be granted.
+const FIRST_FISCAL_YEAR: i32 = 2000;
+/// Prefix of the actor ID recorded in the audit log for advisor access.
+pub const ADVISOR_ACTOR_PREFIX: &str = "advisor:";
+/// Entity type of the audit log entries that record advisor access.
+pub const ADVISOR_ACCESS_ENTITY: &str = "advisor_access";
+
+/// Read-only access of a tax advisor to selected fiscal years.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AdvisorGrant {
+    pub id: String,
+    /// Organisation whose books the advisor may read
+    pub user_id: String,
+    pub advisor_email: String,
+    pub advisor_name: Option<String>,
+    /// Space-separated years, e.g. `2023 2024`
+    pub fiscal_years: String,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub last_accessed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewAdvisorGrant {
+    pub advisor_email: String,
+    pub advisor_name: Option<String>,
+    pub fiscal_years: Vec<i32>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl NewAdvisorGrant {
+    /// Validates the request. Years are stored sorted and without duplicates.
+    pub fn into_grant(self, user_id: String, created_by: String, now: DateTime<Utc>) -> Result<AdvisorGrant, String> {
+        let advisor_email = self.advisor_email.trim().to_lowercase();
+        if !advisor_email.contains('@') {
+            return Err("Invalid email address".to_string());
+        }
+        if self.fiscal_years.is_empty() {
+            return Err("At least one fiscal year is required".to_string());
+        }
+        if let Some(year) = self
+            .fiscal_years
+            .iter()
+            .find(|year| !(FIRST_FISCAL_YEAR..=now.year()).contains(*year))
+        {
+            return Err(format!("Invalid fiscal year: {}", year));
+        }
+        if self.expires_at <= now || self.expires_at > now + Duration::days(MAX_GRANT_DAYS) {
+            return Err(format!("Expiry must be within the next {} days", MAX_GRANT_DAYS));
+        }
+
+        let mut years = self.fiscal_years;
+        years.sort_unstable();
+        years.dedup();
+
+        Ok(AdvisorGrant {
+            id: Uuid::new_v4().to_string(),
+            user_id,
+            advisor_email,
+            advisor_name: self.advisor_name.map(|name| name.trim().to_string()).filter(|name| !name.is_empty()),
+            fiscal_years: years.iter().map(i32::to_string).collect::<Vec<_>>().join(" "),
+            created_by,
+            created_at: now,
+            expires_at: self.expires_at,
+            revoked_at: None,
+            last_accessed_at: None,
+        })
+    }
+}
+
+impl AdvisorGrant {
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.revoked_at.is_none() && self.expires_at > now
+    }
+
+    pub fn covers(&self, year: i32) -> bool {
+        self.fiscal_years.split(' ').any(|value| value.parse() == Ok(year))
+    }
+
+    /// Actor ID of the advisor in the audit log.
+    pub fn actor_id(&self) -> String {
+        format!("{}{}", ADVISOR_ACTOR_PREFIX, self.id)
+    }
+}
+
+/// A single-use login link for the advisor portal.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AdvisorLink {
+    pub id: String,
+    pub grant_id: String,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+impl AdvisorLink {
+    /// A new link with its token. It never outlives the grant; only the
+    /// token's hash is stored.
+    pub fn new(grant: &AdvisorGrant, now: DateTime<Utc>) -> (Self, String) {
+        let token = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+        let link = Self {
+            id: Uuid::new_v4().to_string(),
+            grant_id: grant.id.clone(),
+            token_hash: hash_advisor_token(&token),
+            created_at: now,
+            expires_at: (now + Duration::hours(ADVISOR_LINK_HOURS)).min(grant.expires_at),
+            used_at: None,
+        };
+        (link, token)
+    }
+
+    pub fn is_open(&self, now: DateTime<Utc>) -> bool {
+        self.used_at.is_none() && self.expires_at > now
+    }
+}
+
+pub fn hash_advisor_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// What an advisor looked at, recorded in the owner's audit log.
+#[derive(Debug, Clone, Serialize)]
+pub struct AdvisorAccess {
+    pub grant_id: String,
+    pub advisor_email: String,
+    /// `login`, `invoices`, `payments`, `expenses` or a report name
+    pub resource: String,
+    pub fiscal_year: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdvisorLogin {
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdvisorLinkRequest {
+    pub email: String,
+}
+
+/// Session of an advisor: a short-lived token for the portal endpoints and
+/// what it covers.
+#[derive(Debug, Serialize)]
+pub struct AdvisorSession {
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+    pub organisation: String,
+    pub fiscal_years: Vec<i32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn request(years: &[i32], expires_at: DateTime<Utc>) -> NewAdvisorGrant {
+        NewAdvisorGrant {
+            advisor_email: " Kanzlei@Steuerberater.de ".to_string(),
+            advisor_name: Some(" ".to_string()),
+            fiscal_years: years.to_vec(),
+            expires_at,
+        }
+    }
+
+    #[test]
+    fn grants_cover_the_selected_years_until_they_expire() {
+        let now = Utc.with_ymd_and_hms(2025, 3, 1, 12, 0, 0).unwrap();
+        let grant = request(&[2024, 2023, 2024], now + Duration::days(30))
+            .into_grant("org".to_string(), "u1".to_string(), now)
+            .unwrap();
+
+        assert_eq!(grant.advisor_email, "kanzlei@steuerberater.de");
+        assert_eq!(grant.advisor_name, None);
+        assert_eq!(grant.fiscal_years, "2023 2024");
+        assert!(grant.covers(2023));
+        assert!(!grant.covers(2025));
+        assert!(grant.is_active(now));
+        assert!(!grant.is_active(grant.expires_at));
+        assert_eq!(grant.actor_id(), format!("advisor:{}", grant.id));
+
+        assert!(request(&[], now + Duration::days(30)).into_grant("org".to_string(), "u1".to_string(), now).is_err());
+        assert!(request(&[2026], now + Duration::days(30)).into_grant("org".to_string(), "u1".to_string(), now).is_err());
+        assert!(request(&[2024], now).into_grant("org".to_string(), "u1".to_string(), now).is_err());
+        assert!(request(&[2024], now + Duration::days(400)).into_grant("org".to_string(), "u1".to_string(), now).is_err());
+    }
+
+    #[test]
+    fn links_are_single_use_and_end_with_the_grant() {
+        let now = Utc::now();
+        let grant = request(&[2024], now + Duration::hours(1))
+            .into_grant("org".to_string(), "u1".to_string(), now)
+            .unwrap();
+        let (mut link, token) = AdvisorLink::new(&grant, now);
+
+        assert_eq!(link.token_hash, hash_advisor_token(&token));
+        assert_eq!(link.expires_at, grant.expires_at);
+        assert!(link.is_open(now));
+        link.used_at = Some(now);
+        assert!(!link.is_open(now));
+    }
+}
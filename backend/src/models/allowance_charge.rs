@@ -0,0 +1,78 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use validator::Validate;
+use crate::models::catalog::tax_category_rate;
+use crate::reports::round_cents;
+
+/// A discount (`allowance`) or surcharge (`charge`) on the whole invoice,
+/// such as a project discount or a packaging fee.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AllowanceCharge {
+    pub id: String,
+    pub invoice_id: String,
+    pub kind: String,
+    pub reason: String,
+    /// Percentage of the sum of the invoice lines, if given as one.
+    pub percent: Option<f64>,
+    /// Net amount, always positive; derived from `percent` when that is set.
+    pub amount: f64,
+    pub tax_category: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+pub struct NewAllowanceCharge {
+    pub kind: String,
+    #[validate(length(min = 1, max = 200))]
+    pub reason: String,
+    #[validate(range(min = 0.01, max = 100.0))]
+    pub percent: Option<f64>,
+    #[validate(range(min = 0.01))]
+    pub amount: Option<f64>,
+    pub tax_category: Option<String>,
+}
+
+impl NewAllowanceCharge {
+    pub fn check(&self) -> Result<(), String> {
+        if !matches!(self.kind.as_str(), "allowance" | "charge") {
+            return Err(format!("Unknown kind: {}", self.kind));
+        }
+        if self.percent.is_some() == self.amount.is_some() {
+            return Err("Either percent or amount is required".to_string());
+        }
+        if let Some(category) = self.tax_category.as_deref() {
+            if tax_category_rate(category).is_none() {
+                return Err(format!("Unknown tax category: {}", category));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl AllowanceCharge {
+    pub fn new(invoice_id: String, payload: NewAllowanceCharge) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            invoice_id,
+            kind: payload.kind,
+            reason: payload.reason,
+            percent: payload.percent,
+            amount: payload.amount.map(round_cents).unwrap_or(0.0),
+            tax_category: payload.tax_category.unwrap_or_else(|| "standard".to_string()),
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn is_allowance(&self) -> bool {
+        self.kind == "allowance"
+    }
+
+    /// Recomputes a percentage-based amount from the sum of the invoice lines.
+    pub fn apply_to(&mut self, line_total: f64) {
+        if let Some(percent) = self.percent {
+            self.amount = round_cents(line_total * percent / 100.0);
+        }
+    }
+}
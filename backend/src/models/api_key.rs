@@ -0,0 +1,224 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+
+/// Every API key starts with this, so it can be told apart from an access
+/// token and found by secret scanners.
+pub const API_KEY_PREFIX: &str = "mdk_";
+/// Characters of the key after `API_KEY_PREFIX` kept in clear to identify it.
+const IDENTIFYING_LENGTH: usize = 8;
+const MAX_NAME_LENGTH: usize = 100;
+
+/// What a key may do. Keys never reach account, session, key management,
+/// audit log, retention or DSGVO endpoints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiScope {
+    ClientsRead,
+    ClientsWrite,
+    InvoicesRead,
+    InvoicesWrite,
+    ExpensesRead,
+    ExpensesWrite,
+    TimeRead,
+    TimeWrite,
+    CatalogRead,
+    CatalogWrite,
+    ReportsRead,
+}
+
+impl ApiScope {
+    pub const ALL: [ApiScope; 11] = [
+        ApiScope::ClientsRead,
+        ApiScope::ClientsWrite,
+        ApiScope::InvoicesRead,
+        ApiScope::InvoicesWrite,
+        ApiScope::ExpensesRead,
+        ApiScope::ExpensesWrite,
+        ApiScope::TimeRead,
+        ApiScope::TimeWrite,
+        ApiScope::CatalogRead,
+        ApiScope::CatalogWrite,
+        ApiScope::ReportsRead,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiScope::ClientsRead => "clients:read",
+            ApiScope::ClientsWrite => "clients:write",
+            ApiScope::InvoicesRead => "invoices:read",
+            ApiScope::InvoicesWrite => "invoices:write",
+            ApiScope::ExpensesRead => "expenses:read",
+            ApiScope::ExpensesWrite => "expenses:write",
+            ApiScope::TimeRead => "time:read",
+            ApiScope::TimeWrite => "time:write",
+            ApiScope::CatalogRead => "catalog:read",
+            ApiScope::CatalogWrite => "catalog:write",
+            ApiScope::ReportsRead => "reports:read",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|scope| scope.as_str() == value)
+    }
+
+    /// The scope a request needs when made with an API key, or `None` if
+    /// keys may not use the endpoint. Reads need the read scope, everything
+    /// else the write scope.
+    pub fn required_for(method: &str, path: &str) -> Option<Self> {
+        let read = method == "GET" || method == "HEAD";
+        let resource = path.strip_prefix("/api/")?.split('/').next()?;
+        match resource {
+            // DSGVO export and erasure of a client stay with the account holder
+            "clients" if path.contains("/gdpr/") => None,
+            "clients" => Some(if read { ApiScope::ClientsRead } else { ApiScope::ClientsWrite }),
+            "invoices" | "exchange-rates" => Some(if read { ApiScope::InvoicesRead } else { ApiScope::InvoicesWrite }),
+            "expenses" => Some(if read { ApiScope::ExpensesRead } else { ApiScope::ExpensesWrite }),
+            "projects" | "time-entries" => Some(if read { ApiScope::TimeRead } else { ApiScope::TimeWrite }),
+            "catalog" => Some(if read { ApiScope::CatalogRead } else { ApiScope::CatalogWrite }),
+            "reports" | "exports" if read => Some(ApiScope::ReportsRead),
+            _ => None,
+        }
+    }
+}
+
+/// A personal API key. The key itself is only returned at creation.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct ApiKey {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    /// Start of the key, e.g. `mdk_3f9a1c2e`
+    pub prefix: String,
+    #[serde(skip_serializing)]
+    pub key_hash: String,
+    /// Space-separated scope names
+    pub scopes: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub last_used_ip: Option<String>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewApiKey {
+    pub name: String,
+    pub scopes: Vec<String>,
+    /// Keys without expiry stay valid until revoked
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl NewApiKey {
+    /// Validates the request and returns the stored key with the key itself.
+    pub fn into_api_key(self, user_id: String, now: DateTime<Utc>) -> Result<(ApiKey, String), String> {
+        let name = self.name.trim();
+        if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+            return Err(format!("Name must have 1 to {} characters", MAX_NAME_LENGTH));
+        }
+        if self.scopes.is_empty() {
+            return Err("At least one scope is required".to_string());
+        }
+        let mut scopes = Vec::new();
+        for value in &self.scopes {
+            let scope = ApiScope::parse(value).ok_or_else(|| format!("Unknown scope: {}", value))?;
+            if !scopes.contains(&scope) {
+                scopes.push(scope);
+            }
+        }
+        if self.expires_at.is_some_and(|expires_at| expires_at <= now) {
+            return Err("Expiry must be in the future".to_string());
+        }
+
+        let key = new_api_key();
+        let api_key = ApiKey {
+            id: Uuid::new_v4().to_string(),
+            user_id,
+            name: name.to_string(),
+            prefix: key[..API_KEY_PREFIX.len() + IDENTIFYING_LENGTH].to_string(),
+            key_hash: hash_api_key(&key),
+            scopes: scopes.iter().map(|scope| scope.as_str()).collect::<Vec<_>>().join(" "),
+            created_at: now,
+            expires_at: self.expires_at,
+            last_used_at: None,
+            last_used_ip: None,
+            revoked_at: None,
+        };
+        Ok((api_key, key))
+    }
+}
+
+impl ApiKey {
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.revoked_at.is_none() && self.expires_at.is_none_or(|expires_at| expires_at > now)
+    }
+
+    pub fn has_scope(&self, scope: ApiScope) -> bool {
+        self.scopes.split(' ').any(|value| value == scope.as_str())
+    }
+}
+
+/// A new random key like `mdk_` followed by 40 hex digits.
+pub fn new_api_key() -> String {
+    let random = Uuid::new_v4().simple().to_string() + &Uuid::new_v4().simple().to_string()[..8];
+    format!("{}{}", API_KEY_PREFIX, random)
+}
+
+pub fn is_api_key(token: &str) -> bool {
+    token.starts_with(API_KEY_PREFIX)
+}
+
+pub fn hash_api_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn request(scopes: &[&str]) -> NewApiKey {
+        NewApiKey {
+            name: " Project sync ".to_string(),
+            scopes: scopes.iter().map(|scope| scope.to_string()).collect(),
+            expires_at: None,
+        }
+    }
+
+    #[test]
+    fn keys_are_stored_as_hash_with_identifying_prefix() {
+        let now = Utc::now();
+        let (api_key, key) = request(&["invoices:read", "invoices:write", "invoices:read"])
+            .into_api_key("u1".to_string(), now)
+            .unwrap();
+
+        assert!(is_api_key(&key));
+        assert!(key.starts_with(&api_key.prefix));
+        assert_eq!(api_key.prefix.len(), 12);
+        assert_eq!(api_key.key_hash, hash_api_key(&key));
+        assert_eq!(api_key.name, "Project sync");
+        assert_eq!(api_key.scopes, "invoices:read invoices:write");
+        assert!(api_key.has_scope(ApiScope::InvoicesWrite));
+        assert!(!api_key.has_scope(ApiScope::ClientsWrite));
+        assert!(api_key.is_active(now));
+
+        assert!(request(&["invoices:delete"]).into_api_key("u1".to_string(), now).is_err());
+        assert!(request(&[]).into_api_key("u1".to_string(), now).is_err());
+        let expired = NewApiKey { expires_at: Some(now - Duration::days(1)), ..request(&["clients:read"]) };
+        assert!(expired.into_api_key("u1".to_string(), now).is_err());
+    }
+
+    #[test]
+    fn endpoints_require_the_matching_scope() {
+        assert_eq!(ApiScope::required_for("GET", "/api/invoices/i1"), Some(ApiScope::InvoicesRead));
+        assert_eq!(ApiScope::required_for("POST", "/api/invoices/i1/items"), Some(ApiScope::InvoicesWrite));
+        assert_eq!(ApiScope::required_for("POST", "/api/clients"), Some(ApiScope::ClientsWrite));
+        assert_eq!(ApiScope::required_for("POST", "/api/time-entries/timer"), Some(ApiScope::TimeWrite));
+        assert_eq!(ApiScope::required_for("GET", "/api/reports/euer/2024"), Some(ApiScope::ReportsRead));
+        assert_eq!(ApiScope::required_for("GET", "/api/clients/c1/gdpr/export"), None);
+        assert_eq!(ApiScope::required_for("POST", "/api/api-keys"), None);
+        assert_eq!(ApiScope::required_for("POST", "/api/auth/logout-all"), None);
+        assert_eq!(ApiScope::required_for("GET", "/api/audit-log"), None);
+    }
+}
@@ -0,0 +1,255 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use sqlx::types::Json;
+use sqlx::FromRow;
+use uuid::Uuid;
+use chrono::{DateTime, SecondsFormat, Utc};
+
+/// Actor of changes made by scheduled jobs rather than a user.
+pub const SYSTEM_ACTOR: &str = "system";
+
+/// `prev_hash` of the first entry in a user's chain.
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub id: String,
+    pub user_id: String,
+    pub sequence: i64,
+    pub actor_id: String,
+    pub entity_type: String,
+    pub entity_id: String,
+    pub action: String,
+    pub before_data: Option<Json<Value>>,
+    pub after_data: Option<Json<Value>>,
+    pub created_at: DateTime<Utc>,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+/// The last entry of a user's chain removed by the retention purge.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AuditCheckpoint {
+    pub user_id: String,
+    pub sequence: i64,
+    pub hash: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChainVerification {
+    pub valid: bool,
+    pub entries: usize,
+    /// Entries at the start of the chain removed after their retention period.
+    pub purged_entries: i64,
+    pub head_hash: String,
+    /// Sequence number of the first entry that does not verify.
+    pub broken_at: Option<i64>,
+    pub error: Option<String>,
+}
+
+impl AuditEntry {
+    /// Describes a change to a business record. Sequence and hashes are
+    /// assigned when the entry is appended to the owner's chain.
+    pub fn new<T: Serialize>(
+        user_id: &str,
+        actor_id: &str,
+        entity_type: &str,
+        entity_id: &str,
+        before: Option<&T>,
+        after: Option<&T>,
+    ) -> Self {
+        let action = match (before.is_some(), after.is_some()) {
+            (false, _) => "create",
+            (true, true) => "update",
+            (true, false) => "delete",
+        };
+        let snapshot = |record: Option<&T>| record.and_then(|record| serde_json::to_value(record).ok()).map(Json);
+
+        Self {
+            id: Uuid::new_v4().to_string(),
+            user_id: user_id.to_string(),
+            sequence: 0,
+            actor_id: actor_id.to_string(),
+            entity_type: entity_type.to_string(),
+            entity_id: entity_id.to_string(),
+            action: action.to_string(),
+            before_data: snapshot(before),
+            after_data: snapshot(after),
+            created_at: Utc::now(),
+            prev_hash: String::new(),
+            hash: String::new(),
+        }
+    }
+
+    /// Describes a read of a record, e.g. by a tax advisor. The record that was
+    /// accessed is kept as the entry's `after_data`.
+    pub fn access<T: Serialize>(user_id: &str, actor_id: &str, entity_type: &str, entity_id: &str, record: &T) -> Self {
+        Self {
+            action: "access".to_string(),
+            ..Self::new(user_id, actor_id, entity_type, entity_id, None, Some(record))
+        }
+    }
+
+    /// Links the entry to its predecessor and computes its hash.
+    pub fn seal(&mut self, sequence: i64, prev_hash: &str) {
+        self.sequence = sequence;
+        self.prev_hash = prev_hash.to_string();
+        self.hash = self.compute_hash();
+    }
+
+    /// SHA-256 over the predecessor's hash and every field of the entry.
+    /// Snapshots are hashed in serde_json's canonical form (sorted keys,
+    /// shortest round-trip floats); `float_roundtrip` makes the values read
+    /// back from the TEXT columns serialise to the same bytes.
+    pub fn compute_hash(&self) -> String {
+        let data = |snapshot: &Option<Json<Value>>| snapshot.as_ref().map(|json| json.0.clone());
+        let content = json!([
+            self.prev_hash,
+            self.id,
+            self.user_id,
+            self.sequence,
+            self.actor_id,
+            self.entity_type,
+            self.entity_id,
+            self.action,
+            data(&self.before_data),
+            data(&self.after_data),
+            self.created_at.to_rfc3339_opts(SecondsFormat::Nanos, true),
+        ]);
+
+        hex::encode(Sha256::digest(content.to_string().as_bytes()))
+    }
+}
+
+/// Walks a user's chain in sequence order and reports the first entry whose
+/// link or hash does not match. Once the oldest entries have been purged, the
+/// chain continues from the purge checkpoint.
+pub fn verify_chain(entries: &[AuditEntry], checkpoint: Option<&AuditCheckpoint>) -> ChainVerification {
+    let purged_entries = checkpoint.map_or(0, |checkpoint| checkpoint.sequence);
+    let mut expected_prev = checkpoint.map_or_else(|| GENESIS_HASH.to_string(), |checkpoint| checkpoint.hash.clone());
+
+    for (index, entry) in entries.iter().enumerate() {
+        let expected_sequence = purged_entries + index as i64 + 1;
+        let error = if entry.sequence != expected_sequence {
+            Some(format!("Expected sequence {} but found {}", expected_sequence, entry.sequence))
+        } else if entry.prev_hash != expected_prev {
+            Some("Entry does not link to its predecessor".to_string())
+        } else if entry.hash != entry.compute_hash() {
+            Some("Entry content does not match its hash".to_string())
+        } else {
+            None
+        };
+
+        if let Some(error) = error {
+            return ChainVerification {
+                valid: false,
+                entries: entries.len(),
+                purged_entries,
+                head_hash: expected_prev,
+                broken_at: Some(entry.sequence),
+                error: Some(error),
+            };
+        }
+        expected_prev = entry.hash.clone();
+    }
+
+    ChainVerification {
+        valid: true,
+        entries: entries.len(),
+        purged_entries,
+        head_hash: expected_prev,
+        broken_at: None,
+        error: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain(len: usize) -> Vec<AuditEntry> {
+        let mut prev = GENESIS_HASH.to_string();
+        (1..=len)
+            .map(|sequence| {
+                let after = json!({ "amount": sequence as f64 * 10.5 });
+                let mut entry = AuditEntry::new("u1", "u1", "payment", "p1", None, Some(&after));
+                entry.seal(sequence as i64, &prev);
+                prev = entry.hash.clone();
+                entry
+            })
+            .collect()
+    }
+
+    #[test]
+    fn intact_chain_verifies() {
+        let entries = chain(3);
+        let result = verify_chain(&entries, None);
+
+        assert!(result.valid);
+        assert_eq!(result.entries, 3);
+        assert_eq!(result.head_hash, entries[2].hash);
+        assert_eq!(verify_chain(&[], None).head_hash, GENESIS_HASH);
+    }
+
+    #[test]
+    fn changed_content_is_detected() {
+        let mut entries = chain(3);
+        entries[1].after_data = Some(Json(json!({ "amount": 1.0 })));
+
+        let result = verify_chain(&entries, None);
+        assert!(!result.valid);
+        assert_eq!(result.broken_at, Some(2));
+    }
+
+    #[test]
+    fn removed_entry_is_detected() {
+        let mut entries = chain(3);
+        entries.remove(1);
+
+        let result = verify_chain(&entries, None);
+        assert!(!result.valid);
+        assert_eq!(result.broken_at, Some(3));
+    }
+
+    fn checkpoint(entry: &AuditEntry) -> AuditCheckpoint {
+        AuditCheckpoint {
+            user_id: entry.user_id.clone(),
+            sequence: entry.sequence,
+            hash: entry.hash.clone(),
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn purged_prefix_verifies_from_checkpoint() {
+        let mut entries = chain(4);
+        let purged: Vec<_> = entries.drain(..2).collect();
+
+        let result = verify_chain(&entries, Some(&checkpoint(&purged[1])));
+        assert!(result.valid);
+        assert_eq!(result.purged_entries, 2);
+        assert_eq!(result.head_hash, entries[1].hash);
+    }
+
+    #[test]
+    fn entries_removed_beyond_checkpoint_are_detected() {
+        let mut entries = chain(4);
+        let purged: Vec<_> = entries.drain(..2).collect();
+
+        // Without a checkpoint the chain has to start at the genesis entry
+        let result = verify_chain(&entries, None);
+        assert!(!result.valid);
+        assert_eq!(result.broken_at, Some(3));
+
+        // Removing one more entry than the purge recorded
+        let result = verify_chain(&entries[1..], Some(&checkpoint(&purged[1])));
+        assert!(!result.valid);
+        assert_eq!(result.broken_at, Some(4));
+
+        let result = verify_chain(&entries, Some(&checkpoint(&purged[0])));
+        assert!(!result.valid);
+        assert_eq!(result.broken_at, Some(3));
+    }
+}
@@ -0,0 +1,241 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use validator::Validate;
+use crate::models::invoice::{Invoice, NewInvoiceItem, DEFAULT_UNIT_CODE, UNITS};
+
+/// VAT categories of catalogue items with the German rate (§ 12 UStG).
+pub const TAX_CATEGORIES: &[(&str, f64)] = &[
+    ("standard", 19.0),
+    ("reduced", 7.0),
+    ("zero", 0.0),
+    ("exempt", 0.0),
+];
+
+pub fn tax_category_rate(category: &str) -> Option<f64> {
+    TAX_CATEGORIES
+        .iter()
+        .find(|(name, _)| *name == category)
+        .map(|(_, rate)| *rate)
+}
+
+/// Invoices carry a single VAT rate. Under standard taxation a line's tax
+/// category has to match it; other treatments invoice without German VAT.
+pub fn check_tax_category(invoice: &Invoice, category: &str) -> Result<(), String> {
+    let rate = tax_category_rate(category).ok_or_else(|| format!("Unknown tax category: {}", category))?;
+    if invoice.tax_treatment == "standard" && rate != invoice.tax_rate {
+        return Err(format!(
+            "Tax category '{}' ({} %) does not match the invoice rate of {} %",
+            category, rate, invoice.tax_rate
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct CatalogItem {
+    pub id: String,
+    pub user_id: String,
+    pub item_number: String,
+    pub description: String,
+    pub unit_code: String,
+    pub net_price: f64,
+    pub tax_category: String,
+    /// Revenue account in the user's chart of accounts, e.g. 8400 (SKR03) or 4400 (SKR04).
+    pub revenue_account: Option<String>,
+    pub archived_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+pub struct NewCatalogItem {
+    #[validate(length(min = 1, max = 50))]
+    pub item_number: String,
+    #[validate(length(min = 1, max = 500))]
+    pub description: String,
+    pub unit_code: Option<String>,
+    pub net_price: f64,
+    pub tax_category: Option<String>,
+    #[validate(length(min = 1, max = 10))]
+    pub revenue_account: Option<String>,
+    /// Archived items are left out of searches but stay referenced by invoices.
+    #[serde(default)]
+    pub archived: bool,
+}
+
+impl NewCatalogItem {
+    pub fn check(&self) -> Result<(), String> {
+        if let Some(code) = self.unit_code.as_deref() {
+            if !UNITS.iter().any(|unit| unit.code == code) {
+                return Err(format!("Unknown unit code: {}", code));
+            }
+        }
+        if let Some(category) = self.tax_category.as_deref() {
+            if tax_category_rate(category).is_none() {
+                return Err(format!("Unknown tax category: {}", category));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An invoice line as submitted. Fields left out are taken from the referenced
+/// catalogue item; the line is stored as a copy, so later catalogue changes do
+/// not alter it.
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+pub struct InvoiceLineInput {
+    pub catalog_item_id: Option<String>,
+    #[validate(length(min = 1, max = 500))]
+    pub description: Option<String>,
+    pub quantity: f64,
+    pub unit_code: Option<String>,
+    pub unit_price: Option<f64>,
+    #[validate(range(min = 0.0, max = 100.0))]
+    pub discount_percent: Option<f64>,
+    pub tax_category: Option<String>,
+    #[validate(length(min = 1, max = 10))]
+    pub revenue_account: Option<String>,
+}
+
+impl InvoiceLineInput {
+    pub fn resolve(self, catalog_item: Option<&CatalogItem>) -> Result<NewInvoiceItem, String> {
+        let description = self
+            .description
+            .or_else(|| catalog_item.map(|item| item.description.clone()))
+            .ok_or_else(|| "Description is required".to_string())?;
+        let unit_price = self
+            .unit_price
+            .or_else(|| catalog_item.map(|item| item.net_price))
+            .ok_or_else(|| "Unit price is required".to_string())?;
+
+        let line = NewInvoiceItem {
+            description,
+            quantity: self.quantity,
+            unit_code: self
+                .unit_code
+                .or_else(|| catalog_item.map(|item| item.unit_code.clone())),
+            unit_price,
+            discount_percent: self.discount_percent,
+            catalog_item_id: catalog_item.map(|item| item.id.clone()),
+            tax_category: self
+                .tax_category
+                .or_else(|| catalog_item.map(|item| item.tax_category.clone())),
+            revenue_account: self
+                .revenue_account
+                .or_else(|| catalog_item.and_then(|item| item.revenue_account.clone())),
+        };
+
+        if let Some(category) = line.tax_category.as_deref() {
+            if tax_category_rate(category).is_none() {
+                return Err(format!("Unknown tax category: {}", category));
+            }
+        }
+        line.validate().map_err(|errors| errors.to_string())?;
+        Ok(line)
+    }
+}
+
+impl CatalogItem {
+    pub fn new(user_id: String, item_number: String, description: String, net_price: f64) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            user_id,
+            item_number,
+            description,
+            unit_code: DEFAULT_UNIT_CODE.to_string(),
+            net_price,
+            tax_category: "standard".to_string(),
+            revenue_account: None,
+            archived_at: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    pub fn apply(&mut self, payload: NewCatalogItem) {
+        self.item_number = payload.item_number;
+        self.description = payload.description;
+        self.unit_code = payload.unit_code.unwrap_or_else(|| DEFAULT_UNIT_CODE.to_string());
+        self.net_price = payload.net_price;
+        self.tax_category = payload.tax_category.unwrap_or_else(|| "standard".to_string());
+        self.revenue_account = payload.revenue_account;
+        self.archived_at = match (payload.archived, self.archived_at) {
+            (true, None) => Some(Utc::now()),
+            (true, archived_at) => archived_at,
+            (false, _) => None,
+        };
+        self.updated_at = Utc::now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn catalog_item() -> CatalogItem {
+        let mut item = CatalogItem::new("u1".to_string(), "A-100".to_string(), "Buch".to_string(), 24.0);
+        item.tax_category = "reduced".to_string();
+        item.unit_code = "H87".to_string();
+        item.revenue_account = Some("8300".to_string());
+        item
+    }
+
+    fn line(quantity: f64) -> InvoiceLineInput {
+        InvoiceLineInput {
+            catalog_item_id: None,
+            description: None,
+            quantity,
+            unit_code: None,
+            unit_price: None,
+            discount_percent: None,
+            tax_category: None,
+            revenue_account: None,
+        }
+    }
+
+    #[test]
+    fn catalogue_item_prefills_the_line() {
+        let item = catalog_item();
+        let resolved = line(3.0).resolve(Some(&item)).unwrap();
+
+        assert_eq!(resolved.description, "Buch");
+        assert_eq!(resolved.unit_price, 24.0);
+        assert_eq!(resolved.unit_code.as_deref(), Some("H87"));
+        assert_eq!(resolved.tax_category.as_deref(), Some("reduced"));
+        assert_eq!(resolved.revenue_account.as_deref(), Some("8300"));
+        assert_eq!(resolved.catalog_item_id, Some(item.id));
+        assert_eq!(resolved.total_price(), 72.0);
+    }
+
+    #[test]
+    fn submitted_values_override_the_catalogue() {
+        let mut input = line(1.0);
+        input.unit_price = Some(20.0);
+        input.description = Some("Buch, Mängelexemplar".to_string());
+
+        let resolved = input.resolve(Some(&catalog_item())).unwrap();
+        assert_eq!(resolved.unit_price, 20.0);
+        assert_eq!(resolved.description, "Buch, Mängelexemplar");
+
+        assert!(line(1.0).resolve(None).is_err());
+    }
+
+    #[test]
+    fn tax_category_must_match_a_standard_invoice() {
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let mut invoice = Invoice::new(
+            "u1".to_string(), "c1".to_string(), "RE-1".to_string(), date, date,
+            "EUR".to_string(), 0.0, 19.0, 0.0, 0.0, None,
+        );
+
+        assert!(check_tax_category(&invoice, "standard").is_ok());
+        assert!(check_tax_category(&invoice, "reduced").is_err());
+
+        invoice.tax_treatment = "reverse_charge".to_string();
+        invoice.tax_rate = 0.0;
+        assert!(check_tax_category(&invoice, "reduced").is_ok());
+        assert!(check_tax_category(&invoice, "luxury").is_err());
+    }
+}
@@ -17,6 +17,8 @@ pub struct Client {
     pub vat_number: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub anonymized_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -83,6 +85,8 @@ impl Client {
             vat_number,
             created_at: Utc::now(),
             updated_at: Utc::now(),
+            deleted_at: None,
+            anonymized_at: None,
         }
     }
 }
\ No newline at end of file
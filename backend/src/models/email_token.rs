@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use chrono::{DateTime, Duration, Utc};
+use crate::models::user::User;
+
+/// Verification links stay valid for two days.
+pub const EMAIL_VERIFICATION_HOURS: i64 = 48;
+/// Password reset links stay valid for one hour.
+pub const PASSWORD_RESET_MINUTES: i64 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmailTokenPurpose {
+    VerifyEmail,
+    ResetPassword,
+}
+
+impl EmailTokenPurpose {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EmailTokenPurpose::VerifyEmail => "verify_email",
+            EmailTokenPurpose::ResetPassword => "reset_password",
+        }
+    }
+
+    pub fn lifetime(&self) -> Duration {
+        match self {
+            EmailTokenPurpose::VerifyEmail => Duration::hours(EMAIL_VERIFICATION_HOURS),
+            EmailTokenPurpose::ResetPassword => Duration::minutes(PASSWORD_RESET_MINUTES),
+        }
+    }
+}
+
+/// A token sent by email. The link carries a signed JWT with this row's id;
+/// the row makes the token single-use.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct EmailToken {
+    pub id: String,
+    pub user_id: String,
+    pub purpose: String,
+    /// Address the token was sent to
+    pub email: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+impl EmailToken {
+    pub fn new(user: &User, purpose: EmailTokenPurpose) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4().to_string(),
+            user_id: user.id.clone(),
+            purpose: purpose.as_str().to_string(),
+            email: user.email.clone(),
+            created_at: now,
+            expires_at: now + purpose.lifetime(),
+            used_at: None,
+        }
+    }
+
+    pub fn is_usable(&self, purpose: EmailTokenPurpose, now: DateTime<Utc>) -> bool {
+        self.purpose == purpose.as_str() && self.used_at.is_none() && self.expires_at > now
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EmailTokenRequest {
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PasswordResetRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewPasswordRequest {
+    pub token: String,
+    pub password: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_are_usable_once_for_their_purpose_until_they_expire() {
+        let user = User::new("max@example.com".to_string(), "hash".to_string(), None, None, None, None);
+        let mut token = EmailToken::new(&user, EmailTokenPurpose::ResetPassword);
+        let now = token.created_at;
+
+        assert_eq!(token.expires_at - now, Duration::minutes(PASSWORD_RESET_MINUTES));
+        assert!(token.is_usable(EmailTokenPurpose::ResetPassword, now));
+        assert!(!token.is_usable(EmailTokenPurpose::VerifyEmail, now));
+        assert!(!token.is_usable(EmailTokenPurpose::ResetPassword, token.expires_at));
+
+        token.used_at = Some(now);
+        assert!(!token.is_usable(EmailTokenPurpose::ResetPassword, now));
+    }
+}
@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use chrono::{DateTime, NaiveDate, Utc};
+
+/// Currency used for bookkeeping and all reports.
+pub const BASE_CURRENCY: &str = "EUR";
+
+/// Rates older than this are not used for an invoice; the ECB publishes on
+/// TARGET business days only, so a few days' gap is normal.
+pub const MAX_RATE_AGE_DAYS: i64 = 7;
+
+/// An ECB euro reference rate: `rate` units of `currency` per 1 EUR.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct ExchangeRate {
+    pub user_id: String,
+    pub currency: String,
+    pub rate_date: NaiveDate,
+    pub rate: f64,
+    pub imported_at: DateTime<Utc>,
+}
+
+/// A rate read from an ECB XML file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EcbRate {
+    pub currency: String,
+    pub rate_date: NaiveDate,
+    pub rate: f64,
+}
+
+pub fn is_currency_code(code: &str) -> bool {
+    code.len() == 3 && code.chars().all(|c| c.is_ascii_uppercase())
+}
+
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    for quote in ['"', '\''] {
+        let pattern = format!(" {}={}", name, quote);
+        if let Some(start) = tag.find(&pattern).map(|index| index + pattern.len()) {
+            return tag[start..].split(quote).next();
+        }
+    }
+    None
+}
+
+/// Reads the reference rates from the ECB's `eurofxref-daily.xml`,
+/// `eurofxref-hist-90d.xml` or `eurofxref-hist.xml`. Days are `Cube` elements
+/// with a `time` attribute containing one `Cube` per currency and rate.
+pub fn parse_ecb_xml(xml: &str) -> Result<Vec<EcbRate>, String> {
+    let mut rates = Vec::new();
+    let mut rate_date = None;
+
+    for tag in xml.split("<Cube").skip(1) {
+        let tag = tag.split('>').next().unwrap_or_default();
+
+        if let Some(time) = attribute(tag, "time") {
+            let date = NaiveDate::parse_from_str(time, "%Y-%m-%d")
+                .map_err(|_| format!("Invalid date: {}", time))?;
+            rate_date = Some(date);
+        }
+
+        if let (Some(currency), Some(rate)) = (attribute(tag, "currency"), attribute(tag, "rate")) {
+            let rate_date = rate_date.ok_or_else(|| format!("Rate for {} outside of a dated Cube", currency))?;
+            if !is_currency_code(currency) {
+                return Err(format!("Invalid currency: {}", currency));
+            }
+            let rate: f64 = rate
+                .parse()
+                .ok()
+                .filter(|rate: &f64| *rate > 0.0)
+                .ok_or_else(|| format!("Invalid rate for {}: {}", currency, rate))?;
+
+            rates.push(EcbRate { currency: currency.to_string(), rate_date, rate });
+        }
+    }
+
+    if rates.is_empty() {
+        return Err("No exchange rates found; expected an ECB eurofxref XML file".to_string());
+    }
+    Ok(rates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAILY: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gesmes:Envelope xmlns:gesmes="http://www.gesmes.org/xml/2002-08-01" xmlns="http://www.ecb.int/vocabulary/2002-08-01/eurofxref">
+	<gesmes:subject>Reference rates</gesmes:subject>
+	<gesmes:Sender>
+		<gesmes:name>European Central Bank</gesmes:name>
+	</gesmes:Sender>
+	<Cube>
+		<Cube time='2024-03-01'>
+			<Cube currency='USD' rate='1.0830'/>
+			<Cube currency='JPY' rate='162.37'/>
+		</Cube>
+		<Cube time='2024-02-29'>
+			<Cube currency='USD' rate='1.0813'/>
+		</Cube>
+	</Cube>
+</gesmes:Envelope>"#;
+
+    #[test]
+    fn rates_are_read_per_day() {
+        let rates = parse_ecb_xml(DAILY).unwrap();
+
+        assert_eq!(rates.len(), 3);
+        assert_eq!(rates[0], EcbRate {
+            currency: "USD".to_string(),
+            rate_date: NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+            rate: 1.083,
+        });
+        assert_eq!(rates[1].rate, 162.37);
+        assert_eq!(rates[2].rate_date, NaiveDate::from_ymd_opt(2024, 2, 29).unwrap());
+    }
+
+    #[test]
+    fn invalid_files_are_rejected() {
+        assert!(parse_ecb_xml("<html></html>").is_err());
+        assert!(parse_ecb_xml("<Cube currency='USD' rate='1.08'/>").is_err());
+        assert!(parse_ecb_xml("<Cube time='2024-03-01'><Cube currency='USD' rate='-1'/></Cube>").is_err());
+    }
+}
@@ -0,0 +1,205 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use chrono::{DateTime, Utc, NaiveDate, NaiveTime};
+use validator::Validate;
+use crate::reports::round_cents;
+
+pub const EXPENSE_CATEGORIES: &[&str] = &[
+    "goods",
+    "subcontractors",
+    "personnel",
+    "rent",
+    "telecommunication",
+    "travel",
+    "training",
+    "legal_and_tax_advice",
+    "leasing",
+    "insurance_and_fees",
+    "advertising",
+    "vat_payment",
+    "other",
+];
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct Expense {
+    pub id: String,
+    pub user_id: String,
+    pub vendor: String,
+    pub description: Option<String>,
+    pub expense_date: NaiveDate,
+    pub category: String,
+    pub net_amount: f64,
+    pub tax_rate: f64,
+    pub tax_amount: f64,
+    pub gross_amount: f64,
+    pub tax_treatment: String,
+    /// ISO country code of the supplier, needed for reverse charge
+    pub supplier_country: Option<String>,
+    pub deductible_tax_amount: f64,
+    pub payment_status: String,
+    pub paid_at: Option<DateTime<Utc>>,
+    pub receipt_key: Option<String>,
+    pub receipt_filename: Option<String>,
+    pub receipt_content_type: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+pub struct NewExpense {
+    #[validate(length(min = 1, max = 200))]
+    pub vendor: String,
+    pub description: Option<String>,
+    pub expense_date: NaiveDate,
+    pub category: Option<String>,
+    #[validate(range(min = 0.0))]
+    pub net_amount: f64,
+    #[validate(range(min = 0.0, max = 100.0))]
+    pub tax_rate: Option<f64>,
+    #[validate(range(min = 0.0))]
+    pub tax_amount: Option<f64>,
+    #[validate(range(min = 0.0))]
+    pub deductible_tax_amount: Option<f64>,
+    pub tax_treatment: Option<String>,
+    pub supplier_country: Option<String>,
+    pub payment_status: Option<String>,
+    pub paid_at: Option<DateTime<Utc>>,
+}
+
+impl NewExpense {
+    /// Checks the values the derive cannot express. Returns a message for the client.
+    pub fn check(&self) -> Result<(), String> {
+        if let Some(category) = self.category.as_deref() {
+            if !EXPENSE_CATEGORIES.contains(&category) {
+                return Err(format!("Unknown expense category: {}", category));
+            }
+        }
+        if let Some(treatment) = self.tax_treatment.as_deref() {
+            if !matches!(treatment, "standard" | "reverse_charge") {
+                return Err(format!("Unknown tax treatment: {}", treatment));
+            }
+        }
+        if let Some(country) = self.supplier_country.as_deref() {
+            if country.len() != 2 || !country.chars().all(|c| c.is_ascii_alphabetic()) {
+                return Err(format!("Invalid supplier country: {}", country));
+            }
+        }
+        if let Some(status) = self.payment_status.as_deref() {
+            if !matches!(status, "unpaid" | "paid") {
+                return Err(format!("Unknown payment status: {}", status));
+            }
+        }
+        if let (Some(deductible), Some(tax_amount)) = (self.deductible_tax_amount, self.tax_amount) {
+            if deductible > tax_amount {
+                return Err("Deductible input VAT cannot exceed the VAT amount".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Expense {
+    pub fn new(
+        user_id: String,
+        vendor: String,
+        expense_date: NaiveDate,
+        category: String,
+        net_amount: f64,
+        tax_rate: f64,
+        tax_amount: f64,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            user_id,
+            vendor,
+            description: None,
+            expense_date,
+            category,
+            net_amount,
+            tax_rate,
+            tax_amount,
+            gross_amount: round_cents(net_amount + tax_amount),
+            tax_treatment: "standard".to_string(),
+            supplier_country: None,
+            deductible_tax_amount: tax_amount,
+            payment_status: "unpaid".to_string(),
+            paid_at: None,
+            receipt_key: None,
+            receipt_filename: None,
+            receipt_content_type: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    /// Overwrites the expense with the submitted values, deriving VAT and
+    /// gross amount where they were left out.
+    pub fn apply(&mut self, payload: NewExpense) {
+        let tax_rate = payload.tax_rate.unwrap_or(19.0);
+        let tax_amount = payload
+            .tax_amount
+            .unwrap_or_else(|| round_cents(payload.net_amount * tax_rate / 100.0));
+        let tax_treatment = payload.tax_treatment.unwrap_or_else(|| "standard".to_string());
+
+        self.vendor = payload.vendor;
+        self.description = payload.description;
+        self.expense_date = payload.expense_date;
+        self.category = payload.category.unwrap_or_else(|| "other".to_string());
+        self.net_amount = payload.net_amount;
+        self.tax_rate = tax_rate;
+        self.tax_amount = tax_amount;
+        // Under reverse charge the VAT goes to the tax office, the vendor is paid the net amount.
+        self.gross_amount = if tax_treatment == "reverse_charge" {
+            payload.net_amount
+        } else {
+            round_cents(payload.net_amount + tax_amount)
+        };
+        self.tax_treatment = tax_treatment;
+        self.supplier_country = payload.supplier_country.map(|country| country.to_uppercase());
+        self.deductible_tax_amount = payload.deductible_tax_amount.unwrap_or(tax_amount).min(tax_amount);
+        self.payment_status = payload.payment_status.unwrap_or_else(|| {
+            if payload.paid_at.is_some() { "paid" } else { "unpaid" }.to_string()
+        });
+        self.paid_at = match self.payment_status.as_str() {
+            "paid" => payload
+                .paid_at
+                .or_else(|| Some(payload.expense_date.and_time(NaiveTime::MIN).and_utc())),
+            _ => None,
+        };
+        self.updated_at = Utc::now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(net_amount: f64, tax_amount: f64) -> NewExpense {
+        NewExpense {
+            vendor: "Bürobedarf GmbH".to_string(),
+            description: None,
+            expense_date: NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+            category: None,
+            net_amount,
+            tax_rate: Some(19.0),
+            tax_amount: Some(tax_amount),
+            deductible_tax_amount: None,
+            tax_treatment: None,
+            supplier_country: None,
+            payment_status: None,
+            paid_at: None,
+        }
+    }
+
+    #[test]
+    fn gross_amounts_are_rounded_to_cents() {
+        // 100.10 + 19.02 is 119.11999999999999 in f64
+        let date = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let mut expense = Expense::new("u1".to_string(), "Vendor".to_string(), date, "other".to_string(), 100.10, 19.0, 19.02);
+        assert_eq!(expense.gross_amount, 119.12);
+
+        expense.apply(payload(100.10, 19.02));
+        assert_eq!(expense.gross_amount, 119.12);
+    }
+}
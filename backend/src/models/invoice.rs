@@ -1,7 +1,10 @@
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
-use chrono::{DateTime, Utc, NaiveDate};
+use validator::{Validate, ValidationError};
+use chrono::{DateTime, Days, Utc, NaiveDate};
+use crate::models::allowance_charge::AllowanceCharge;
+use crate::reports::{format_amount_de, format_decimal_de, round_cents};
 
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
 pub struct Invoice {
@@ -12,11 +15,32 @@ pub struct Invoice {
     pub issue_date: NaiveDate,
     pub due_date: NaiveDate,
     pub currency: String,
+    /// ECB reference rate on the issue date (units of `currency` per 1 EUR);
+    /// `None` for EUR invoices.
+    pub exchange_rate: Option<f64>,
+    pub exchange_rate_date: Option<NaiveDate>,
+    /// Taxable amount: sum of the lines less allowances plus charges.
     pub subtotal: f64,
+    pub allowance_total: f64,
+    pub charge_total: f64,
     pub tax_rate: f64,
     pub tax_amount: f64,
     pub total_amount: f64,
+    /// Early-payment discount in percent of the amount due.
+    pub skonto_percent: Option<f64>,
+    /// Days after the issue date within which Skonto may be deducted.
+    pub skonto_days: Option<i64>,
+    /// `standard`, `advance` (Abschlagsrechnung) or `final` (Schlussrechnung).
+    pub billing_type: String,
+    /// Project an advance or final invoice belongs to.
+    pub project_id: Option<String>,
+    /// Advance payments received and deducted on a final invoice, net and VAT.
+    pub advance_deduction_net: f64,
+    pub advance_deduction_tax: f64,
     pub status: String,
+    pub tax_treatment: String,
+    pub invoice_type: String,
+    pub corrected_invoice_id: Option<String>,
     pub notes: Option<String>,
     pub pdf_url: Option<String>,
     pub sent_at: Option<DateTime<Utc>>,
@@ -30,9 +54,17 @@ pub struct InvoiceItem {
     pub id: String,
     pub invoice_id: String,
     pub description: String,
-    pub quantity: i32,
+    pub quantity: f64,
+    /// UN/ECE Recommendation 20 code, see [`UNITS`].
+    pub unit_code: String,
     pub unit_price: f64,
+    pub discount_percent: f64,
     pub total_price: f64,
+    /// Catalogue item the line was prefilled from. The line is a copy and does
+    /// not follow later changes to the catalogue.
+    pub catalog_item_id: Option<String>,
+    pub tax_category: Option<String>,
+    pub revenue_account: Option<String>,
     pub created_at: DateTime<Utc>,
 }
 
@@ -47,11 +79,62 @@ pub struct NewInvoice {
     pub items: Vec<NewInvoiceItem>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Validate)]
 pub struct NewInvoiceItem {
     pub description: String,
-    pub quantity: i32,
+    pub quantity: f64,
+    #[validate(custom = "validate_unit_code")]
+    pub unit_code: Option<String>,
     pub unit_price: f64,
+    #[validate(range(min = 0.0, max = 100.0))]
+    pub discount_percent: Option<f64>,
+    pub catalog_item_id: Option<String>,
+    pub tax_category: Option<String>,
+    pub revenue_account: Option<String>,
+}
+
+/// A unit of measure with its UN/ECE Recommendation 20 code, as XRechnung and
+/// ZUGFeRD expect it.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Unit {
+    pub code: &'static str,
+    pub name: &'static str,
+    /// Abbreviation printed on German invoices.
+    pub symbol: &'static str,
+}
+
+pub const UNITS: &[Unit] = &[
+    Unit { code: "HUR", name: "hour", symbol: "Std." },
+    Unit { code: "DAY", name: "day", symbol: "Tg." },
+    Unit { code: "H87", name: "piece", symbol: "Stk." },
+    Unit { code: "KMT", name: "kilometre", symbol: "km" },
+    Unit { code: "MTK", name: "square metre", symbol: "m²" },
+    Unit { code: "LS", name: "flat rate", symbol: "pauschal" },
+    Unit { code: "C62", name: "unit", symbol: "Einh." },
+];
+
+/// Unit of items created without one, and of items from before units existed.
+pub const DEFAULT_UNIT_CODE: &str = "C62";
+
+fn validate_unit_code(code: &str) -> Result<(), ValidationError> {
+    if UNITS.iter().any(|unit| unit.code == code) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("unknown_unit_code"))
+    }
+}
+
+/// Net amount of a line: quantity times unit price, less the line discount,
+/// each step rounded to cents.
+pub fn line_total(quantity: f64, unit_price: f64, discount_percent: f64) -> f64 {
+    let amount = round_cents(quantity * unit_price);
+    round_cents(amount - round_cents(amount * discount_percent / 100.0))
+}
+
+impl NewInvoiceItem {
+    pub fn total_price(&self) -> f64 {
+        line_total(self.quantity, self.unit_price, self.discount_percent.unwrap_or(0.0))
+    }
 }
 
 impl Invoice {
@@ -76,11 +159,24 @@ impl Invoice {
             issue_date,
             due_date,
             currency,
+            exchange_rate: None,
+            exchange_rate_date: None,
             subtotal,
+            allowance_total: 0.0,
+            charge_total: 0.0,
             tax_rate,
             tax_amount,
             total_amount,
+            skonto_percent: None,
+            skonto_days: None,
+            billing_type: "standard".to_string(),
+            project_id: None,
+            advance_deduction_net: 0.0,
+            advance_deduction_tax: 0.0,
             status: "draft".to_string(),
+            tax_treatment: "standard".to_string(),
+            invoice_type: "invoice".to_string(),
+            corrected_invoice_id: None,
             notes,
             pdf_url: None,
             sent_at: None,
@@ -91,11 +187,182 @@ impl Invoice {
     }
 }
 
+impl Invoice {
+    pub fn is_credit_note(&self) -> bool {
+        self.invoice_type == "credit_note"
+    }
+
+    /// Recomputes the totals from the invoice lines and the document-level
+    /// allowances and charges, whose percentages refer to the sum of the lines.
+    pub fn recalculate(&mut self, items: &[InvoiceItem], allowance_charges: &mut [AllowanceCharge]) {
+        let line_total = round_cents(items.iter().fold(0.0, |sum, item| sum + item.total_price));
+        for allowance_charge in allowance_charges.iter_mut() {
+            allowance_charge.apply_to(line_total);
+        }
+
+        let sum = |allowances: bool| -> f64 {
+            allowance_charges
+                .iter()
+                .filter(|allowance_charge| allowance_charge.is_allowance() == allowances)
+                .fold(0.0, |sum, allowance_charge| sum + allowance_charge.amount)
+        };
+        self.allowance_total = round_cents(sum(true));
+        self.charge_total = round_cents(sum(false));
+
+        self.subtotal = round_cents(line_total - self.allowance_total + self.charge_total);
+        self.tax_amount = round_cents(self.subtotal * self.tax_rate / 100.0);
+        self.total_amount = round_cents(self.subtotal + self.tax_amount);
+        self.updated_at = Utc::now();
+    }
+
+    pub fn is_advance(&self) -> bool {
+        self.billing_type == "advance"
+    }
+
+    /// Net, VAT and gross amount still to be paid and taxed: the totals less
+    /// the advance payments deducted on a final invoice.
+    pub fn amounts_due(&self) -> (f64, f64, f64) {
+        let net = round_cents(self.subtotal - self.advance_deduction_net);
+        let tax = round_cents(self.tax_amount - self.advance_deduction_tax);
+        let gross = round_cents(self.total_amount - self.advance_deduction_net - self.advance_deduction_tax);
+        (net, tax, gross)
+    }
+
+    pub fn amount_due(&self) -> f64 {
+        self.amounts_due().2
+    }
+
+    /// Converts an amount in the invoice currency to EUR at the stored rate.
+    pub fn to_eur(&self, amount: f64) -> f64 {
+        match self.exchange_rate {
+            Some(rate) => round_cents(amount / rate),
+            None => amount,
+        }
+    }
+
+    /// Amounts in EUR and the statement of the VAT in EUR that invoices in
+    /// foreign currencies must carry (§ 14 Abs. 4 Nr. 8, § 16 Abs. 6 UStG).
+    pub fn currency_conversion(&self) -> CurrencyConversion {
+        let statement = match (self.exchange_rate, self.exchange_rate_date) {
+            (Some(rate), Some(rate_date)) => Some(format!(
+                "Umsatzsteuer: {} EUR (1 EUR = {} {}, EZB-Referenzkurs vom {})",
+                format_amount_de(self.to_eur(self.tax_amount)),
+                format_decimal_de(rate, 4),
+                self.currency,
+                rate_date.format("%d.%m.%Y"),
+            )),
+            _ => None,
+        };
+
+        CurrencyConversion {
+            currency: self.currency.clone(),
+            exchange_rate: self.exchange_rate,
+            exchange_rate_date: self.exchange_rate_date,
+            subtotal_eur: self.to_eur(self.subtotal),
+            tax_amount_eur: self.to_eur(self.tax_amount),
+            total_amount_eur: self.to_eur(self.total_amount),
+            statement,
+        }
+    }
+
+    pub fn skonto_due_date(&self) -> Option<NaiveDate> {
+        self.skonto_percent?;
+        self.issue_date.checked_add_days(Days::new(self.skonto_days?.max(0) as u64))
+    }
+
+    /// Skonto on the gross amount due; zero without Skonto terms.
+    pub fn skonto_amount(&self) -> f64 {
+        self.skonto_percent
+            .map_or(0.0, |percent| round_cents(self.amount_due() * percent / 100.0))
+    }
+
+    /// The discount granted with a payment of `amount` on `payment_date`, when
+    /// `already_paid` was received before. A payment within the Skonto period
+    /// that brings the total received to at least the discounted amount
+    /// settles the invoice; the difference to the full amount is the Skonto.
+    pub fn skonto_for_payment(&self, already_paid: f64, amount: f64, payment_date: NaiveDate) -> f64 {
+        let Some(due_date) = self.skonto_due_date() else {
+            return 0.0;
+        };
+        let received = round_cents(already_paid + amount);
+        if payment_date > due_date
+            || received >= self.amount_due()
+            || received < round_cents(self.amount_due() - self.skonto_amount())
+        {
+            return 0.0;
+        }
+        round_cents(self.amount_due() - received)
+    }
+
+    /// Payment terms as printed on the invoice, with the Skonto amount and the
+    /// amount payable after deduction (§ 14 Abs. 4 Nr. 7 UStG).
+    pub fn payment_terms(&self) -> PaymentTerms {
+        let due = self.due_date.format("%d.%m.%Y");
+        let skonto_due_date = self.skonto_due_date();
+
+        let (text, xrechnung) = match (self.skonto_percent, skonto_due_date) {
+            (Some(percent), Some(skonto_due)) => (
+                format!(
+                    "Zahlbar bis {} abzüglich {} % Skonto ({} {}), Zahlbetrag {} {}, oder bis {} ohne Abzug.",
+                    skonto_due.format("%d.%m.%Y"),
+                    format_amount_de(percent),
+                    format_amount_de(self.skonto_amount()),
+                    self.currency,
+                    format_amount_de(self.amount_due() - self.skonto_amount()),
+                    self.currency,
+                    due,
+                ),
+                // Payment terms syntax of XRechnung (BT-20)
+                Some(format!(
+                    "#SKONTO#TAGE={}#PROZENT={:.2}#",
+                    self.skonto_days.unwrap_or_default(),
+                    percent
+                )),
+            ),
+            _ => (format!("Zahlbar ohne Abzug bis {}.", due), None),
+        };
+
+        PaymentTerms {
+            due_date: self.due_date,
+            skonto_percent: self.skonto_percent,
+            skonto_days: self.skonto_days,
+            skonto_due_date,
+            skonto_amount: self.skonto_amount(),
+            discounted_amount: round_cents(self.amount_due() - self.skonto_amount()),
+            text,
+            xrechnung,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CurrencyConversion {
+    pub currency: String,
+    pub exchange_rate: Option<f64>,
+    pub exchange_rate_date: Option<NaiveDate>,
+    pub subtotal_eur: f64,
+    pub tax_amount_eur: f64,
+    pub total_amount_eur: f64,
+    pub statement: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PaymentTerms {
+    pub due_date: NaiveDate,
+    pub skonto_percent: Option<f64>,
+    pub skonto_days: Option<i64>,
+    pub skonto_due_date: Option<NaiveDate>,
+    pub skonto_amount: f64,
+    pub discounted_amount: f64,
+    pub text: String,
+    pub xrechnung: Option<String>,
+}
+
 impl InvoiceItem {
     pub fn new(
         invoice_id: String,
         description: String,
-        quantity: i32,
+        quantity: f64,
         unit_price: f64,
         total_price: f64,
     ) -> Self {
@@ -104,9 +371,131 @@ impl InvoiceItem {
             invoice_id,
             description,
             quantity,
+            unit_code: DEFAULT_UNIT_CODE.to_string(),
             unit_price,
+            discount_percent: 0.0,
             total_price,
+            catalog_item_id: None,
+            tax_category: None,
+            revenue_account: None,
             created_at: Utc::now(),
         }
     }
-}
\ No newline at end of file
+
+    pub fn from_new(invoice_id: String, item: &NewInvoiceItem) -> Self {
+        let mut line = Self::new(
+            invoice_id,
+            item.description.clone(),
+            item.quantity,
+            item.unit_price,
+            item.total_price(),
+        );
+        if let Some(unit_code) = &item.unit_code {
+            line.unit_code = unit_code.clone();
+        }
+        line.discount_percent = item.discount_percent.unwrap_or(0.0);
+        line.catalog_item_id = item.catalog_item_id.clone();
+        line.tax_category = item.tax_category.clone();
+        line.revenue_account = item.revenue_account.clone();
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fractional_quantities_are_rounded_to_cents() {
+        assert_eq!(line_total(7.5, 85.0, 0.0), 637.5);
+        assert_eq!(line_total(2.25, 19.99, 0.0), 44.98);
+        assert_eq!(line_total(1.0, 1.005, 0.0), 1.01);
+    }
+
+    #[test]
+    fn discount_is_taken_from_the_rounded_line_amount() {
+        // 44.98 less 10 % (4.50)
+        assert_eq!(line_total(2.25, 19.99, 10.0), 40.48);
+        assert_eq!(line_total(3.0, 100.0, 100.0), 0.0);
+    }
+
+    fn invoice() -> Invoice {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let due = NaiveDate::from_ymd_opt(2024, 3, 31).unwrap();
+        Invoice::new(
+            "u1".to_string(), "c1".to_string(), "RE-1".to_string(), date, due,
+            "EUR".to_string(), 0.0, 19.0, 0.0, 0.0, None,
+        )
+    }
+
+    #[test]
+    fn allowances_and_charges_change_the_taxable_amount() {
+        let mut invoice = invoice();
+        let items = vec![
+            InvoiceItem::new(invoice.id.clone(), "Beratung".to_string(), 10.0, 95.0, 950.0),
+            InvoiceItem::new(invoice.id.clone(), "Workshop".to_string(), 1.0, 50.0, 50.0),
+        ];
+        let new = |kind: &str, percent: Option<f64>, amount: Option<f64>| {
+            AllowanceCharge::new(invoice.id.clone(), crate::models::allowance_charge::NewAllowanceCharge {
+                kind: kind.to_string(),
+                reason: "Test".to_string(),
+                percent,
+                amount,
+                tax_category: None,
+            })
+        };
+        let mut allowance_charges = vec![new("allowance", Some(10.0), None), new("charge", None, Some(25.0))];
+
+        invoice.recalculate(&items, &mut allowance_charges);
+
+        assert_eq!(allowance_charges[0].amount, 100.0);
+        assert_eq!(invoice.allowance_total, 100.0);
+        assert_eq!(invoice.charge_total, 25.0);
+        assert_eq!(invoice.subtotal, 925.0);
+        assert_eq!(invoice.tax_amount, 175.75);
+        assert_eq!(invoice.total_amount, 1100.75);
+    }
+
+    #[test]
+    fn payment_within_skonto_period_settles_the_invoice() {
+        let mut invoice = invoice();
+        invoice.total_amount = 595.0;
+        invoice.skonto_percent = Some(3.0);
+        invoice.skonto_days = Some(10);
+        let in_time = NaiveDate::from_ymd_opt(2024, 3, 11).unwrap();
+        let late = NaiveDate::from_ymd_opt(2024, 3, 12).unwrap();
+
+        assert_eq!(invoice.skonto_amount(), 17.85);
+        assert_eq!(invoice.skonto_for_payment(0.0, 577.15, in_time), 17.85);
+        assert_eq!(invoice.skonto_for_payment(0.0, 577.15, late), 0.0);
+        assert_eq!(invoice.skonto_for_payment(0.0, 500.0, in_time), 0.0);
+        assert_eq!(invoice.skonto_for_payment(300.0, 280.0, in_time), 15.0);
+        assert_eq!(invoice.skonto_for_payment(0.0, 595.0, in_time), 0.0);
+
+        let terms = invoice.payment_terms();
+        assert_eq!(terms.xrechnung.as_deref(), Some("#SKONTO#TAGE=10#PROZENT=3.00#"));
+        assert!(terms.text.contains("11.03.2024 abzüglich 3,00 % Skonto (17,85 EUR), Zahlbetrag 577,15 EUR"));
+    }
+
+    #[test]
+    fn foreign_currency_amounts_are_converted_at_the_stored_rate() {
+        let mut invoice = invoice();
+        invoice.currency = "USD".to_string();
+        invoice.subtotal = 1000.0;
+        invoice.tax_amount = 190.0;
+        invoice.total_amount = 1190.0;
+        invoice.exchange_rate = Some(1.083);
+        invoice.exchange_rate_date = NaiveDate::from_ymd_opt(2024, 3, 1);
+
+        let conversion = invoice.currency_conversion();
+        assert_eq!(conversion.subtotal_eur, 923.36);
+        assert_eq!(conversion.tax_amount_eur, 175.44);
+        assert_eq!(
+            conversion.statement.as_deref(),
+            Some("Umsatzsteuer: 175,44 EUR (1 EUR = 1,0830 USD, EZB-Referenzkurs vom 01.03.2024)")
+        );
+
+        invoice.exchange_rate = None;
+        assert_eq!(invoice.to_eur(190.0), 190.0);
+    }
+}
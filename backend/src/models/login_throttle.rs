@@ -0,0 +1,120 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use chrono::{DateTime, Duration, Utc};
+
+/// Failures older than this no longer count.
+pub const FAILURE_WINDOW_MINUTES: i64 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThrottleScope {
+    Account,
+    Ip,
+    /// Requests for mailed links per address; every request counts
+    MailAccount,
+    MailIp,
+}
+
+impl ThrottleScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ThrottleScope::Account => "account",
+            ThrottleScope::Ip => "ip",
+            ThrottleScope::MailAccount => "mail_account",
+            ThrottleScope::MailIp => "mail_ip",
+        }
+    }
+
+    /// Accounts are locked sooner; one address may be shared by a whole office.
+    pub fn policy(&self) -> ThrottlePolicy {
+        match self {
+            ThrottleScope::Account => ThrottlePolicy { free_failures: 3, lockout_failures: 10, lockout_minutes: 15 },
+            ThrottleScope::Ip => ThrottlePolicy { free_failures: 10, lockout_failures: 50, lockout_minutes: 15 },
+            ThrottleScope::MailAccount => ThrottlePolicy { free_failures: 3, lockout_failures: 5, lockout_minutes: 60 },
+            ThrottleScope::MailIp => ThrottlePolicy { free_failures: 10, lockout_failures: 30, lockout_minutes: 60 },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThrottlePolicy {
+    /// Failures without delay
+    pub free_failures: i64,
+    /// Failures that lock the key for `lockout_minutes`
+    pub lockout_failures: i64,
+    pub lockout_minutes: i64,
+}
+
+impl ThrottlePolicy {
+    /// Time to wait after the given number of consecutive failures: nothing
+    /// for the first few, then 2, 4, 8, ... seconds, then the lockout.
+    pub fn delay(&self, failures: i64) -> Option<Duration> {
+        let lockout = Duration::minutes(self.lockout_minutes);
+        if failures >= self.lockout_failures {
+            return Some(lockout);
+        }
+        if failures <= self.free_failures {
+            return None;
+        }
+        let exponent = (failures - self.free_failures).min(20) as u32;
+        Some(Duration::seconds(2i64.pow(exponent)).min(lockout))
+    }
+}
+
+/// Failed login attempts of one email address or IP address.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct LoginThrottle {
+    pub scope: String,
+    pub key: String,
+    pub failures: i64,
+    pub last_failure_at: DateTime<Utc>,
+    pub locked_until: Option<DateTime<Utc>>,
+}
+
+impl LoginThrottle {
+    /// Whole seconds until the next attempt is allowed, if it is not yet.
+    pub fn retry_after(&self, now: DateTime<Utc>) -> Option<i64> {
+        self.locked_until
+            .filter(|until| *until > now)
+            .map(|until| ((until - now).num_milliseconds() + 999) / 1000)
+    }
+}
+
+/// Throttle key of an email address; case and surrounding spaces are ignored.
+pub fn account_key(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delays_grow_exponentially_until_the_lockout() {
+        let policy = ThrottleScope::Account.policy();
+        let delays: Vec<Option<i64>> = (1..=11).map(|failures| policy.delay(failures).map(|d| d.num_seconds())).collect();
+
+        assert_eq!(
+            delays,
+            vec![None, None, None, Some(2), Some(4), Some(8), Some(16), Some(32), Some(64), Some(900), Some(900)]
+        );
+        assert_eq!(ThrottleScope::Ip.policy().delay(10), None);
+    }
+
+    #[test]
+    fn retry_after_rounds_up_and_ends_with_the_lock() {
+        let now = Utc::now();
+        let mut throttle = LoginThrottle {
+            scope: "account".to_string(),
+            key: account_key(" Max@Example.com "),
+            failures: 4,
+            last_failure_at: now,
+            locked_until: Some(now + Duration::milliseconds(1500)),
+        };
+
+        assert_eq!(throttle.key, "max@example.com");
+        assert_eq!(throttle.retry_after(now), Some(2));
+        assert_eq!(throttle.retry_after(now + Duration::seconds(2)), None);
+        throttle.locked_until = None;
+        assert_eq!(throttle.retry_after(now), None);
+    }
+}
@@ -0,0 +1,192 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use chrono::{DateTime, Duration, Utc};
+use sha2::{Digest, Sha256};
+
+/// Invitation links stay valid for a week.
+pub const INVITATION_DAYS: i64 = 7;
+
+/// Role of an account in an organisation. The account holder is always owner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Owner,
+    Accountant,
+    Member,
+    ReadOnly,
+}
+
+/// What a request may do in an organisation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    /// Read clients, invoices, expenses, time entries and the catalogue
+    View,
+    /// Create and change business records, delete drafts and own entries
+    Edit,
+    /// Delete clients and handle their DSGVO requests
+    DeleteClients,
+    /// Tax reports and exports
+    Reports,
+    /// Audit log and retention overview
+    AuditLog,
+    /// Legal hold
+    ManageRetention,
+    /// Invitations, memberships and tax advisor access
+    ManageTeam,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Owner => "owner",
+            Role::Accountant => "accountant",
+            Role::Member => "member",
+            Role::ReadOnly => "read_only",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        [Role::Owner, Role::Accountant, Role::Member, Role::ReadOnly]
+            .into_iter()
+            .find(|role| role.as_str() == value)
+    }
+
+    /// Accountants prepare the books and reports but do not delete clients or
+    /// manage the team; members do the daily work; read-only users look.
+    pub fn allows(&self, permission: Permission) -> bool {
+        match self {
+            Role::Owner => true,
+            Role::Accountant => matches!(
+                permission,
+                Permission::View | Permission::Edit | Permission::Reports | Permission::AuditLog
+            ),
+            Role::Member => matches!(permission, Permission::View | Permission::Edit),
+            Role::ReadOnly => permission == Permission::View,
+        }
+    }
+}
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct Membership {
+    pub organisation_id: String,
+    pub user_id: String,
+    pub role: String,
+    pub invited_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A member of the current organisation for the member list.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct TeamMember {
+    pub user_id: String,
+    pub email: String,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub role: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// An organisation the current user can work in.
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct OrganisationAccess {
+    pub organisation_id: String,
+    pub name: String,
+    pub role: String,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct Invitation {
+    pub id: String,
+    pub organisation_id: String,
+    pub email: String,
+    pub role: String,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub invited_by: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub accepted_at: Option<DateTime<Utc>>,
+}
+
+impl Invitation {
+    /// A new invitation with the token for its link. Only the token's hash is
+    /// stored.
+    pub fn new(organisation_id: String, email: &str, role: Role, invited_by: String) -> (Self, String) {
+        let token = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+        let now = Utc::now();
+        let invitation = Self {
+            id: Uuid::new_v4().to_string(),
+            organisation_id,
+            email: email.trim().to_lowercase(),
+            role: role.as_str().to_string(),
+            token_hash: hash_invitation_token(&token),
+            invited_by,
+            created_at: now,
+            expires_at: now + Duration::days(INVITATION_DAYS),
+            accepted_at: None,
+        };
+        (invitation, token)
+    }
+
+    pub fn is_open(&self, now: DateTime<Utc>) -> bool {
+        self.accepted_at.is_none() && self.expires_at > now
+    }
+
+    /// Invitations are for one address; case does not matter.
+    pub fn is_for(&self, email: &str) -> bool {
+        self.email == email.trim().to_lowercase()
+    }
+}
+
+pub fn hash_invitation_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewInvitation {
+    pub email: String,
+    pub role: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AcceptInvitation {
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RoleUpdate {
+    pub role: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accountants_export_reports_but_do_not_delete_clients() {
+        assert!(Role::Accountant.allows(Permission::Reports));
+        assert!(Role::Accountant.allows(Permission::Edit));
+        assert!(!Role::Accountant.allows(Permission::DeleteClients));
+        assert!(!Role::Accountant.allows(Permission::ManageTeam));
+
+        assert!(Role::Member.allows(Permission::Edit));
+        assert!(!Role::Member.allows(Permission::Reports));
+        assert!(Role::ReadOnly.allows(Permission::View));
+        assert!(!Role::ReadOnly.allows(Permission::Edit));
+        assert!(Role::Owner.allows(Permission::ManageTeam));
+
+        assert_eq!(Role::parse("read_only"), Some(Role::ReadOnly));
+        assert_eq!(Role::parse("admin"), None);
+    }
+
+    #[test]
+    fn invitations_are_for_one_address_and_expire() {
+        let (invitation, token) = Invitation::new("org".to_string(), " Tax@Advisor.de ", Role::Accountant, "u1".to_string());
+
+        assert_eq!(invitation.token_hash, hash_invitation_token(&token));
+        assert!(invitation.is_for("tax@advisor.de"));
+        assert!(!invitation.is_for("other@advisor.de"));
+        assert!(invitation.is_open(Utc::now()));
+        assert!(!invitation.is_open(invitation.expires_at));
+    }
+}
@@ -1,4 +1,21 @@
 pub mod user;
 pub mod client;
 pub mod invoice;
-pub mod settings;
\ No newline at end of file
+pub mod settings;
+pub mod expense;
+pub mod payment;
+pub mod audit;
+pub mod project;
+pub mod time_entry;
+pub mod catalog;
+pub mod allowance_charge;
+pub mod exchange_rate;
+pub mod advance_deduction;
+pub mod session;
+pub mod two_factor;
+pub mod email_token;
+pub mod login_throttle;
+pub mod api_key;
+pub mod membership;
+pub mod advisor_grant;
+pub mod identity;
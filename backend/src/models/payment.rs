@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use chrono::{DateTime, Utc, NaiveDate};
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct Payment {
+    pub id: String,
+    pub invoice_id: String,
+    pub amount: f64,
+    pub payment_date: NaiveDate,
+    pub method: Option<String>,
+    /// Skonto deducted by the customer when this payment settled the invoice.
+    pub skonto_amount: f64,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewPayment {
+    pub amount: f64,
+    pub payment_date: NaiveDate,
+    pub method: Option<String>,
+}
+
+impl Payment {
+    pub fn new(
+        invoice_id: String,
+        amount: f64,
+        payment_date: NaiveDate,
+        method: Option<String>,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            invoice_id,
+            amount,
+            payment_date,
+            method,
+            skonto_amount: 0.0,
+            created_at: Utc::now(),
+        }
+    }
+}
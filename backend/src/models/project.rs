@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use validator::Validate;
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct Project {
+    pub id: String,
+    pub user_id: String,
+    pub client_id: String,
+    pub name: String,
+    pub hourly_rate: f64,
+    pub archived_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+pub struct NewProject {
+    pub client_id: String,
+    #[validate(length(min = 1, max = 200))]
+    pub name: String,
+    #[validate(range(min = 0.0))]
+    pub hourly_rate: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+pub struct UpdateProject {
+    #[validate(length(min = 1, max = 200))]
+    pub name: String,
+    #[validate(range(min = 0.0))]
+    pub hourly_rate: f64,
+    /// Archived projects are hidden from timers but can still be billed.
+    #[serde(default)]
+    pub archived: bool,
+}
+
+impl Project {
+    pub fn new(user_id: String, client_id: String, name: String, hourly_rate: f64) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            user_id,
+            client_id,
+            name,
+            hourly_rate,
+            archived_at: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    pub fn apply(&mut self, payload: UpdateProject) {
+        self.name = payload.name;
+        self.hourly_rate = payload.hourly_rate;
+        self.archived_at = match (payload.archived, self.archived_at) {
+            (true, None) => Some(Utc::now()),
+            (true, archived_at) => archived_at,
+            (false, _) => None,
+        };
+        self.updated_at = Utc::now();
+    }
+}
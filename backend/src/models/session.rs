@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use chrono::{DateTime, Duration, Utc};
+use sha2::{Digest, Sha256};
+
+/// Sessions end after this many days without a refresh.
+pub const SESSION_IDLE_DAYS: i64 = 30;
+const MAX_DEVICE_LABEL_LENGTH: usize = 200;
+
+/// A login on one device. The refresh tokens issued for it form one family:
+/// each refresh replaces the token, and presenting a replaced token again
+/// revokes the session.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct Session {
+    pub id: String,
+    pub user_id: String,
+    pub device_label: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub revoked_reason: Option<String>,
+}
+
+/// Why a refresh token was not accepted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RefreshError {
+    /// Unknown token, or the session was revoked or has expired.
+    Invalid,
+    /// The token was already replaced; the session has to be revoked.
+    Reused,
+}
+
+impl Session {
+    pub fn new(user_id: String, device_label: Option<String>, ip_address: Option<String>) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4().to_string(),
+            user_id,
+            device_label: device_label
+                .map(|label| label.trim().chars().take(MAX_DEVICE_LABEL_LENGTH).collect::<String>())
+                .filter(|label| !label.is_empty()),
+            ip_address,
+            created_at: now,
+            last_used_at: now,
+            expires_at: now + Duration::days(SESSION_IDLE_DAYS),
+            revoked_at: None,
+            revoked_reason: None,
+        }
+    }
+
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.revoked_at.is_none() && self.expires_at > now
+    }
+
+    /// Checks a refresh token of this session that was replaced at `used_at`,
+    /// if it was.
+    pub fn check_refresh(&self, used_at: Option<DateTime<Utc>>, now: DateTime<Utc>) -> Result<(), RefreshError> {
+        if !self.is_active(now) {
+            return Err(RefreshError::Invalid);
+        }
+        if used_at.is_some() {
+            return Err(RefreshError::Reused);
+        }
+        Ok(())
+    }
+}
+
+/// A new random refresh token. Only its hash is stored.
+pub fn new_refresh_token() -> String {
+    format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+pub fn hash_refresh_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaced_tokens_are_reported_as_reuse() {
+        let session = Session::new("u1".to_string(), Some("  Firefox on Linux ".to_string()), None);
+        let now = Utc::now();
+
+        assert_eq!(session.device_label.as_deref(), Some("Firefox on Linux"));
+        assert_eq!(session.check_refresh(None, now), Ok(()));
+        assert_eq!(session.check_refresh(Some(now), now), Err(RefreshError::Reused));
+    }
+
+    #[test]
+    fn revoked_and_expired_sessions_reject_all_tokens() {
+        let mut session = Session::new("u1".to_string(), None, None);
+        let later = session.expires_at + Duration::seconds(1);
+        assert_eq!(session.check_refresh(None, later), Err(RefreshError::Invalid));
+
+        session.revoked_at = Some(Utc::now());
+        assert_eq!(session.check_refresh(Some(Utc::now()), Utc::now()), Err(RefreshError::Invalid));
+    }
+
+    #[test]
+    fn refresh_tokens_are_random_and_stored_as_hashes() {
+        let token = new_refresh_token();
+
+        assert_eq!(token.len(), 64);
+        assert_ne!(token, new_refresh_token());
+        assert_eq!(hash_refresh_token(&token), hash_refresh_token(&token));
+        assert_ne!(hash_refresh_token(&token), token);
+    }
+}
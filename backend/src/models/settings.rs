@@ -11,6 +11,7 @@ pub struct UserSettings {
     pub next_invoice_number: i32,
     pub company_logo_url: Option<String>,
     pub payment_terms_days: i32,
+    pub taxation_method: String,
     pub updated_at: DateTime<Utc>,
 }
 
@@ -23,6 +24,7 @@ pub struct NewUserSettings {
     pub next_invoice_number: Option<i32>,
     pub company_logo_url: Option<String>,
     pub payment_terms_days: Option<i32>,
+    pub taxation_method: Option<String>,
 }
 
 impl UserSettings {
@@ -35,6 +37,7 @@ impl UserSettings {
             next_invoice_number: 1,
             company_logo_url: None,
             payment_terms_days: 14,
+            taxation_method: "soll".to_string(),
             updated_at: Utc::now(),
         }
     }
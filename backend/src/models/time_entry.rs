@@ -0,0 +1,222 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use chrono::{DateTime, NaiveDate, Utc};
+use validator::Validate;
+use crate::models::invoice::NewInvoiceItem;
+use crate::models::project::Project;
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct TimeEntry {
+    pub id: String,
+    pub user_id: String,
+    pub project_id: String,
+    pub task: Option<String>,
+    pub description: Option<String>,
+    pub started_at: DateTime<Utc>,
+    /// `None` while the timer is running.
+    pub ended_at: Option<DateTime<Utc>>,
+    pub billable: bool,
+    pub invoice_id: Option<String>,
+    pub billed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A manually entered period of work.
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+pub struct NewTimeEntry {
+    pub project_id: String,
+    #[validate(length(max = 200))]
+    pub task: Option<String>,
+    pub description: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    pub billable: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+pub struct StartTimer {
+    pub project_id: String,
+    #[validate(length(max = 200))]
+    pub task: Option<String>,
+    pub description: Option<String>,
+    pub billable: Option<bool>,
+}
+
+impl NewTimeEntry {
+    pub fn check(&self) -> Result<(), String> {
+        if self.ended_at <= self.started_at {
+            return Err("A time entry must end after it starts".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl TimeEntry {
+    pub fn new(user_id: String, project_id: String, started_at: DateTime<Utc>) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            user_id,
+            project_id,
+            task: None,
+            description: None,
+            started_at,
+            ended_at: None,
+            billable: true,
+            invoice_id: None,
+            billed_at: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    pub fn apply(&mut self, payload: NewTimeEntry) {
+        self.project_id = payload.project_id;
+        self.task = payload.task;
+        self.description = payload.description;
+        self.started_at = payload.started_at;
+        self.ended_at = Some(payload.ended_at);
+        self.billable = payload.billable.unwrap_or(true);
+        self.updated_at = Utc::now();
+    }
+
+    /// Whole minutes worked; a running timer counts as zero.
+    pub fn minutes(&self) -> i64 {
+        self.ended_at
+            .map_or(0, |ended_at| (ended_at - self.started_at).num_minutes())
+    }
+
+    pub fn is_billed(&self) -> bool {
+        self.billed_at.is_some()
+    }
+}
+
+/// Unbilled time of one project and task, billed as one invoice item.
+#[derive(Debug, Clone, Serialize)]
+pub struct TimeBillingGroup {
+    pub project_id: String,
+    pub task: Option<String>,
+    pub minutes: i64,
+    pub item: NewInvoiceItem,
+    pub entry_ids: Vec<String>,
+}
+
+type GroupKey<'a> = (&'a str, &'a str, Option<&'a str>);
+
+/// Minutes as hours with two decimals, the precision hours are billed in.
+fn hours(minutes: i64) -> f64 {
+    (minutes as f64 / 60.0 * 100.0).round() / 100.0
+}
+
+/// Groups the billable, finished and unbilled entries started between `from`
+/// and `to` (inclusive) by project and task, billed in hours at the project's
+/// rate. Entries of projects not in `projects` are left out.
+pub fn group_for_billing(
+    projects: &[Project],
+    entries: &[TimeEntry],
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Vec<TimeBillingGroup> {
+    // Keyed by project name, project id and task, holding minutes and entry ids
+    let mut groups: BTreeMap<GroupKey, (&Project, i64, Vec<String>)> = BTreeMap::new();
+
+    for entry in entries {
+        let date = entry.started_at.date_naive();
+        if !entry.billable || entry.is_billed() || entry.ended_at.is_none() || date < from || date > to {
+            continue;
+        }
+        let Some(project) = projects.iter().find(|project| project.id == entry.project_id) else {
+            continue;
+        };
+
+        let group = groups
+            .entry((project.name.as_str(), project.id.as_str(), entry.task.as_deref()))
+            .or_insert((project, 0, Vec::new()));
+        group.1 += entry.minutes();
+        group.2.push(entry.id.clone());
+    }
+
+    groups
+        .into_iter()
+        .map(|((_, _, task), (project, minutes, entry_ids))| {
+            let description = match task {
+                Some(task) => format!("{} – {}", project.name, task),
+                None => project.name.clone(),
+            };
+
+            TimeBillingGroup {
+                project_id: project.id.clone(),
+                task: task.map(str::to_string),
+                minutes,
+                item: NewInvoiceItem {
+                    description,
+                    quantity: hours(minutes),
+                    unit_code: Some("HUR".to_string()),
+                    unit_price: project.hourly_rate,
+                    ..Default::default()
+                },
+                entry_ids,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn entry(project_id: &str, task: Option<&str>, day: u32, hours: i64) -> TimeEntry {
+        let started_at = Utc.with_ymd_and_hms(2024, 3, day, 9, 0, 0).unwrap();
+        let mut entry = TimeEntry::new("u1".to_string(), project_id.to_string(), started_at);
+        entry.task = task.map(str::to_string);
+        entry.ended_at = Some(started_at + chrono::Duration::minutes(hours * 60 + 30));
+        entry
+    }
+
+    #[test]
+    fn entries_are_grouped_by_project_and_task() {
+        let mut web = Project::new("u1".to_string(), "c1".to_string(), "Website".to_string(), 80.0);
+        web.id = "p1".to_string();
+        let projects = vec![web];
+        let entries = vec![
+            entry("p1", Some("Design"), 4, 2),
+            entry("p1", Some("Design"), 5, 1),
+            entry("p1", None, 6, 3),
+        ];
+        let from = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let to = NaiveDate::from_ymd_opt(2024, 3, 31).unwrap();
+
+        let groups = group_for_billing(&projects, &entries, from, to);
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].task, None);
+        assert_eq!(groups[0].minutes, 210);
+        assert_eq!(groups[0].item.quantity, 3.5);
+        assert_eq!(groups[1].minutes, 240);
+        assert_eq!(groups[1].entry_ids.len(), 2);
+        assert_eq!(groups[1].item.description, "Website – Design");
+        assert_eq!(groups[1].item.unit_code.as_deref(), Some("HUR"));
+        assert_eq!(groups[1].item.total_price(), 320.0);
+    }
+
+    #[test]
+    fn billed_running_and_out_of_period_entries_are_skipped() {
+        let mut project = Project::new("u1".to_string(), "c1".to_string(), "Support".to_string(), 60.0);
+        project.id = "p1".to_string();
+        let mut billed = entry("p1", None, 4, 1);
+        billed.billed_at = Some(Utc::now());
+        let mut running = entry("p1", None, 5, 1);
+        running.ended_at = None;
+        let mut unbillable = entry("p1", None, 6, 1);
+        unbillable.billable = false;
+        let entries = vec![billed, running, unbillable, entry("p1", None, 20, 1), entry("p2", None, 7, 1)];
+        let from = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let to = NaiveDate::from_ymd_opt(2024, 3, 15).unwrap();
+
+        assert!(group_for_billing(&[project], &entries, from, to).is_empty());
+    }
+}
@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use crate::auth::totp::base32_encode;
+
+/// Recovery codes issued at once; issuing new ones replaces all old codes.
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+/// A one-time code to log in without the authenticator app.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct RecoveryCode {
+    pub id: String,
+    pub user_id: String,
+    #[serde(skip_serializing)]
+    pub code_hash: String,
+    pub used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl RecoveryCode {
+    pub fn new(user_id: String, code: &str) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            user_id,
+            code_hash: hash_recovery_code(code),
+            used_at: None,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+/// New random recovery codes like `k7q2m-x4wpa`, 50 bits each.
+pub fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut bytes = [0u8; 7];
+            rand::rngs::OsRng.fill_bytes(&mut bytes);
+            let code = base32_encode(&bytes)[..10].to_lowercase();
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect()
+}
+
+/// Hash of a recovery code; case, spaces and dashes are ignored.
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    hex::encode(Sha256::digest(normalized.as_bytes()))
+}
+
+/// Two-factor settings of the current user.
+#[derive(Debug, Clone, Serialize)]
+pub struct TwoFactorStatus {
+    pub enabled: bool,
+    pub enabled_at: Option<DateTime<Utc>>,
+    pub required: bool,
+    pub recovery_codes_left: i64,
+}
+
+/// Secret and `otpauth://` URI for the authenticator app, shown once at
+/// enrolment. The frontend renders the URI as QR code.
+#[derive(Debug, Clone, Serialize)]
+pub struct TwoFactorEnrolment {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TwoFactorCode {
+    pub code: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TwoFactorRequirement {
+    pub required: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recovery_codes_are_unique_and_hashed_leniently() {
+        let codes = generate_recovery_codes();
+
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        assert_eq!(codes[0].len(), 11);
+        assert_ne!(codes[0], codes[1]);
+        assert_eq!(hash_recovery_code("k7q2m-x4wpa"), hash_recovery_code(" K7Q2M X4WPA "));
+        assert_ne!(hash_recovery_code("k7q2m-x4wpa"), hash_recovery_code("k7q2m-x4wpb"));
+    }
+}
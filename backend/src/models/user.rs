@@ -1,7 +1,10 @@
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
+
+/// Applies to registration and password reset.
+pub const MIN_PASSWORD_LENGTH: usize = 8;
 
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
 pub struct User {
@@ -15,36 +18,16 @@ pub struct User {
     pub tax_id: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct NewUser {
-    pub email: String,
-    pub password: String,
-    pub first_name: Option<String>,
-    pub last_name: Option<String>,
-    pub company_name: Option<String>,
-    pub tax_id: Option<String>,
-}
-
-impl NewUser {
-    pub fn new(
-        email: String,
-        password: String,
-        first_name: Option<String>,
-        last_name: Option<String>,
-        company_name: Option<String>,
-        tax_id: Option<String>,
-    ) -> Self {
-        Self {
-            email,
-            password,
-            first_name,
-            last_name,
-            company_name,
-            tax_id,
-        }
-    }
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub anonymized_at: Option<DateTime<Utc>>,
+    pub legal_hold_until: Option<NaiveDate>,
+    #[serde(skip_serializing)]
+    pub totp_secret: Option<String>,
+    pub totp_enabled_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing)]
+    pub totp_last_step: Option<i64>,
+    pub two_factor_required: bool,
+    pub email_verified_at: Option<DateTime<Utc>>,
 }
 
 impl User {
@@ -66,6 +49,18 @@ impl User {
             tax_id,
             created_at: Utc::now(),
             updated_at: Utc::now(),
+            deleted_at: None,
+            anonymized_at: None,
+            legal_hold_until: None,
+            totp_secret: None,
+            totp_enabled_at: None,
+            totp_last_step: None,
+            two_factor_required: false,
+            email_verified_at: None,
         }
     }
-}
\ No newline at end of file
+
+    pub fn two_factor_enabled(&self) -> bool {
+        self.totp_enabled_at.is_some()
+    }
+}
@@ -0,0 +1,68 @@
+use chrono::Datelike;
+use serde::Serialize;
+
+use crate::models::invoice::Invoice;
+use crate::models::payment::Payment;
+use super::period::{attribute, TaxationMethod};
+use super::round_cents;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DashboardMonth {
+    pub month: u32,
+    pub net_revenue: f64,
+    pub output_tax: f64,
+}
+
+/// Yearly revenue overview, attributed to months the same way the VAT return is.
+#[derive(Debug, Clone, Serialize)]
+pub struct DashboardSummary {
+    pub year: i32,
+    pub taxation_method: TaxationMethod,
+    pub months: Vec<DashboardMonth>,
+    pub net_revenue: f64,
+    pub output_tax: f64,
+    pub outstanding: f64,
+}
+
+impl DashboardSummary {
+    pub fn build(
+        year: i32,
+        taxation_method: TaxationMethod,
+        invoices: &[Invoice],
+        payments: &[Payment],
+    ) -> Self {
+        let mut months: Vec<DashboardMonth> = (1..=12)
+            .map(|month| DashboardMonth { month, net_revenue: 0.0, output_tax: 0.0 })
+            .collect();
+        let mut outstanding = 0.0;
+
+        for invoice in invoices {
+            for attribution in attribute(invoice, payments, taxation_method) {
+                if attribution.date.year() != year {
+                    continue;
+                }
+                let month = &mut months[attribution.date.month0() as usize];
+                month.net_revenue = round_cents(month.net_revenue + attribution.net_amount);
+                month.output_tax = round_cents(month.output_tax + attribution.tax_amount);
+            }
+
+            if matches!(invoice.status.as_str(), "sent" | "overdue") && !invoice.is_credit_note() {
+                let paid: f64 = payments
+                    .iter()
+                    .filter(|payment| payment.invoice_id == invoice.id)
+                    .map(|payment| payment.amount + payment.skonto_amount)
+                    .sum();
+                outstanding += invoice.to_eur((invoice.amount_due() - paid).max(0.0));
+            }
+        }
+
+        Self {
+            year,
+            taxation_method,
+            net_revenue: round_cents(months.iter().map(|month| month.net_revenue).sum()),
+            output_tax: round_cents(months.iter().map(|month| month.output_tax).sum()),
+            months,
+            outstanding: round_cents(outstanding),
+        }
+    }
+}
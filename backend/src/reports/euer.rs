@@ -0,0 +1,250 @@
+use chrono::Datelike;
+use serde::Serialize;
+
+use crate::models::expense::Expense;
+use crate::models::invoice::Invoice;
+use crate::models::payment::Payment;
+use super::period::{attribute, TaxationMethod};
+use super::{csv_field, format_amount_de, round_cents};
+
+/// A single line of the Anlage EÜR, identified by its Kennzahl.
+#[derive(Debug, Clone, Serialize)]
+pub struct EuerLine {
+    pub kennzahl: u16,
+    pub label: &'static str,
+    pub amount: f64,
+}
+
+/// Einnahmenüberschussrechnung for one calendar year.
+///
+/// Cash-basis accounting: invoices count in the year they were paid and
+/// expenses in the year they were settled, regardless of their issue date.
+#[derive(Debug, Clone, Serialize)]
+pub struct EuerReport {
+    pub year: i32,
+    pub income: Vec<EuerLine>,
+    pub expenses: Vec<EuerLine>,
+    pub total_income: f64,
+    pub total_expenses: f64,
+    pub profit: f64,
+}
+
+const INCOME_LINES: &[(u16, &str)] = &[
+    (112, "Umsatzsteuerpflichtige Betriebseinnahmen"),
+    (103, "Umsatzsteuerfreie, nicht umsatzsteuerbare Betriebseinnahmen"),
+    (140, "Vereinnahmte Umsatzsteuer"),
+];
+
+const EXPENSE_LINES: &[(u16, &str)] = &[
+    (100, "Waren, Rohstoffe und Hilfsstoffe"),
+    (110, "Bezogene Fremdleistungen"),
+    (120, "Ausgaben für eigenes Personal"),
+    (150, "Miete/Pacht für Geschäftsräume"),
+    (280, "Aufwendungen für Telekommunikation"),
+    (221, "Übernachtungs- und Reisenebenkosten bei Geschäftsreisen"),
+    (281, "Fortbildungskosten"),
+    (194, "Kosten für Rechts- und Steuerberatung, Buchführung"),
+    (222, "Miete/Leasing für bewegliche Wirtschaftsgüter"),
+    (223, "Beiträge, Gebühren, Abgaben und Versicherungen"),
+    (224, "Werbekosten"),
+    (183, "Übrige unbeschränkt abziehbare Betriebsausgaben"),
+    (185, "Gezahlte Vorsteuerbeträge"),
+    (186, "An das Finanzamt gezahlte Umsatzsteuer"),
+];
+
+const KZ_TOTAL_INCOME: u16 = 159;
+const KZ_TOTAL_EXPENSES: u16 = 199;
+const KZ_PROFIT: u16 = 219;
+
+/// Maps an expense category onto the Kennzahl its net amount is reported in.
+pub fn expense_kennzahl(category: &str) -> u16 {
+    match category {
+        "goods" => 100,
+        "subcontractors" => 110,
+        "personnel" => 120,
+        "rent" => 150,
+        "telecommunication" => 280,
+        "travel" => 221,
+        "training" => 281,
+        "legal_and_tax_advice" => 194,
+        "leasing" => 222,
+        "insurance_and_fees" => 223,
+        "advertising" => 224,
+        "vat_payment" => 186,
+        _ => 183,
+    }
+}
+
+impl EuerReport {
+    pub fn build(year: i32, invoices: &[Invoice], payments: &[Payment], expenses: &[Expense]) -> Self {
+        let mut income = lines(INCOME_LINES);
+        let mut expense_lines = lines(EXPENSE_LINES);
+
+        // The EÜR is cash-based by law, independent of the VAT taxation method.
+        for invoice in invoices {
+            for attribution in attribute(invoice, payments, TaxationMethod::Ist) {
+                if attribution.date.year() != year {
+                    continue;
+                }
+
+                if invoice.tax_amount != 0.0 {
+                    add(&mut income, 112, attribution.net_amount);
+                    add(&mut income, 140, attribution.tax_amount);
+                } else {
+                    add(&mut income, 103, attribution.net_amount);
+                }
+            }
+        }
+
+        for expense in expenses {
+            let paid_in_year = expense.paid_at.is_some_and(|paid_at| paid_at.year() == year);
+            if !paid_in_year {
+                continue;
+            }
+
+            // VAT remitted to the tax office is itself the expense, it carries no input tax.
+            if expense.category == "vat_payment" {
+                add(&mut expense_lines, 186, expense.gross_amount);
+            } else {
+                let kennzahl = expense_kennzahl(&expense.category);
+                // Reverse-charge VAT is owed and deducted in the same return, so no cash moves.
+                if expense.tax_treatment == "reverse_charge" {
+                    add(&mut expense_lines, kennzahl, expense.net_amount);
+                } else {
+                    // Input VAT that cannot be deducted is part of the expense itself.
+                    let non_deductible = expense.tax_amount - expense.deductible_tax_amount;
+                    add(&mut expense_lines, kennzahl, expense.net_amount + non_deductible);
+                    add(&mut expense_lines, 185, expense.deductible_tax_amount);
+                }
+            }
+        }
+
+        let total_income = round_cents(income.iter().map(|line| line.amount).sum());
+        let total_expenses = round_cents(expense_lines.iter().map(|line| line.amount).sum());
+
+        Self {
+            year,
+            income,
+            expenses: expense_lines,
+            total_income,
+            total_expenses,
+            profit: round_cents(total_income - total_expenses),
+        }
+    }
+
+    /// Renders the report as semicolon-separated CSV with German number formatting.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("Kennzahl;Bezeichnung;Betrag\n");
+        let mut row = |kennzahl: u16, label: &str, amount: f64| {
+            csv.push_str(&format!(
+                "{};{};{}\n",
+                kennzahl,
+                csv_field(label, ';'),
+                format_amount_de(amount)
+            ));
+        };
+
+        for line in &self.income {
+            row(line.kennzahl, line.label, line.amount);
+        }
+        row(KZ_TOTAL_INCOME, "Summe Betriebseinnahmen", self.total_income);
+        for line in &self.expenses {
+            row(line.kennzahl, line.label, line.amount);
+        }
+        row(KZ_TOTAL_EXPENSES, "Summe Betriebsausgaben", self.total_expenses);
+        row(KZ_PROFIT, "Steuerpflichtiger Gewinn/Verlust", self.profit);
+
+        csv
+    }
+}
+
+fn lines(definitions: &[(u16, &'static str)]) -> Vec<EuerLine> {
+    definitions
+        .iter()
+        .map(|&(kennzahl, label)| EuerLine { kennzahl, label, amount: 0.0 })
+        .collect()
+}
+
+fn add(lines: &mut [EuerLine], kennzahl: u16, amount: f64) {
+    if let Some(line) = lines.iter_mut().find(|line| line.kennzahl == kennzahl) {
+        line.amount = round_cents(line.amount + amount);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, TimeZone, Utc};
+
+    fn paid_invoice(paid: (i32, u32, u32), subtotal: f64, tax_rate: f64) -> Invoice {
+        let tax_amount = round_cents(subtotal * tax_rate / 100.0);
+        let mut invoice = Invoice::new(
+            "user".to_string(),
+            "client".to_string(),
+            "INV-1".to_string(),
+            NaiveDate::from_ymd_opt(2023, 12, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2023, 12, 15).unwrap(),
+            "EUR".to_string(),
+            subtotal,
+            tax_rate,
+            tax_amount,
+            subtotal + tax_amount,
+            None,
+        );
+        invoice.status = "paid".to_string();
+        invoice.paid_at = Some(Utc.with_ymd_and_hms(paid.0, paid.1, paid.2, 12, 0, 0).unwrap());
+        invoice
+    }
+
+    fn amount(lines: &[EuerLine], kennzahl: u16) -> f64 {
+        lines.iter().find(|line| line.kennzahl == kennzahl).unwrap().amount
+    }
+
+    #[test]
+    fn counts_invoices_in_the_year_they_were_paid() {
+        let invoices = vec![
+            paid_invoice((2024, 1, 10), 1000.0, 19.0),
+            paid_invoice((2023, 12, 30), 500.0, 19.0),
+            paid_invoice((2024, 3, 1), 200.0, 0.0),
+        ];
+
+        let report = EuerReport::build(2024, &invoices, &[], &[]);
+
+        assert_eq!(amount(&report.income, 112), 1000.0);
+        assert_eq!(amount(&report.income, 140), 190.0);
+        assert_eq!(amount(&report.income, 103), 200.0);
+        assert_eq!(report.total_income, 1390.0);
+    }
+
+    #[test]
+    fn maps_paid_expenses_onto_their_kennzahl() {
+        let mut phone = Expense::new(
+            "user".to_string(),
+            "Telekom".to_string(),
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            "telecommunication".to_string(),
+            100.0,
+            19.0,
+            19.0,
+        );
+        phone.paid_at = Some(Utc.with_ymd_and_hms(2024, 2, 3, 9, 0, 0).unwrap());
+        let unpaid = Expense::new(
+            "user".to_string(),
+            "Vermieter".to_string(),
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            "rent".to_string(),
+            800.0,
+            0.0,
+            0.0,
+        );
+
+        let report = EuerReport::build(2024, &[paid_invoice((2024, 5, 5), 1000.0, 19.0)], &[], &[phone, unpaid]);
+
+        assert_eq!(amount(&report.expenses, 280), 100.0);
+        assert_eq!(amount(&report.expenses, 185), 19.0);
+        assert_eq!(amount(&report.expenses, 150), 0.0);
+        assert_eq!(report.total_expenses, 119.0);
+        assert_eq!(report.profit, 1071.0);
+        assert!(report.to_csv().contains("219;Steuerpflichtiger Gewinn/Verlust;1071,00\n"));
+    }
+}
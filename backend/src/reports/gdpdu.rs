@@ -0,0 +1,470 @@
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+
+use crate::models::audit::AuditEntry;
+use crate::models::client::Client;
+use crate::models::invoice::{Invoice, InvoiceItem};
+use crate::models::payment::Payment;
+use crate::models::user::User;
+use super::{csv_field, data_supplier_name, format_amount_de, format_decimal_de, xml_escape, zip};
+
+const SEPARATOR: char = ';';
+
+/// Column types of the GDPdU Beschreibungsstandard.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnType {
+    AlphaNumeric,
+    /// Decimal number with the given number of decimal places.
+    Numeric(u8),
+    /// Date in `DD.MM.YYYY`.
+    Date,
+}
+
+#[derive(Debug, Clone)]
+pub struct Column {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub column_type: ColumnType,
+}
+
+/// One CSV file of the export. The first column is the primary key.
+#[derive(Debug, Clone)]
+pub struct Table {
+    pub url: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub columns: Vec<Column>,
+    /// Columns referencing the primary key of another table, by table name.
+    pub foreign_keys: Vec<(&'static str, &'static str)>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// Data export for the Datenträgerüberlassung (Z3 access) of one fiscal year.
+#[derive(Debug, Clone)]
+pub struct GdpduExport {
+    pub year: i32,
+    pub data_supplier: String,
+    pub tables: Vec<Table>,
+}
+
+fn column(name: &'static str, description: &'static str, column_type: ColumnType) -> Column {
+    Column { name, description, column_type }
+}
+
+fn date(value: NaiveDate) -> String {
+    value.format("%d.%m.%Y").to_string()
+}
+
+fn optional_date(value: Option<DateTime<Utc>>) -> String {
+    value.map(|value| date(value.date_naive())).unwrap_or_default()
+}
+
+fn text(value: &Option<String>) -> String {
+    value.clone().unwrap_or_default()
+}
+
+impl GdpduExport {
+    /// Collects the records of the fiscal year: issued invoices and credit notes
+    /// by issue date with their items and clients, payments by payment date and
+    /// the audit log entries written during the year.
+    pub fn build(
+        year: i32,
+        user: &User,
+        invoices: &[Invoice],
+        items: &[InvoiceItem],
+        payments: &[Payment],
+        clients: &[Client],
+        audit_log: &[AuditEntry],
+    ) -> Self {
+        let invoices: Vec<&Invoice> = invoices
+            .iter()
+            .filter(|invoice| invoice.status != "draft" && invoice.issue_date.year() == year)
+            .collect();
+        let invoice_ids: BTreeSet<&str> = invoices.iter().map(|invoice| invoice.id.as_str()).collect();
+        let client_ids: BTreeSet<&str> = invoices.iter().map(|invoice| invoice.client_id.as_str()).collect();
+
+        let invoice_table = Table {
+            url: "invoices.csv",
+            name: "Rechnungen",
+            description: "Ausgangsrechnungen und Gutschriften",
+            columns: vec![
+                column("id", "Interne ID", ColumnType::AlphaNumeric),
+                column("invoice_number", "Rechnungsnummer", ColumnType::AlphaNumeric),
+                column("invoice_type", "Belegart (invoice, credit_note)", ColumnType::AlphaNumeric),
+                column("corrected_invoice_id", "Korrigierte Rechnung", ColumnType::AlphaNumeric),
+                column("client_id", "Kunde", ColumnType::AlphaNumeric),
+                column("issue_date", "Rechnungsdatum", ColumnType::Date),
+                column("due_date", "Fälligkeitsdatum", ColumnType::Date),
+                column("currency", "Währung", ColumnType::AlphaNumeric),
+                column("subtotal", "Nettobetrag", ColumnType::Numeric(2)),
+                column("tax_rate", "Steuersatz in Prozent", ColumnType::Numeric(2)),
+                column("tax_amount", "Umsatzsteuer", ColumnType::Numeric(2)),
+                column("total_amount", "Bruttobetrag", ColumnType::Numeric(2)),
+                column("tax_treatment", "Steuerliche Behandlung", ColumnType::AlphaNumeric),
+                column("status", "Status", ColumnType::AlphaNumeric),
+                column("paid_at", "Zahlungsdatum", ColumnType::Date),
+                column("allowance_total", "Nachlässe auf Belegebene", ColumnType::Numeric(2)),
+                column("charge_total", "Zuschläge auf Belegebene", ColumnType::Numeric(2)),
+                column("skonto_percent", "Skonto in Prozent", ColumnType::Numeric(2)),
+                column("skonto_days", "Skontofrist in Tagen", ColumnType::Numeric(0)),
+                column("exchange_rate", "Umrechnungskurs (Währung je EUR)", ColumnType::Numeric(4)),
+                column("exchange_rate_date", "Kursdatum", ColumnType::Date),
+                column("billing_type", "Rechnungsart (standard, advance, final)", ColumnType::AlphaNumeric),
+                column("project_id", "Projekt", ColumnType::AlphaNumeric),
+                column("advance_deduction_net", "Abgezogene Anzahlungen netto", ColumnType::Numeric(2)),
+                column("advance_deduction_tax", "Umsatzsteuer auf abgezogene Anzahlungen", ColumnType::Numeric(2)),
+            ],
+            foreign_keys: vec![("client_id", "Kunden")],
+            rows: invoices
+                .iter()
+                .map(|invoice| {
+                    vec![
+                        invoice.id.clone(),
+                        invoice.invoice_number.clone(),
+                        invoice.invoice_type.clone(),
+                        text(&invoice.corrected_invoice_id),
+                        invoice.client_id.clone(),
+                        date(invoice.issue_date),
+                        date(invoice.due_date),
+                        invoice.currency.clone(),
+                        format_amount_de(invoice.subtotal),
+                        format_amount_de(invoice.tax_rate),
+                        format_amount_de(invoice.tax_amount),
+                        format_amount_de(invoice.total_amount),
+                        invoice.tax_treatment.clone(),
+                        invoice.status.clone(),
+                        optional_date(invoice.paid_at),
+                        format_amount_de(invoice.allowance_total),
+                        format_amount_de(invoice.charge_total),
+                        invoice.skonto_percent.map(format_amount_de).unwrap_or_default(),
+                        invoice.skonto_days.map(|days| days.to_string()).unwrap_or_default(),
+                        invoice.exchange_rate.map(|rate| format_decimal_de(rate, 4)).unwrap_or_default(),
+                        invoice.exchange_rate_date.map(date).unwrap_or_default(),
+                        invoice.billing_type.clone(),
+                        text(&invoice.project_id),
+                        format_amount_de(invoice.advance_deduction_net),
+                        format_amount_de(invoice.advance_deduction_tax),
+                    ]
+                })
+                .collect(),
+        };
+
+        let item_table = Table {
+            url: "invoice_items.csv",
+            name: "Rechnungspositionen",
+            description: "Positionen der Ausgangsrechnungen",
+            columns: vec![
+                column("id", "Interne ID", ColumnType::AlphaNumeric),
+                column("invoice_id", "Rechnung", ColumnType::AlphaNumeric),
+                column("description", "Bezeichnung", ColumnType::AlphaNumeric),
+                column("quantity", "Menge", ColumnType::Numeric(4)),
+                column("unit_code", "Einheit (UN/ECE Rec. 20)", ColumnType::AlphaNumeric),
+                column("unit_price", "Einzelpreis netto", ColumnType::Numeric(2)),
+                column("discount_percent", "Rabatt in Prozent", ColumnType::Numeric(2)),
+                column("total_price", "Gesamtpreis netto", ColumnType::Numeric(2)),
+                column("tax_category", "Steuerkategorie", ColumnType::AlphaNumeric),
+                column("revenue_account", "Erlöskonto", ColumnType::AlphaNumeric),
+            ],
+            foreign_keys: vec![("invoice_id", "Rechnungen")],
+            rows: items
+                .iter()
+                .filter(|item| invoice_ids.contains(item.invoice_id.as_str()))
+                .map(|item| {
+                    vec![
+                        item.id.clone(),
+                        item.invoice_id.clone(),
+                        item.description.clone(),
+                        format_decimal_de(item.quantity, 4),
+                        item.unit_code.clone(),
+                        format_amount_de(item.unit_price),
+                        format_amount_de(item.discount_percent),
+                        format_amount_de(item.total_price),
+                        item.tax_category.clone().unwrap_or_default(),
+                        item.revenue_account.clone().unwrap_or_default(),
+                    ]
+                })
+                .collect(),
+        };
+
+        let payment_table = Table {
+            url: "payments.csv",
+            name: "Zahlungen",
+            description: "Zahlungseingänge und Erstattungen",
+            columns: vec![
+                column("id", "Interne ID", ColumnType::AlphaNumeric),
+                column("invoice_id", "Rechnung", ColumnType::AlphaNumeric),
+                column("payment_date", "Zahlungsdatum", ColumnType::Date),
+                column("amount", "Betrag", ColumnType::Numeric(2)),
+                column("method", "Zahlungsart", ColumnType::AlphaNumeric),
+                column("skonto_amount", "Skontoabzug", ColumnType::Numeric(2)),
+            ],
+            foreign_keys: vec![("invoice_id", "Rechnungen")],
+            rows: payments
+                .iter()
+                .filter(|payment| payment.payment_date.year() == year)
+                .map(|payment| {
+                    vec![
+                        payment.id.clone(),
+                        payment.invoice_id.clone(),
+                        date(payment.payment_date),
+                        format_amount_de(payment.amount),
+                        text(&payment.method),
+                        format_amount_de(payment.skonto_amount),
+                    ]
+                })
+                .collect(),
+        };
+
+        let client_table = Table {
+            url: "clients.csv",
+            name: "Kunden",
+            description: "Kunden der exportierten Rechnungen",
+            columns: vec![
+                column("id", "Interne ID", ColumnType::AlphaNumeric),
+                column("name", "Name", ColumnType::AlphaNumeric),
+                column("company", "Firma", ColumnType::AlphaNumeric),
+                column("street", "Straße", ColumnType::AlphaNumeric),
+                column("postal_code", "Postleitzahl", ColumnType::AlphaNumeric),
+                column("city", "Ort", ColumnType::AlphaNumeric),
+                column("country", "Land", ColumnType::AlphaNumeric),
+                column("vat_number", "USt-IdNr.", ColumnType::AlphaNumeric),
+            ],
+            foreign_keys: Vec::new(),
+            rows: clients
+                .iter()
+                .filter(|client| client_ids.contains(client.id.as_str()))
+                .map(|client| {
+                    vec![
+                        client.id.clone(),
+                        client.name.clone(),
+                        text(&client.company),
+                        text(&client.street),
+                        text(&client.postal_code),
+                        text(&client.city),
+                        client.country.clone(),
+                        text(&client.vat_number),
+                    ]
+                })
+                .collect(),
+        };
+
+        let snapshot = |data: &Option<sqlx::types::Json<serde_json::Value>>| {
+            data.as_ref().map(|json| json.0.to_string()).unwrap_or_default()
+        };
+        let audit_table = Table {
+            url: "audit_log.csv",
+            name: "Protokoll",
+            description: "Änderungsprotokoll mit Hash-Kette (SHA-256)",
+            columns: vec![
+                column("id", "Interne ID", ColumnType::AlphaNumeric),
+                column("sequence", "Laufende Nummer", ColumnType::Numeric(0)),
+                column("created_at", "Zeitpunkt (UTC, RFC 3339)", ColumnType::AlphaNumeric),
+                column("actor_id", "Benutzer", ColumnType::AlphaNumeric),
+                column("entity_type", "Datensatzart", ColumnType::AlphaNumeric),
+                column("entity_id", "Datensatz", ColumnType::AlphaNumeric),
+                column("action", "Vorgang (create, update, delete)", ColumnType::AlphaNumeric),
+                column("before_data", "Inhalt vor der Änderung (JSON)", ColumnType::AlphaNumeric),
+                column("after_data", "Inhalt nach der Änderung (JSON)", ColumnType::AlphaNumeric),
+                column("prev_hash", "Hash des Vorgängers", ColumnType::AlphaNumeric),
+                column("hash", "Hash des Eintrags", ColumnType::AlphaNumeric),
+            ],
+            foreign_keys: Vec::new(),
+            rows: audit_log
+                .iter()
+                .filter(|entry| entry.created_at.year() == year)
+                .map(|entry| {
+                    vec![
+                        entry.id.clone(),
+                        entry.sequence.to_string(),
+                        entry.created_at.to_rfc3339(),
+                        entry.actor_id.clone(),
+                        entry.entity_type.clone(),
+                        entry.entity_id.clone(),
+                        entry.action.clone(),
+                        snapshot(&entry.before_data),
+                        snapshot(&entry.after_data),
+                        entry.prev_hash.clone(),
+                        entry.hash.clone(),
+                    ]
+                })
+                .collect(),
+        };
+
+        Self {
+            year,
+            data_supplier: data_supplier_name(user),
+            tables: vec![invoice_table, item_table, payment_table, client_table, audit_table],
+        }
+    }
+
+    /// Renders the `index.xml` describing every table for the auditor's
+    /// analysis software (GDPdU Beschreibungsstandard, DTD gdpdu-01-08-2002).
+    pub fn index_xml(&self, created_on: NaiveDate) -> String {
+        let from = date(NaiveDate::from_ymd_opt(self.year, 1, 1).unwrap_or(created_on));
+        let to = date(NaiveDate::from_ymd_opt(self.year, 12, 31).unwrap_or(created_on));
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str("<!DOCTYPE DataSet SYSTEM \"gdpdu-01-08-2002.dtd\">\n");
+        xml.push_str("<DataSet>\n");
+        xml.push_str("  <Version>1.0</Version>\n");
+        xml.push_str("  <DataSupplier>\n");
+        xml.push_str(&format!("    <Name>{}</Name>\n", xml_escape(&self.data_supplier)));
+        xml.push_str("    <Location></Location>\n");
+        xml.push_str(&format!(
+            "    <Comment>Datenträgerüberlassung Geschäftsjahr {}, erstellt am {}</Comment>\n",
+            self.year,
+            date(created_on)
+        ));
+        xml.push_str("  </DataSupplier>\n");
+        xml.push_str("  <Media>\n");
+        xml.push_str(&format!("    <Name>MiniDebet Export {}</Name>\n", self.year));
+
+        for table in &self.tables {
+            xml.push_str("    <Table>\n");
+            xml.push_str(&format!("      <URL>{}</URL>\n", table.url));
+            xml.push_str(&format!("      <Name>{}</Name>\n", xml_escape(table.name)));
+            xml.push_str(&format!("      <Description>{}</Description>\n", xml_escape(table.description)));
+            xml.push_str("      <Validity>\n");
+            xml.push_str(&format!("        <Range><From>{}</From><To>{}</To></Range>\n", from, to));
+            xml.push_str("      </Validity>\n");
+            xml.push_str("      <UTF8/>\n");
+            xml.push_str("      <DecimalSymbol>,</DecimalSymbol>\n");
+            xml.push_str("      <DigitGroupingSymbol>.</DigitGroupingSymbol>\n");
+            xml.push_str("      <VariableLength>\n");
+            xml.push_str(&format!("        <ColumnDelimiter>{}</ColumnDelimiter>\n", SEPARATOR));
+            xml.push_str("        <RecordDelimiter>&#10;</RecordDelimiter>\n");
+            xml.push_str("        <TextEncapsulator>&quot;</TextEncapsulator>\n");
+            for (index, column) in table.columns.iter().enumerate() {
+                let element = if index == 0 { "VariablePrimaryKey" } else { "VariableColumn" };
+                xml.push_str(&format!("        <{}>\n", element));
+                xml.push_str(&format!("          <Name>{}</Name>\n", column.name));
+                xml.push_str(&format!("          <Description>{}</Description>\n", xml_escape(column.description)));
+                match column.column_type {
+                    ColumnType::AlphaNumeric => xml.push_str("          <AlphaNumeric/>\n"),
+                    ColumnType::Numeric(0) => xml.push_str("          <Numeric/>\n"),
+                    ColumnType::Numeric(accuracy) => xml.push_str(&format!(
+                        "          <Numeric><Accuracy>{}</Accuracy></Numeric>\n",
+                        accuracy
+                    )),
+                    ColumnType::Date => xml.push_str("          <Date><Format>DD.MM.YYYY</Format></Date>\n"),
+                }
+                xml.push_str(&format!("        </{}>\n", element));
+            }
+            for (column, references) in &table.foreign_keys {
+                xml.push_str("        <ForeignKey>\n");
+                xml.push_str(&format!("          <Name>{}</Name>\n", column));
+                xml.push_str(&format!("          <References>{}</References>\n", xml_escape(references)));
+                xml.push_str("        </ForeignKey>\n");
+            }
+            xml.push_str("      </VariableLength>\n");
+            xml.push_str("    </Table>\n");
+        }
+
+        xml.push_str("  </Media>\n");
+        xml.push_str("</DataSet>\n");
+
+        xml
+    }
+
+    /// Packages `index.xml` and the CSV files as a ZIP archive.
+    pub fn to_zip(&self, created_at: DateTime<Utc>) -> Vec<u8> {
+        let mut files = vec![("index.xml".to_string(), self.index_xml(created_at.date_naive()).into_bytes())];
+        files.extend(
+            self.tables
+                .iter()
+                .map(|table| (table.url.to_string(), table.to_csv().into_bytes())),
+        );
+
+        zip::write_stored(&files, created_at.naive_utc())
+    }
+}
+
+impl Table {
+    /// Semicolon-separated rows without a header line; the columns are
+    /// described in `index.xml`.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::new();
+        for row in &self.rows {
+            let fields: Vec<String> = row.iter().map(|value| csv_field(value, SEPARATOR)).collect();
+            csv.push_str(&fields.join(&SEPARATOR.to_string()));
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invoice(number: &str, issue_date: NaiveDate, status: &str) -> Invoice {
+        let mut invoice = Invoice::new(
+            "user".to_string(),
+            "client".to_string(),
+            number.to_string(),
+            issue_date,
+            issue_date,
+            "EUR".to_string(),
+            1000.0,
+            19.0,
+            190.0,
+            1190.0,
+            None,
+        );
+        invoice.status = status.to_string();
+        invoice
+    }
+
+    fn user() -> User {
+        User::new(
+            "info@example.com".to_string(),
+            String::new(),
+            None,
+            None,
+            Some("Muster & Söhne GmbH".to_string()),
+            None,
+        )
+    }
+
+    fn table<'a>(export: &'a GdpduExport, url: &str) -> &'a Table {
+        export.tables.iter().find(|table| table.url == url).unwrap()
+    }
+
+    #[test]
+    fn exports_issued_invoices_of_the_year_with_their_items() {
+        let day = |year, month| NaiveDate::from_ymd_opt(year, month, 15).unwrap();
+        let invoices = vec![
+            invoice("RE-1", day(2024, 3), "sent"),
+            invoice("RE-2", day(2024, 4), "draft"),
+            invoice("RE-3", day(2023, 12), "paid"),
+        ];
+        let items = vec![
+            InvoiceItem::new(invoices[0].id.clone(), "Beratung; vor Ort".to_string(), 2.0, 500.0, 1000.0),
+            InvoiceItem::new(invoices[1].id.clone(), "Entwurf".to_string(), 1.0, 1000.0, 1000.0),
+        ];
+
+        let export = GdpduExport::build(2024, &user(), &invoices, &items, &[], &[], &[]);
+
+        let invoice_csv = table(&export, "invoices.csv").to_csv();
+        assert_eq!(invoice_csv.lines().count(), 1);
+        assert!(invoice_csv.contains(";RE-1;invoice;;client;15.03.2024;15.03.2024;EUR;1000,00;19,00;190,00;1190,00;standard;sent;"));
+
+        let item_csv = table(&export, "invoice_items.csv").to_csv();
+        assert_eq!(item_csv.lines().count(), 1);
+        assert!(item_csv.contains(";\"Beratung; vor Ort\";2,0000;C62;500,00;0,00;1000,00"));
+    }
+
+    #[test]
+    fn index_describes_every_table() {
+        let export = GdpduExport::build(2024, &user(), &[], &[], &[], &[], &[]);
+        let xml = export.index_xml(NaiveDate::from_ymd_opt(2025, 5, 2).unwrap());
+
+        assert!(xml.contains("<Name>Muster &amp; Söhne GmbH</Name>"));
+        assert!(xml.contains("<Range><From>01.01.2024</From><To>31.12.2024</To></Range>"));
+        assert_eq!(xml.matches("<Table>").count(), 5);
+        assert_eq!(xml.matches("<VariablePrimaryKey>").count(), 5);
+        assert!(xml.contains("<Name>total_amount</Name>\n          <Description>Bruttobetrag</Description>\n          <Numeric><Accuracy>2</Accuracy></Numeric>"));
+        assert!(xml.contains("<References>Kunden</References>"));
+    }
+}
@@ -0,0 +1,63 @@
+pub mod dashboard;
+pub mod euer;
+pub mod gdpdu;
+pub mod period;
+pub mod ustva;
+pub mod zip;
+pub mod zm;
+
+use crate::models::user::User;
+
+/// Rounds an amount to whole cents, halves away from zero. The cent value is
+/// first cut to six decimals so that binary representation errors such as
+/// `1.005 * 100.0 == 100.49999999999999` do not round down.
+pub fn round_cents(amount: f64) -> f64 {
+    let cents = ((amount * 100.0) * 1e6).round() / 1e6;
+    cents.round() / 100.0
+}
+
+/// Formats an amount with a decimal comma, as German spreadsheet and tax
+/// software expect it (`1234,56`).
+pub fn format_amount_de(amount: f64) -> String {
+    format!("{:.2}", round_cents(amount)).replace('.', ",")
+}
+
+/// Formats a value with a fixed number of decimals and a decimal comma.
+pub fn format_decimal_de(value: f64, decimals: usize) -> String {
+    format!("{:.*}", decimals, value).replace('.', ",")
+}
+
+/// Quotes a CSV field when it contains the separator, quotes or line breaks.
+pub fn csv_field(value: &str, separator: char) -> String {
+    if value.contains(separator) || value.contains('"') || value.contains('\n') || value.contains('\r') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Escapes the characters that are not allowed verbatim in XML text and attributes.
+pub fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Name of the data supplier in exports: the company name, otherwise the
+/// user's full name.
+pub fn data_supplier_name(user: &User) -> String {
+    if let Some(company) = user.company_name.as_deref().filter(|name| !name.trim().is_empty()) {
+        return company.trim().to_string();
+    }
+
+    [user.first_name.as_deref(), user.last_name.as_deref()]
+        .into_iter()
+        .flatten()
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
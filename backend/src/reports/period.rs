@@ -0,0 +1,300 @@
+use std::fmt;
+
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use crate::models::invoice::Invoice;
+use crate::models::payment::Payment;
+use super::round_cents;
+
+/// When VAT on an invoice falls due: with the invoice (Sollversteuerung,
+/// § 13 Abs. 1 Nr. 1a UStG) or with the payment (Istversteuerung, § 20 UStG).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TaxationMethod {
+    Soll,
+    Ist,
+}
+
+impl TaxationMethod {
+    /// Reads the `taxation_method` stored in the user settings. Anything but
+    /// `ist` is treated as the statutory default, Sollversteuerung.
+    pub fn from_setting(value: &str) -> Self {
+        if value.eq_ignore_ascii_case("ist") {
+            TaxationMethod::Ist
+        } else {
+            TaxationMethod::Soll
+        }
+    }
+}
+
+/// The part of an invoice that belongs to the tax period containing `date`.
+/// Amounts of credit notes are negative.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Attribution {
+    pub date: NaiveDate,
+    pub net_amount: f64,
+    pub tax_amount: f64,
+    pub gross_amount: f64,
+}
+
+/// Assigns an invoice (or credit note) to tax periods. Every report goes
+/// through this function so they all agree on which period an amount is in.
+///
+/// Under Sollversteuerung the whole invoice is attributed to its issue date.
+/// Under Istversteuerung each payment attributes its share of net and VAT to
+/// the payment date; an invoice marked as paid without recorded payments
+/// counts in full on `paid_at`. Refunds of credit notes are recorded as
+/// payments on the credit note. Drafts and cancelled invoices are never
+/// attributed.
+///
+/// Amounts are converted to EUR at the rate stored on the invoice; payments
+/// are recorded in the invoice currency.
+///
+/// Advance invoices are always attributed to their payments. A final invoice
+/// only attributes the amounts left after deducting the advance payments,
+/// which were taxed when they were received.
+///
+/// Skonto reduces the consideration (§ 17 Abs. 1 UStG). Under Istversteuerung
+/// only the amount received is attributed anyway; under Sollversteuerung the
+/// deducted Skonto is corrected in the period of the payment.
+pub fn attribute(invoice: &Invoice, payments: &[Payment], method: TaxationMethod) -> Vec<Attribution> {
+    if matches!(invoice.status.as_str(), "draft" | "cancelled") {
+        return Vec::new();
+    }
+
+    let sign = if invoice.is_credit_note() { -1.0 } else { 1.0 };
+    let (net_due, tax_due, gross_due) = invoice.amounts_due();
+    let share = |date: NaiveDate, fraction: f64| Attribution {
+        date,
+        net_amount: round_cents(invoice.to_eur(net_due) * fraction) * sign,
+        tax_amount: round_cents(invoice.to_eur(tax_due) * fraction) * sign,
+        gross_amount: round_cents(invoice.to_eur(gross_due) * fraction) * sign,
+    };
+
+    // Advance payments are taxed when received, under either method
+    // (§ 13 Abs. 1 Nr. 1a Satz 4 UStG).
+    let method = if invoice.is_advance() { TaxationMethod::Ist } else { method };
+
+    match method {
+        TaxationMethod::Soll => {
+            let mut attributions = vec![share(invoice.issue_date, 1.0)];
+            if gross_due != 0.0 {
+                attributions.extend(
+                    payments
+                        .iter()
+                        .filter(|payment| payment.invoice_id == invoice.id && payment.skonto_amount != 0.0)
+                        .map(|payment| share(payment.payment_date, -payment.skonto_amount / gross_due)),
+                );
+            }
+            attributions
+        }
+        TaxationMethod::Ist => {
+            let invoice_payments: Vec<&Payment> = payments
+                .iter()
+                .filter(|payment| payment.invoice_id == invoice.id)
+                .collect();
+
+            if invoice_payments.is_empty() {
+                return match invoice.paid_at {
+                    Some(paid_at) if invoice.status == "paid" => vec![share(paid_at.date_naive(), 1.0)],
+                    _ => Vec::new(),
+                };
+            }
+
+            invoice_payments
+                .into_iter()
+                .map(|payment| {
+                    let fraction = if gross_due != 0.0 {
+                        payment.amount / gross_due
+                    } else {
+                        1.0
+                    };
+                    share(payment.payment_date, fraction)
+                })
+                .collect()
+        }
+    }
+}
+
+/// A VAT return period (Voranmeldungszeitraum): a calendar month or quarter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VatPeriod {
+    Month(u32),
+    Quarter(u32),
+}
+
+impl VatPeriod {
+    /// Parses `01`–`12` (or `1`–`12`) as a month and `Q1`–`Q4` or the ELSTER
+    /// codes `41`–`44` as a quarter.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if let Some(quarter) = value.strip_prefix('Q').or_else(|| value.strip_prefix('q')) {
+            return quarter.parse().ok().filter(|q| (1..=4).contains(q)).map(VatPeriod::Quarter);
+        }
+
+        match value.parse::<u32>().ok()? {
+            month @ 1..=12 => Some(VatPeriod::Month(month)),
+            code @ 41..=44 => Some(VatPeriod::Quarter(code - 40)),
+            _ => None,
+        }
+    }
+
+    /// The `Zeitraum` code used by ELSTER: `01`–`12` for months, `41`–`44` for quarters.
+    pub fn elster_code(&self) -> String {
+        match self {
+            VatPeriod::Month(month) => format!("{:02}", month),
+            VatPeriod::Quarter(quarter) => format!("{}", 40 + quarter),
+        }
+    }
+
+    /// First and last day of the period in the given year.
+    pub fn date_range(&self, year: i32) -> (NaiveDate, NaiveDate) {
+        let (first_month, last_month) = match *self {
+            VatPeriod::Month(month) => (month, month),
+            VatPeriod::Quarter(quarter) => (quarter * 3 - 2, quarter * 3),
+        };
+
+        let start = NaiveDate::from_ymd_opt(year, first_month, 1).expect("valid period start");
+        let end = if last_month == 12 {
+            NaiveDate::from_ymd_opt(year, 12, 31)
+        } else {
+            NaiveDate::from_ymd_opt(year, last_month + 1, 1).and_then(|date| date.pred_opt())
+        }
+        .expect("valid period end");
+
+        (start, end)
+    }
+
+    pub fn contains(&self, year: i32, date: NaiveDate) -> bool {
+        let (start, end) = self.date_range(year);
+        date >= start && date <= end
+    }
+}
+
+impl fmt::Display for VatPeriod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VatPeriod::Month(month) => write!(f, "{:02}", month),
+            VatPeriod::Quarter(quarter) => write!(f, "Q{}", quarter),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn invoice(total_net: f64) -> Invoice {
+        let mut invoice = Invoice::new(
+            "user".to_string(),
+            "client".to_string(),
+            "INV-1".to_string(),
+            NaiveDate::from_ymd_opt(2024, 3, 28).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 4, 11).unwrap(),
+            "EUR".to_string(),
+            total_net,
+            19.0,
+            round_cents(total_net * 0.19),
+            round_cents(total_net * 1.19),
+            None,
+        );
+        invoice.status = "sent".to_string();
+        invoice
+    }
+
+    #[test]
+    fn partial_payments_are_attributed_to_their_payment_dates() {
+        let invoice = invoice(1000.0);
+        let payments = vec![
+            Payment::new(invoice.id.clone(), 595.0, NaiveDate::from_ymd_opt(2024, 4, 2).unwrap(), None),
+            Payment::new(invoice.id.clone(), 595.0, NaiveDate::from_ymd_opt(2024, 5, 6).unwrap(), None),
+            Payment::new("other".to_string(), 100.0, NaiveDate::from_ymd_opt(2024, 5, 6).unwrap(), None),
+        ];
+
+        let soll = attribute(&invoice, &payments, TaxationMethod::Soll);
+        let ist = attribute(&invoice, &payments, TaxationMethod::Ist);
+
+        assert_eq!(soll.len(), 1);
+        assert_eq!(soll[0].date, NaiveDate::from_ymd_opt(2024, 3, 28).unwrap());
+        assert_eq!(soll[0].tax_amount, 190.0);
+        assert_eq!(ist.len(), 2);
+        assert_eq!(ist[0].date, NaiveDate::from_ymd_opt(2024, 4, 2).unwrap());
+        assert_eq!(ist[0].net_amount, 500.0);
+        assert_eq!(ist[1].tax_amount, 95.0);
+    }
+
+    #[test]
+    fn foreign_currency_invoices_are_attributed_in_eur() {
+        let mut invoice = invoice(1000.0);
+        invoice.currency = "USD".to_string();
+        invoice.exchange_rate = Some(1.25);
+        let payments = vec![Payment::new(invoice.id.clone(), 595.0, NaiveDate::from_ymd_opt(2024, 4, 2).unwrap(), None)];
+
+        let soll = attribute(&invoice, &payments, TaxationMethod::Soll);
+        let ist = attribute(&invoice, &payments, TaxationMethod::Ist);
+
+        assert_eq!(soll[0].net_amount, 800.0);
+        assert_eq!(soll[0].tax_amount, 152.0);
+        assert_eq!(ist[0].net_amount, 400.0);
+        assert_eq!(ist[0].gross_amount, 476.0);
+    }
+
+    #[test]
+    fn advance_payments_are_taxed_on_receipt_and_deducted_from_the_final_invoice() {
+        let mut advance = invoice(400.0);
+        advance.billing_type = "advance".to_string();
+        let payments = vec![Payment::new(advance.id.clone(), 476.0, NaiveDate::from_ymd_opt(2024, 2, 10).unwrap(), None)];
+
+        let unpaid = attribute(&advance, &[], TaxationMethod::Soll);
+        let paid = attribute(&advance, &payments, TaxationMethod::Soll);
+        assert!(unpaid.is_empty());
+        assert_eq!(paid[0].date, NaiveDate::from_ymd_opt(2024, 2, 10).unwrap());
+        assert_eq!(paid[0].tax_amount, 76.0);
+
+        let mut final_invoice = invoice(1000.0);
+        final_invoice.billing_type = "final".to_string();
+        final_invoice.advance_deduction_net = 400.0;
+        final_invoice.advance_deduction_tax = 76.0;
+
+        let soll = attribute(&final_invoice, &[], TaxationMethod::Soll);
+        assert_eq!(soll[0].net_amount, 600.0);
+        assert_eq!(soll[0].tax_amount, 114.0);
+        assert_eq!(final_invoice.amount_due(), 714.0);
+    }
+
+    #[test]
+    fn skonto_corrects_output_tax_in_the_payment_period() {
+        let invoice = invoice(1000.0);
+        let mut payment = Payment::new(invoice.id.clone(), 1154.30, NaiveDate::from_ymd_opt(2024, 4, 5).unwrap(), None);
+        payment.skonto_amount = 35.70;
+        let payments = vec![payment];
+
+        let soll = attribute(&invoice, &payments, TaxationMethod::Soll);
+        let ist = attribute(&invoice, &payments, TaxationMethod::Ist);
+
+        assert_eq!(soll.len(), 2);
+        assert_eq!(soll[1].date, NaiveDate::from_ymd_opt(2024, 4, 5).unwrap());
+        assert_eq!(soll[1].net_amount, -30.0);
+        assert_eq!(soll[1].tax_amount, -5.7);
+        assert_eq!(ist[0].net_amount, 970.0);
+        assert_eq!(ist[0].tax_amount, 184.3);
+    }
+
+    #[test]
+    fn credit_notes_reduce_and_legacy_paid_invoices_count_in_full() {
+        let mut credit_note = invoice(200.0);
+        credit_note.invoice_type = "credit_note".to_string();
+        let mut legacy = invoice(100.0);
+        legacy.status = "paid".to_string();
+        legacy.paid_at = Some(Utc.with_ymd_and_hms(2024, 6, 1, 8, 0, 0).unwrap());
+        let mut draft = invoice(100.0);
+        draft.status = "draft".to_string();
+
+        assert_eq!(attribute(&credit_note, &[], TaxationMethod::Soll)[0].net_amount, -200.0);
+        assert_eq!(attribute(&legacy, &[], TaxationMethod::Ist)[0].gross_amount, 119.0);
+        assert!(attribute(&draft, &[], TaxationMethod::Soll).is_empty());
+        assert_eq!(TaxationMethod::from_setting("ist"), TaxationMethod::Ist);
+    }
+}
@@ -0,0 +1,386 @@
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, NaiveDate};
+use serde::Serialize;
+
+use crate::models::expense::Expense;
+use crate::models::invoice::Invoice;
+use crate::models::payment::Payment;
+use crate::models::user::User;
+use super::period::{attribute, TaxationMethod, VatPeriod};
+use super::zm::is_eu_member_state;
+use super::{data_supplier_name, round_cents, xml_escape};
+
+/// A single Kennzahl of the Umsatzsteuer-Voranmeldung.
+#[derive(Debug, Clone, Serialize)]
+pub struct UstvaField {
+    pub kennzahl: u16,
+    pub label: &'static str,
+    pub amount: f64,
+}
+
+/// Umsatzsteuer-Voranmeldung for one month or quarter.
+#[derive(Debug, Clone, Serialize)]
+pub struct UstvaReport {
+    pub year: i32,
+    pub period: String,
+    pub taxation_method: TaxationMethod,
+    pub fields: Vec<UstvaField>,
+    pub output_tax: f64,
+    pub input_tax: f64,
+    pub advance_payment: f64,
+}
+
+/// Kennzahlen in form order. Assessment bases (`true`) are declared in whole euros.
+const FIELDS: &[(u16, &str, bool)] = &[
+    (81, "Steuerpflichtige Umsätze zum Steuersatz von 19 %", true),
+    (86, "Steuerpflichtige Umsätze zum Steuersatz von 7 %", true),
+    (35, "Steuerpflichtige Umsätze zu anderen Steuersätzen", true),
+    (36, "Steuer auf Umsätze zu anderen Steuersätzen", false),
+    (41, "Innergemeinschaftliche Lieferungen an Abnehmer mit USt-IdNr.", true),
+    (43, "Weitere steuerfreie Umsätze mit Vorsteuerabzug", true),
+    (48, "Steuerfreie Umsätze ohne Vorsteuerabzug", true),
+    (21, "Nicht steuerbare sonstige Leistungen gem. § 18b Satz 1 Nr. 2 UStG", true),
+    (45, "Übrige nicht steuerbare Umsätze", true),
+    (46, "Sonstige Leistungen nach § 13b Abs. 1 UStG eines im übrigen Gemeinschaftsgebiet ansässigen Unternehmers", true),
+    (47, "Steuer auf Leistungen nach § 13b Abs. 1 UStG", false),
+    (84, "Andere Leistungen nach § 13b Abs. 2 Nr. 1, 2, 4 bis 12 UStG", true),
+    (85, "Steuer auf andere Leistungen nach § 13b Abs. 2 UStG", false),
+    (66, "Vorsteuerbeträge aus Rechnungen von anderen Unternehmern", false),
+    (67, "Vorsteuerbeträge aus Leistungen im Sinne des § 13b UStG", false),
+];
+
+const KZ_ADVANCE_PAYMENT: u16 = 83;
+const ADVANCE_PAYMENT_LABEL: &str = "Verbleibende Umsatzsteuer-Vorauszahlung bzw. verbleibender Überschuss";
+
+const ELSTER_NAMESPACE: &str = "http://finkonsens.de/elster/elsteranmeldung/ustva/v";
+
+fn is_rate(tax_rate: f64, expected: f64) -> bool {
+    (tax_rate - expected).abs() < 0.001
+}
+
+impl UstvaReport {
+    pub fn build(
+        year: i32,
+        period: VatPeriod,
+        taxation_method: TaxationMethod,
+        invoices: &[Invoice],
+        payments: &[Payment],
+        expenses: &[Expense],
+    ) -> Self {
+        let mut values: BTreeMap<u16, f64> = BTreeMap::new();
+        let mut add = |kennzahl: u16, amount: f64| {
+            *values.entry(kennzahl).or_insert(0.0) += amount;
+        };
+
+        for invoice in invoices {
+            for attribution in attribute(invoice, payments, taxation_method) {
+                if !period.contains(year, attribution.date) {
+                    continue;
+                }
+
+                let net_amount = attribution.net_amount;
+                match invoice.tax_treatment.as_str() {
+                    "reverse_charge" => add(21, net_amount),
+                    "intra_community_supply" => add(41, net_amount),
+                    "export" => add(43, net_amount),
+                    "non_taxable" => add(45, net_amount),
+                    "exempt" => add(48, net_amount),
+                    _ if is_rate(invoice.tax_rate, 19.0) => add(81, net_amount),
+                    _ if is_rate(invoice.tax_rate, 7.0) => add(86, net_amount),
+                    _ if invoice.tax_rate > 0.0 => {
+                        add(35, net_amount);
+                        add(36, attribution.tax_amount);
+                    }
+                    _ => add(48, net_amount),
+                }
+            }
+        }
+
+        // Input tax is deductible once the supplier's invoice is received, under both methods.
+        for expense in expenses {
+            if expense.category == "vat_payment" || !period.contains(year, expense.expense_date) {
+                continue;
+            }
+
+            if expense.tax_treatment == "reverse_charge" {
+                // Services from other member states fall under § 13b Abs. 1; domestic cases such as
+                // construction work and suppliers outside the EU under § 13b Abs. 2.
+                let (base, tax) = match expense.supplier_country.as_deref() {
+                    Some(country) if country.eq_ignore_ascii_case("DE") || !is_eu_member_state(country) => (84, 85),
+                    _ => (46, 47),
+                };
+                add(base, expense.net_amount);
+                add(tax, expense.tax_amount);
+                add(67, expense.deductible_tax_amount);
+            } else {
+                add(66, expense.deductible_tax_amount);
+            }
+        }
+
+        let mut fields: Vec<UstvaField> = FIELDS
+            .iter()
+            .filter_map(|&(kennzahl, label, whole_euros)| {
+                let amount = values.get(&kennzahl).copied().unwrap_or(0.0);
+                let amount = if whole_euros { amount.trunc() } else { round_cents(amount) };
+                (amount != 0.0).then_some(UstvaField { kennzahl, label, amount })
+            })
+            .collect();
+
+        let field = |kennzahl: u16| {
+            fields
+                .iter()
+                .find(|field| field.kennzahl == kennzahl)
+                .map_or(0.0, |field| field.amount)
+        };
+
+        // Tax on 81 and 86 is derived from the declared bases, exactly as the form computes it.
+        let output_tax = round_cents(field(81) * 0.19 + field(86) * 0.07 + field(36) + field(47) + field(85));
+        let input_tax = round_cents(field(66) + field(67));
+        let advance_payment = round_cents(output_tax - input_tax);
+
+        fields.push(UstvaField {
+            kennzahl: KZ_ADVANCE_PAYMENT,
+            label: ADVANCE_PAYMENT_LABEL,
+            amount: advance_payment,
+        });
+
+        Self {
+            year,
+            period: period.elster_code(),
+            taxation_method,
+            fields,
+            output_tax,
+            input_tax,
+            advance_payment,
+        }
+    }
+
+    fn amount(&self, kennzahl: u16) -> f64 {
+        self.fields
+            .iter()
+            .find(|field| field.kennzahl == kennzahl)
+            .map_or(0.0, |field| field.amount)
+    }
+
+    /// Checks the data set the way ELSTER's plausibility checks would before
+    /// submission. Returns human-readable problems; empty means valid.
+    pub fn validate(&self, user: &User) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if !(2000..=9999).contains(&self.year) {
+            errors.push(format!("Invalid year: {}", self.year));
+        }
+        if elster_tax_number(user).is_none() {
+            errors.push("Steuernummer must be given in the 13-digit ELSTER format".to_string());
+        }
+        if data_supplier_name(user).is_empty() {
+            errors.push("Name of the data supplier is missing".to_string());
+        }
+        if self.amount(36) != 0.0 && self.amount(35) == 0.0 {
+            errors.push("Kz 36 requires an assessment base in Kz 35".to_string());
+        }
+        if self.amount(47) != 0.0 && self.amount(46) == 0.0 {
+            errors.push("Kz 47 requires an assessment base in Kz 46".to_string());
+        }
+        if self.amount(85) != 0.0 && self.amount(84) == 0.0 {
+            errors.push("Kz 85 requires an assessment base in Kz 84".to_string());
+        }
+
+        errors
+    }
+
+    /// Renders the ELSTER `Anmeldungssteuern` data set. Transport envelope,
+    /// signing and encryption are left to the submitting software.
+    pub fn to_xml(&self, user: &User, created_on: NaiveDate) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str(&format!(
+            "<Anmeldungssteuern xmlns=\"{}{}\" art=\"UStVA\" version=\"{}\">\n",
+            ELSTER_NAMESPACE, self.year, self.year
+        ));
+        xml.push_str("  <DatenLieferant>\n");
+        xml.push_str(&format!("    <Name>{}</Name>\n", xml_escape(&data_supplier_name(user))));
+        xml.push_str(&format!("    <Email>{}</Email>\n", xml_escape(&user.email)));
+        xml.push_str("  </DatenLieferant>\n");
+        xml.push_str(&format!(
+            "  <Erstellungsdatum>{:04}{:02}{:02}</Erstellungsdatum>\n",
+            created_on.year(),
+            created_on.month(),
+            created_on.day()
+        ));
+        xml.push_str("  <Steuerfall>\n");
+        xml.push_str("    <Umsatzsteuervoranmeldung>\n");
+        xml.push_str(&format!("      <Jahr>{}</Jahr>\n", self.year));
+        xml.push_str(&format!("      <Zeitraum>{}</Zeitraum>\n", self.period));
+        xml.push_str(&format!(
+            "      <Steuernummer>{}</Steuernummer>\n",
+            elster_tax_number(user).unwrap_or_default()
+        ));
+        for field in &self.fields {
+            let whole_euros = FIELDS
+                .iter()
+                .any(|&(kennzahl, _, whole_euros)| kennzahl == field.kennzahl && whole_euros);
+            let amount = if whole_euros {
+                format!("{}", field.amount as i64)
+            } else {
+                format!("{:.2}", field.amount)
+            };
+            xml.push_str(&format!("      <Kz{}>{}</Kz{}>\n", field.kennzahl, amount, field.kennzahl));
+        }
+        xml.push_str("    </Umsatzsteuervoranmeldung>\n");
+        xml.push_str("  </Steuerfall>\n");
+        xml.push_str("</Anmeldungssteuern>\n");
+
+        xml
+    }
+}
+
+/// The user's tax number, if it is in the 13-digit ELSTER format.
+fn elster_tax_number(user: &User) -> Option<String> {
+    let digits: String = user
+        .tax_id
+        .as_deref()?
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '/' | '-'))
+        .collect();
+
+    (digits.len() == 13 && digits.chars().all(|c| c.is_ascii_digit())).then_some(digits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn invoice(issue: (i32, u32, u32), subtotal: f64, tax_rate: f64, treatment: &str) -> Invoice {
+        let tax_amount = round_cents(subtotal * tax_rate / 100.0);
+        let mut invoice = Invoice::new(
+            "user".to_string(),
+            "client".to_string(),
+            "INV-1".to_string(),
+            NaiveDate::from_ymd_opt(issue.0, issue.1, issue.2).unwrap(),
+            NaiveDate::from_ymd_opt(issue.0, issue.1, issue.2).unwrap(),
+            "EUR".to_string(),
+            subtotal,
+            tax_rate,
+            tax_amount,
+            subtotal + tax_amount,
+            None,
+        );
+        invoice.status = "sent".to_string();
+        invoice.tax_treatment = treatment.to_string();
+        invoice
+    }
+
+    fn user(tax_id: &str) -> User {
+        User::new(
+            "max@example.com".to_string(),
+            String::new(),
+            Some("Max".to_string()),
+            Some("Mustermann".to_string()),
+            None,
+            Some(tax_id.to_string()),
+        )
+    }
+
+    #[test]
+    fn soll_versteuerung_uses_issue_date_and_whole_euro_bases() {
+        let invoices = vec![
+            invoice((2024, 1, 15), 1000.99, 19.0, "standard"),
+            invoice((2024, 2, 10), 200.0, 7.0, "standard"),
+            invoice((2024, 3, 1), 500.0, 0.0, "reverse_charge"),
+            invoice((2024, 4, 1), 999.0, 19.0, "standard"),
+        ];
+        let mut expense = Expense::new(
+            "user".to_string(),
+            "Hoster".to_string(),
+            NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            "other".to_string(),
+            100.0,
+            19.0,
+            19.0,
+        );
+        expense.tax_treatment = "standard".to_string();
+
+        let report = UstvaReport::build(2024, VatPeriod::Quarter(1), TaxationMethod::Soll, &invoices, &[], &[expense]);
+
+        assert_eq!(report.period, "41");
+        assert_eq!(report.amount(81), 1000.0);
+        assert_eq!(report.amount(86), 200.0);
+        assert_eq!(report.amount(21), 500.0);
+        assert_eq!(report.amount(66), 19.0);
+        assert_eq!(report.advance_payment, 190.0 + 14.0 - 19.0);
+    }
+
+    #[test]
+    fn reverse_charge_expenses_are_split_by_supplier_country() {
+        let expense = |vendor: &str, country: Option<&str>, net_amount: f64| {
+            let mut expense = Expense::new(
+                "user".to_string(),
+                vendor.to_string(),
+                NaiveDate::from_ymd_opt(2024, 5, 10).unwrap(),
+                "subcontractors".to_string(),
+                net_amount,
+                19.0,
+                round_cents(net_amount * 0.19),
+            );
+            expense.tax_treatment = "reverse_charge".to_string();
+            expense.supplier_country = country.map(str::to_string);
+            expense
+        };
+        let expenses = [
+            expense("Dachdecker Müller", Some("DE"), 2000.0),
+            expense("Hosting Inc.", Some("US"), 100.0),
+            expense("Agentur B.V.", Some("NL"), 500.0),
+            expense("Designer", None, 300.0),
+        ];
+
+        let report = UstvaReport::build(2024, VatPeriod::Month(5), TaxationMethod::Soll, &[], &[], &expenses);
+
+        assert_eq!(report.amount(84), 2100.0);
+        assert_eq!(report.amount(85), 399.0);
+        assert_eq!(report.amount(46), 800.0);
+        assert_eq!(report.amount(47), 152.0);
+        assert_eq!(report.amount(67), 551.0);
+        assert_eq!(report.advance_payment, 0.0);
+        assert!(report.validate(&user("9198011310010")).is_empty());
+    }
+
+    #[test]
+    fn ist_versteuerung_only_counts_payments_in_the_period() {
+        let mut paid = invoice((2024, 1, 20), 1000.0, 19.0, "standard");
+        paid.status = "paid".to_string();
+        paid.paid_at = Some(Utc.with_ymd_and_hms(2024, 2, 5, 10, 0, 0).unwrap());
+        let open = invoice((2024, 2, 1), 300.0, 19.0, "standard");
+
+        let invoices = [paid, open];
+
+        let january = UstvaReport::build(2024, VatPeriod::Month(1), TaxationMethod::Ist, &invoices, &[], &[]);
+        let february = UstvaReport::build(2024, VatPeriod::Month(2), TaxationMethod::Ist, &invoices, &[], &[]);
+
+        assert_eq!(january.advance_payment, 0.0);
+        assert_eq!(february.amount(81), 1000.0);
+        assert_eq!(february.advance_payment, 190.0);
+    }
+
+    #[test]
+    fn xml_contains_kennzahlen_and_validation_flags_tax_number() {
+        let report = UstvaReport::build(
+            2024,
+            VatPeriod::Month(1),
+            TaxationMethod::Soll,
+            &[invoice((2024, 1, 15), 1000.0, 19.0, "standard")],
+            &[],
+            &[],
+        );
+
+        assert!(report.validate(&user("9198011310010")).is_empty());
+        assert_eq!(report.validate(&user("DE123456789")).len(), 1);
+
+        let xml = report.to_xml(&user("91/980/11310010"), NaiveDate::from_ymd_opt(2024, 2, 10).unwrap());
+        assert!(xml.contains("<Zeitraum>01</Zeitraum>"));
+        assert!(xml.contains("<Steuernummer>9198011310010</Steuernummer>"));
+        assert!(xml.contains("<Kz81>1000</Kz81>"));
+        assert!(xml.contains("<Kz83>190.00</Kz83>"));
+    }
+}
@@ -0,0 +1,91 @@
+use chrono::{Datelike, NaiveDateTime, Timelike};
+use crc::{Crc, CRC_32_ISO_HDLC};
+
+const CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+/// Packs the files into a ZIP archive without compression. The exports are
+/// small CSV and XML files, so storing them keeps the writer simple.
+pub fn write_stored(files: &[(String, Vec<u8>)], modified: NaiveDateTime) -> Vec<u8> {
+    let time = ((modified.hour() << 11) | (modified.minute() << 5) | (modified.second() / 2)) as u16;
+    let date = (((modified.year().max(1980) - 1980) as u32) << 9 | modified.month() << 5 | modified.day()) as u16;
+
+    let mut archive = Vec::new();
+    let mut directory = Vec::new();
+
+    for (name, data) in files {
+        let offset = archive.len() as u32;
+        let crc = CRC32.checksum(data);
+        let size = data.len() as u32;
+
+        // Local file header
+        archive.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        push_entry_fields(&mut archive, time, date, crc, size, name.len() as u16);
+        archive.extend_from_slice(&0u16.to_le_bytes());
+        archive.extend_from_slice(name.as_bytes());
+        archive.extend_from_slice(data);
+
+        // Central directory header
+        directory.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        directory.extend_from_slice(&20u16.to_le_bytes());
+        push_entry_fields(&mut directory, time, date, crc, size, name.len() as u16);
+        directory.extend_from_slice(&[0u8; 12]);
+        directory.extend_from_slice(&offset.to_le_bytes());
+        directory.extend_from_slice(name.as_bytes());
+    }
+
+    let directory_offset = archive.len() as u32;
+    let directory_size = directory.len() as u32;
+    archive.extend_from_slice(&directory);
+
+    // End of central directory record
+    archive.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+    archive.extend_from_slice(&[0u8; 4]);
+    archive.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    archive.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    archive.extend_from_slice(&directory_size.to_le_bytes());
+    archive.extend_from_slice(&directory_offset.to_le_bytes());
+    archive.extend_from_slice(&0u16.to_le_bytes());
+
+    archive
+}
+
+/// Fields shared by the local and the central header, from "version needed"
+/// up to the file name length.
+fn push_entry_fields(buffer: &mut Vec<u8>, time: u16, date: u16, crc: u32, size: u32, name_len: u16) {
+    buffer.extend_from_slice(&20u16.to_le_bytes());
+    // Bit 11: file names are UTF-8
+    buffer.extend_from_slice(&0x0800u16.to_le_bytes());
+    buffer.extend_from_slice(&0u16.to_le_bytes());
+    buffer.extend_from_slice(&time.to_le_bytes());
+    buffer.extend_from_slice(&date.to_le_bytes());
+    buffer.extend_from_slice(&crc.to_le_bytes());
+    buffer.extend_from_slice(&size.to_le_bytes());
+    buffer.extend_from_slice(&size.to_le_bytes());
+    buffer.extend_from_slice(&name_len.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn archive_lists_every_file() {
+        let modified = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(12, 30, 0).unwrap();
+        let files = vec![
+            ("index.xml".to_string(), b"<DataSet/>".to_vec()),
+            ("invoices.csv".to_string(), b"1;RE-1\n".to_vec()),
+        ];
+        let archive = write_stored(&files, modified);
+
+        assert_eq!(&archive[..4], &[0x50, 0x4b, 0x03, 0x04]);
+        let end = &archive[archive.len() - 22..];
+        assert_eq!(&end[..4], &[0x50, 0x4b, 0x05, 0x06]);
+        assert_eq!(u16::from_le_bytes([end[10], end[11]]), 2);
+        let directory_size = u32::from_le_bytes([end[12], end[13], end[14], end[15]]) as usize;
+        let directory_offset = u32::from_le_bytes([end[16], end[17], end[18], end[19]]) as usize;
+        assert_eq!(directory_size, 2 * 46 + "index.xml".len() + "invoices.csv".len());
+        assert_eq!(directory_offset + directory_size, archive.len() - 22);
+        assert_eq!(CRC32.checksum(b"123456789"), 0xcbf4_3926);
+    }
+}
@@ -0,0 +1,269 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::models::client::Client;
+use crate::models::invoice::Invoice;
+use super::period::{attribute, TaxationMethod, VatPeriod};
+
+/// One line of the Zusammenfassende Meldung: the total per customer VAT ID
+/// and kind of supply.
+#[derive(Debug, Clone, Serialize)]
+pub struct ZmEntry {
+    pub country_code: String,
+    pub vat_number: String,
+    pub amount: i64,
+    /// `L` for intra-community supplies of goods, `S` for services under reverse charge.
+    pub supply_type: &'static str,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ZmWarning {
+    pub client_id: String,
+    pub client_name: String,
+    pub message: String,
+}
+
+/// Zusammenfassende Meldung (EC Sales List) for one month or quarter.
+#[derive(Debug, Clone, Serialize)]
+pub struct ZmReport {
+    pub year: i32,
+    pub period: String,
+    pub entries: Vec<ZmEntry>,
+    pub warnings: Vec<ZmWarning>,
+}
+
+/// EU member states (plus Northern Ireland for goods) with the allowed length
+/// of the VAT ID after the country prefix.
+const VAT_ID_FORMATS: &[(&str, usize, usize)] = &[
+    ("AT", 9, 9),
+    ("BE", 10, 10),
+    ("BG", 9, 10),
+    ("CY", 9, 9),
+    ("CZ", 8, 10),
+    ("DE", 9, 9),
+    ("DK", 8, 8),
+    ("EE", 9, 9),
+    ("EL", 9, 9),
+    ("ES", 9, 9),
+    ("FI", 8, 8),
+    ("FR", 11, 11),
+    ("HR", 11, 11),
+    ("HU", 8, 8),
+    ("IE", 8, 9),
+    ("IT", 11, 11),
+    ("LT", 9, 12),
+    ("LU", 8, 8),
+    ("LV", 11, 11),
+    ("MT", 8, 8),
+    ("NL", 12, 12),
+    ("PL", 10, 10),
+    ("PT", 9, 9),
+    ("RO", 2, 10),
+    ("SE", 12, 12),
+    ("SI", 8, 8),
+    ("SK", 10, 10),
+    ("XI", 5, 12),
+];
+
+/// Whether the ISO country code belongs to an EU member state. Northern
+/// Ireland only counts for goods, so services from there are not included.
+pub fn is_eu_member_state(country: &str) -> bool {
+    let prefix = match country.to_uppercase().as_str() {
+        "GR" => "EL".to_string(),
+        other => other.to_string(),
+    };
+    prefix != "XI" && VAT_ID_FORMATS.iter().any(|(code, _, _)| *code == prefix)
+}
+
+/// Splits a VAT ID into its country prefix and number and checks the syntax
+/// for the client's country. Greece uses `EL` in VAT IDs but `GR` as ISO code.
+pub fn parse_vat_id(vat_number: &str, client_country: &str) -> Result<(String, String), String> {
+    let normalized: String = vat_number
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '.' && *c != '-')
+        .collect::<String>()
+        .to_uppercase();
+
+    if normalized.len() < 4 || !normalized.is_char_boundary(2) {
+        return Err(format!("VAT ID '{}' is too short", vat_number));
+    }
+    let (prefix, number) = normalized.split_at(2);
+
+    let expected_prefix = match client_country.to_uppercase().as_str() {
+        "GR" => "EL".to_string(),
+        other => other.to_string(),
+    };
+    if prefix != expected_prefix {
+        return Err(format!(
+            "VAT ID '{}' does not match the client's country {}",
+            vat_number, client_country
+        ));
+    }
+
+    let (_, min, max) = VAT_ID_FORMATS
+        .iter()
+        .find(|(country, _, _)| *country == prefix)
+        .ok_or_else(|| format!("{} is not an EU member state", client_country))?;
+
+    if number.len() < *min || number.len() > *max || !number.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("VAT ID '{}' is not a valid {} VAT ID", vat_number, prefix));
+    }
+
+    Ok((prefix.to_string(), number.to_string()))
+}
+
+impl ZmReport {
+    pub fn build(year: i32, period: VatPeriod, invoices: &[Invoice], clients: &[Client]) -> Self {
+        let mut totals: BTreeMap<(String, String, &'static str), f64> = BTreeMap::new();
+        let mut warnings: BTreeMap<String, ZmWarning> = BTreeMap::new();
+
+        for invoice in invoices {
+            let supply_type = match invoice.tax_treatment.as_str() {
+                "reverse_charge" => "S",
+                "intra_community_supply" => "L",
+                _ => continue,
+            };
+            // Supplies are reported for the period they were invoiced in, whatever the taxation method.
+            let net_amount: f64 = attribute(invoice, &[], TaxationMethod::Soll)
+                .iter()
+                .filter(|attribution| period.contains(year, attribution.date))
+                .map(|attribution| attribution.net_amount)
+                .sum();
+            if net_amount == 0.0 {
+                continue;
+            }
+
+            let Some(client) = clients.iter().find(|client| client.id == invoice.client_id) else {
+                continue;
+            };
+
+            if client.country.eq_ignore_ascii_case("DE") {
+                warn(&mut warnings, client, "Domestic client invoiced under reverse charge".to_string());
+                continue;
+            }
+
+            let vat_id = match client.vat_number.as_deref().filter(|v| !v.trim().is_empty()) {
+                Some(vat_number) => parse_vat_id(vat_number, &client.country),
+                None => Err("Client has no VAT ID".to_string()),
+            };
+
+            match vat_id {
+                Ok((country_code, vat_number)) => {
+                    *totals.entry((country_code, vat_number, supply_type)).or_insert(0.0) += net_amount;
+                }
+                Err(message) => warn(&mut warnings, client, message),
+            }
+        }
+
+        let entries = totals
+            .into_iter()
+            .map(|((country_code, vat_number, supply_type), amount)| ZmEntry {
+                country_code,
+                vat_number,
+                amount: amount.round() as i64,
+                supply_type,
+            })
+            .filter(|entry| entry.amount != 0)
+            .collect();
+
+        Self {
+            year,
+            period: period.to_string(),
+            entries,
+            warnings: warnings.into_values().collect(),
+        }
+    }
+
+    /// Renders the CSV layout accepted by the BZSt online portal's ZM import.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("Laenderkennzeichen,USt-IdNr.,Betrag(EUR),Art der Leistung\n");
+        for entry in &self.entries {
+            csv.push_str(&format!(
+                "{},{},{},{}\n",
+                entry.country_code, entry.vat_number, entry.amount, entry.supply_type
+            ));
+        }
+        csv
+    }
+}
+
+fn warn(warnings: &mut BTreeMap<String, ZmWarning>, client: &Client, message: String) {
+    warnings.entry(client.id.clone()).or_insert_with(|| ZmWarning {
+        client_id: client.id.clone(),
+        client_name: client.name.clone(),
+        message,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn client(name: &str, country: &str, vat_number: Option<&str>) -> Client {
+        Client::new(
+            "user".to_string(),
+            name.to_string(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            country.to_string(),
+            vat_number.map(str::to_string),
+        )
+    }
+
+    fn invoice(client: &Client, month: u32, subtotal: f64, treatment: &str) -> Invoice {
+        let date = NaiveDate::from_ymd_opt(2024, month, 10).unwrap();
+        let mut invoice = Invoice::new(
+            "user".to_string(),
+            client.id.clone(),
+            "INV-1".to_string(),
+            date,
+            date,
+            "EUR".to_string(),
+            subtotal,
+            0.0,
+            0.0,
+            subtotal,
+            None,
+        );
+        invoice.status = "sent".to_string();
+        invoice.tax_treatment = treatment.to_string();
+        invoice
+    }
+
+    #[test]
+    fn aggregates_per_vat_id_and_warns_about_invalid_ones() {
+        let austrian = client("Wien GmbH", "AT", Some("ATU12345678"));
+        let greek = client("Athens SA", "GR", Some("EL 123456789"));
+        let missing = client("Paris SARL", "FR", None);
+        let invoices = vec![
+            invoice(&austrian, 1, 1000.40, "reverse_charge"),
+            invoice(&austrian, 2, 500.30, "reverse_charge"),
+            invoice(&austrian, 4, 999.0, "reverse_charge"),
+            invoice(&greek, 3, 250.0, "intra_community_supply"),
+            invoice(&missing, 3, 700.0, "reverse_charge"),
+        ];
+
+        let report = ZmReport::build(2024, VatPeriod::Quarter(1), &invoices, &[austrian, greek, missing]);
+
+        assert_eq!(report.period, "Q1");
+        assert_eq!(
+            report.to_csv(),
+            "Laenderkennzeichen,USt-IdNr.,Betrag(EUR),Art der Leistung\nAT,U12345678,1501,S\nEL,123456789,250,L\n"
+        );
+        assert_eq!(report.warnings.len(), 1);
+        assert_eq!(report.warnings[0].client_name, "Paris SARL");
+    }
+
+    #[test]
+    fn rejects_vat_ids_from_another_country() {
+        assert!(parse_vat_id("ATU12345678", "AT").is_ok());
+        assert!(parse_vat_id("ATU12345678", "FR").is_err());
+        assert!(parse_vat_id("FR123", "FR").is_err());
+        assert!(parse_vat_id("US123456789", "US").is_err());
+    }
+}
@@ -0,0 +1,167 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{Datelike, NaiveDate, Utc};
+use serde::Serialize;
+
+use crate::db::{self, Db};
+use crate::storage::ReceiptStorage;
+
+/// How long a kind of record has to be kept. The period starts at the end of
+/// the calendar year the record belongs to (§ 147 Abs. 4 AO).
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct RetentionRule {
+    pub record_type: &'static str,
+    pub years: i32,
+    pub legal_basis: &'static str,
+}
+
+pub const INVOICE_RETENTION: RetentionRule = RetentionRule {
+    record_type: "invoice",
+    years: 8,
+    legal_basis: "§ 147 Abs. 1 Nr. 4, Abs. 3 AO; § 14b Abs. 1 UStG",
+};
+
+pub const EXPENSE_RETENTION: RetentionRule = RetentionRule {
+    record_type: "expense",
+    years: 8,
+    legal_basis: "§ 147 Abs. 1 Nr. 4, Abs. 3 AO",
+};
+
+pub const AUDIT_LOG_RETENTION: RetentionRule = RetentionRule {
+    record_type: "audit_log",
+    years: 10,
+    legal_basis: "§ 147 Abs. 1 Nr. 1, Abs. 3 AO",
+};
+
+/// Invoice items and payments share the retention of their invoice, clients
+/// are kept as long as an invoice refers to them.
+pub const RETENTION_RULES: &[RetentionRule] = &[INVOICE_RETENTION, EXPENSE_RETENTION, AUDIT_LOG_RETENTION];
+
+impl RetentionRule {
+    /// Records dated in this year or earlier have passed their retention on `today`.
+    pub fn cutoff_year(&self, today: NaiveDate) -> i32 {
+        today.year() - self.years - 1
+    }
+
+    /// Last day a record dated in `year` has to be kept.
+    pub fn retained_until(&self, year: i32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year + self.years, 12, 31).unwrap_or(NaiveDate::MAX)
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct PurgeSummary {
+    pub invoices: usize,
+    pub expenses: usize,
+    pub audit_entries: u64,
+    pub clients_anonymized: usize,
+    pub users_anonymized: usize,
+    pub sessions: u64,
+    pub email_tokens: u64,
+    pub login_throttles: u64,
+    pub invitations: u64,
+    pub advisor_links: u64,
+    pub sso_logins: u64,
+}
+
+/// Deletes the documents of deleted users and clients whose retention has
+/// elapsed, then anonymises clients and users that have nothing left to keep.
+/// Owners under legal hold are skipped. A record that cannot be removed is
+/// logged and retried on the next run.
+pub async fn purge(
+    db: &Db,
+    receipts: &dyn ReceiptStorage,
+    today: NaiveDate,
+) -> Result<PurgeSummary, sqlx::Error> {
+    let mut summary = PurgeSummary::default();
+
+    let mut invoices =
+        db::retention::find_purgeable_invoices(db, INVOICE_RETENTION.cutoff_year(today), today).await?;
+    // Credit notes refer to the invoice they correct and go first.
+    invoices.sort_by_key(|invoice| !invoice.is_credit_note());
+    for invoice in &invoices {
+        match db::retention::purge_invoice(db, invoice).await {
+            Ok(()) => summary.invoices += 1,
+            Err(error) => tracing::warn!("Retention purge skipped invoice {}: {}", invoice.id, error),
+        }
+    }
+
+    let expenses =
+        db::retention::find_purgeable_expenses(db, EXPENSE_RETENTION.cutoff_year(today), today).await?;
+    for expense in &expenses {
+        if let Some(key) = expense.receipt_key.as_deref() {
+            if let Err(error) = receipts.delete(key).await {
+                tracing::warn!("Retention purge skipped expense {}: {}", expense.id, error);
+                continue;
+            }
+        }
+        match db::retention::purge_expense(db, expense).await {
+            Ok(()) => summary.expenses += 1,
+            Err(error) => tracing::warn!("Retention purge skipped expense {}: {}", expense.id, error),
+        }
+    }
+
+    for client in db::retention::find_anonymizable_clients(db, today).await? {
+        db::retention::anonymize_client(db, &client).await?;
+        summary.clients_anonymized += 1;
+    }
+
+    for user in db::retention::find_anonymizable_users(db, today).await? {
+        db::retention::anonymize_user(db, &user).await?;
+        summary.users_anonymized += 1;
+    }
+
+    summary.audit_entries =
+        db::retention::purge_audit_log(db, AUDIT_LOG_RETENTION.cutoff_year(today), today).await?;
+
+    // Sessions, mailed tokens, login throttles, invitations, advisor links and
+    // SSO logins are no records under retention; ended ones are not needed
+    summary.sessions = db::session::purge_inactive(db).await?;
+    summary.email_tokens = db::email_token::purge_inactive(db).await?;
+    summary.login_throttles = db::login_throttle::purge_inactive(db).await?;
+    summary.invitations = db::membership::purge_inactive_invitations(db).await?;
+    summary.advisor_links = db::advisor_grant::purge_inactive_links(db).await?;
+    summary.sso_logins = db::identity::purge_expired_logins(db).await?;
+
+    Ok(summary)
+}
+
+/// Runs the purge at startup and once a day.
+pub fn spawn_purge_job(db: Db, receipts: Arc<dyn ReceiptStorage>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(24 * 60 * 60));
+        loop {
+            interval.tick().await;
+            match purge(&db, receipts.as_ref(), Utc::now().date_naive()).await {
+                Ok(summary) => tracing::info!("Retention purge finished: {:?}", summary),
+                Err(error) => tracing::error!("Retention purge failed: {}", error),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn retention_runs_to_the_end_of_the_year() {
+        // Issued in 2024, kept until 31.12.2032
+        assert_eq!(INVOICE_RETENTION.cutoff_year(date(2032, 12, 31)), 2023);
+        assert_eq!(INVOICE_RETENTION.cutoff_year(date(2033, 1, 1)), 2024);
+        assert_eq!(INVOICE_RETENTION.retained_until(2024), date(2032, 12, 31));
+    }
+
+    #[test]
+    fn audit_log_is_kept_longer_than_documents() {
+        let today = date(2035, 1, 1);
+
+        assert_eq!(AUDIT_LOG_RETENTION.cutoff_year(today), 2024);
+        assert_eq!(EXPENSE_RETENTION.cutoff_year(today), 2026);
+    }
+}
@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use axum::extract::FromRef;
+
+use crate::auth::keys::JwtKeys;
+use crate::auth::oidc::Oidc;
+use crate::db::Db;
+use crate::mail::Mailer;
+use crate::storage::ReceiptStorage;
+
+#[derive(Clone)]
+pub struct AppState {
+    pub db: Db,
+    pub receipts: Arc<dyn ReceiptStorage>,
+    pub jwt_keys: Arc<JwtKeys>,
+    pub mailer: Arc<Mailer>,
+    pub oidc: Arc<Oidc>,
+}
+
+impl FromRef<AppState> for Db {
+    fn from_ref(state: &AppState) -> Self {
+        state.db.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<dyn ReceiptStorage> {
+    fn from_ref(state: &AppState) -> Self {
+        state.receipts.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<JwtKeys> {
+    fn from_ref(state: &AppState) -> Self {
+        state.jwt_keys.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<Mailer> {
+    fn from_ref(state: &AppState) -> Self {
+        state.mailer.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<Oidc> {
+    fn from_ref(state: &AppState) -> Self {
+        state.oidc.clone()
+    }
+}
@@ -0,0 +1,56 @@
+use std::io::{Error, ErrorKind};
+use std::path::{Component, Path, PathBuf};
+
+use async_trait::async_trait;
+use tokio::fs;
+
+use super::ReceiptStorage;
+
+pub struct LocalReceiptStorage {
+    root: PathBuf,
+}
+
+impl LocalReceiptStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Reads the directory from `RECEIPTS_DIR`, defaulting to `./receipts`.
+    pub fn from_env() -> Self {
+        Self::new(std::env::var("RECEIPTS_DIR").unwrap_or_else(|_| "receipts".to_string()))
+    }
+
+    fn path(&self, key: &str) -> std::io::Result<PathBuf> {
+        let relative = Path::new(key);
+        if relative.components().any(|component| !matches!(component, Component::Normal(_))) {
+            return Err(Error::new(ErrorKind::InvalidInput, "invalid storage key"));
+        }
+        Ok(self.root.join(relative))
+    }
+}
+
+#[async_trait]
+impl ReceiptStorage for LocalReceiptStorage {
+    async fn put(&self, key: &str, data: &[u8], _content_type: &str) -> std::io::Result<()> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(path, data).await
+    }
+
+    async fn get(&self, key: &str) -> std::io::Result<Option<Vec<u8>>> {
+        match fs::read(self.path(key)?).await {
+            Ok(data) => Ok(Some(data)),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> std::io::Result<()> {
+        match fs::remove_file(self.path(key)?).await {
+            Err(error) if error.kind() != ErrorKind::NotFound => Err(error),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,19 @@
+pub mod local;
+
+use async_trait::async_trait;
+
+pub use local::LocalReceiptStorage;
+
+/// Stores receipt files by key. The Axum server keeps them on the local
+/// filesystem, the worker in an R2 bucket.
+#[async_trait]
+pub trait ReceiptStorage: Send + Sync {
+    async fn put(&self, key: &str, data: &[u8], content_type: &str) -> std::io::Result<()>;
+    async fn get(&self, key: &str) -> std::io::Result<Option<Vec<u8>>>;
+    async fn delete(&self, key: &str) -> std::io::Result<()>;
+}
+
+/// Storage key of an expense's receipt.
+pub fn receipt_key(user_id: &str, expense_id: &str) -> String {
+    format!("receipts/{}/{}", user_id, expense_id)
+}
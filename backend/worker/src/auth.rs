@@ -1,29 +1,278 @@
-use worker::{Request, Result, Headers};
+use worker::{Env, Headers};
+
+use crate::error::{ApiError, ApiResult};
 use serde::{Deserialize, Serialize};
-use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use jsonwebtoken::{decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
 use bcrypt::{hash, verify, DEFAULT_COST};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+const MIN_SECRET_LENGTH: usize = 32;
+/// Value from the old setup guide; never accepted outside development mode.
+const PLACEHOLDER_SECRET: &str = "your-secret-key-change-in-production";
+
+/// Every API key starts with this, so it can be told apart from a JWT.
+pub const API_KEY_PREFIX: &str = "mdk_";
 
-// JWT Secret - in production, use Workers Secrets
-const JWT_SECRET: &str = "minidebet-jwt-secret-key-change-in-production";
+/// Header that selects the organisation a request works in.
+pub const ORGANISATION_HEADER: &str = "X-Organisation-Id";
+
+/// What a request may do in an organisation. Team management, reports and
+/// client deletion are only available on the Axum server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    View,
+    Edit,
+}
+
+/// The organisation a request works in and the caller's role there.
+#[derive(Debug, Clone)]
+pub struct Member {
+    pub organisation_id: String,
+    pub role: String,
+}
+
+impl Member {
+    /// Same roles as on the Axum server: everyone but `read_only` may edit.
+    pub fn allows(&self, permission: Permission) -> bool {
+        match permission {
+            Permission::View => matches!(self.role.as_str(), "owner" | "accountant" | "member" | "read_only"),
+            Permission::Edit => matches!(self.role.as_str(), "owner" | "accountant" | "member"),
+        }
+    }
+}
+
+/// Scopes an API key can be given.
+pub const API_SCOPES: &[&str] = &[
+    "clients:read",
+    "clients:write",
+    "invoices:read",
+    "invoices:write",
+    "expenses:read",
+    "expenses:write",
+    "time:read",
+    "time:write",
+    "catalog:read",
+    "catalog:write",
+    "reports:read",
+];
+const DEFAULT_KEY_ID: &str = "primary";
+
+/// A key still accepted for verification after the signing key was rotated.
+#[derive(Debug, Deserialize)]
+struct RetiredKey {
+    kid: String,
+    #[serde(default = "default_algorithm")]
+    alg: String,
+    secret: Option<String>,
+    public_key: Option<String>,
+}
+
+fn invalid_token() -> ApiError {
+    ApiError::unauthorized("Invalid or missing authentication token")
+}
+
+fn invalid_challenge() -> ApiError {
+    ApiError::unauthorized("Invalid or expired login challenge")
+}
+
+fn default_algorithm() -> String {
+    "HS256".to_string()
+}
+
+fn parse_algorithm(name: &str) -> ApiResult<Algorithm> {
+    match name {
+        "HS256" => Ok(Algorithm::HS256),
+        "EdDSA" => Ok(Algorithm::EdDSA),
+        "RS256" => Ok(Algorithm::RS256),
+        _ => Err(ApiError::internal(format!("Unsupported JWT algorithm: {}", name))),
+    }
+}
+
+fn check_secret(secret: &str, development: bool) -> ApiResult<()> {
+    if secret == PLACEHOLDER_SECRET && !development {
+        return Err(ApiError::internal("The example JWT secret is only allowed in development mode"));
+    }
+    Ok(())
+}
+
+fn decoding_key(algorithm: Algorithm, secret: Option<&str>, public_key: Option<&str>) -> ApiResult<DecodingKey> {
+    let key = match (algorithm, secret, public_key) {
+        (Algorithm::HS256, Some(secret), _) if secret.len() >= MIN_SECRET_LENGTH => {
+            Ok(DecodingKey::from_secret(secret.as_bytes()))
+        }
+        (Algorithm::EdDSA, _, Some(pem)) => DecodingKey::from_ed_pem(pem.as_bytes()),
+        (Algorithm::RS256, _, Some(pem)) => DecodingKey::from_rsa_pem(pem.as_bytes()),
+        _ => return Err(ApiError::internal("JWT key is missing or too short")),
+    };
+    key.map_err(|e| ApiError::internal(format!("Invalid JWT public key: {}", e)))
+}
+
+/// Signing and verification keys, read from Workers secrets. Works like the
+/// Axum server's `JwtKeys`: `JWT_SECRET` (HS256) or `JWT_PRIVATE_KEY` and
+/// `JWT_PUBLIC_KEY` (EdDSA, RS256, chosen by the `JWT_ALGORITHM` variable),
+/// `JWT_KEY_ID` naming the signing key and `JWT_RETIRED_KEYS` listing keys
+/// still accepted after a rotation. For `wrangler dev`, put them in `.dev.vars`;
+/// the example secret is only accepted there with `MINIDEBET_ENV=development`.
+pub struct JwtKeys {
+    kid: String,
+    algorithm: Algorithm,
+    encoding: EncodingKey,
+    decoding: HashMap<String, (Algorithm, DecodingKey)>,
+}
+
+impl JwtKeys {
+    pub fn from_env(env: &Env) -> ApiResult<Self> {
+        let secret = |name: &str| env.secret(name).ok().map(|value| value.to_string()).filter(|value| !value.is_empty());
+        let var = |name: &str| env.var(name).ok().map(|value| value.to_string()).filter(|value| !value.is_empty());
+
+        let development = var("MINIDEBET_ENV").as_deref() == Some("development");
+        let algorithm = parse_algorithm(var("JWT_ALGORITHM").as_deref().unwrap_or("HS256"))?;
+        let kid = var("JWT_KEY_ID").unwrap_or_else(|| DEFAULT_KEY_ID.to_string());
+
+        let (encoding, decoding) = match algorithm {
+            Algorithm::HS256 => {
+                let jwt_secret = secret("JWT_SECRET")
+                    .ok_or_else(|| ApiError::internal("JWT_SECRET is not configured"))?;
+                check_secret(&jwt_secret, development)?;
+                let decoding = decoding_key(algorithm, Some(&jwt_secret), None)?;
+                (EncodingKey::from_secret(jwt_secret.as_bytes()), decoding)
+            }
+            _ => {
+                let private_key = secret("JWT_PRIVATE_KEY")
+                    .ok_or_else(|| ApiError::internal("JWT_PRIVATE_KEY is not configured"))?;
+                let encoding = match algorithm {
+                    Algorithm::EdDSA => EncodingKey::from_ed_pem(private_key.as_bytes()),
+                    _ => EncodingKey::from_rsa_pem(private_key.as_bytes()),
+                }
+                .map_err(|e| ApiError::internal(format!("Invalid JWT_PRIVATE_KEY: {}", e)))?;
+                let public_key = secret("JWT_PUBLIC_KEY").or_else(|| var("JWT_PUBLIC_KEY"));
+                (encoding, decoding_key(algorithm, None, public_key.as_deref())?)
+            }
+        };
+
+        let mut keys = Self {
+            kid: kid.clone(),
+            algorithm,
+            encoding,
+            decoding: HashMap::from([(kid, (algorithm, decoding))]),
+        };
+
+        if let Some(retired) = secret("JWT_RETIRED_KEYS") {
+            let retired: Vec<RetiredKey> = serde_json::from_str(&retired)
+                .map_err(|e| ApiError::internal(format!("Invalid JWT_RETIRED_KEYS: {}", e)))?;
+            for key in retired {
+                if keys.decoding.contains_key(&key.kid) {
+                    return Err(ApiError::internal(format!("Duplicate JWT key id: {}", key.kid)));
+                }
+                let algorithm = parse_algorithm(&key.alg)?;
+                if let Some(secret) = key.secret.as_deref() {
+                    check_secret(secret, development)?;
+                }
+                let decoding = decoding_key(algorithm, key.secret.as_deref(), key.public_key.as_deref())?;
+                keys.decoding.insert(key.kid, (algorithm, decoding));
+            }
+        }
+
+        Ok(keys)
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String, // user ID
     pub email: String,
     pub exp: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>, // session ID
+}
+
+/// Access tokens live 15 minutes; clients renew them with a refresh token.
+pub const ACCESS_TOKEN_SECONDS: usize = 15 * 60;
+
+/// The password step of a two-factor login has to be completed within
+/// 5 minutes.
+pub const CHALLENGE_SECONDS: usize = 5 * 60;
+const CHALLENGE_AUDIENCE: &str = "two_factor";
+
+/// Claims of a login challenge. The audience keeps it from being accepted as
+/// access token.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChallengeClaims {
+    pub sub: String,
+    pub aud: String,
+    pub exp: usize,
 }
 
+/// Failed login limits, the same as on the Axum server: no delay for the first
+/// few failures, then 2, 4, 8, ... seconds, then a 15 minute lockout. Accounts
+/// get 3 free failures and are locked at 10, IP addresses 10 and 50.
+const LOGIN_LOCKOUT_SECONDS: i64 = 15 * 60;
+/// bcrypt hash at the registration cost, checked when no account has the
+/// address so the response time does not tell which addresses exist.
+const DUMMY_PASSWORD_HASH: &str = "$2b$12$s8x62B0OFvYLM.0BI90fqecY.oRIJANeipVoilN9wMkV1022nUfaq";
+
+/// Same parameters as the Axum server: RFC 6238 with SHA-1, six digits,
+/// 30 second steps and one step of clock drift.
+const TOTP_DIGITS: u32 = 6;
+const TOTP_STEP_SECONDS: u64 = 30;
+const TOTP_SECRET_BYTES: usize = 20;
+const RECOVERY_CODE_COUNT: usize = 10;
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LoginRequest {
     pub email: String,
     pub password: String,
+    pub device_label: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TwoFactorLoginRequest {
+    pub challenge_token: String,
+    pub code: Option<String>,
+    pub recovery_code: Option<String>,
+    pub device_label: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChallengeRequest {
+    pub challenge_token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TwoFactorChallenge {
+    pub two_factor_required: bool,
+    pub setup_required: bool,
+    pub challenge_token: String,
+    pub expires_in: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TwoFactorEnrolment {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LoginResponse {
     pub token: String,
-    pub user: User,
+    pub refresh_token: String,
+    pub expires_in: usize,
+    pub session_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<User>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recovery_codes: Option<Vec<String>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -39,46 +288,51 @@ pub struct User {
 pub struct AuthService;
 
 impl AuthService {
-    pub fn generate_token(user_id: &str, email: &str) -> Result<String> {
+    pub fn generate_token(keys: &JwtKeys, user_id: &str, email: &str, session_id: &str) -> ApiResult<String> {
         let expiration = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .expect("Time went backwards")
-            .as_secs() as usize + 24 * 60 * 60; // 24 hours
+            .as_secs() as usize + ACCESS_TOKEN_SECONDS;
 
         let claims = Claims {
             sub: user_id.to_string(),
             email: email.to_string(),
             exp: expiration,
+            sid: Some(session_id.to_string()),
         };
 
-        encode(
-            &Header::default(),
-            &claims,
-            &EncodingKey::from_secret(JWT_SECRET.as_ref()),
-        )
-        .map_err(|e| worker::Error::from(format!("JWT encoding failed: {}", e)))
+        let mut header = Header::new(keys.algorithm);
+        header.kid = Some(keys.kid.clone());
+
+        encode(&header, &claims, &keys.encoding)
+            .map_err(|e| ApiError::internal(format!("JWT encoding failed: {}", e)))
     }
 
-    pub fn verify_token(token: &str) -> Result<Claims> {
-        let validation = Validation::new(Algorithm::HS256);
-        
-        decode::<Claims>(
-            token,
-            &DecodingKey::from_secret(JWT_SECRET.as_ref()),
-            &validation,
-        )
-        .map(|data| data.claims)
-        .map_err(|e| worker::Error::from(format!("JWT verification failed: {}", e)))
+    /// Verifies a token with the key named in its `kid` header.
+    pub fn verify_token(keys: &JwtKeys, token: &str) -> ApiResult<Claims> {
+        let (algorithm, key) = decode_header(token)
+            .ok()
+            .and_then(|header| header.kid)
+            .and_then(|kid| keys.decoding.get(&kid))
+            .ok_or_else(invalid_token)?;
+
+        decode::<Claims>(token, key, &Validation::new(*algorithm))
+            .map(|data| data.claims)
+            .map_err(|_| invalid_token())
     }
 
-    pub fn hash_password(password: &str) -> Result<String> {
-        hash(password, DEFAULT_COST)
-            .map_err(|e| worker::Error::from(format!("Password hashing failed: {}", e)))
+    /// A new random refresh token; only its hash is stored in D1.
+    pub fn new_refresh_token() -> String {
+        format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
     }
 
-    pub fn verify_password(password: &str, hash: &str) -> Result<bool> {
-        verify(password, hash)
-            .map_err(|e| worker::Error::from(format!("Password verification failed: {}", e)))
+    pub fn hash_refresh_token(token: &str) -> String {
+        hex::encode(Sha256::digest(token.as_bytes()))
+    }
+
+    pub fn hash_password(password: &str) -> ApiResult<String> {
+        hash(password, DEFAULT_COST)
+            .map_err(|e| ApiError::internal(format!("Password hashing failed: {}", e)))
     }
 
     pub fn extract_token_from_header(headers: &Headers) -> Option<String> {
@@ -86,16 +340,212 @@ impl AuthService {
             .get("Authorization")
             .ok()
             .flatten()
-            .and_then(|auth_header| {
-                if auth_header.starts_with("Bearer ") {
-                    Some(auth_header[7..].to_string())
-                } else {
-                    None
-                }
+            .and_then(|auth_header| auth_header.strip_prefix("Bearer ").map(str::to_string))
+    }
+
+    pub fn create_auth_response(user: Option<User>, token: String, refresh_token: String, session_id: String) -> LoginResponse {
+        LoginResponse {
+            token,
+            refresh_token,
+            expires_in: ACCESS_TOKEN_SECONDS,
+            session_id,
+            user,
+            recovery_codes: None,
+        }
+    }
+
+    pub fn generate_challenge(keys: &JwtKeys, user_id: &str) -> ApiResult<String> {
+        let claims = ChallengeClaims {
+            sub: user_id.to_string(),
+            aud: CHALLENGE_AUDIENCE.to_string(),
+            exp: unix_time() as usize + CHALLENGE_SECONDS,
+        };
+
+        let mut header = Header::new(keys.algorithm);
+        header.kid = Some(keys.kid.clone());
+
+        encode(&header, &claims, &keys.encoding)
+            .map_err(|e| ApiError::internal(format!("JWT encoding failed: {}", e)))
+    }
+
+    pub fn verify_challenge(keys: &JwtKeys, token: &str) -> ApiResult<ChallengeClaims> {
+        let (algorithm, key) = decode_header(token)
+            .ok()
+            .and_then(|header| header.kid)
+            .and_then(|kid| keys.decoding.get(&kid))
+            .ok_or_else(invalid_challenge)?;
+
+        let mut validation = Validation::new(*algorithm);
+        validation.set_audience(&[CHALLENGE_AUDIENCE]);
+
+        decode::<ChallengeClaims>(token, key, &validation)
+            .map(|data| data.claims)
+            .map_err(|_| invalid_challenge())
+    }
+
+    /// Checks a password, also when there is no account, in constant time.
+    pub fn verify_login_password(password: &str, password_hash: Option<&str>) -> bool {
+        let is_valid = verify(password, password_hash.unwrap_or(DUMMY_PASSWORD_HASH)).unwrap_or(false);
+        is_valid && password_hash.is_some()
+    }
+
+    /// Throttle key of an email address; case and surrounding spaces are ignored.
+    pub fn account_key(email: &str) -> String {
+        email.trim().to_lowercase()
+    }
+
+    /// Seconds to lock a throttle `scope` (`account` or `ip`) after the given
+    /// number of consecutive failures.
+    pub fn login_delay_seconds(scope: &str, failures: i64) -> Option<i64> {
+        let (free_failures, lockout_failures) = if scope == "account" { (3, 10) } else { (10, 50) };
+        if failures >= lockout_failures {
+            return Some(LOGIN_LOCKOUT_SECONDS);
+        }
+        if failures <= free_failures {
+            return None;
+        }
+        Some(2i64.pow((failures - free_failures).min(20) as u32).min(LOGIN_LOCKOUT_SECONDS))
+    }
+
+    /// A new random TOTP secret, base32-encoded.
+    pub fn generate_totp_secret() -> String {
+        base32_encode(&random_bytes(TOTP_SECRET_BYTES))
+    }
+
+    pub fn totp_provisioning_uri(secret: &str, account: &str) -> String {
+        let account: String = account
+            .bytes()
+            .map(|byte| match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+                _ => format!("%{:02X}", byte),
+            })
+            .collect();
+        format!(
+            "otpauth://totp/MiniDebet:{}?secret={}&issuer=MiniDebet&algorithm=SHA1&digits={}&period={}",
+            account, secret, TOTP_DIGITS, TOTP_STEP_SECONDS
+        )
+    }
+
+    /// Returns the time step a code matches. Steps up to `last_used_step`
+    /// are skipped so a code is accepted only once.
+    pub fn verify_totp(secret: &str, code: &str, last_used_step: Option<i64>) -> Option<i64> {
+        let secret = base32_decode(secret)?;
+        let code = code.trim().replace(' ', "");
+        if code.len() != TOTP_DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+
+        let current = unix_time() / TOTP_STEP_SECONDS;
+        (current.saturating_sub(1)..=current + 1)
+            .filter(|step| last_used_step.is_none_or(|last| *step as i64 > last))
+            .find(|step| format!("{:0width$}", hotp(&secret, *step), width = TOTP_DIGITS as usize) == code)
+            .map(|step| step as i64)
+    }
+
+    /// New recovery codes like `k7q2m-x4wpa`; store only their hashes.
+    pub fn generate_recovery_codes() -> Vec<String> {
+        (0..RECOVERY_CODE_COUNT)
+            .map(|_| {
+                let code = base32_encode(&random_bytes(7))[..10].to_lowercase();
+                format!("{}-{}", &code[..5], &code[5..])
             })
+            .collect()
+    }
+
+    /// Hash of a recovery code; case, spaces and dashes are ignored.
+    pub fn hash_recovery_code(code: &str) -> String {
+        let normalized: String = code
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        hex::encode(Sha256::digest(normalized.as_bytes()))
+    }
+
+    /// A new random API key like `mdk_` followed by 40 hex digits.
+    pub fn new_api_key() -> String {
+        format!("{}{}", API_KEY_PREFIX, hex::encode(random_bytes(20)))
+    }
+
+    pub fn is_api_key(token: &str) -> bool {
+        token.starts_with(API_KEY_PREFIX)
+    }
+
+    /// Start of a key kept in clear to identify it, e.g. `mdk_3f9a1c2e`.
+    pub fn api_key_prefix(key: &str) -> String {
+        key.chars().take(API_KEY_PREFIX.len() + 8).collect()
+    }
+
+    pub fn hash_api_key(key: &str) -> String {
+        hex::encode(Sha256::digest(key.as_bytes()))
+    }
+
+    /// The scope a request needs when made with an API key, or `None` if
+    /// keys may not use the endpoint; same rules as on the Axum server.
+    pub fn required_scope(method: &str, path: &str) -> Option<&'static str> {
+        let read = method == "GET" || method == "HEAD";
+        let resource = path.strip_prefix("/api/")?.split('/').next()?;
+        match resource {
+            "clients" if path.contains("/gdpr/") => None,
+            "clients" => Some(if read { "clients:read" } else { "clients:write" }),
+            "invoices" | "exchange-rates" => Some(if read { "invoices:read" } else { "invoices:write" }),
+            "expenses" => Some(if read { "expenses:read" } else { "expenses:write" }),
+            "projects" | "time-entries" => Some(if read { "time:read" } else { "time:write" }),
+            "catalog" => Some(if read { "catalog:read" } else { "catalog:write" }),
+            "reports" | "exports" if read => Some("reports:read"),
+            _ => None,
+        }
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs()
+}
+
+/// Random bytes from the Workers runtime's `crypto.getRandomValues`.
+fn random_bytes(length: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; length];
+    getrandom::getrandom(&mut bytes).expect("crypto.getRandomValues is available in Workers");
+    bytes
+}
+
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(5) {
+        let mut buffer = [0u8; 5];
+        buffer[..chunk.len()].copy_from_slice(chunk);
+        let bits = buffer.iter().fold(0u64, |bits, byte| (bits << 8) | *byte as u64);
+        for index in 0..(chunk.len() * 8).div_ceil(5) {
+            encoded.push(BASE32_ALPHABET[((bits >> (35 - index * 5)) & 31) as usize] as char);
+        }
     }
+    encoded
+}
 
-    pub fn create_auth_response(user: User, token: String) -> LoginResponse {
-        LoginResponse { token, user }
+fn base32_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let (mut bits, mut length) = (0u64, 0);
+    for character in encoded.trim_end_matches('=').chars() {
+        let value = BASE32_ALPHABET.iter().position(|c| *c as char == character.to_ascii_uppercase())?;
+        bits = (bits << 5) | value as u64;
+        length += 5;
+        if length >= 8 {
+            length -= 8;
+            bytes.push((bits >> length) as u8);
+        }
     }
+    Some(bytes)
+}
+
+fn hotp(secret: &[u8], counter: u64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let code = u32::from_be_bytes([hash[offset] & 0x7f, hash[offset + 1], hash[offset + 2], hash[offset + 3]]);
+    code % 10u32.pow(TOTP_DIGITS)
 }
\ No newline at end of file
@@ -1,6 +1,8 @@
-use worker::{Env, Result};
+use worker::{wasm_bindgen::JsValue, Env, Result};
 use serde::{Deserialize, Serialize};
 
+use crate::auth::{AuthService, API_SCOPES};
+
 // D1 Database wrapper for Cloudflare Workers
 pub struct Database {
     env: Env,
@@ -28,15 +30,14 @@ impl Database {
         let result = d1
             .prepare(query)
             .bind(&[
-                serde_json::to_value(&user_data.id)?,
-                serde_json::to_value(&user_data.email)?,
-                serde_json::to_value(&user_data.password_hash)?,
-                serde_json::to_value(&user_data.first_name)?,
-                serde_json::to_value(&user_data.last_name)?,
-                serde_json::to_value(&user_data.company_name)?,
-                serde_json::to_value(&user_data.tax_id)?,
-            ])
-            .await?
+                JsValue::from_str(&user_data.id),
+                JsValue::from_str(&user_data.email),
+                JsValue::from_str(&user_data.password_hash),
+                optional_text(&user_data.first_name),
+                optional_text(&user_data.last_name),
+                optional_text(&user_data.company_name),
+                optional_text(&user_data.tax_id),
+            ])?
             .first::<User>(None)
             .await?;
 
@@ -49,14 +50,23 @@ impl Database {
         let query = "SELECT * FROM users WHERE email = ?";
         let result = d1
             .prepare(query)
-            .bind(&[serde_json::to_value(email)?])
-            .await?
+            .bind(&[JsValue::from_str(email)])?
             .first::<User>(None)
             .await?;
 
         Ok(result)
     }
 
+    pub async fn find_user_by_id(&self, id: &str) -> Result<Option<User>> {
+        let d1 = self.get_d1().await?;
+
+        let query = "SELECT * FROM users WHERE id = ? AND deleted_at IS NULL";
+        d1.prepare(query)
+            .bind(&[JsValue::from_str(id)])?
+            .first::<User>(None)
+            .await
+    }
+
     // Client operations
     pub async fn create_client(&self, client_data: &NewClient) -> Result<Client> {
         let d1 = self.get_d1().await?;
@@ -70,18 +80,17 @@ impl Database {
         let result = d1
             .prepare(query)
             .bind(&[
-                serde_json::to_value(&client_data.id)?,
-                serde_json::to_value(&client_data.user_id)?,
-                serde_json::to_value(&client_data.name)?,
-                serde_json::to_value(&client_data.email)?,
-                serde_json::to_value(&client_data.company)?,
-                serde_json::to_value(&client_data.street)?,
-                serde_json::to_value(&client_data.city)?,
-                serde_json::to_value(&client_data.postal_code)?,
-                serde_json::to_value(&client_data.country)?,
-                serde_json::to_value(&client_data.vat_number)?,
-            ])
-            .await?
+                JsValue::from_str(&client_data.id),
+                JsValue::from_str(&client_data.user_id),
+                JsValue::from_str(&client_data.name),
+                optional_text(&client_data.email),
+                optional_text(&client_data.company),
+                optional_text(&client_data.street),
+                optional_text(&client_data.city),
+                optional_text(&client_data.postal_code),
+                JsValue::from_str(&client_data.country),
+                optional_text(&client_data.vat_number),
+            ])?
             .first::<Client>(None)
             .await?;
 
@@ -94,12 +103,11 @@ impl Database {
         let query = "SELECT * FROM clients WHERE user_id = ? ORDER BY created_at DESC";
         let result = d1
             .prepare(query)
-            .bind(&[serde_json::to_value(user_id)?])
-            .await?
+            .bind(&[JsValue::from_str(user_id)])?
             .all()
             .await?;
 
-        result.deserialize()
+        result.results()
     }
 
     // Invoice operations
@@ -115,22 +123,21 @@ impl Database {
         let result = d1
             .prepare(query)
             .bind(&[
-                serde_json::to_value(&invoice_data.id)?,
-                serde_json::to_value(&invoice_data.user_id)?,
-                serde_json::to_value(&invoice_data.client_id)?,
-                serde_json::to_value(&invoice_data.invoice_number)?,
-                serde_json::to_value(&invoice_data.issue_date)?,
-                serde_json::to_value(&invoice_data.due_date)?,
-                serde_json::to_value(&invoice_data.currency)?,
-                serde_json::to_value(&invoice_data.subtotal)?,
-                serde_json::to_value(&invoice_data.tax_rate)?,
-                serde_json::to_value(&invoice_data.tax_amount)?,
-                serde_json::to_value(&invoice_data.total_amount)?,
-                serde_json::to_value(&invoice_data.status)?,
-                serde_json::to_value(&invoice_data.notes)?,
-                serde_json::to_value(&invoice_data.pdf_url)?,
-            ])
-            .await?
+                JsValue::from_str(&invoice_data.id),
+                JsValue::from_str(&invoice_data.user_id),
+                JsValue::from_str(&invoice_data.client_id),
+                JsValue::from_str(&invoice_data.invoice_number),
+                JsValue::from_str(&invoice_data.issue_date),
+                JsValue::from_str(&invoice_data.due_date),
+                JsValue::from_str(&invoice_data.currency),
+                JsValue::from_f64(invoice_data.subtotal),
+                JsValue::from_f64(invoice_data.tax_rate),
+                JsValue::from_f64(invoice_data.tax_amount),
+                JsValue::from_f64(invoice_data.total_amount),
+                JsValue::from_str(&invoice_data.status),
+                optional_text(&invoice_data.notes),
+                optional_text(&invoice_data.pdf_url),
+            ])?
             .first::<Invoice>(None)
             .await?;
 
@@ -143,13 +150,490 @@ impl Database {
         let query = "SELECT * FROM invoices WHERE user_id = ? ORDER BY created_at DESC";
         let result = d1
             .prepare(query)
-            .bind(&[serde_json::to_value(user_id)?])
-            .await?
+            .bind(&[JsValue::from_str(user_id)])?
+            .all()
+            .await?;
+
+        result.results()
+    }
+
+    // Expense operations
+    pub async fn create_expense(&self, expense: &Expense) -> Result<Expense> {
+        let d1 = self.get_d1().await?;
+
+        let query = "
+            INSERT INTO expenses (id, user_id, vendor, description, expense_date, category, net_amount, tax_rate, tax_amount, gross_amount, tax_treatment, supplier_country, deductible_tax_amount, payment_status, paid_at, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, datetime('now'), datetime('now'))
+            RETURNING *
+        ";
+
+        let result = d1
+            .prepare(query)
+            .bind(&[
+                JsValue::from_str(&expense.id),
+                JsValue::from_str(&expense.user_id),
+                JsValue::from_str(&expense.vendor),
+                optional_text(&expense.description),
+                JsValue::from_str(&expense.expense_date),
+                JsValue::from_str(&expense.category),
+                JsValue::from_f64(expense.net_amount),
+                JsValue::from_f64(expense.tax_rate),
+                JsValue::from_f64(expense.tax_amount),
+                JsValue::from_f64(expense.gross_amount),
+                JsValue::from_str(&expense.tax_treatment),
+                optional_text(&expense.supplier_country),
+                JsValue::from_f64(expense.deductible_tax_amount),
+                JsValue::from_str(&expense.payment_status),
+                optional_text(&expense.paid_at),
+            ])?
+            .first::<Expense>(None)
+            .await?;
+
+        result.ok_or_else(|| worker::Error::from("Failed to create expense"))
+    }
+
+    pub async fn get_expenses_by_user(&self, user_id: &str) -> Result<Vec<Expense>> {
+        let d1 = self.get_d1().await?;
+
+        let query = "SELECT * FROM expenses WHERE user_id = ? ORDER BY expense_date DESC, created_at DESC";
+        let result = d1
+            .prepare(query)
+            .bind(&[JsValue::from_str(user_id)])?
+            .all()
+            .await?;
+
+        result.results()
+    }
+
+    pub async fn get_expense(&self, user_id: &str, id: &str) -> Result<Option<Expense>> {
+        let d1 = self.get_d1().await?;
+
+        let query = "SELECT * FROM expenses WHERE id = ? AND user_id = ?";
+        d1.prepare(query)
+            .bind(&[JsValue::from_str(id), JsValue::from_str(user_id)])?
+            .first::<Expense>(None)
+            .await
+    }
+
+    pub async fn update_expense(&self, expense: &Expense) -> Result<Option<Expense>> {
+        let d1 = self.get_d1().await?;
+
+        let query = "
+            UPDATE expenses
+            SET vendor = ?, description = ?, expense_date = ?, category = ?, net_amount = ?, tax_rate = ?, tax_amount = ?, gross_amount = ?,
+                tax_treatment = ?, supplier_country = ?, deductible_tax_amount = ?, payment_status = ?, paid_at = ?, updated_at = datetime('now')
+            WHERE id = ? AND user_id = ?
+            RETURNING *
+        ";
+
+        d1.prepare(query)
+            .bind(&[
+                JsValue::from_str(&expense.vendor),
+                optional_text(&expense.description),
+                JsValue::from_str(&expense.expense_date),
+                JsValue::from_str(&expense.category),
+                JsValue::from_f64(expense.net_amount),
+                JsValue::from_f64(expense.tax_rate),
+                JsValue::from_f64(expense.tax_amount),
+                JsValue::from_f64(expense.gross_amount),
+                JsValue::from_str(&expense.tax_treatment),
+                optional_text(&expense.supplier_country),
+                JsValue::from_f64(expense.deductible_tax_amount),
+                JsValue::from_str(&expense.payment_status),
+                optional_text(&expense.paid_at),
+                JsValue::from_str(&expense.id),
+                JsValue::from_str(&expense.user_id),
+            ])?
+            .first::<Expense>(None)
+            .await
+    }
+
+    pub async fn set_expense_receipt(
+        &self,
+        id: &str,
+        key: Option<&str>,
+        filename: Option<&str>,
+        content_type: Option<&str>,
+    ) -> Result<()> {
+        let d1 = self.get_d1().await?;
+
+        let query = "
+            UPDATE expenses
+            SET receipt_key = ?, receipt_filename = ?, receipt_content_type = ?, updated_at = datetime('now')
+            WHERE id = ?
+        ";
+
+        let text = |value: Option<&str>| value.map(JsValue::from_str).unwrap_or(JsValue::NULL);
+        d1.prepare(query)
+            .bind(&[text(key), text(filename), text(content_type), JsValue::from_str(id)])?
+            .run()
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_expense(&self, user_id: &str, id: &str) -> Result<()> {
+        let d1 = self.get_d1().await?;
+
+        let query = "DELETE FROM expenses WHERE id = ? AND user_id = ?";
+        d1.prepare(query)
+            .bind(&[JsValue::from_str(id), JsValue::from_str(user_id)])?
+            .run()
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl Database {
+    // Session operations. Each session is one family of rotating refresh
+    // tokens, stored as SHA-256 hashes like on the Axum server.
+    pub async fn create_session(&self, session: &NewSession, token_hash: &str) -> Result<()> {
+        let d1 = self.get_d1().await?;
+
+        let insert_session = d1
+            .prepare(
+                "INSERT INTO sessions (id, user_id, device_label, ip_address, created_at, last_used_at, expires_at)
+                 VALUES (?, ?, ?, ?, datetime('now'), datetime('now'), datetime('now', '+30 days'))",
+            )
+            .bind(&[
+                JsValue::from_str(&session.id),
+                JsValue::from_str(&session.user_id),
+                optional_text(&session.device_label),
+                optional_text(&session.ip_address),
+            ])?;
+        let insert_token = d1
+            .prepare("INSERT INTO refresh_tokens (token_hash, session_id, created_at) VALUES (?, ?, datetime('now'))")
+            .bind(&[JsValue::from_str(token_hash), JsValue::from_str(&session.id)])?;
+
+        d1.batch(vec![insert_session, insert_token]).await?;
+        Ok(())
+    }
+
+    /// Whether a session of the user exists and is neither revoked nor expired.
+    pub async fn is_session_active(&self, session_id: &str, user_id: &str) -> Result<bool> {
+        let d1 = self.get_d1().await?;
+
+        let query = "SELECT id FROM sessions WHERE id = ? AND user_id = ? AND revoked_at IS NULL AND expires_at > datetime('now')";
+        let session = d1
+            .prepare(query)
+            .bind(&[JsValue::from_str(session_id), JsValue::from_str(user_id)])?
+            .first::<serde_json::Value>(None)
+            .await?;
+
+        Ok(session.is_some())
+    }
+
+    pub async fn find_refresh_token(&self, token_hash: &str) -> Result<Option<RefreshTokenSession>> {
+        let d1 = self.get_d1().await?;
+
+        let query = "
+            SELECT t.session_id, t.used_at, s.user_id,
+                   s.revoked_at IS NULL AND s.expires_at > datetime('now') AS active
+            FROM refresh_tokens t JOIN sessions s ON s.id = t.session_id
+            WHERE t.token_hash = ?
+        ";
+        d1.prepare(query)
+            .bind(&[JsValue::from_str(token_hash)])?
+            .first::<RefreshTokenSession>(None)
+            .await
+    }
+
+    /// Marks a refresh token as used. Returns false if it was used before,
+    /// also by a concurrent request.
+    pub async fn use_refresh_token(&self, token_hash: &str) -> Result<bool> {
+        let d1 = self.get_d1().await?;
+
+        let query = "UPDATE refresh_tokens SET used_at = datetime('now') WHERE token_hash = ? AND used_at IS NULL RETURNING token_hash";
+        let used = d1
+            .prepare(query)
+            .bind(&[JsValue::from_str(token_hash)])?
+            .first::<serde_json::Value>(None)
+            .await?;
+
+        Ok(used.is_some())
+    }
+
+    /// Stores the next refresh token of a session and extends the session.
+    pub async fn extend_session(&self, session_id: &str, token_hash: &str, ip_address: Option<String>) -> Result<()> {
+        let d1 = self.get_d1().await?;
+
+        let insert_token = d1
+            .prepare("INSERT INTO refresh_tokens (token_hash, session_id, created_at) VALUES (?, ?, datetime('now'))")
+            .bind(&[JsValue::from_str(token_hash), JsValue::from_str(session_id)])?;
+        let update_session = d1
+            .prepare(
+                "UPDATE sessions SET last_used_at = datetime('now'), expires_at = datetime('now', '+30 days'),
+                 ip_address = COALESCE(?, ip_address) WHERE id = ?",
+            )
+            .bind(&[optional_text(&ip_address), JsValue::from_str(session_id)])?;
+
+        d1.batch(vec![insert_token, update_session]).await?;
+        Ok(())
+    }
+
+    /// Revokes one session, for logout (`logout`) or refresh token reuse
+    /// (`reuse_detected`).
+    pub async fn revoke_session(&self, session_id: &str, reason: &str) -> Result<()> {
+        let d1 = self.get_d1().await?;
+
+        let query = "UPDATE sessions SET revoked_at = datetime('now'), revoked_reason = ? WHERE id = ? AND revoked_at IS NULL";
+        d1.prepare(query)
+            .bind(&[JsValue::from_str(reason), JsValue::from_str(session_id)])?
+            .run()
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn revoke_all_sessions(&self, user_id: &str) -> Result<()> {
+        let d1 = self.get_d1().await?;
+
+        let query = "UPDATE sessions SET revoked_at = datetime('now'), revoked_reason = 'logout_all' WHERE user_id = ? AND revoked_at IS NULL";
+        d1.prepare(query)
+            .bind(&[JsValue::from_str(user_id)])?
+            .run()
+            .await?;
+
+        Ok(())
+    }
+
+    // Login throttle operations
+    /// Seconds until a locked throttle key accepts attempts again.
+    pub async fn login_retry_after(&self, scope: &str, key: &str) -> Result<Option<i64>> {
+        let d1 = self.get_d1().await?;
+
+        let query = "
+            SELECT CAST((julianday(locked_until) - julianday('now')) * 86400 + 0.999 AS INTEGER) AS retry_after
+            FROM login_throttles
+            WHERE scope = ? AND key = ? AND locked_until > datetime('now')
+        ";
+        let throttle = d1
+            .prepare(query)
+            .bind(&[JsValue::from_str(scope), JsValue::from_str(key)])?
+            .first::<LoginRetryAfter>(None)
+            .await?;
+
+        Ok(throttle.map(|throttle| throttle.retry_after.max(1)))
+    }
+
+    /// Counts a failed attempt and returns the consecutive failures. The
+    /// count restarts once the last failure is an hour old.
+    pub async fn record_login_failure(&self, scope: &str, key: &str) -> Result<i64> {
+        let d1 = self.get_d1().await?;
+
+        let query = "
+            INSERT INTO login_throttles (scope, key, failures, last_failure_at) VALUES (?, ?, 1, datetime('now'))
+            ON CONFLICT (scope, key) DO UPDATE SET
+                failures = CASE WHEN last_failure_at < datetime('now', '-60 minutes') THEN 1 ELSE failures + 1 END,
+                last_failure_at = excluded.last_failure_at
+            RETURNING failures
+        ";
+        let throttle = d1
+            .prepare(query)
+            .bind(&[JsValue::from_str(scope), JsValue::from_str(key)])?
+            .first::<LoginFailures>(None)
+            .await?;
+
+        Ok(throttle.map(|throttle| throttle.failures).unwrap_or(1))
+    }
+
+    pub async fn lock_login(&self, scope: &str, key: &str, seconds: i64) -> Result<()> {
+        let d1 = self.get_d1().await?;
+
+        let query = "UPDATE login_throttles SET locked_until = datetime('now', ?) WHERE scope = ? AND key = ?";
+        d1.prepare(query)
+            .bind(&[
+                JsValue::from_str(&format!("+{} seconds", seconds)),
+                JsValue::from_str(scope),
+                JsValue::from_str(key),
+            ])?
+            .run()
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn clear_login_failures(&self, scope: &str, key: &str) -> Result<()> {
+        let d1 = self.get_d1().await?;
+
+        d1.prepare("DELETE FROM login_throttles WHERE scope = ? AND key = ?")
+            .bind(&[JsValue::from_str(scope), JsValue::from_str(key)])?
+            .run()
+            .await?;
+
+        Ok(())
+    }
+
+    // Two-factor operations
+    /// Stores a new TOTP secret that becomes active once a code generated
+    /// from it has been confirmed.
+    pub async fn start_totp_enrolment(&self, user_id: &str, secret: &str) -> Result<()> {
+        let d1 = self.get_d1().await?;
+
+        let query = "UPDATE users SET totp_secret = ?, totp_last_step = NULL, updated_at = datetime('now') WHERE id = ? AND totp_enabled_at IS NULL";
+        d1.prepare(query)
+            .bind(&[JsValue::from_str(secret), JsValue::from_str(user_id)])?
+            .run()
+            .await?;
+
+        Ok(())
+    }
+
+    /// Records the time step of an accepted code. Returns false if a code of
+    /// this or a later step was accepted in the meantime.
+    pub async fn record_totp_step(&self, user_id: &str, step: i64) -> Result<bool> {
+        let d1 = self.get_d1().await?;
+
+        let query = "UPDATE users SET totp_last_step = ? WHERE id = ? AND (totp_last_step IS NULL OR totp_last_step < ?) RETURNING id";
+        let step = JsValue::from_f64(step as f64);
+        let recorded = d1
+            .prepare(query)
+            .bind(&[step.clone(), JsValue::from_str(user_id), step])?
+            .first::<serde_json::Value>(None)
+            .await?;
+
+        Ok(recorded.is_some())
+    }
+
+    /// Activates the enrolled secret and replaces the recovery codes.
+    pub async fn enable_totp(&self, user_id: &str, code_hashes: &[String]) -> Result<()> {
+        let d1 = self.get_d1().await?;
+
+        let mut statements = vec![
+            d1.prepare("UPDATE users SET totp_enabled_at = datetime('now'), updated_at = datetime('now') WHERE id = ?")
+                .bind(&[JsValue::from_str(user_id)])?,
+            d1.prepare("DELETE FROM recovery_codes WHERE user_id = ?")
+                .bind(&[JsValue::from_str(user_id)])?,
+        ];
+        for code_hash in code_hashes {
+            statements.push(
+                d1.prepare("INSERT INTO recovery_codes (id, user_id, code_hash, created_at) VALUES (?, ?, ?, datetime('now'))")
+                    .bind(&[
+                        JsValue::from_str(&uuid::Uuid::new_v4().to_string()),
+                        JsValue::from_str(user_id),
+                        JsValue::from_str(code_hash),
+                    ])?,
+            );
+        }
+
+        d1.batch(statements).await?;
+        Ok(())
+    }
+
+    /// Marks a recovery code as used. Returns false for unknown and used codes.
+    pub async fn use_recovery_code(&self, user_id: &str, code_hash: &str) -> Result<bool> {
+        let d1 = self.get_d1().await?;
+
+        let query = "UPDATE recovery_codes SET used_at = datetime('now') WHERE user_id = ? AND code_hash = ? AND used_at IS NULL RETURNING id";
+        let used = d1
+            .prepare(query)
+            .bind(&[JsValue::from_str(user_id), JsValue::from_str(code_hash)])?
+            .first::<serde_json::Value>(None)
+            .await?;
+
+        Ok(used.is_some())
+    }
+}
+
+impl Database {
+    /// Role of a user in another account's organisation.
+    pub async fn find_membership_role(&self, organisation_id: &str, user_id: &str) -> Result<Option<String>> {
+        let d1 = self.get_d1().await?;
+
+        let query = "
+            SELECT m.role FROM memberships m JOIN users u ON u.id = m.organisation_id
+            WHERE m.organisation_id = ? AND m.user_id = ? AND u.deleted_at IS NULL
+        ";
+        let membership = d1
+            .prepare(query)
+            .bind(&[JsValue::from_str(organisation_id), JsValue::from_str(user_id)])?
+            .first::<MembershipRole>(None)
+            .await?;
+
+        Ok(membership.map(|membership| membership.role))
+    }
+
+    // API key operations. Keys are stored as SHA-256 hashes like on the
+    // Axum server.
+    pub async fn create_api_key(&self, api_key: &ApiKey) -> Result<()> {
+        let d1 = self.get_d1().await?;
+
+        let query = "
+            INSERT INTO api_keys (id, user_id, name, prefix, key_hash, scopes, created_at, expires_at)
+            VALUES (?, ?, ?, ?, ?, ?, datetime('now'), datetime(?))
+        ";
+        d1.prepare(query)
+            .bind(&[
+                JsValue::from_str(&api_key.id),
+                JsValue::from_str(&api_key.user_id),
+                JsValue::from_str(&api_key.name),
+                JsValue::from_str(&api_key.prefix),
+                JsValue::from_str(&api_key.key_hash),
+                JsValue::from_str(&api_key.scopes),
+                optional_text(&api_key.expires_at),
+            ])?
+            .run()
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_api_keys_by_user(&self, user_id: &str) -> Result<Vec<ApiKey>> {
+        let d1 = self.get_d1().await?;
+
+        let result = d1
+            .prepare("SELECT * FROM api_keys WHERE user_id = ? ORDER BY created_at DESC")
+            .bind(&[JsValue::from_str(user_id)])?
             .all()
             .await?;
 
-        result.deserialize()
+        result.results()
+    }
+
+    /// A key that is neither revoked nor expired.
+    pub async fn find_active_api_key(&self, key_hash: &str) -> Result<Option<ApiKey>> {
+        let d1 = self.get_d1().await?;
+
+        let query = "
+            SELECT * FROM api_keys
+            WHERE key_hash = ? AND revoked_at IS NULL AND (expires_at IS NULL OR expires_at > datetime('now'))
+        ";
+        d1.prepare(query)
+            .bind(&[JsValue::from_str(key_hash)])?
+            .first::<ApiKey>(None)
+            .await
+    }
+
+    pub async fn record_api_key_use(&self, id: &str, ip_address: Option<String>) -> Result<()> {
+        let d1 = self.get_d1().await?;
+
+        let query = "UPDATE api_keys SET last_used_at = datetime('now'), last_used_ip = COALESCE(?, last_used_ip) WHERE id = ?";
+        d1.prepare(query)
+            .bind(&[optional_text(&ip_address), JsValue::from_str(id)])?
+            .run()
+            .await?;
+
+        Ok(())
     }
+
+    /// Returns false for unknown keys and keys that were already revoked.
+    pub async fn revoke_api_key(&self, user_id: &str, id: &str) -> Result<bool> {
+        let d1 = self.get_d1().await?;
+
+        let query = "UPDATE api_keys SET revoked_at = datetime('now') WHERE id = ? AND user_id = ? AND revoked_at IS NULL RETURNING id";
+        let revoked = d1
+            .prepare(query)
+            .bind(&[JsValue::from_str(id), JsValue::from_str(user_id)])?
+            .first::<serde_json::Value>(None)
+            .await?;
+
+        Ok(revoked.is_some())
+    }
+}
+
+fn optional_text(value: &Option<String>) -> JsValue {
+    value.as_deref().map(JsValue::from_str).unwrap_or(JsValue::NULL)
 }
 
 // Data Models for D1
@@ -162,10 +646,24 @@ pub struct User {
     pub last_name: Option<String>,
     pub company_name: Option<String>,
     pub tax_id: Option<String>,
+    #[serde(default, skip_serializing)]
+    pub totp_secret: Option<String>,
+    #[serde(default)]
+    pub totp_enabled_at: Option<String>,
+    #[serde(default, skip_serializing)]
+    pub totp_last_step: Option<i64>,
+    #[serde(default)]
+    pub two_factor_required: u8,
     pub created_at: String,
     pub updated_at: String,
 }
 
+impl User {
+    pub fn two_factor_enabled(&self) -> bool {
+        self.totp_enabled_at.is_some()
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct NewUser {
     pub id: String,
@@ -245,4 +743,249 @@ pub struct NewInvoice {
     pub status: String,
     pub notes: Option<String>,
     pub pdf_url: Option<String>,
-}
\ No newline at end of file
+}
+
+pub const EXPENSE_CATEGORIES: &[&str] = &[
+    "goods",
+    "subcontractors",
+    "personnel",
+    "rent",
+    "telecommunication",
+    "travel",
+    "training",
+    "legal_and_tax_advice",
+    "leasing",
+    "insurance_and_fees",
+    "advertising",
+    "vat_payment",
+    "other",
+];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewSession {
+    pub id: String,
+    pub user_id: String,
+    pub device_label: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginRetryAfter {
+    pub retry_after: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginFailures {
+    pub failures: i64,
+}
+
+/// A refresh token with the state of its session.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RefreshTokenSession {
+    pub session_id: String,
+    pub used_at: Option<String>,
+    pub user_id: String,
+    pub active: u8,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MembershipRole {
+    pub role: String,
+}
+
+/// A personal API key; the key itself is only returned at creation.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiKey {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    pub prefix: String,
+    #[serde(default, skip_serializing)]
+    pub key_hash: String,
+    /// Space-separated scope names
+    pub scopes: String,
+    pub created_at: Option<String>,
+    pub expires_at: Option<String>,
+    pub last_used_at: Option<String>,
+    pub last_used_ip: Option<String>,
+    pub revoked_at: Option<String>,
+}
+
+impl ApiKey {
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.split(' ').any(|value| value == scope)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewApiKey {
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<String>,
+}
+
+impl NewApiKey {
+    /// Validates the request the same way the Axum server does and returns
+    /// the key to store for `key`.
+    pub fn into_api_key(self, id: String, user_id: String, key: &str) -> std::result::Result<ApiKey, String> {
+        let name = self.name.trim();
+        if name.is_empty() || name.chars().count() > 100 {
+            return Err("Name must have 1 to 100 characters".to_string());
+        }
+        if self.scopes.is_empty() {
+            return Err("At least one scope is required".to_string());
+        }
+        let mut scopes: Vec<&str> = Vec::new();
+        for value in &self.scopes {
+            if !API_SCOPES.contains(&value.as_str()) {
+                return Err(format!("Unknown scope: {}", value));
+            }
+            if !scopes.contains(&value.as_str()) {
+                scopes.push(value);
+            }
+        }
+        if let Some(expires_at) = &self.expires_at {
+            match chrono::DateTime::parse_from_rfc3339(expires_at) {
+                Ok(expires_at) if expires_at > chrono::Utc::now() => {}
+                Ok(_) => return Err("Expiry must be in the future".to_string()),
+                Err(_) => return Err("Expiry must be an RFC 3339 timestamp".to_string()),
+            }
+        }
+
+        Ok(ApiKey {
+            id,
+            user_id,
+            name: name.to_string(),
+            prefix: AuthService::api_key_prefix(key),
+            key_hash: AuthService::hash_api_key(key),
+            scopes: scopes.join(" "),
+            created_at: None,
+            expires_at: self.expires_at,
+            last_used_at: None,
+            last_used_ip: None,
+            revoked_at: None,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Expense {
+    pub id: String,
+    pub user_id: String,
+    pub vendor: String,
+    pub description: Option<String>,
+    pub expense_date: String,
+    pub category: String,
+    pub net_amount: f64,
+    pub tax_rate: f64,
+    pub tax_amount: f64,
+    pub gross_amount: f64,
+    pub tax_treatment: String,
+    #[serde(default)]
+    pub supplier_country: Option<String>,
+    pub deductible_tax_amount: f64,
+    pub payment_status: String,
+    pub paid_at: Option<String>,
+    pub receipt_key: Option<String>,
+    pub receipt_filename: Option<String>,
+    pub receipt_content_type: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewExpense {
+    pub vendor: String,
+    pub description: Option<String>,
+    pub expense_date: String,
+    pub category: Option<String>,
+    pub net_amount: f64,
+    pub tax_rate: Option<f64>,
+    pub tax_amount: Option<f64>,
+    pub deductible_tax_amount: Option<f64>,
+    pub tax_treatment: Option<String>,
+    pub supplier_country: Option<String>,
+    pub payment_status: Option<String>,
+    pub paid_at: Option<String>,
+}
+
+impl NewExpense {
+    /// Validates the payload and derives VAT, gross amount and payment date
+    /// the same way the Axum server does.
+    pub fn into_expense(self, id: String, user_id: String) -> std::result::Result<Expense, String> {
+        if self.vendor.trim().is_empty() || self.vendor.len() > 200 {
+            return Err("Vendor must be between 1 and 200 characters".to_string());
+        }
+        if chrono::NaiveDate::parse_from_str(&self.expense_date, "%Y-%m-%d").is_err() {
+            return Err("Expense date must be formatted as YYYY-MM-DD".to_string());
+        }
+        let category = self.category.unwrap_or_else(|| "other".to_string());
+        if !EXPENSE_CATEGORIES.contains(&category.as_str()) {
+            return Err(format!("Unknown expense category: {}", category));
+        }
+        let tax_treatment = self.tax_treatment.unwrap_or_else(|| "standard".to_string());
+        if !matches!(tax_treatment.as_str(), "standard" | "reverse_charge") {
+            return Err(format!("Unknown tax treatment: {}", tax_treatment));
+        }
+        if let Some(country) = self.supplier_country.as_deref() {
+            if country.len() != 2 || !country.chars().all(|c| c.is_ascii_alphabetic()) {
+                return Err(format!("Invalid supplier country: {}", country));
+            }
+        }
+        let payment_status = self.payment_status.unwrap_or_else(|| {
+            if self.paid_at.is_some() { "paid" } else { "unpaid" }.to_string()
+        });
+        if !matches!(payment_status.as_str(), "unpaid" | "paid") {
+            return Err(format!("Unknown payment status: {}", payment_status));
+        }
+
+        let tax_rate = self.tax_rate.unwrap_or(19.0);
+        if self.net_amount < 0.0 || !(0.0..=100.0).contains(&tax_rate) {
+            return Err("Amounts must not be negative and the tax rate must be between 0 and 100".to_string());
+        }
+        let tax_amount = self
+            .tax_amount
+            .unwrap_or_else(|| (self.net_amount * tax_rate).round() / 100.0);
+        let deductible_tax_amount = self.deductible_tax_amount.unwrap_or(tax_amount);
+        if tax_amount < 0.0 || deductible_tax_amount < 0.0 {
+            return Err("Amounts must not be negative".to_string());
+        }
+        if deductible_tax_amount > tax_amount {
+            return Err("Deductible input VAT cannot exceed the VAT amount".to_string());
+        }
+
+        // Under reverse charge the VAT goes to the tax office, the vendor is paid the net amount.
+        let gross_amount = if tax_treatment == "reverse_charge" {
+            self.net_amount
+        } else {
+            ((self.net_amount + tax_amount) * 100.0).round() / 100.0
+        };
+        let paid_at = match payment_status.as_str() {
+            "paid" => self.paid_at.or_else(|| Some(format!("{} 00:00:00", self.expense_date))),
+            _ => None,
+        };
+
+        Ok(Expense {
+            id,
+            user_id,
+            vendor: self.vendor,
+            description: self.description,
+            expense_date: self.expense_date,
+            category,
+            net_amount: self.net_amount,
+            tax_rate,
+            tax_amount,
+            gross_amount,
+            tax_treatment,
+            supplier_country: self.supplier_country.map(|country| country.to_uppercase()),
+            deductible_tax_amount,
+            payment_status,
+            paid_at,
+            receipt_key: None,
+            receipt_filename: None,
+            receipt_content_type: None,
+            created_at: String::new(),
+            updated_at: String::new(),
+        })
+    }
+}
@@ -0,0 +1,169 @@
+use serde::Serialize;
+use serde_json::{json, Value};
+
+/// A problem with one field of the request body.
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+/// Errors returned by the handlers, sent with the same JSON body and codes
+/// as the Axum server's `ApiError`. It has the same variants, also those no
+/// worker endpoint needs yet.
+#[derive(Debug)]
+#[allow(dead_code)]
+pub enum ApiError {
+    /// The request data is invalid; `field_errors` names the fields if known.
+    Validation { message: String, field_errors: Vec<FieldError> },
+    /// Missing or invalid credentials
+    Unauthorized(String),
+    /// Authenticated, but not allowed to do this
+    Forbidden(String),
+    NotFound(String),
+    /// The request clashes with the current state, e.g. an enrolment that
+    /// has not been started
+    Conflict(String),
+    /// The data is valid but breaks a business rule
+    Unprocessable(String),
+    PayloadTooLarge(String),
+    UnsupportedMediaType(String),
+    RateLimited { message: String, retry_after: i64 },
+    /// A service the request depends on failed, e.g. the mail server or an
+    /// identity provider
+    Upstream(String),
+    Internal(String),
+}
+
+pub type ApiResult<T> = std::result::Result<T, ApiError>;
+
+impl ApiError {
+    pub fn validation(message: impl Into<String>) -> Self {
+        ApiError::Validation { message: message.into(), field_errors: Vec::new() }
+    }
+
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        ApiError::Unauthorized(message.into())
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        ApiError::Forbidden(message.into())
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        ApiError::NotFound(message.into())
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        ApiError::Conflict(message.into())
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        ApiError::Internal(message.into())
+    }
+
+    pub fn status(&self) -> u16 {
+        match self {
+            ApiError::Validation { .. } => 400,
+            ApiError::Unauthorized(_) => 401,
+            ApiError::Forbidden(_) => 403,
+            ApiError::NotFound(_) => 404,
+            ApiError::Conflict(_) => 409,
+            ApiError::Unprocessable(_) => 422,
+            ApiError::PayloadTooLarge(_) => 413,
+            ApiError::UnsupportedMediaType(_) => 415,
+            ApiError::RateLimited { .. } => 429,
+            ApiError::Upstream(_) => 502,
+            ApiError::Internal(_) => 500,
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            ApiError::Validation { .. } => "Bad Request",
+            ApiError::Unauthorized(_) => "Unauthorized",
+            ApiError::Forbidden(_) => "Forbidden",
+            ApiError::NotFound(_) => "Not Found",
+            ApiError::Conflict(_) => "Conflict",
+            ApiError::Unprocessable(_) => "Unprocessable Entity",
+            ApiError::PayloadTooLarge(_) => "Payload Too Large",
+            ApiError::UnsupportedMediaType(_) => "Unsupported Media Type",
+            ApiError::RateLimited { .. } => "Too Many Requests",
+            ApiError::Upstream(_) => "Bad Gateway",
+            ApiError::Internal(_) => "Internal Server Error",
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::Validation { .. } => "validation_failed",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::Unprocessable(_) => "unprocessable",
+            ApiError::PayloadTooLarge(_) => "payload_too_large",
+            ApiError::UnsupportedMediaType(_) => "unsupported_media_type",
+            ApiError::RateLimited { .. } => "rate_limited",
+            ApiError::Upstream(_) => "upstream_failed",
+            ApiError::Internal(_) => "internal",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            ApiError::Validation { message, .. } | ApiError::RateLimited { message, .. } => message,
+            ApiError::Unauthorized(message)
+            | ApiError::Forbidden(message)
+            | ApiError::NotFound(message)
+            | ApiError::Conflict(message)
+            | ApiError::Unprocessable(message)
+            | ApiError::PayloadTooLarge(message)
+            | ApiError::UnsupportedMediaType(message)
+            | ApiError::Upstream(message)
+            | ApiError::Internal(message) => message,
+        }
+    }
+
+    pub fn retry_after(&self) -> Option<i64> {
+        match self {
+            ApiError::RateLimited { retry_after, .. } => Some(*retry_after),
+            _ => None,
+        }
+    }
+
+    /// The response body, see `docs/api/api-reference.md` (Error Handling).
+    /// Internal errors keep their details to the log.
+    pub fn body(&self) -> Value {
+        let message = match self {
+            ApiError::Internal(_) => "Internal server error",
+            _ => self.message(),
+        };
+        let mut body = json!({
+            "error": self.title(),
+            "code": self.code(),
+            "message": message,
+        });
+        match self {
+            ApiError::Validation { field_errors, .. } if !field_errors.is_empty() => {
+                body["field_errors"] = json!(field_errors);
+            }
+            ApiError::RateLimited { retry_after, .. } => body["retry_after"] = json!(retry_after),
+            _ => {}
+        }
+        body
+    }
+}
+
+/// Failures of the runtime, D1 or R2 are internal errors.
+impl From<worker::Error> for ApiError {
+    fn from(error: worker::Error) -> Self {
+        ApiError::Internal(error.to_string())
+    }
+}
+
+impl From<serde_json::Error> for ApiError {
+    fn from(error: serde_json::Error) -> Self {
+        ApiError::Internal(error.to_string())
+    }
+}
@@ -1,19 +1,27 @@
-use worker::{Request, Response, RouteContext, Result, Url};
+use worker::{console_error, Env, Request, Response, RouteContext, Result};
 use serde_json::json;
 use uuid::Uuid;
 
-use crate::db::{Database, NewUser, NewClient, NewInvoice};
-use crate::auth::{AuthService, LoginRequest, User};
+use crate::db::{self, Database, NewApiKey, NewUser, NewClient, NewInvoice, NewExpense, NewSession};
+use crate::auth::{
+    AuthService, ChallengeRequest, JwtKeys, LoginRequest, LoginResponse, Member, Permission, RefreshRequest,
+    TwoFactorChallenge, TwoFactorEnrolment, TwoFactorLoginRequest, User, CHALLENGE_SECONDS, ORGANISATION_HEADER,
+};
+use crate::error::{ApiError, ApiResult};
+use crate::storage::{receipt_key, R2ReceiptStorage, ReceiptStorage};
 
-pub async fn health_check(_req: Request, _ctx: RouteContext<()>) -> Result<Response> {
-    Response::ok("OK")
+/// Also loads the JWT keys, so a missing or invalid key shows up in the
+/// health check right after a deployment instead of in the first login.
+pub async fn health_check(_req: Request, ctx: RouteContext<()>) -> ApiResult<Response> {
+    JwtKeys::from_env(&ctx.env)?;
+    Ok(Response::ok("OK")?)
 }
 
-pub async fn register_user(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
+pub async fn register_user(mut req: Request, ctx: RouteContext<()>) -> ApiResult<Response> {
     let db = Database::new(ctx.env);
     
     // Parse request body
-    let user_data: NewUser = req.json().await?;
+    let user_data: NewUser = read_json(&mut req).await?;
     
     // Hash password
     let password_hash = AuthService::hash_password(&user_data.password_hash)?;
@@ -38,37 +46,68 @@ pub async fn register_user(mut req: Request, ctx: RouteContext<()>) -> Result<Re
             "tax_id": user.tax_id
         }
     });
-    
-    let mut headers = worker::Headers::new();
-    headers.set("Access-Control-Allow-Origin", "https://minidebet.pages.dev")?;
-    headers.set("Content-Type", "application/json")?;
-    
-    Response::from_json(&response_body)
-        .unwrap()
-        .with_headers(headers)
+
+    json_response(&response_body, 200)
 }
 
-pub async fn login_user(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
+pub async fn login_user(mut req: Request, ctx: RouteContext<()>) -> ApiResult<Response> {
+    let keys = JwtKeys::from_env(&ctx.env)?;
     let db = Database::new(ctx.env);
     
     // Parse request body
-    let login_data: LoginRequest = req.json().await?;
-    
-    // Find user by email
-    let user = db.find_user_by_email(&login_data.email).await?
-        .ok_or_else(|| worker::Error::from("User not found"))?;
-    
-    // Verify password
-    let is_valid = AuthService::verify_password(&login_data.password, &user.password_hash)?;
+    let login_data: LoginRequest = read_json(&mut req).await?;
     
-    if !is_valid {
-        return Response::error("Invalid credentials", 401);
+    let account = AuthService::account_key(&login_data.email);
+    let ip_address = req.headers().get("CF-Connecting-IP")?;
+    check_login_throttle(&db, &account, ip_address.as_deref()).await?;
+
+    // Unknown addresses and wrong passwords get the same answer
+    let user = db.find_user_by_email(login_data.email.trim()).await?;
+    let password_hash = user.as_ref().map(|user| user.password_hash.as_str());
+    let user = match user {
+        Some(user) if AuthService::verify_login_password(&login_data.password, password_hash) => user,
+        _ => {
+            record_login_failure(&db, &account, ip_address.as_deref()).await?;
+            return Err(ApiError::unauthorized("Invalid credentials"));
+        }
+    };
+
+    // Accounts with two-factor authentication get a challenge instead of tokens
+    if user.two_factor_enabled() || user.two_factor_required == 1 {
+        let challenge = TwoFactorChallenge {
+            two_factor_required: true,
+            setup_required: !user.two_factor_enabled(),
+            challenge_token: AuthService::generate_challenge(&keys, &user.id)?,
+            expires_in: CHALLENGE_SECONDS,
+        };
+        return json_response(&serde_json::to_value(&challenge)?, 200);
     }
-    
-    // Generate JWT token
-    let token = AuthService::generate_token(&user.id, &user.email)?;
-    
-    // Create user response object
+
+    db.clear_login_failures("account", &account).await?;
+    let login_response = start_session(&db, &keys, user, login_data.device_label, &req).await?;
+
+    json_response(&serde_json::to_value(&login_response)?, 200)
+}
+
+/// Starts a session with its first refresh token.
+async fn start_session(
+    db: &Database,
+    keys: &JwtKeys,
+    user: db::User,
+    device_label: Option<String>,
+    req: &Request,
+) -> ApiResult<LoginResponse> {
+    let session = NewSession {
+        id: Uuid::new_v4().to_string(),
+        user_id: user.id.clone(),
+        device_label: device_label.or(req.headers().get("User-Agent")?),
+        ip_address: req.headers().get("CF-Connecting-IP")?,
+    };
+    let refresh_token = AuthService::new_refresh_token();
+    db.create_session(&session, &AuthService::hash_refresh_token(&refresh_token)).await?;
+
+    let token = AuthService::generate_token(keys, &user.id, &user.email, &session.id)?;
+
     let user_response = User {
         id: user.id,
         email: user.email,
@@ -77,30 +116,205 @@ pub async fn login_user(mut req: Request, ctx: RouteContext<()>) -> Result<Respo
         company_name: user.company_name,
         tax_id: user.tax_id,
     };
-    
-    let login_response = AuthService::create_auth_response(user_response, token);
-    
-    let mut headers = worker::Headers::new();
-    headers.set("Access-Control-Allow-Origin", "https://minidebet.pages.dev")?;
-    headers.set("Content-Type", "application/json")?;
-    
-    Response::from_json(&login_response)
-        .unwrap()
-        .with_headers(headers)
+
+    Ok(AuthService::create_auth_response(Some(user_response), token, refresh_token, session.id))
 }
 
-pub async fn create_client(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
-    // Extract and verify JWT token
-    let token = AuthService::extract_token_from_header(&req.headers())
-        .ok_or_else(|| worker::Error::from("Missing authorization token"))?;
-    
-    let claims = AuthService::verify_token(&token)?;
+/// Rejects the attempt while the account or the client address is locked
+/// after failed attempts.
+async fn check_login_throttle(db: &Database, account: &str, ip_address: Option<&str>) -> ApiResult<()> {
+    let keys = [Some(("account", account)), ip_address.map(|ip| ("ip", ip))];
+    for (scope, key) in keys.into_iter().flatten() {
+        if let Some(seconds) = db.login_retry_after(scope, key).await? {
+            return Err(ApiError::RateLimited {
+                message: format!("Too many failed login attempts; try again in {} seconds", seconds),
+                retry_after: seconds,
+            });
+        }
+    }
+    Ok(())
+}
+
+async fn record_login_failure(db: &Database, account: &str, ip_address: Option<&str>) -> Result<()> {
+    let keys = [Some(("account", account)), ip_address.map(|ip| ("ip", ip))];
+    for (scope, key) in keys.into_iter().flatten() {
+        let failures = db.record_login_failure(scope, key).await?;
+        if let Some(seconds) = AuthService::login_delay_seconds(scope, failures) {
+            db.lock_login(scope, key, seconds).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn challenge_user(db: &Database, keys: &JwtKeys, challenge_token: &str) -> Result<Option<db::User>> {
+    match AuthService::verify_challenge(keys, challenge_token) {
+        Ok(claims) => db.find_user_by_id(&claims.sub).await,
+        Err(_) => Ok(None),
+    }
+}
+
+/// Checks a TOTP code and records its time step so it cannot be replayed.
+async fn verify_totp(db: &Database, user: &db::User, code: &str) -> Result<bool> {
+    let step = user
+        .totp_secret
+        .as_deref()
+        .and_then(|secret| AuthService::verify_totp(secret, code, user.totp_last_step));
+
+    match step {
+        Some(step) => db.record_totp_step(&user.id, step).await,
+        None => Ok(false),
+    }
+}
+
+/// Second login step with a TOTP code or a recovery code. Accounts that
+/// have to set up two-factor authentication confirm their new secret here
+/// and receive recovery codes.
+pub async fn login_two_factor(mut req: Request, ctx: RouteContext<()>) -> ApiResult<Response> {
+    let keys = JwtKeys::from_env(&ctx.env)?;
+    let db = Database::new(ctx.env);
+
+    let login_data: TwoFactorLoginRequest = read_json(&mut req).await?;
+    let user = match challenge_user(&db, &keys, &login_data.challenge_token).await? {
+        Some(user) => user,
+        None => return Err(ApiError::unauthorized("Invalid or expired login challenge")),
+    };
+    // Wrong codes count like wrong passwords
+    let account = AuthService::account_key(&user.email);
+    let ip_address = req.headers().get("CF-Connecting-IP")?;
+    check_login_throttle(&db, &account, ip_address.as_deref()).await?;
+
+    let mut recovery_codes = None;
+    if user.two_factor_enabled() {
+        match (&login_data.code, &login_data.recovery_code) {
+            (Some(code), _) => {
+                if !verify_totp(&db, &user, code).await? {
+                    record_login_failure(&db, &account, ip_address.as_deref()).await?;
+                    return Err(ApiError::unauthorized("Invalid two-factor code"));
+                }
+            }
+            (None, Some(recovery_code)) => {
+                if !db.use_recovery_code(&user.id, &AuthService::hash_recovery_code(recovery_code)).await? {
+                    record_login_failure(&db, &account, ip_address.as_deref()).await?;
+                    return Err(ApiError::unauthorized("Invalid recovery code"));
+                }
+            }
+            (None, None) => return Err(ApiError::validation("A two-factor code or recovery code is required")),
+        }
+    } else {
+        let code = match &login_data.code {
+            Some(code) => code,
+            None => return Err(ApiError::validation("A two-factor code is required")),
+        };
+        if user.totp_secret.is_none() {
+            return Err(ApiError::conflict("Start the enrolment first"));
+        }
+        if !verify_totp(&db, &user, code).await? {
+            record_login_failure(&db, &account, ip_address.as_deref()).await?;
+            return Err(ApiError::unauthorized("Invalid two-factor code"));
+        }
+
+        let codes = AuthService::generate_recovery_codes();
+        let hashes: Vec<String> = codes.iter().map(|code| AuthService::hash_recovery_code(code)).collect();
+        db.enable_totp(&user.id, &hashes).await?;
+        recovery_codes = Some(codes);
+    }
+
+    db.clear_login_failures("account", &account).await?;
+    let mut response = start_session(&db, &keys, user, login_data.device_label, &req).await?;
+    response.recovery_codes = recovery_codes;
+    json_response(&serde_json::to_value(&response)?, 200)
+}
+
+/// Enrolment during login for accounts that require two-factor
+/// authentication but have none set up.
+pub async fn login_two_factor_setup(mut req: Request, ctx: RouteContext<()>) -> ApiResult<Response> {
+    let keys = JwtKeys::from_env(&ctx.env)?;
+    let db = Database::new(ctx.env);
+
+    let challenge: ChallengeRequest = read_json(&mut req).await?;
+    let user = match challenge_user(&db, &keys, &challenge.challenge_token).await? {
+        Some(user) => user,
+        None => return Err(ApiError::unauthorized("Invalid or expired login challenge")),
+    };
+    if user.two_factor_enabled() {
+        return Err(ApiError::conflict("Two-factor authentication is already enabled"));
+    }
+    if user.two_factor_required != 1 {
+        return Err(ApiError::conflict("Set up two-factor authentication in the account settings"));
+    }
+
+    let secret = AuthService::generate_totp_secret();
+    db.start_totp_enrolment(&user.id, &secret).await?;
+
+    let enrolment = TwoFactorEnrolment {
+        otpauth_uri: AuthService::totp_provisioning_uri(&secret, &user.email),
+        secret,
+    };
+    json_response(&serde_json::to_value(&enrolment)?, 200)
+}
+
+/// Rotates a refresh token. A token that was already used revokes its
+/// session, since either the client or an attacker holds a stolen copy.
+pub async fn refresh_session(mut req: Request, ctx: RouteContext<()>) -> ApiResult<Response> {
+    let keys = JwtKeys::from_env(&ctx.env)?;
+    let db = Database::new(ctx.env);
+
+    let refresh_data: RefreshRequest = read_json(&mut req).await?;
+    let token_hash = AuthService::hash_refresh_token(&refresh_data.refresh_token);
+
+    let token = match db.find_refresh_token(&token_hash).await? {
+        Some(token) if token.active == 1 => token,
+        _ => return Err(ApiError::unauthorized("Invalid or expired refresh token")),
+    };
+    if token.used_at.is_some() || !db.use_refresh_token(&token_hash).await? {
+        db.revoke_session(&token.session_id, "reuse_detected").await?;
+        return Err(ApiError::unauthorized("Refresh token was already used; the session has been revoked"));
+    }
+
+    let user = match db.find_user_by_id(&token.user_id).await? {
+        Some(user) => user,
+        None => return Err(ApiError::unauthorized("Invalid or expired refresh token")),
+    };
+
+    let refresh_token = AuthService::new_refresh_token();
+    let ip_address = req.headers().get("CF-Connecting-IP")?;
+    db.extend_session(&token.session_id, &AuthService::hash_refresh_token(&refresh_token), ip_address).await?;
+
+    let access_token = AuthService::generate_token(&keys, &user.id, &user.email, &token.session_id)?;
+    let response = AuthService::create_auth_response(None, access_token, refresh_token, token.session_id);
+    json_response(&serde_json::to_value(&response)?, 200)
+}
+
+/// Ends the session of a refresh token.
+pub async fn logout_session(mut req: Request, ctx: RouteContext<()>) -> ApiResult<Response> {
+    let db = Database::new(ctx.env);
+
+    let refresh_data: RefreshRequest = read_json(&mut req).await?;
+    if let Some(token) = db.find_refresh_token(&AuthService::hash_refresh_token(&refresh_data.refresh_token)).await? {
+        db.revoke_session(&token.session_id, "logout").await?;
+    }
+
+    Ok(Response::empty()?.with_status(204).with_headers(cors_headers()?))
+}
+
+/// Ends all sessions of the current user.
+pub async fn logout_all_sessions(req: Request, ctx: RouteContext<()>) -> ApiResult<Response> {
+    let user_id = user_id_from_request(&req, &ctx.env).await?;
+    let db = Database::new(ctx.env);
+
+    db.revoke_all_sessions(&user_id).await?;
+
+    Ok(Response::empty()?.with_status(204).with_headers(cors_headers()?))
+}
+
+pub async fn create_client(mut req: Request, ctx: RouteContext<()>) -> ApiResult<Response> {
+    let user_id = member_from_request(&req, &ctx.env, Permission::Edit).await?.organisation_id;
     
     let db = Database::new(ctx.env);
     
     // Parse request body
-    let mut client_data: NewClient = req.json().await?;
-    client_data.user_id = claims.sub; // Set user_id from token
+    let mut client_data: NewClient = read_json(&mut req).await?;
+    client_data.user_id = user_id;
     
     // Create client in database
     let client = db.create_client(&client_data).await?;
@@ -109,53 +323,33 @@ pub async fn create_client(mut req: Request, ctx: RouteContext<()>) -> Result<Re
         "message": "Client created successfully",
         "client": client
     });
-    
-    let mut headers = worker::Headers::new();
-    headers.set("Access-Control-Allow-Origin", "https://minidebet.pages.dev")?;
-    headers.set("Content-Type", "application/json")?;
-    
-    Response::from_json(&response_body)
-        .unwrap()
-        .with_headers(headers)
+
+    json_response(&response_body, 200)
 }
 
-pub async fn get_clients(req: Request, ctx: RouteContext<()>) -> Result<Response> {
-    // Extract and verify JWT token
-    let token = AuthService::extract_token_from_header(&req.headers())
-        .ok_or_else(|| worker::Error::from("Missing authorization token"))?;
-    
-    let claims = AuthService::verify_token(&token)?;
+pub async fn get_clients(req: Request, ctx: RouteContext<()>) -> ApiResult<Response> {
+    let user_id = member_from_request(&req, &ctx.env, Permission::View).await?.organisation_id;
     
     let db = Database::new(ctx.env);
     
     // Get clients for user
-    let clients = db.get_clients_by_user(&claims.sub).await?;
+    let clients = db.get_clients_by_user(&user_id).await?;
     
     let response_body = json!({
         "clients": clients
     });
-    
-    let mut headers = worker::Headers::new();
-    headers.set("Access-Control-Allow-Origin", "https://minidebet.pages.dev")?;
-    headers.set("Content-Type", "application/json")?;
-    
-    Response::from_json(&response_body)
-        .unwrap()
-        .with_headers(headers)
+
+    json_response(&response_body, 200)
 }
 
-pub async fn create_invoice(mut req: Request, ctx: RouteContext<()>) -> Result<Response> {
-    // Extract and verify JWT token
-    let token = AuthService::extract_token_from_header(&req.headers())
-        .ok_or_else(|| worker::Error::from("Missing authorization token"))?;
-    
-    let claims = AuthService::verify_token(&token)?;
+pub async fn create_invoice(mut req: Request, ctx: RouteContext<()>) -> ApiResult<Response> {
+    let user_id = member_from_request(&req, &ctx.env, Permission::Edit).await?.organisation_id;
     
     let db = Database::new(ctx.env);
     
     // Parse request body
-    let mut invoice_data: NewInvoice = req.json().await?;
-    invoice_data.user_id = claims.sub; // Set user_id from token
+    let mut invoice_data: NewInvoice = read_json(&mut req).await?;
+    invoice_data.user_id = user_id;
     
     // Create invoice in database
     let invoice = db.create_invoice(&invoice_data).await?;
@@ -164,37 +358,334 @@ pub async fn create_invoice(mut req: Request, ctx: RouteContext<()>) -> Result<R
         "message": "Invoice created successfully",
         "invoice": invoice
     });
-    
-    let mut headers = worker::Headers::new();
-    headers.set("Access-Control-Allow-Origin", "https://minidebet.pages.dev")?;
-    headers.set("Content-Type", "application/json")?;
-    
-    Response::from_json(&response_body)
-        .unwrap()
-        .with_headers(headers)
+
+    json_response(&response_body, 200)
 }
 
-pub async fn get_invoices(req: Request, ctx: RouteContext<()>) -> Result<Response> {
-    // Extract and verify JWT token
-    let token = AuthService::extract_token_from_header(&req.headers())
-        .ok_or_else(|| worker::Error::from("Missing authorization token"))?;
-    
-    let claims = AuthService::verify_token(&token)?;
+pub async fn get_invoices(req: Request, ctx: RouteContext<()>) -> ApiResult<Response> {
+    let user_id = member_from_request(&req, &ctx.env, Permission::View).await?.organisation_id;
     
     let db = Database::new(ctx.env);
     
     // Get invoices for user
-    let invoices = db.get_invoices_by_user(&claims.sub).await?;
+    let invoices = db.get_invoices_by_user(&user_id).await?;
     
     let response_body = json!({
         "invoices": invoices
     });
-    
-    let mut headers = worker::Headers::new();
+
+    json_response(&response_body, 200)
+}
+
+const MAX_RECEIPT_SIZE: usize = 10 * 1024 * 1024;
+
+const RECEIPT_CONTENT_TYPES: &[&str] = &["application/pdf", "image/jpeg", "image/png", "image/webp"];
+
+fn cors_headers() -> Result<worker::Headers> {
+    let headers = worker::Headers::new();
     headers.set("Access-Control-Allow-Origin", "https://minidebet.pages.dev")?;
+    Ok(headers)
+}
+
+fn json_response(body: &serde_json::Value, status: u16) -> ApiResult<Response> {
+    let headers = cors_headers()?;
     headers.set("Content-Type", "application/json")?;
-    
-    Response::from_json(&response_body)
-        .unwrap()
-        .with_headers(headers)
-}
\ No newline at end of file
+
+    Ok(Response::from_json(body)?
+        .with_status(status)
+        .with_headers(headers))
+}
+
+/// Sends a handler's error as JSON; the router maps every handler's result
+/// with `respond`.
+pub fn error_response(error: ApiError) -> Result<Response> {
+    if let ApiError::Internal(details) = &error {
+        console_error!("Internal error: {}", details);
+    }
+    let headers = cors_headers()?;
+    headers.set("Content-Type", "application/json")?;
+    if let Some(seconds) = error.retry_after() {
+        headers.set("Retry-After", &seconds.to_string())?;
+    }
+
+    Ok(Response::from_json(&error.body())?
+        .with_status(error.status())
+        .with_headers(headers))
+}
+
+pub fn respond(result: ApiResult<Response>) -> Result<Response> {
+    result.or_else(error_response)
+}
+
+/// Parses the JSON request body; a malformed body is the client's error.
+async fn read_json<T: serde::de::DeserializeOwned>(req: &mut Request) -> ApiResult<T> {
+    req.json().await.map_err(|_| ApiError::validation("The request body is not valid JSON"))
+}
+
+/// The user a request is made for, from an access token or from an API key
+/// that has the scope the endpoint needs.
+async fn user_id_from_request(req: &Request, env: &Env) -> ApiResult<String> {
+    let invalid_token = || ApiError::unauthorized("Invalid or missing authentication token");
+    let token = AuthService::extract_token_from_header(req.headers()).ok_or_else(invalid_token)?;
+    if !AuthService::is_api_key(&token) {
+        let claims = AuthService::verify_token(&JwtKeys::from_env(env)?, &token).map_err(|_| invalid_token())?;
+        // Access tokens of revoked sessions are rejected before they expire.
+        let session_id = claims.sid.as_deref().ok_or_else(invalid_token)?;
+        if !Database::new(env.clone()).is_session_active(session_id, &claims.sub).await? {
+            return Err(invalid_token());
+        }
+        return Ok(claims.sub);
+    }
+
+    let db = Database::new(env.clone());
+    let api_key = db
+        .find_active_api_key(&AuthService::hash_api_key(&token))
+        .await?
+        .ok_or_else(invalid_token)?;
+    let scope = AuthService::required_scope(req.method().as_ref(), &req.path());
+    if !scope.is_some_and(|scope| api_key.has_scope(scope)) {
+        return Err(ApiError::forbidden("The API key lacks the scope for this endpoint"));
+    }
+
+    db.record_api_key_use(&api_key.id, req.headers().get("CF-Connecting-IP")?).await?;
+    Ok(api_key.user_id)
+}
+
+/// The organisation a request works in: the caller's own, or the one named
+/// in the `X-Organisation-Id` header if the caller is a member with a role
+/// that allows `permission`.
+async fn member_from_request(req: &Request, env: &Env, permission: Permission) -> ApiResult<Member> {
+    let user_id = user_id_from_request(req, env).await?;
+    let organisation_id = req.headers().get(ORGANISATION_HEADER)?.unwrap_or_else(|| user_id.clone());
+
+    let role = if organisation_id == user_id {
+        "owner".to_string()
+    } else {
+        Database::new(env.clone())
+            .find_membership_role(&organisation_id, &user_id)
+            .await?
+            .ok_or_else(|| ApiError::forbidden("Not a member of this organisation"))?
+    };
+    let member = Member { organisation_id, role };
+    if !member.allows(permission) {
+        return Err(ApiError::forbidden(format!("The {} role does not allow this", member.role)));
+    }
+    Ok(member)
+}
+
+pub async fn create_expense(mut req: Request, ctx: RouteContext<()>) -> ApiResult<Response> {
+    let user_id = member_from_request(&req, &ctx.env, Permission::Edit).await?.organisation_id;
+    let db = Database::new(ctx.env);
+
+    let expense_data: NewExpense = read_json(&mut req).await?;
+    let expense = match expense_data.into_expense(Uuid::new_v4().to_string(), user_id) {
+        Ok(expense) => expense,
+        Err(message) => return Err(ApiError::validation(message)),
+    };
+
+    let expense = db.create_expense(&expense).await?;
+
+    json_response(
+        &json!({
+            "message": "Expense created successfully",
+            "expense": expense
+        }),
+        201,
+    )
+}
+
+pub async fn get_expenses(req: Request, ctx: RouteContext<()>) -> ApiResult<Response> {
+    let user_id = member_from_request(&req, &ctx.env, Permission::View).await?.organisation_id;
+    let db = Database::new(ctx.env);
+
+    let expenses = db.get_expenses_by_user(&user_id).await?;
+
+    json_response(&json!({ "expenses": expenses }), 200)
+}
+
+pub async fn get_expense(req: Request, ctx: RouteContext<()>) -> ApiResult<Response> {
+    let user_id = member_from_request(&req, &ctx.env, Permission::View).await?.organisation_id;
+    let id = ctx.param("id").cloned().unwrap_or_default();
+    let db = Database::new(ctx.env);
+
+    match db.get_expense(&user_id, &id).await? {
+        Some(expense) => json_response(&json!({ "expense": expense }), 200),
+        None => Err(ApiError::not_found("Expense not found")),
+    }
+}
+
+pub async fn update_expense(mut req: Request, ctx: RouteContext<()>) -> ApiResult<Response> {
+    let user_id = member_from_request(&req, &ctx.env, Permission::Edit).await?.organisation_id;
+    let id = ctx.param("id").cloned().unwrap_or_default();
+    let db = Database::new(ctx.env);
+
+    let expense_data: NewExpense = read_json(&mut req).await?;
+    let expense = match expense_data.into_expense(id, user_id) {
+        Ok(expense) => expense,
+        Err(message) => return Err(ApiError::validation(message)),
+    };
+
+    match db.update_expense(&expense).await? {
+        Some(expense) => json_response(&json!({ "expense": expense }), 200),
+        None => Err(ApiError::not_found("Expense not found")),
+    }
+}
+
+pub async fn delete_expense(req: Request, ctx: RouteContext<()>) -> ApiResult<Response> {
+    let user_id = member_from_request(&req, &ctx.env, Permission::Edit).await?.organisation_id;
+    let id = ctx.param("id").cloned().unwrap_or_default();
+    let receipts = R2ReceiptStorage::new(&ctx.env)?;
+    let db = Database::new(ctx.env);
+
+    let expense = match db.get_expense(&user_id, &id).await? {
+        Some(expense) => expense,
+        None => return Err(ApiError::not_found("Expense not found")),
+    };
+
+    if let Some(key) = expense.receipt_key.as_deref() {
+        receipts.delete(key).await?;
+    }
+    db.delete_expense(&user_id, &id).await?;
+
+    Ok(Response::empty()?.with_status(204).with_headers(cors_headers()?))
+}
+
+pub async fn upload_receipt(mut req: Request, ctx: RouteContext<()>) -> ApiResult<Response> {
+    let user_id = member_from_request(&req, &ctx.env, Permission::Edit).await?.organisation_id;
+    let id = ctx.param("id").cloned().unwrap_or_default();
+    let receipts = R2ReceiptStorage::new(&ctx.env)?;
+    let db = Database::new(ctx.env);
+
+    let expense = match db.get_expense(&user_id, &id).await? {
+        Some(expense) => expense,
+        None => return Err(ApiError::not_found("Expense not found")),
+    };
+
+    let content_type = req
+        .headers()
+        .get("Content-Type")?
+        .map(|value| value.split(';').next().unwrap_or_default().trim().to_lowercase())
+        .unwrap_or_default();
+    if !RECEIPT_CONTENT_TYPES.contains(&content_type.as_str()) {
+        return Err(ApiError::UnsupportedMediaType(
+            "Receipts must be PDF, JPEG, PNG or WebP files".to_string(),
+        ));
+    }
+
+    let data = req.bytes().await?;
+    if data.is_empty() {
+        return Err(ApiError::validation("Receipt file is empty"));
+    }
+    if data.len() > MAX_RECEIPT_SIZE {
+        return Err(ApiError::PayloadTooLarge("Receipt file exceeds 10 MB".to_string()));
+    }
+
+    let filename = req
+        .url()?
+        .query_pairs()
+        .find(|(name, _)| name == "filename")
+        .map(|(_, value)| value.into_owned());
+
+    let key = receipt_key(&user_id, &expense.id);
+    receipts.put(&key, data, &content_type).await?;
+    db.set_expense_receipt(&expense.id, Some(&key), filename.as_deref(), Some(&content_type))
+        .await?;
+
+    match db.get_expense(&user_id, &id).await? {
+        Some(expense) => json_response(&json!({ "expense": expense }), 200),
+        None => Err(ApiError::not_found("Expense not found")),
+    }
+}
+
+pub async fn get_receipt(req: Request, ctx: RouteContext<()>) -> ApiResult<Response> {
+    let user_id = member_from_request(&req, &ctx.env, Permission::View).await?.organisation_id;
+    let id = ctx.param("id").cloned().unwrap_or_default();
+    let receipts = R2ReceiptStorage::new(&ctx.env)?;
+    let db = Database::new(ctx.env);
+
+    let expense = match db.get_expense(&user_id, &id).await? {
+        Some(expense) => expense,
+        None => return Err(ApiError::not_found("Expense not found")),
+    };
+    let key = match expense.receipt_key.as_deref() {
+        Some(key) => key,
+        None => return Err(ApiError::not_found("Expense has no receipt")),
+    };
+    let data = match receipts.get(key).await? {
+        Some(data) => data,
+        None => return Err(ApiError::not_found("Receipt file not found")),
+    };
+
+    let headers = cors_headers()?;
+    headers.set(
+        "Content-Type",
+        expense.receipt_content_type.as_deref().unwrap_or("application/octet-stream"),
+    )?;
+    if let Some(filename) = expense.receipt_filename.as_deref() {
+        headers.set(
+            "Content-Disposition",
+            &format!("inline; filename=\"{}\"", filename.replace('"', "")),
+        )?;
+    }
+
+    Ok(Response::from_bytes(data)?.with_headers(headers))
+}
+
+pub async fn delete_receipt(req: Request, ctx: RouteContext<()>) -> ApiResult<Response> {
+    let user_id = member_from_request(&req, &ctx.env, Permission::Edit).await?.organisation_id;
+    let id = ctx.param("id").cloned().unwrap_or_default();
+    let receipts = R2ReceiptStorage::new(&ctx.env)?;
+    let db = Database::new(ctx.env);
+
+    let expense = match db.get_expense(&user_id, &id).await? {
+        Some(expense) => expense,
+        None => return Err(ApiError::not_found("Expense not found")),
+    };
+    let key = match expense.receipt_key.as_deref() {
+        Some(key) => key,
+        None => return Err(ApiError::not_found("Expense has no receipt")),
+    };
+
+    receipts.delete(key).await?;
+    db.set_expense_receipt(&expense.id, None, None, None).await?;
+
+    Ok(Response::empty()?.with_status(204).with_headers(cors_headers()?))
+}
+
+/// Lists the API keys of the current user, also revoked ones.
+pub async fn get_api_keys(req: Request, ctx: RouteContext<()>) -> ApiResult<Response> {
+    let user_id = user_id_from_request(&req, &ctx.env).await?;
+    let db = Database::new(ctx.env);
+
+    let api_keys = db.get_api_keys_by_user(&user_id).await?;
+
+    json_response(&json!({ "api_keys": api_keys }), 200)
+}
+
+/// Creates an API key. The key is returned once; only its hash is stored.
+pub async fn create_api_key(mut req: Request, ctx: RouteContext<()>) -> ApiResult<Response> {
+    let user_id = user_id_from_request(&req, &ctx.env).await?;
+    let db = Database::new(ctx.env);
+
+    let key = AuthService::new_api_key();
+    let api_key_data: NewApiKey = read_json(&mut req).await?;
+    let api_key = match api_key_data.into_api_key(Uuid::new_v4().to_string(), user_id, &key) {
+        Ok(api_key) => api_key,
+        Err(message) => return Err(ApiError::validation(message)),
+    };
+    db.create_api_key(&api_key).await?;
+
+    json_response(&json!({ "api_key": api_key, "key": key }), 201)
+}
+
+pub async fn delete_api_key(req: Request, ctx: RouteContext<()>) -> ApiResult<Response> {
+    let user_id = user_id_from_request(&req, &ctx.env).await?;
+    let id = ctx.param("id").cloned().unwrap_or_default();
+    let db = Database::new(ctx.env);
+
+    if !db.revoke_api_key(&user_id, &id).await? {
+        return Err(ApiError::not_found("API key not found"));
+    }
+
+    Ok(Response::empty()?.with_status(204).with_headers(cors_headers()?))
+}
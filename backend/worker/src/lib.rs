@@ -1,42 +1,69 @@
 use worker::*;
-use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize)]
-struct HealthResponse {
-    status: String,
-    timestamp: String,
-    version: String,
+mod db;
+mod auth;
+mod error;
+mod handlers;
+mod storage;
+
+use handlers::*;
+
+/// Route handler for a function returning `ApiResult`: failures are sent as
+/// the JSON error body by `respond`.
+macro_rules! api {
+    ($handler:ident) => {
+        |req, ctx| async move { respond($handler(req, ctx).await) }
+    };
 }
 
 #[event(fetch)]
 pub async fn main(req: Request, env: Env, _ctx: Context) -> Result<Response> {
-    console_log!("MiniDebet Worker received request: {:?}", req.url());
-    
+    console_log!("Received request: {:?}", req.url());
+
+    // Handle CORS preflight requests for all routes
+    if req.method() == Method::Options {
+        return handle_cors_preflight();
+    }
+
     let router = Router::new();
-    
+
     router
         .get("/", |_, _| Response::ok("MiniDebet Worker API"))
-        .get("/health", |_, _| {
-            let response = HealthResponse {
-                status: "healthy".to_string(),
-                timestamp: chrono::Utc::now().to_rfc3339(),
-                version: "1.0.0".to_string(),
-            };
-            Response::from_json(&response)
-        })
-        .get_async("/api/users", get_users)
-        .post_async("/api/users", create_user)
+        .get_async("/health", api!(health_check))
+        .post_async("/api/auth/register", api!(register_user))
+        .post_async("/api/auth/login", api!(login_user))
+        .post_async("/api/auth/login/2fa", api!(login_two_factor))
+        .post_async("/api/auth/login/2fa/setup", api!(login_two_factor_setup))
+        .post_async("/api/auth/refresh", api!(refresh_session))
+        .post_async("/api/auth/logout", api!(logout_session))
+        .post_async("/api/auth/logout-all", api!(logout_all_sessions))
+        .get_async("/api/api-keys", api!(get_api_keys))
+        .post_async("/api/api-keys", api!(create_api_key))
+        .delete_async("/api/api-keys/:id", api!(delete_api_key))
+        .post_async("/api/clients", api!(create_client))
+        .get_async("/api/clients", api!(get_clients))
+        .post_async("/api/invoices", api!(create_invoice))
+        .get_async("/api/invoices", api!(get_invoices))
+        .post_async("/api/expenses", api!(create_expense))
+        .get_async("/api/expenses", api!(get_expenses))
+        .get_async("/api/expenses/:id", api!(get_expense))
+        .put_async("/api/expenses/:id", api!(update_expense))
+        .delete_async("/api/expenses/:id", api!(delete_expense))
+        .put_async("/api/expenses/:id/receipt", api!(upload_receipt))
+        .get_async("/api/expenses/:id/receipt", api!(get_receipt))
+        .delete_async("/api/expenses/:id/receipt", api!(delete_receipt))
+        .options("/*catchall", |_, _| handle_cors_preflight())
         .run(req, env)
         .await
 }
 
-async fn get_users(_req: Request, _ctx: RouteContext<()>) -> Result<Response> {
-    // Placeholder implementation
-    Response::ok("[]")
-}
+fn handle_cors_preflight() -> Result<Response> {
+    let cors_headers = Headers::new();
+    cors_headers.set("Access-Control-Allow-Origin", "https://minidebet.pages.dev")?;
+    cors_headers.set("Access-Control-Allow-Methods", "GET, POST, PUT, DELETE, OPTIONS")?;
+    cors_headers.set("Access-Control-Allow-Headers", "Content-Type, Authorization, X-Organisation-Id")?;
+    cors_headers.set("Access-Control-Max-Age", "86400")?;
+    cors_headers.set("Access-Control-Allow-Credentials", "true")?;
 
-async fn create_user(mut req: Request, _ctx: RouteContext<()>) -> Result<Response> {
-    // Placeholder implementation
-    let _body = req.text().await?;
-    Response::ok("{\"id\": \"user-123\"}")
-}
\ No newline at end of file
+    Ok(Response::empty()?.with_status(204).with_headers(cors_headers))
+}
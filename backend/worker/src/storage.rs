@@ -0,0 +1,57 @@
+use worker::{async_trait::async_trait, Bucket, Env, HttpMetadata, Result};
+
+/// Stores receipt files by key. The worker keeps them in the R2 bucket bound
+/// as `RECEIPTS`, the Axum server on the local filesystem.
+#[async_trait(?Send)]
+pub trait ReceiptStorage {
+    async fn put(&self, key: &str, data: Vec<u8>, content_type: &str) -> Result<()>;
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+    async fn delete(&self, key: &str) -> Result<()>;
+}
+
+pub struct R2ReceiptStorage {
+    bucket: Bucket,
+}
+
+impl R2ReceiptStorage {
+    pub fn new(env: &Env) -> Result<Self> {
+        Ok(Self {
+            bucket: env.bucket("RECEIPTS")?,
+        })
+    }
+}
+
+#[async_trait(?Send)]
+impl ReceiptStorage for R2ReceiptStorage {
+    async fn put(&self, key: &str, data: Vec<u8>, content_type: &str) -> Result<()> {
+        self.bucket
+            .put(key, data)
+            .http_metadata(HttpMetadata {
+                content_type: Some(content_type.to_string()),
+                ..Default::default()
+            })
+            .execute()
+            .await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let object = match self.bucket.get(key).execute().await? {
+            Some(object) => object,
+            None => return Ok(None),
+        };
+        match object.body() {
+            Some(body) => Ok(Some(body.bytes().await?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.bucket.delete(key).await
+    }
+}
+
+/// Storage key of an expense's receipt, same layout as the Axum server.
+pub fn receipt_key(user_id: &str, expense_id: &str) -> String {
+    format!("receipts/{}/{}", user_id, expense_id)
+}
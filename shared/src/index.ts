@@ -7,6 +7,7 @@ export interface User {
   lastName?: string;
   companyName?: string;
   taxId?: string;
+  emailVerifiedAt?: string;
   createdAt: string;
   updatedAt: string;
 }
@@ -26,14 +27,38 @@ export interface Client {
   updatedAt: string;
 }
 
+// UN/ECE Recommendation 20 unit codes
+export type UnitCode = 'HUR' | 'DAY' | 'H87' | 'KMT' | 'MTK' | 'LS' | 'C62';
+
 export interface InvoiceItem {
   id: string;
   invoiceId: string;
   description: string;
   quantity: number;
+  unitCode: UnitCode;
   unitPrice: number;
+  discountPercent: number;
   totalPrice: number;
+  catalogItemId?: string;
+  taxCategory?: TaxCategory;
+  revenueAccount?: string;
+  createdAt: string;
+}
+
+export type TaxCategory = 'standard' | 'reduced' | 'zero' | 'exempt';
+
+export interface CatalogItem {
+  id: string;
+  userId: string;
+  itemNumber: string;
+  description: string;
+  unitCode: UnitCode;
+  netPrice: number;
+  taxCategory: TaxCategory;
+  revenueAccount?: string;
+  archivedAt?: string;
   createdAt: string;
+  updatedAt: string;
 }
 
 export interface Invoice {
@@ -44,10 +69,20 @@ export interface Invoice {
   issueDate: string;
   dueDate: string;
   currency: string;
+  exchangeRate?: number;
+  exchangeRateDate?: string;
   subtotal: number;
+  allowanceTotal: number;
+  chargeTotal: number;
   taxRate: number;
   taxAmount: number;
   totalAmount: number;
+  skontoPercent?: number;
+  skontoDays?: number;
+  billingType: 'standard' | 'advance' | 'final';
+  projectId?: string;
+  advanceDeductionNet: number;
+  advanceDeductionTax: number;
   status: 'draft' | 'sent' | 'paid' | 'overdue' | 'cancelled';
   notes?: string;
   pdfUrl?: string;
@@ -58,6 +93,72 @@ export interface Invoice {
   items: InvoiceItem[];
 }
 
+export interface AllowanceCharge {
+  id: string;
+  invoiceId: string;
+  kind: 'allowance' | 'charge';
+  reason: string;
+  percent?: number;
+  amount: number;
+  taxCategory: TaxCategory;
+  createdAt: string;
+}
+
+export interface AdvanceDeduction {
+  id: string;
+  finalInvoiceId: string;
+  advanceInvoiceId: string;
+  advanceInvoiceNumber: string;
+  advanceIssueDate: string;
+  taxRate: number;
+  netAmount: number;
+  taxAmount: number;
+  grossAmount: number;
+  createdAt: string;
+}
+
+export interface BillingSummary {
+  billingType: 'standard' | 'advance' | 'final';
+  projectId?: string;
+  subtotal: number;
+  taxAmount: number;
+  totalAmount: number;
+  deductions: AdvanceDeduction[];
+  deductedNet: number;
+  deductedTax: number;
+  deductedGross: number;
+  amountDue: number;
+}
+
+export interface ExchangeRate {
+  currency: string;
+  rateDate: string;
+  // Units of the currency per 1 EUR
+  rate: number;
+  importedAt: string;
+}
+
+export interface CurrencyConversion {
+  currency: string;
+  exchangeRate?: number;
+  exchangeRateDate?: string;
+  subtotalEur: number;
+  taxAmountEur: number;
+  totalAmountEur: number;
+  statement?: string;
+}
+
+export interface PaymentTerms {
+  dueDate: string;
+  skontoPercent?: number;
+  skontoDays?: number;
+  skontoDueDate?: string;
+  skontoAmount: number;
+  discountedAmount: number;
+  text: string;
+  xrechnung?: string;
+}
+
 export interface UserSettings {
   userId: string;
   defaultTaxRate: number;
@@ -99,11 +200,162 @@ export interface CreateInvoiceRequest {
   items: Array<{
     description: string;
     quantity: number;
+    unitCode?: UnitCode;
     unitPrice: number;
+    discountPercent?: number;
   }>;
 }
 
 export interface AuthResponse {
-  user: User;
+  user?: User;
+  token: string;
+  refreshToken: string;
+  expiresIn: number;
+  sessionId: string;
+  // Only when two-factor authentication was set up during login
+  recoveryCodes?: string[];
+}
+
+export interface TwoFactorChallenge {
+  twoFactorRequired: true;
+  setupRequired: boolean;
+  challengeToken: string;
+  expiresIn: number;
+}
+
+export interface TwoFactorStatus {
+  enabled: boolean;
+  enabledAt?: string;
+  required: boolean;
+  recoveryCodesLeft: number;
+}
+
+export interface TwoFactorEnrolment {
+  secret: string;
+  otpauthUri: string;
+}
+
+export interface Session {
+  id: string;
+  userId: string;
+  deviceLabel?: string;
+  ipAddress?: string;
+  createdAt: string;
+  lastUsedAt: string;
+  expiresAt: string;
+  revokedAt?: string;
+  revokedReason?: 'logout' | 'logout_all' | 'reuse_detected';
+  current: boolean;
+}
+
+export type ApiScope =
+  | 'clients:read'
+  | 'clients:write'
+  | 'invoices:read'
+  | 'invoices:write'
+  | 'expenses:read'
+  | 'expenses:write'
+  | 'time:read'
+  | 'time:write'
+  | 'catalog:read'
+  | 'catalog:write'
+  | 'reports:read';
+
+export interface ApiKey {
+  id: string;
+  userId: string;
+  name: string;
+  prefix: string;
+  // Space-separated scope names
+  scopes: string;
+  createdAt: string;
+  expiresAt?: string;
+  lastUsedAt?: string;
+  lastUsedIp?: string;
+  revokedAt?: string;
+}
+
+export interface CreatedApiKey extends ApiKey {
+  // Shown once; only its hash is stored
+  key: string;
+}
+
+export type Role = 'owner' | 'accountant' | 'member' | 'read_only';
+
+export interface OrganisationAccess {
+  organisationId: string;
+  name: string;
+  role: Role;
+}
+
+export interface TeamMember {
+  userId: string;
+  email: string;
+  firstName?: string;
+  lastName?: string;
+  role: Role;
+  createdAt: string;
+}
+
+export interface Invitation {
+  id: string;
+  organisationId: string;
+  email: string;
+  role: Role;
+  invitedBy: string;
+  createdAt: string;
+  expiresAt: string;
+  acceptedAt?: string;
+}
+
+export interface AdvisorGrant {
+  id: string;
+  userId: string;
+  advisorEmail: string;
+  advisorName?: string;
+  // Space-separated years, e.g. "2023 2024"
+  fiscalYears: string;
+  createdBy: string;
+  createdAt: string;
+  expiresAt: string;
+  revokedAt?: string;
+  lastAccessedAt?: string;
+}
+
+export interface AdvisorSession {
+  // Only accepted by the /api/advisor endpoints
   token: string;
+  expiresAt: string;
+  organisation: string;
+  fiscalYears: number[];
+}
+
+export interface SsoProvider {
+  id: string;
+  name: string;
+}
+
+export interface UserIdentity {
+  provider: string;
+  // `sub` claim of the provider's ID tokens
+  subject: string;
+  userId: string;
+  email: string;
+  createdAt: string;
+  lastLoginAt?: string;
+}
+export interface FieldError {
+  field: string;
+  message: string;
+}
+
+export interface ApiError {
+  // HTTP status text, e.g. "Not Found"
+  error: string;
+  // Stable code such as "validation_failed" or "rate_limited"
+  code: string;
+  message: string;
+  field_errors?: FieldError[];
+  // Seconds to wait, only with code "rate_limited"
+  retry_after?: number;
 }
\ No newline at end of file